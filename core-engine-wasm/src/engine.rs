@@ -10,7 +10,10 @@ use base64::{Engine as _, engine::general_purpose};
 
 use drawconnect_core::{
     DrawEngine, Color, Stroke, StrokePoint, BrushMode,
-    selection::SelectionMode,
+    geometry::Transform,
+    selection::{SampleSource, SelectionMode},
+    transform::Interpolation,
+    ContentAwareOptions, FillContent, FillOptions, MeasureUnit, PickerOptions, Shape, ToolEvent, ToolOptions, ToolType,
 };
 
 use crate::bridge::{hex_to_color, color_to_hex, layer_to_js, pixels_to_base64_png};
@@ -292,6 +295,35 @@ impl WasmDrawEngine {
         Ok(())
     }
 
+    /// Get the shapes of a vector layer
+    #[wasm_bindgen(js_name = getLayerShapes)]
+    pub fn get_layer_shapes(&self, layer_id: String) -> Result<JsValue, JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+
+        let uuid = uuid::Uuid::parse_str(&layer_id).map_err(|e| JsError::new(&e.to_string()))?;
+        let layer = engine
+            .layer_manager()
+            .read()
+            .get_layer(uuid)
+            .ok_or_else(|| JsError::new("Layer not found"))?;
+        let shapes = layer.read().shapes.clone();
+
+        serde_wasm_bindgen::to_value(&shapes)
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Replace the shapes of a vector layer and redraw it
+    #[wasm_bindgen(js_name = setLayerShapes)]
+    pub fn set_layer_shapes(&self, layer_id: String, shapes: JsValue) -> Result<(), JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+
+        let uuid = uuid::Uuid::parse_str(&layer_id).map_err(|e| JsError::new(&e.to_string()))?;
+        let shapes: Vec<Shape> = serde_wasm_bindgen::from_value(shapes)
+            .map_err(|e| JsError::new(&e.to_string()))?;
+
+        engine.set_layer_shapes(uuid, shapes).map_err(|e| JsError::new(&e.to_string()))
+    }
+
     /// Set active layer
     #[wasm_bindgen(js_name = setActiveLayer)]
     pub fn set_active_layer(&self, layer_id: String) -> Result<(), JsError> {
@@ -572,12 +604,19 @@ impl WasmDrawEngine {
     // Color Commands
     // ========================================================================
 
-    /// Pick color at position
+    /// Pick color at position and add it to the color history
+    ///
+    /// `options` may be undefined to sample the active layer's pixel.
     #[wasm_bindgen(js_name = pickColor)]
-    pub fn pick_color(&self, x: u32, y: u32) -> Result<JsValue, JsError> {
+    pub fn pick_color(&self, x: u32, y: u32, options: JsValue) -> Result<JsValue, JsError> {
         let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
 
-        let color = engine.pick_color(x, y).map_err(|e| JsError::new(&e.to_string()))?;
+        let options: PickerOptions = if options.is_undefined() || options.is_null() {
+            PickerOptions::default()
+        } else {
+            serde_wasm_bindgen::from_value(options).map_err(|e| JsError::new(&e.to_string()))?
+        };
+        let color = engine.pick_color(x, y, &options).map_err(|e| JsError::new(&e.to_string()))?;
         let (r, g, b, a) = color.to_rgba8();
 
         let result = ColorResult {
@@ -591,6 +630,39 @@ impl WasmDrawEngine {
         serde_wasm_bindgen::to_value(&result).map_err(|e| JsError::new(&e.to_string()))
     }
 
+    /// Get recently picked colors, newest first
+    #[wasm_bindgen(js_name = getColorHistory)]
+    pub fn get_color_history(&self) -> Result<JsValue, JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+
+        let color_manager_arc = engine.color_manager();
+        let color_manager = color_manager_arc.read();
+        let history: Vec<ColorResult> = color_manager
+            .history()
+            .iter()
+            .map(|color| {
+                let (r, g, b, a) = color.to_rgba8();
+                ColorResult {
+                    hex: color_to_hex(color),
+                    r,
+                    g,
+                    b,
+                    a,
+                }
+            })
+            .collect();
+
+        serde_wasm_bindgen::to_value(&history).map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Forget recently picked colors
+    #[wasm_bindgen(js_name = clearColorHistory)]
+    pub fn clear_color_history(&self) -> Result<(), JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+        engine.color_manager().write().clear_history();
+        Ok(())
+    }
+
     /// Flood fill at position
     #[wasm_bindgen(js_name = floodFill)]
     pub fn flood_fill(&self, x: u32, y: u32, hex: String, tolerance: f32) -> Result<(), JsError> {
@@ -600,6 +672,51 @@ impl WasmDrawEngine {
         engine.flood_fill(x, y, color, tolerance).map_err(|e| JsError::new(&e.to_string()))
     }
 
+    /// Fill the region around a point with a color, pattern or gradient
+    ///
+    /// `options` may be undefined to use the defaults.
+    #[wasm_bindgen(js_name = bucketFill)]
+    pub fn bucket_fill(&self, x: u32, y: u32, content: JsValue, options: JsValue) -> Result<(), JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+
+        let content: FillContent = serde_wasm_bindgen::from_value(content)
+            .map_err(|e| JsError::new(&e.to_string()))?;
+        let options: FillOptions = if options.is_undefined() || options.is_null() {
+            FillOptions::default()
+        } else {
+            serde_wasm_bindgen::from_value(options).map_err(|e| JsError::new(&e.to_string()))?
+        };
+        engine.bucket_fill(x, y, &content, &options).map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Fill the selection with content synthesized from the rest of the image
+    ///
+    /// `options` may be undefined to use the defaults; `progress` is called
+    /// with the finished fraction while it runs. Returns the id of the new
+    /// layer when the output is a new layer, otherwise undefined.
+    #[wasm_bindgen(js_name = contentAwareFill)]
+    pub fn content_aware_fill(
+        &self,
+        options: JsValue,
+        progress: Option<js_sys::Function>,
+    ) -> Result<Option<String>, JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+
+        let options: ContentAwareOptions = if options.is_undefined() || options.is_null() {
+            ContentAwareOptions::default()
+        } else {
+            serde_wasm_bindgen::from_value(options).map_err(|e| JsError::new(&e.to_string()))?
+        };
+        let id = engine
+            .content_aware_fill(&options, |fraction| {
+                if let Some(callback) = &progress {
+                    let _ = callback.call1(&JsValue::NULL, &JsValue::from_f64(fraction as f64));
+                }
+            })
+            .map_err(|e| JsError::new(&e.to_string()))?;
+        Ok(id.map(|id| id.to_string()))
+    }
+
     // ========================================================================
     // Selection Commands
     // ========================================================================
@@ -637,6 +754,450 @@ impl WasmDrawEngine {
             .map_err(|e| JsError::new(&e.to_string()))
     }
 
+    /// Start a magnetic lasso outline snapping to edges
+    ///
+    /// `source` is "active", "merged" or a reference layer id.
+    #[wasm_bindgen(js_name = beginMagneticLasso)]
+    pub fn begin_magnetic_lasso(&self, source: Option<String>) -> Result<(), JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+
+        let source = match source.as_deref() {
+            None | Some("active") => SampleSource::ActiveLayer,
+            Some("merged") => SampleSource::Merged,
+            Some(id) => SampleSource::Layer(
+                uuid::Uuid::parse_str(id).map_err(|e| JsError::new(&e.to_string()))?,
+            ),
+        };
+
+        engine.begin_magnetic_lasso(source).map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Place a magnetic lasso anchor, returning the traced path
+    #[wasm_bindgen(js_name = addMagneticAnchor)]
+    pub fn add_magnetic_anchor(&self, x: f32, y: f32) -> Result<JsValue, JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+
+        let selection_manager_arc = engine.selection_manager();
+        let mut selection_manager = selection_manager_arc.write();
+        let lasso = selection_manager
+            .magnetic_lasso_mut()
+            .ok_or_else(|| JsError::new("No magnetic lasso in progress"))?;
+        lasso.add_anchor(x, y);
+
+        serde_wasm_bindgen::to_value(&lasso.path())
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Remove the last magnetic lasso anchor, returning the traced path
+    #[wasm_bindgen(js_name = removeMagneticAnchor)]
+    pub fn remove_magnetic_anchor(&self) -> Result<JsValue, JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+
+        let selection_manager_arc = engine.selection_manager();
+        let mut selection_manager = selection_manager_arc.write();
+        let lasso = selection_manager
+            .magnetic_lasso_mut()
+            .ok_or_else(|| JsError::new("No magnetic lasso in progress"))?;
+        lasso.remove_last_anchor();
+
+        serde_wasm_bindgen::to_value(&lasso.path())
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Preview the magnetic lasso path to the cursor
+    #[wasm_bindgen(js_name = previewMagneticLasso)]
+    pub fn preview_magnetic_lasso(&self, x: f32, y: f32) -> Result<JsValue, JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+
+        let selection_manager_arc = engine.selection_manager();
+        let selection_manager = selection_manager_arc.read();
+        let lasso = selection_manager
+            .magnetic_lasso()
+            .ok_or_else(|| JsError::new("No magnetic lasso in progress"))?;
+
+        serde_wasm_bindgen::to_value(&lasso.preview(x, y))
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Close the magnetic lasso outline into a selection
+    #[wasm_bindgen(js_name = closeMagneticLasso)]
+    pub fn close_magnetic_lasso(&self) -> Result<JsValue, JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+
+        let selection_manager_arc = engine.selection_manager();
+        let mut selection_manager = selection_manager_arc.write();
+        selection_manager
+            .finish_magnetic_lasso()
+            .map_err(|e| JsError::new(&e.to_string()))?;
+        let info = selection_manager.get_info();
+
+        serde_wasm_bindgen::to_value(&SelectionInfoDto::from(info))
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Abandon the magnetic lasso outline
+    #[wasm_bindgen(js_name = cancelMagneticLasso)]
+    pub fn cancel_magnetic_lasso(&self) -> Result<(), JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+
+        engine.selection_manager().write().cancel_magnetic_lasso();
+        Ok(())
+    }
+
+    /// Trace the selection outline for marching ants at a display zoom
+    #[wasm_bindgen(js_name = getSelectionOutline)]
+    pub fn get_selection_outline(&self, zoom: f32) -> Result<JsValue, JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+
+        let outline = engine.selection_manager().read().outline(zoom);
+
+        serde_wasm_bindgen::to_value(&outline)
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Move, scale, rotate, skew or flip the selection shape by a matrix [a, b, c, d, tx, ty]
+    #[wasm_bindgen(js_name = transformSelection)]
+    pub fn transform_selection(&self, matrix: &[f32], interpolation: Option<String>) -> Result<JsValue, JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+        let transform = matrix_to_transform(matrix)?;
+
+        let selection_manager_arc = engine.selection_manager();
+        let mut selection_manager = selection_manager_arc.write();
+        selection_manager
+            .transform_selection(&transform, parse_interpolation(interpolation))
+            .map_err(|e| JsError::new(&e.to_string()))?;
+        let info = selection_manager.get_info();
+
+        serde_wasm_bindgen::to_value(&SelectionInfoDto::from(info))
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Lift the selected pixels into a floating selection, returning its corners
+    #[wasm_bindgen(js_name = floatSelection)]
+    pub fn float_selection(&self) -> Result<JsValue, JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+
+        engine.float_selection().map_err(|e| JsError::new(&e.to_string()))?;
+
+        let selection_manager_arc = engine.selection_manager();
+        let selection_manager = selection_manager_arc.read();
+        let floating = selection_manager
+            .floating()
+            .ok_or_else(|| JsError::new("No floating selection"))?;
+
+        serde_wasm_bindgen::to_value(&floating.corners())
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Apply a further matrix [a, b, c, d, tx, ty] to the floating selection,
+    /// returning its corners
+    #[wasm_bindgen(js_name = transformFloatingSelection)]
+    pub fn transform_floating_selection(&self, matrix: &[f32]) -> Result<JsValue, JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+        let transform = matrix_to_transform(matrix)?;
+
+        let selection_manager_arc = engine.selection_manager();
+        let mut selection_manager = selection_manager_arc.write();
+        let floating = selection_manager
+            .floating_mut()
+            .ok_or_else(|| JsError::new("No floating selection"))?;
+        floating.apply(&transform);
+
+        serde_wasm_bindgen::to_value(&floating.corners())
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Flip the floating selection around its center, returning its corners
+    #[wasm_bindgen(js_name = flipFloatingSelection)]
+    pub fn flip_floating_selection(&self, horizontal: bool) -> Result<JsValue, JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+
+        let selection_manager_arc = engine.selection_manager();
+        let mut selection_manager = selection_manager_arc.write();
+        let floating = selection_manager
+            .floating_mut()
+            .ok_or_else(|| JsError::new("No floating selection"))?;
+        if horizontal {
+            floating.flip_horizontal();
+        } else {
+            floating.flip_vertical();
+        }
+
+        serde_wasm_bindgen::to_value(&floating.corners())
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Pin the floating selection's corners to free positions, given as
+    /// [x0, y0, x1, y1, x2, y2, x3, y3] from the top-left clockwise,
+    /// returning its corners
+    #[wasm_bindgen(js_name = distortFloatingSelection)]
+    pub fn distort_floating_selection(&self, corners: &[f32]) -> Result<JsValue, JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+        if corners.len() != 8 {
+            return Err(JsError::new("Expected 8 corner coordinates"));
+        }
+        let corners = [0, 1, 2, 3].map(|i| (corners[i * 2], corners[i * 2 + 1]));
+
+        let selection_manager_arc = engine.selection_manager();
+        let mut selection_manager = selection_manager_arc.write();
+        let floating = selection_manager
+            .floating_mut()
+            .ok_or_else(|| JsError::new("No floating selection"))?;
+        floating.distort(corners).map_err(|e| JsError::new(&e.to_string()))?;
+
+        serde_wasm_bindgen::to_value(&floating.corners())
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Drag one corner of the floating selection in perspective, returning
+    /// its corners
+    #[wasm_bindgen(js_name = perspectiveFloatingSelection)]
+    pub fn perspective_floating_selection(&self, corner: usize, x: f32, y: f32) -> Result<JsValue, JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+
+        let selection_manager_arc = engine.selection_manager();
+        let mut selection_manager = selection_manager_arc.write();
+        let floating = selection_manager
+            .floating_mut()
+            .ok_or_else(|| JsError::new("No floating selection"))?;
+        floating.perspective(corner, (x, y)).map_err(|e| JsError::new(&e.to_string()))?;
+
+        serde_wasm_bindgen::to_value(&floating.corners())
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Put a warp mesh over the floating selection, returning its control
+    /// points row by row
+    #[wasm_bindgen(js_name = warpFloatingSelection)]
+    pub fn warp_floating_selection(&self, columns: u32, rows: u32) -> Result<JsValue, JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+
+        let selection_manager_arc = engine.selection_manager();
+        let mut selection_manager = selection_manager_arc.write();
+        let floating = selection_manager
+            .floating_mut()
+            .ok_or_else(|| JsError::new("No floating selection"))?;
+        floating.begin_mesh(columns, rows);
+
+        serde_wasm_bindgen::to_value(&floating.mesh_points())
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Move one control point of the floating selection's warp mesh,
+    /// returning the control points row by row
+    #[wasm_bindgen(js_name = moveFloatingMeshPoint)]
+    pub fn move_floating_mesh_point(&self, column: u32, row: u32, x: f32, y: f32) -> Result<JsValue, JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+
+        let selection_manager_arc = engine.selection_manager();
+        let mut selection_manager = selection_manager_arc.write();
+        let floating = selection_manager
+            .floating_mut()
+            .ok_or_else(|| JsError::new("No floating selection"))?;
+        floating
+            .move_mesh_point(column, row, (x, y))
+            .map_err(|e| JsError::new(&e.to_string()))?;
+
+        serde_wasm_bindgen::to_value(&floating.mesh_points())
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Drop the floating selection into its layer
+    #[wasm_bindgen(js_name = commitFloatingSelection)]
+    pub fn commit_floating_selection(&self, interpolation: Option<String>) -> Result<JsValue, JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+
+        engine
+            .commit_floating_selection(parse_interpolation(interpolation))
+            .map_err(|e| JsError::new(&e.to_string()))?;
+        let info = engine.selection_manager().read().get_info();
+
+        serde_wasm_bindgen::to_value(&SelectionInfoDto::from(info))
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Put the floating selection back where it was lifted from
+    #[wasm_bindgen(js_name = cancelFloatingSelection)]
+    pub fn cancel_floating_selection(&self) -> Result<JsValue, JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+
+        engine
+            .cancel_floating_selection()
+            .map_err(|e| JsError::new(&e.to_string()))?;
+        let info = engine.selection_manager().read().get_info();
+
+        serde_wasm_bindgen::to_value(&SelectionInfoDto::from(info))
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Switch the active tool, committing any pending work of the previous one
+    #[wasm_bindgen(js_name = setTool)]
+    pub fn set_tool(&self, tool: JsValue) -> Result<(), JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+        let tool: ToolType = serde_wasm_bindgen::from_value(tool)
+            .map_err(|e| JsError::new(&e.to_string()))?;
+
+        engine.set_tool(tool).map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Forward a pointer press in canvas coordinates to the active tool,
+    /// returning the tool preview
+    #[wasm_bindgen(js_name = pointerDown)]
+    pub fn pointer_down(&self, event: JsValue) -> Result<JsValue, JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+        let event: ToolEvent = serde_wasm_bindgen::from_value(event)
+            .map_err(|e| JsError::new(&e.to_string()))?;
+
+        engine.pointer_down(event).map_err(|e| JsError::new(&e.to_string()))?;
+
+        serde_wasm_bindgen::to_value(&engine.tool_preview())
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Forward a pointer move in canvas coordinates to the active tool,
+    /// returning the tool preview
+    #[wasm_bindgen(js_name = pointerMove)]
+    pub fn pointer_move(&self, event: JsValue) -> Result<JsValue, JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+        let event: ToolEvent = serde_wasm_bindgen::from_value(event)
+            .map_err(|e| JsError::new(&e.to_string()))?;
+
+        engine.pointer_move(event).map_err(|e| JsError::new(&e.to_string()))?;
+
+        serde_wasm_bindgen::to_value(&engine.tool_preview())
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Forward a pointer release in canvas coordinates to the active tool,
+    /// returning the tool preview
+    #[wasm_bindgen(js_name = pointerUp)]
+    pub fn pointer_up(&self, event: JsValue) -> Result<JsValue, JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+        let event: ToolEvent = serde_wasm_bindgen::from_value(event)
+            .map_err(|e| JsError::new(&e.to_string()))?;
+
+        engine.pointer_up(event).map_err(|e| JsError::new(&e.to_string()))?;
+
+        serde_wasm_bindgen::to_value(&engine.tool_preview())
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Finish the active tool's pending work
+    #[wasm_bindgen(js_name = commitTool)]
+    pub fn commit_tool(&self) -> Result<(), JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+
+        engine.commit_tool().map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Abandon the active tool's pending work
+    #[wasm_bindgen(js_name = cancelTool)]
+    pub fn cancel_tool(&self) -> Result<(), JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+
+        engine.cancel_tool().map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Relax the pending liquify mesh `amount` (0-1) of the way back to the
+    /// original layer
+    #[wasm_bindgen(js_name = reconstructLiquify)]
+    pub fn reconstruct_liquify(&self, amount: f32) -> Result<(), JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+
+        engine.reconstruct_liquify(amount).map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Ruler lines kept by the measure tool, read out in `unit` ("Pixels",
+    /// "Millimeters", "Centimeters" or "Inches"; pixels if undefined)
+    #[wasm_bindgen(js_name = getMeasurements)]
+    pub fn get_measurements(&self, unit: JsValue) -> Result<JsValue, JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+
+        let unit: MeasureUnit = if unit.is_undefined() || unit.is_null() {
+            MeasureUnit::default()
+        } else {
+            serde_wasm_bindgen::from_value(unit).map_err(|e| JsError::new(&e.to_string()))?
+        };
+        serde_wasm_bindgen::to_value(&engine.measurement_readouts(unit))
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Keep a ruler line between two canvas points, returning its id
+    #[wasm_bindgen(js_name = addMeasurement)]
+    pub fn add_measurement(&self, x0: f32, y0: f32, x1: f32, y1: f32) -> Result<String, JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+        Ok(engine.add_measurement((x0, y0), (x1, y1)).to_string())
+    }
+
+    /// Remove one ruler line
+    #[wasm_bindgen(js_name = removeMeasurement)]
+    pub fn remove_measurement(&self, id: String) -> Result<bool, JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+        let uuid = uuid::Uuid::parse_str(&id).map_err(|e| JsError::new(&e.to_string()))?;
+        Ok(engine.remove_measurement(uuid))
+    }
+
+    /// Remove every ruler line
+    #[wasm_bindgen(js_name = clearMeasurements)]
+    pub fn clear_measurements(&self) -> Result<(), JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+        engine.clear_measurements();
+        Ok(())
+    }
+
+    /// Rotate the active layer to level a ruler line, returning the
+    /// clockwise rotation in degrees
+    #[wasm_bindgen(js_name = straightenLayer)]
+    pub fn straighten_layer(&self, id: String) -> Result<f32, JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+        let uuid = uuid::Uuid::parse_str(&id).map_err(|e| JsError::new(&e.to_string()))?;
+        engine.straighten_layer(uuid).map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Overlay the frontend should draw for the active tool
+    #[wasm_bindgen(js_name = getToolPreview)]
+    pub fn get_tool_preview(&self) -> Result<JsValue, JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+
+        serde_wasm_bindgen::to_value(&engine.tool_preview())
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Get the shared tool options
+    #[wasm_bindgen(js_name = getToolOptions)]
+    pub fn get_tool_options(&self) -> Result<JsValue, JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+        let options = engine.tool_manager().read().options().clone();
+
+        serde_wasm_bindgen::to_value(&options)
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Replace the shared tool options
+    #[wasm_bindgen(js_name = setToolOptions)]
+    pub fn set_tool_options(&self, options: JsValue) -> Result<(), JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+        let options: ToolOptions = serde_wasm_bindgen::from_value(options)
+            .map_err(|e| JsError::new(&e.to_string()))?;
+
+        *engine.tool_manager().write().options_mut() = options;
+        Ok(())
+    }
+
+    /// Set the primary and secondary colors used by the tools
+    #[wasm_bindgen(js_name = setToolColors)]
+    pub fn set_tool_colors(&self, primary: String, secondary: String) -> Result<(), JsError> {
+        let engine = self.engine.as_ref().ok_or_else(|| JsError::new("No canvas open"))?;
+        let primary = hex_to_color(&primary).map_err(|e| JsError::new(&e))?;
+        let secondary = hex_to_color(&secondary).map_err(|e| JsError::new(&e))?;
+
+        let tool_manager = engine.tool_manager();
+        let mut tool_manager = tool_manager.write();
+        tool_manager.set_primary_color(primary);
+        tool_manager.set_secondary_color(secondary);
+        Ok(())
+    }
+
     /// Clear selection
     #[wasm_bindgen(js_name = clearSelection)]
     pub fn clear_selection(&self) -> Result<(), JsError> {
@@ -800,6 +1361,21 @@ struct ColorResult {
     a: u8,
 }
 
+/// Parse a transform matrix [a, b, c, d, tx, ty]
+fn matrix_to_transform(matrix: &[f32]) -> Result<Transform, JsError> {
+    let matrix: [f32; 6] = matrix
+        .try_into()
+        .map_err(|_| JsError::new("Transform matrix needs 6 values"))?;
+    Ok(Transform { matrix })
+}
+
+/// Parse an interpolation name, defaulting to bilinear
+fn parse_interpolation(interpolation: Option<String>) -> Interpolation {
+    interpolation
+        .and_then(|s| s.parse::<Interpolation>().ok())
+        .unwrap_or(Interpolation::Bilinear)
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 struct SelectionInfoDto {
     is_active: bool,
@@ -136,9 +136,9 @@ fn bench_tile_manager(c: &mut Criterion) {
     group.finish();
 }
 
-/// Benchmark undo/redo operations
+/// Benchmark capturing canvas state for undo
 fn bench_undo_redo(c: &mut Criterion) {
-    c.bench_function("save_undo_512x512", |b| {
+    c.bench_function("canvas_snapshot_512x512", |b| {
         let mut canvas = Canvas::with_size(512, 512).unwrap();
         // Draw something
         for y in 0..100 {
@@ -148,7 +148,7 @@ fn bench_undo_redo(c: &mut Criterion) {
         }
 
         b.iter(|| {
-            canvas.save_undo("test");
+            black_box(canvas.snapshot());
         })
     });
 }
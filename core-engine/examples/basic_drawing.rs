@@ -78,12 +78,10 @@ fn main() {
     println!("6. Testing undo/redo...");
     drop(canvas);
     {
-        let canvas_arc = engine.canvas();
-        let mut canvas = canvas_arc.write();
-        canvas.save_undo("Draw stroke");
+        engine.record_canvas_state("Draw stroke");
         println!("   Saved undo state");
-        println!("   Can undo: {}", canvas.can_undo());
-        println!("   Undo count: {}\n", canvas.undo_count());
+        println!("   Can undo: {}", engine.can_undo());
+        println!("   Undo count: {}\n", engine.history_manager().read().undo_count());
     }
 
     // 7. Color operations
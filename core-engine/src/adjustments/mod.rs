@@ -36,7 +36,7 @@ pub use threshold::Threshold;
 
 use crate::color::Color;
 use crate::layer::Layer;
-use crate::selection::Selection;
+use crate::selection::{blend_by_coverage, Selection};
 
 /// Common trait for all image adjustments
 pub trait Adjustment: Send + Sync {
@@ -71,32 +71,36 @@ pub trait Adjustment: Send + Sync {
     }
 
     /// Apply adjustment respecting a selection mask
+    ///
+    /// Partially selected pixels mix the adjusted and original colors.
+    /// Without an active selection the whole layer is adjusted.
     fn apply_with_selection(&self, layer: &mut Layer, selection: &Selection) {
+        if !selection.is_active {
+            self.apply_to_layer(layer);
+            return;
+        }
+
         let width = layer.width();
         let height = layer.height();
         let pixels = &mut layer.pixels;
 
         for y in 0..height {
             for x in 0..width {
-                // Check if pixel is within selection
-                if !selection.contains(x as f32, y as f32) {
+                // Skip pixels outside the selection
+                let coverage = selection.mask.get(x, y);
+                if coverage == 0 {
                     continue;
                 }
 
                 let idx = ((y * width + x) * 4) as usize;
                 if idx + 3 < pixels.len() {
-                    let color = Color::from_rgba8(
-                        pixels[idx],
-                        pixels[idx + 1],
-                        pixels[idx + 2],
-                        pixels[idx + 3],
-                    );
-                    let adjusted = self.apply_pixel(color);
-                    let (r, g, b, a) = adjusted.to_rgba8();
-                    pixels[idx] = r;
-                    pixels[idx + 1] = g;
-                    pixels[idx + 2] = b;
-                    pixels[idx + 3] = a;
+                    let original = [pixels[idx], pixels[idx + 1], pixels[idx + 2], pixels[idx + 3]];
+                    let color =
+                        Color::from_rgba8(original[0], original[1], original[2], original[3]);
+                    let (r, g, b, a) = self.apply_pixel(color).to_rgba8();
+                    let mut adjusted = [r, g, b, a];
+                    blend_by_coverage(&original, &mut adjusted, coverage);
+                    pixels[idx..idx + 4].copy_from_slice(&adjusted);
                 }
             }
         }
@@ -5,7 +5,7 @@
 //! - 16K resolution support
 //! - Tile-based rendering
 //! - Memory-efficient sparse storage
-//! - Undo/Redo support
+//! - Snapshots for the engine's undo history
 
 mod tile;
 
@@ -55,23 +55,29 @@ pub struct Canvas {
     settings: CanvasSettings,
     /// Tile manager for efficient storage
     tile_manager: TileManager,
-    /// Undo history
-    undo_history: Vec<CanvasSnapshot>,
-    /// Redo history
-    redo_history: Vec<CanvasSnapshot>,
-    /// Maximum undo steps
-    max_undo: usize,
     /// Canvas is modified
     modified: bool,
 }
 
-/// Canvas snapshot for undo/redo
-#[derive(Clone)]
-struct CanvasSnapshot {
-    /// Modified tiles
-    tiles: HashMap<(u32, u32), Vec<u8>>,
-    /// Description of the change
-    description: String,
+/// Canvas size and tile contents at a point in time
+///
+/// Stored in [`HistoryState`](crate::history::HistoryState) so canvas-level
+/// operations are undone through the same history as layer edits.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CanvasSnapshot {
+    /// Canvas width in pixels
+    pub width: u32,
+    /// Canvas height in pixels
+    pub height: u32,
+    /// LZ4-compressed tile data keyed by tile position
+    tiles: Vec<((u32, u32), Vec<u8>)>,
+}
+
+impl CanvasSnapshot {
+    /// Get memory size of this snapshot
+    pub fn memory_size(&self) -> usize {
+        std::mem::size_of::<Self>() + self.tiles.iter().map(|(_, data)| data.len()).sum::<usize>()
+    }
 }
 
 impl Canvas {
@@ -97,9 +103,6 @@ impl Canvas {
             id: Uuid::new_v4(),
             settings,
             tile_manager,
-            undo_history: Vec::new(),
-            redo_history: Vec::new(),
-            max_undo: 100,
             modified: false,
         })
     }
@@ -241,78 +244,34 @@ impl Canvas {
         Ok(())
     }
 
-    /// Save current state for undo
-    pub fn save_undo(&mut self, description: impl Into<String>) {
-        let snapshot = CanvasSnapshot {
-            tiles: self.tile_manager.snapshot(),
-            description: description.into(),
-        };
-
-        self.undo_history.push(snapshot);
-        self.redo_history.clear();
-
-        // Limit undo history
-        while self.undo_history.len() > self.max_undo {
-            self.undo_history.remove(0);
-        }
-    }
-
-    /// Undo last change
-    pub fn undo(&mut self) -> bool {
-        if let Some(snapshot) = self.undo_history.pop() {
-            // Save current state for redo
-            let current = CanvasSnapshot {
-                tiles: self.tile_manager.snapshot(),
-                description: snapshot.description.clone(),
-            };
-            self.redo_history.push(current);
-
-            // Restore snapshot
-            self.tile_manager.restore(&snapshot.tiles);
-            self.modified = true;
-            true
-        } else {
-            false
+    /// Capture the canvas size and tiles for the undo history
+    pub fn snapshot(&self) -> CanvasSnapshot {
+        let tiles = self
+            .tile_manager
+            .snapshot()
+            .into_iter()
+            .map(|(pos, data)| (pos, lz4_flex::compress_prepend_size(&data)))
+            .collect();
+
+        CanvasSnapshot {
+            width: self.settings.width,
+            height: self.settings.height,
+            tiles,
         }
     }
 
-    /// Redo last undone change
-    pub fn redo(&mut self) -> bool {
-        if let Some(snapshot) = self.redo_history.pop() {
-            // Save current state for undo
-            let current = CanvasSnapshot {
-                tiles: self.tile_manager.snapshot(),
-                description: snapshot.description.clone(),
-            };
-            self.undo_history.push(current);
-
-            // Restore snapshot
-            self.tile_manager.restore(&snapshot.tiles);
-            self.modified = true;
-            true
-        } else {
-            false
+    /// Restore the canvas size and tiles from a snapshot
+    pub fn restore(&mut self, snapshot: &CanvasSnapshot) -> EngineResult<()> {
+        let mut tiles = HashMap::with_capacity(snapshot.tiles.len());
+        for (pos, data) in &snapshot.tiles {
+            let data = lz4_flex::decompress_size_prepended(data)
+                .map_err(|e| EngineError::CompressionError(e.to_string()))?;
+            tiles.insert(*pos, data);
         }
-    }
-
-    /// Check if undo is available
-    pub fn can_undo(&self) -> bool {
-        !self.undo_history.is_empty()
-    }
-
-    /// Check if redo is available
-    pub fn can_redo(&self) -> bool {
-        !self.redo_history.is_empty()
-    }
 
-    /// Get number of undo steps available
-    pub fn undo_count(&self) -> usize {
-        self.undo_history.len()
-    }
-
-    /// Get number of redo steps available
-    pub fn redo_count(&self) -> usize {
-        self.redo_history.len()
+        self.resize(snapshot.width, snapshot.height)?;
+        self.tile_manager.restore(&tiles);
+        Ok(())
     }
 
     /// Get raw pixel data (RGBA)
@@ -381,16 +340,17 @@ mod tests {
     }
 
     #[test]
-    fn test_undo_redo() {
+    fn test_snapshot_restore() {
         let mut canvas = Canvas::with_size(100, 100).unwrap();
 
         canvas.set_pixel(0, 0, Color::red()).unwrap();
-        canvas.save_undo("Set red pixel");
+        let snapshot = canvas.snapshot();
 
         canvas.set_pixel(0, 0, Color::blue()).unwrap();
+        canvas.resize(50, 40).unwrap();
 
-        assert!(canvas.can_undo());
-        canvas.undo();
+        canvas.restore(&snapshot).unwrap();
+        assert_eq!((canvas.width(), canvas.height()), (100, 100));
 
         let color = canvas.get_pixel(0, 0).unwrap();
         assert!((color.r - 1.0).abs() < 0.01);
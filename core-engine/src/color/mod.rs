@@ -8,10 +8,12 @@
 
 mod convert;
 mod palette;
+mod picker;
 mod profile;
 
 pub use convert::ColorConverter;
 pub use palette::ColorPalette;
+pub use picker::{average_colors, PickerOptions, SampleLayers, SampleSize};
 pub use profile::IccProfile;
 
 use serde::{Deserialize, Serialize};
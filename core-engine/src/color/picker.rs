@@ -0,0 +1,93 @@
+//! Eyedropper sampling options
+
+use super::Color;
+use serde::{Deserialize, Serialize};
+
+/// Area the eyedropper averages around the picked pixel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SampleSize {
+    /// The single pixel under the pointer
+    #[default]
+    Point,
+    /// 3 x 3 average
+    Average3,
+    /// 5 x 5 average
+    Average5,
+    /// 11 x 11 average
+    Average11,
+    /// 31 x 31 average
+    Average31,
+}
+
+impl SampleSize {
+    /// Pixels sampled on each side of the picked pixel
+    pub fn radius(&self) -> u32 {
+        match self {
+            SampleSize::Point => 0,
+            SampleSize::Average3 => 1,
+            SampleSize::Average5 => 2,
+            SampleSize::Average11 => 5,
+            SampleSize::Average31 => 15,
+        }
+    }
+}
+
+/// Layers the eyedropper samples
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SampleLayers {
+    /// The active layer's own pixels
+    #[default]
+    Current,
+    /// Every visible layer, composited
+    All,
+    /// The visible layers under the active layer, composited
+    Below,
+}
+
+/// Eyedropper settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PickerOptions {
+    /// Area averaged around the picked pixel
+    pub sample_size: SampleSize,
+    /// Layers sampled
+    pub layers: SampleLayers,
+}
+
+/// Average colors weighted by alpha, so transparent samples do not darken
+/// the result
+pub fn average_colors(colors: impl IntoIterator<Item = Color>) -> Color {
+    let (mut r, mut g, mut b, mut a, mut count) = (0.0, 0.0, 0.0, 0.0, 0usize);
+    for color in colors {
+        r += color.r * color.a;
+        g += color.g * color.a;
+        b += color.b * color.a;
+        a += color.a;
+        count += 1;
+    }
+    if a <= 0.0 {
+        return Color::transparent();
+    }
+    Color::from_rgba(r / a, g / a, b / a, a / count as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_size_radius() {
+        assert_eq!(SampleSize::Point.radius(), 0);
+        assert_eq!(SampleSize::Average11.radius() * 2 + 1, 11);
+        assert_eq!(SampleSize::Average31.radius() * 2 + 1, 31);
+    }
+
+    #[test]
+    fn test_average_ignores_transparent_color() {
+        let red = Color::from_rgba(1.0, 0.0, 0.0, 1.0);
+        let average = average_colors([red, Color::transparent(), Color::from_rgba(0.0, 0.0, 1.0, 0.0)]);
+        assert!((average.r - 1.0).abs() < 1e-6 && average.b == 0.0);
+        assert!((average.a - 1.0 / 3.0).abs() < 1e-6);
+        assert_eq!(average_colors([]).a, 0.0);
+    }
+}
@@ -104,3 +104,9 @@ impl From<bincode::Error> for EngineError {
         EngineError::SerializationError(err.to_string())
     }
 }
+
+impl From<crate::transform::TransformError> for EngineError {
+    fn from(err: crate::transform::TransformError) -> Self {
+        EngineError::InvalidOperation(err.to_string())
+    }
+}
@@ -0,0 +1,345 @@
+//! Fill Module
+//!
+//! Bucket fills find a region like the magic wand does, in the layer being
+//! filled, a reference layer such as line art, or the merged image, and can
+//! seal gaps in the surrounding lines and grow or shrink the region under
+//! them. The region is then painted with a color, a tiled pattern or a
+//! gradient spanning it. With anti-aliasing the fill fades out across one
+//! pixel beyond the region, so it tucks under the soft rim of the lines
+//! instead of leaving a stair-stepped edge.
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::color::Color;
+use crate::error::{EngineError, EngineResult};
+use crate::gradient::GradientOptions;
+use crate::import::ImportedPattern;
+use crate::layer::Layer;
+use crate::selection::{self, SampleSource, SelectionMask, WandOptions};
+
+/// Width of the soft edge added by anti-aliasing, in pixels
+const ANTI_ALIAS_RADIUS: f32 = 1.0;
+
+/// Tiling image used as fill content
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FillPattern {
+    /// Pattern name
+    pub name: String,
+    /// Tile width in pixels
+    pub width: u32,
+    /// Tile height in pixels
+    pub height: u32,
+    /// RGBA pixels of one tile
+    pub pixels: Vec<u8>,
+}
+
+impl FillPattern {
+    /// Create a pattern from one tile of RGBA pixels
+    pub fn new(name: &str, width: u32, height: u32, pixels: Vec<u8>) -> EngineResult<Self> {
+        if width == 0 || height == 0 || pixels.len() != (width * height * 4) as usize {
+            return Err(EngineError::InvalidOperation("Invalid pattern data".to_string()));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// Color at a canvas position, with the tile repeated from the origin
+    pub fn color_at(&self, x: u32, y: u32) -> Color {
+        let i = (((y % self.height) * self.width + x % self.width) * 4) as usize;
+        match self.pixels.get(i..i + 4) {
+            Some(p) => Color::from_rgba8(p[0], p[1], p[2], p[3]),
+            None => Color::transparent(),
+        }
+    }
+}
+
+impl TryFrom<ImportedPattern> for FillPattern {
+    type Error = EngineError;
+
+    fn try_from(pattern: ImportedPattern) -> EngineResult<Self> {
+        Self::new(&pattern.name, pattern.width, pattern.height, pattern.data)
+    }
+}
+
+/// What a bucket fill paints
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum FillContent {
+    /// A solid color
+    Color(Color),
+    /// A pattern tiled across the canvas
+    Pattern(FillPattern),
+    /// A gradient spanning the filled region
+    Gradient {
+        /// Gradient and how it is drawn
+        options: GradientOptions,
+        /// Direction across the region in degrees, 0 running left to right
+        angle: f32,
+        /// Color for foreground stops
+        foreground: Color,
+        /// Color for background stops
+        background: Color,
+    },
+}
+
+impl FillContent {
+    /// Paint the content into `area` of the layer, ignoring the region
+    pub(crate) fn paint(&self, layer: &mut Layer, area: (u32, u32, u32, u32), opacity: f32) {
+        let (x0, y0, width, height) = area;
+        match self {
+            FillContent::Color(color) => {
+                let color = color.with_alpha(color.a * opacity);
+                for y in y0..y0 + height {
+                    for x in x0..x0 + width {
+                        layer.blend_pixel(x, y, color);
+                    }
+                }
+            }
+            FillContent::Pattern(pattern) => {
+                for y in y0..y0 + height {
+                    for x in x0..x0 + width {
+                        let color = pattern.color_at(x, y);
+                        layer.blend_pixel(x, y, color.with_alpha(color.a * opacity));
+                    }
+                }
+            }
+            FillContent::Gradient { options, angle, foreground, background } => {
+                let (dx, dy) = (angle.to_radians().cos(), angle.to_radians().sin());
+                let half = (width as f32 * dx.abs() + height as f32 * dy.abs()) / 2.0;
+                let center = (x0 as f32 + width as f32 / 2.0, y0 as f32 + height as f32 / 2.0);
+                let start = (center.0 - dx * half, center.1 - dy * half);
+                let end = (center.0 + dx * half, center.1 + dy * half);
+                options.render(layer, area, start, end, *foreground, *background, opacity);
+            }
+        }
+    }
+}
+
+/// How a bucket fill finds its region
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FillOptions {
+    /// Pixels the region is found in
+    pub source: SampleSource,
+    /// Per-channel color tolerance (0-255)
+    pub tolerance: f32,
+    /// Only fill pixels connected to the clicked pixel
+    pub contiguous: bool,
+    /// Widest gap in the surrounding lines to seal, in pixels
+    pub close_gaps: f32,
+    /// Pixels to grow the region under the surrounding lines; negative
+    /// values contract it
+    pub expand: f32,
+    /// Soften the edge of the fill
+    pub anti_alias: bool,
+    /// Fill opacity (0-1)
+    pub opacity: f32,
+}
+
+impl Default for FillOptions {
+    fn default() -> Self {
+        Self {
+            source: SampleSource::ActiveLayer,
+            tolerance: 32.0,
+            contiguous: true,
+            close_gaps: 0.0,
+            expand: 0.0,
+            anti_alias: true,
+            opacity: 1.0,
+        }
+    }
+}
+
+impl FillOptions {
+    /// Fill options finding the same region as the magic wand
+    pub fn from_wand(source: SampleSource, wand: &WandOptions) -> Self {
+        Self {
+            source,
+            tolerance: wand.tolerance,
+            contiguous: wand.contiguous,
+            close_gaps: wand.close_gaps,
+            expand: wand.expand,
+            anti_alias: false,
+            opacity: 1.0,
+        }
+    }
+
+    fn wand(&self) -> WandOptions {
+        WandOptions {
+            tolerance: self.tolerance,
+            contiguous: self.contiguous,
+            close_gaps: self.close_gaps,
+            expand: self.expand,
+        }
+    }
+}
+
+/// Bucket settings of the paint bucket tool
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BucketOptions {
+    /// Layer to find regions in instead of the active layer or merged image
+    pub reference_layer: Option<Uuid>,
+    /// Widest gap in the surrounding lines to seal, in pixels
+    pub close_gaps: f32,
+    /// Pixels to grow the fill under the surrounding lines; negative values
+    /// contract it
+    pub expand: f32,
+    /// What the tool fills with
+    pub content: BucketContent,
+    /// Pattern for [`BucketContent::Pattern`]
+    pub pattern: Option<FillPattern>,
+    /// Gradient direction for [`BucketContent::Gradient`] in degrees
+    pub gradient_angle: f32,
+}
+
+impl Default for BucketOptions {
+    fn default() -> Self {
+        Self {
+            reference_layer: None,
+            close_gaps: 0.0,
+            expand: 0.0,
+            content: BucketContent::Color,
+            pattern: None,
+            gradient_angle: 0.0,
+        }
+    }
+}
+
+/// Kind of content the paint bucket tool fills with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BucketContent {
+    /// The primary color, or the secondary color with Alt
+    #[default]
+    Color,
+    /// The pattern in the bucket options
+    Pattern,
+    /// The gradient tool's gradient, from the primary to the secondary color
+    Gradient,
+}
+
+/// Coverage of the region to fill around (x, y) in RGBA pixels
+pub(crate) fn fill_region(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    x: u32,
+    y: u32,
+    options: &FillOptions,
+) -> EngineResult<SelectionMask> {
+    let mut region = selection::magic_wand(pixels, width, height, x, y, &options.wand())?;
+    if options.anti_alias {
+        anti_alias(&mut region);
+    }
+    Ok(region)
+}
+
+/// Fade the region out across one pixel beyond its edge
+///
+/// Coverage inside the region is kept, so the fill stays solid right up to
+/// the pixels it was bounded by.
+fn anti_alias(region: &mut SelectionMask) {
+    let mut soft = region.clone();
+    soft.grow(ANTI_ALIAS_RADIUS / 2.0);
+    soft.feather(ANTI_ALIAS_RADIUS);
+    for (out, &s) in region.data_mut().iter_mut().zip(soft.data()) {
+        *out = (*out).max(s);
+    }
+}
+
+/// Paint `content` into the layer by the coverage of `region`
+///
+/// `before` holds the layer pixels as they were before the fill; `area`
+/// must lie within the layer and enclose the region.
+pub(crate) fn render(
+    layer: &mut Layer,
+    before: &[u8],
+    region: &SelectionMask,
+    area: (u32, u32, u32, u32),
+    content: &FillContent,
+    opacity: f32,
+) {
+    content.paint(layer, area, opacity);
+
+    let (x0, y0, width, height) = area;
+    let layer_width = layer.width();
+    for y in y0..y0 + height {
+        for x in x0..x0 + width {
+            let idx = ((y * layer_width + x) * 4) as usize;
+            if let (Some(original), Some(painted)) =
+                (before.get(idx..idx + 4), layer.pixels.get_mut(idx..idx + 4))
+            {
+                selection::blend_by_coverage(original, painted, region.get(x, y));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Transparent 16x16 pixels with a black vertical line at x = 8
+    fn split() -> Vec<u8> {
+        let mut pixels = vec![0u8; 16 * 16 * 4];
+        for y in 0..16 {
+            let i = ((y * 16 + 8) * 4) as usize;
+            pixels[i..i + 4].copy_from_slice(&[0, 0, 0, 255]);
+        }
+        pixels
+    }
+
+    #[test]
+    fn test_fill_region_anti_aliases_outside_edge() {
+        let hard = FillOptions {
+            anti_alias: false,
+            ..FillOptions::default()
+        };
+        let region = fill_region(&split(), 16, 16, 2, 2, &hard).unwrap();
+        assert_eq!(region.get(7, 4), 255);
+        assert_eq!(region.get(8, 4), 0);
+
+        let region = fill_region(&split(), 16, 16, 2, 2, &FillOptions::default()).unwrap();
+        assert_eq!(region.get(7, 4), 255);
+        assert!(region.get(8, 4) > 0 && region.get(8, 4) < 255);
+        assert_eq!(region.get(12, 4), 0);
+    }
+
+    #[test]
+    fn test_pattern_tiles_from_origin() {
+        let pixels = [[255, 0, 0, 255], [0, 0, 255, 255]].concat();
+        let pattern = FillPattern::new("Stripes", 2, 1, pixels).unwrap();
+        assert_eq!(pattern.color_at(4, 7).to_rgba8(), (255, 0, 0, 255));
+        assert_eq!(pattern.color_at(5, 0).to_rgba8(), (0, 0, 255, 255));
+        assert!(FillPattern::new("Broken", 2, 2, vec![0; 4]).is_err());
+    }
+
+    #[test]
+    fn test_render_gradient_spans_region() {
+        let mut layer = Layer::new("Fill", 16, 16);
+        let before = layer.pixels.clone();
+        let region = fill_region(&split(), 16, 16, 2, 2, &FillOptions {
+            anti_alias: false,
+            ..FillOptions::default()
+        })
+        .unwrap();
+        let content = FillContent::Gradient {
+            options: GradientOptions {
+                dither: false,
+                ..GradientOptions::default()
+            },
+            angle: 0.0,
+            foreground: Color::black(),
+            background: Color::white(),
+        };
+        render(&mut layer, &before, &region, (0, 0, 8, 16), &content, 1.0);
+
+        assert!(layer.get_pixel(0, 4).unwrap().r < 0.1);
+        assert!(layer.get_pixel(7, 4).unwrap().r > 0.9);
+        assert_eq!(layer.get_pixel(12, 4).unwrap().a, 0.0);
+    }
+}
@@ -21,7 +21,7 @@ impl GaussianBlur {
     }
 
     /// Calculate box sizes for Gaussian approximation
-    fn boxes_for_gauss(sigma: f32, n: usize) -> Vec<u32> {
+    pub(crate) fn boxes_for_gauss(sigma: f32, n: usize) -> Vec<u32> {
         let w_ideal = ((12.0 * sigma * sigma / n as f32) + 1.0).sqrt();
         let mut wl = w_ideal.floor() as u32;
         if wl % 2 == 0 {
@@ -42,7 +42,15 @@ pub trait Filter: Send + Sync {
     }
 
     /// Apply filter respecting a selection mask
+    ///
+    /// Partially selected pixels mix the filtered and original colors.
+    /// Without an active selection the whole layer is filtered.
     fn apply_with_selection(&self, layer: &mut Layer, selection: &Selection) {
+        if !selection.is_active {
+            self.apply_to_layer(layer);
+            return;
+        }
+
         let width = layer.width();
         let height = layer.height();
 
@@ -50,20 +58,9 @@ pub trait Filter: Send + Sync {
         let mut filtered = layer.pixels.clone();
         self.apply(&mut filtered, width, height);
 
-        // Blend filtered result with original based on selection
-        for y in 0..height {
-            for x in 0..width {
-                if selection.contains(x as f32, y as f32) {
-                    let idx = ((y * width + x) * 4) as usize;
-                    if idx + 3 < layer.pixels.len() {
-                        layer.pixels[idx] = filtered[idx];
-                        layer.pixels[idx + 1] = filtered[idx + 1];
-                        layer.pixels[idx + 2] = filtered[idx + 2];
-                        layer.pixels[idx + 3] = filtered[idx + 3];
-                    }
-                }
-            }
-        }
+        // Blend filtered result with original based on selection coverage
+        selection.apply_to_pixels(&layer.pixels, &mut filtered, width);
+        layer.pixels = filtered;
     }
 
     /// Get filter name for UI/history
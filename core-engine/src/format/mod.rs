@@ -3,11 +3,16 @@
 //! Supports native .dcpaint format and import/export of common formats
 
 use crate::canvas::Canvas;
+use crate::color::Color;
 use crate::error::{EngineError, EngineResult};
-use crate::layer::LayerManager;
+use crate::history::{HistoryManager, SavedHistory};
+use crate::layer::{Layer, LayerManager};
+use crate::selection::SelectionChannel;
+use crate::shape::Shape;
 
 use serde::{Deserialize, Serialize};
 use std::path::Path;
+use uuid::Uuid;
 
 /// Supported file formats
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -116,7 +121,11 @@ impl DcPaintHeader {
     pub const MAGIC: [u8; 8] = *b"DCPAINT\0";
 
     /// Current file format version
-    pub const VERSION: u32 = 1;
+    ///
+    /// - 1: header followed by unframed data (only the header is readable)
+    /// - 2: header followed by tagged sections (layers, tiles, history,
+    ///   selection channels and vector shapes)
+    pub const VERSION: u32 = 2;
 
     /// Create new header
     pub fn new(width: u32, height: u32) -> Self {
@@ -185,6 +194,56 @@ impl Default for ExportOptions {
     }
 }
 
+/// Tag of the layer section
+const SECTION_LAYERS: [u8; 4] = *b"LAYR";
+/// Tag of the canvas tile section
+const SECTION_TILES: [u8; 4] = *b"TILE";
+/// Tag of the undo history section
+const SECTION_HISTORY: [u8; 4] = *b"HIST";
+/// Tag of the saved selection channel section
+const SECTION_CHANNELS: [u8; 4] = *b"CHAN";
+/// Tag of the vector layer shape section
+const SECTION_SHAPES: [u8; 4] = *b"SHAP";
+
+/// Default amount of undo history stored in documents (64 MB)
+pub const DEFAULT_SAVED_HISTORY_BYTES: usize = 64 * 1024 * 1024;
+
+/// A tagged section read from a native document
+struct Section<'a> {
+    tag: [u8; 4],
+    payload: &'a [u8],
+    /// Offset of the following section
+    next: usize,
+}
+
+/// A layer as stored in a native document
+#[derive(Serialize, Deserialize)]
+struct SavedLayer {
+    /// Layer properties (pixels are skipped by serde)
+    layer: Layer,
+    /// Zstd-compressed pixel data
+    pixels: Vec<u8>,
+}
+
+/// Layer section of a native document
+#[derive(Serialize, Deserialize)]
+struct SavedLayers {
+    layers: Vec<SavedLayer>,
+    active_layer: Option<Uuid>,
+}
+
+/// Contents of a native document
+pub struct NativeDocument {
+    /// Canvas
+    pub canvas: Canvas,
+    /// Layers
+    pub layer_manager: LayerManager,
+    /// Saved selection channels
+    pub channels: Vec<SelectionChannel>,
+    /// Saved undo history, if the file has one this version can read
+    pub history: Option<SavedHistory>,
+}
+
 /// File handler for saving and loading
 pub struct FileHandler;
 
@@ -195,32 +254,88 @@ impl FileHandler {
         canvas: &Canvas,
         layer_manager: &LayerManager,
     ) -> EngineResult<()> {
-        let header = DcPaintHeader::new(canvas.width(), canvas.height());
+        Self::save_native_with_history(path, canvas, layer_manager, None)
+    }
+
+    /// Save to native format, storing up to `max_history_bytes` of undo history
+    pub fn save_native_with_history(
+        path: &Path,
+        canvas: &Canvas,
+        layer_manager: &LayerManager,
+        history: Option<(&HistoryManager, usize)>,
+    ) -> EngineResult<()> {
+        Self::save_native_document(path, canvas, layer_manager, &[], history)
+    }
+
+    /// Save to native format with selection channels and optional undo history
+    pub fn save_native_document(
+        path: &Path,
+        canvas: &Canvas,
+        layer_manager: &LayerManager,
+        channels: &[SelectionChannel],
+        history: Option<(&HistoryManager, usize)>,
+    ) -> EngineResult<()> {
+        let mut header = DcPaintHeader::new(canvas.width(), canvas.height());
+        header.dpi = canvas.settings().dpi;
+        header.color_profile = canvas.settings().color_profile.clone();
+        header.layer_count = layer_manager.layer_count() as u32;
 
         // Serialize header
-        let header_data = bincode::serialize(&header)?;
+        let mut file_data = bincode::serialize(&header)?;
 
         // Serialize layer data
         let layers_data = Self::serialize_layers(layer_manager)?;
+        Self::write_section(&mut file_data, SECTION_LAYERS, &layers_data);
 
         // Compress tile data
         let tiles_data = Self::compress_tiles(canvas)?;
+        Self::write_section(&mut file_data, SECTION_TILES, &tiles_data);
+
+        // Compress vector layer shapes
+        let shapes: Vec<(Uuid, Vec<Shape>)> = layer_manager
+            .layers()
+            .iter()
+            .map(|layer_arc| layer_arc.read())
+            .filter(|layer| !layer.shapes.is_empty())
+            .map(|layer| (layer.id, layer.shapes.clone()))
+            .collect();
+        if !shapes.is_empty() {
+            let shape_data = zstd::encode_all(bincode::serialize(&shapes)?.as_slice(), 3)?;
+            Self::write_section(&mut file_data, SECTION_SHAPES, &shape_data);
+        }
 
-        // Write file
-        let mut file_data = Vec::new();
-        file_data.extend_from_slice(&header_data);
-        file_data.extend_from_slice(&layers_data);
-        file_data.extend_from_slice(&tiles_data);
+        // Compress selection channels
+        if !channels.is_empty() {
+            let channel_data = zstd::encode_all(bincode::serialize(channels)?.as_slice(), 3)?;
+            Self::write_section(&mut file_data, SECTION_CHANNELS, &channel_data);
+        }
+
+        // Compress history deltas
+        if let Some((history, max_bytes)) = history {
+            let saved = history.export_history(max_bytes);
+            let history_data = zstd::encode_all(saved.encode()?.as_slice(), 3)?;
+            Self::write_section(&mut file_data, SECTION_HISTORY, &history_data);
+        }
 
+        // Write file
         std::fs::write(path, file_data)?;
         Ok(())
     }
 
     /// Load from native format
     pub fn load_native(path: &Path) -> EngineResult<(Canvas, LayerManager)> {
+        let document = Self::load_native_document(path)?;
+        Ok((document.canvas, document.layer_manager))
+    }
+
+    /// Load from native format, including saved undo history
+    ///
+    /// Version 1 files and files whose history uses an unknown layout still
+    /// load, just without history.
+    pub fn load_native_document(path: &Path) -> EngineResult<NativeDocument> {
         let data = std::fs::read(path)?;
 
-        if data.len() < 8 || &data[0..8] != DcPaintHeader::MAGIC {
+        if data.len() < 8 || data[0..8] != DcPaintHeader::MAGIC {
             return Err(EngineError::UnsupportedFormat(
                 "Invalid dcpaint file".into(),
             ));
@@ -235,10 +350,56 @@ impl FileHandler {
         }
 
         // Create canvas
-        let canvas = Canvas::with_size(header.width, header.height)?;
-        let layer_manager = LayerManager::with_canvas_size(header.width, header.height);
+        let mut canvas = Canvas::with_size(header.width, header.height)?;
+        canvas.settings_mut().dpi = header.dpi;
+        canvas.settings_mut().color_profile = header.color_profile.clone();
+        let mut layer_manager = LayerManager::with_canvas_size(header.width, header.height);
+        let mut channels = Vec::new();
+        let mut history = None;
+        let mut shapes: Vec<(Uuid, Vec<Shape>)> = Vec::new();
+
+        if header.version >= 2 {
+            let mut offset = bincode::serialized_size(&header)? as usize;
+            while let Some(section) = Self::read_section(&data, offset)? {
+                match section.tag {
+                    SECTION_LAYERS => Self::deserialize_layers(section.payload, &mut layer_manager)?,
+                    SECTION_TILES => Self::decompress_tiles(section.payload, &mut canvas)?,
+                    SECTION_CHANNELS => {
+                        let raw = zstd::decode_all(section.payload)?;
+                        channels = bincode::deserialize(&raw)?;
+                    }
+                    SECTION_SHAPES => {
+                        let raw = zstd::decode_all(section.payload)?;
+                        shapes = bincode::deserialize(&raw)?;
+                    }
+                    SECTION_HISTORY => {
+                        let decoded = zstd::decode_all(section.payload)
+                            .map_err(EngineError::from)
+                            .and_then(|raw| SavedHistory::decode(&raw));
+                        match decoded {
+                            Ok(saved) => history = Some(saved),
+                            Err(e) => log::warn!("Ignoring saved history in {:?}: {}", path, e),
+                        }
+                    }
+                    // Sections from newer writers are skipped
+                    _ => {}
+                }
+                offset = section.next;
+            }
+        }
+
+        for (layer_id, layer_shapes) in shapes {
+            if let Some(layer_arc) = layer_manager.get_layer(layer_id) {
+                layer_arc.write().shapes = layer_shapes;
+            }
+        }
 
-        Ok((canvas, layer_manager))
+        Ok(NativeDocument {
+            canvas,
+            layer_manager,
+            channels,
+            history,
+        })
     }
 
     /// Export to image format
@@ -287,10 +448,71 @@ impl FileHandler {
         Ok((pixels, width, height))
     }
 
+    /// Append a section as tag, little-endian u64 length and payload
+    fn write_section(out: &mut Vec<u8>, tag: [u8; 4], payload: &[u8]) {
+        out.extend_from_slice(&tag);
+        out.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+        out.extend_from_slice(payload);
+    }
+
+    /// Read the section at `offset`
+    fn read_section(data: &[u8], offset: usize) -> EngineResult<Option<Section<'_>>> {
+        if offset >= data.len() {
+            return Ok(None);
+        }
+        if offset + 12 > data.len() {
+            return Err(EngineError::SerializationError("Truncated section header".into()));
+        }
+
+        let mut tag = [0u8; 4];
+        tag.copy_from_slice(&data[offset..offset + 4]);
+        let mut len_bytes = [0u8; 8];
+        len_bytes.copy_from_slice(&data[offset + 4..offset + 12]);
+        let len = u64::from_le_bytes(len_bytes) as usize;
+
+        let start = offset + 12;
+        let end = start
+            .checked_add(len)
+            .filter(|&end| end <= data.len())
+            .ok_or_else(|| EngineError::SerializationError("Truncated section data".into()))?;
+
+        Ok(Some(Section {
+            tag,
+            payload: &data[start..end],
+            next: end,
+        }))
+    }
+
     fn serialize_layers(layer_manager: &LayerManager) -> EngineResult<Vec<u8>> {
-        // Simplified - would serialize layer metadata
-        let data = Vec::new();
-        Ok(data)
+        let mut layers = Vec::with_capacity(layer_manager.layer_count());
+        for layer_arc in layer_manager.layers() {
+            let layer = layer_arc.read();
+            let pixels = zstd::encode_all(layer.pixels.as_slice(), 3)?;
+            layers.push(SavedLayer {
+                layer: layer.clone(),
+                pixels,
+            });
+        }
+
+        let saved = SavedLayers {
+            layers,
+            active_layer: layer_manager.active_layer().map(|l| l.read().id),
+        };
+        Ok(bincode::serialize(&saved)?)
+    }
+
+    fn deserialize_layers(data: &[u8], layer_manager: &mut LayerManager) -> EngineResult<()> {
+        let saved: SavedLayers = bincode::deserialize(data)?;
+        for saved_layer in saved.layers {
+            let mut layer = saved_layer.layer;
+            layer.pixels = zstd::decode_all(saved_layer.pixels.as_slice())?;
+            layer_manager.add_existing_layer(layer);
+        }
+
+        if let Some(id) = saved.active_layer {
+            layer_manager.set_active_layer(id)?;
+        }
+        Ok(())
     }
 
     fn compress_tiles(canvas: &Canvas) -> EngineResult<Vec<u8>> {
@@ -298,6 +520,23 @@ impl FileHandler {
         let compressed = zstd::encode_all(pixels.as_slice(), 3)?;
         Ok(compressed)
     }
+
+    fn decompress_tiles(data: &[u8], canvas: &mut Canvas) -> EngineResult<()> {
+        let pixels = zstd::decode_all(data)?;
+        let width = canvas.width();
+
+        for (i, px) in pixels.chunks_exact(4).enumerate() {
+            // Unallocated tiles are transparent already
+            if px[3] == 0 {
+                continue;
+            }
+            let x = i as u32 % width;
+            let y = i as u32 / width;
+            canvas.set_pixel(x, y, Color::from_rgba8(px[0], px[1], px[2], px[3]))?;
+        }
+        canvas.clear_modified();
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -327,4 +566,78 @@ mod tests {
         assert!(FileFormat::DcPaint.supports_layers());
         assert!(!FileFormat::Png.supports_layers());
     }
+
+    #[test]
+    fn test_native_round_trip_with_history() {
+        use crate::history::{DirtyRect, HistoryState, LayerSnapshot};
+
+        let path = std::env::temp_dir().join(format!("dc-format-{}.dcpaint", Uuid::new_v4()));
+        let canvas = Canvas::with_size(32, 32).unwrap();
+        let mut layer_manager = LayerManager::with_canvas_size(32, 32);
+        let layer_id = layer_manager.add_layer("Ink");
+        let layer_arc = layer_manager.get_layer(layer_id).unwrap();
+        layer_arc.write().fill(Color::red());
+
+        let mut history = HistoryManager::new();
+        let mut state = HistoryState::new("Fill");
+        state.add_snapshot(LayerSnapshot::incremental(
+            layer_id,
+            &vec![0u8; 32 * 32 * 4],
+            32,
+            32,
+            DirtyRect::full(32, 32),
+        ));
+        history.push_state(state);
+
+        FileHandler::save_native_with_history(&path, &canvas, &layer_manager, Some((&history, usize::MAX)))
+            .unwrap();
+        let document = FileHandler::load_native_document(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(document.layer_manager.layer_count(), 1);
+        let layer = document.layer_manager.get_layer(layer_id).unwrap();
+        assert!(layer.read().get_pixel(5, 5).unwrap().r > 0.99);
+        assert_eq!(document.history.unwrap().node_count(), 2);
+    }
+
+    #[test]
+    fn test_vector_shapes_round_trip() {
+        use crate::shape::{ShapeKind, ShapeStyle};
+
+        let path = std::env::temp_dir().join(format!("dc-format-{}.dcpaint", Uuid::new_v4()));
+        let canvas = Canvas::with_size(32, 32).unwrap();
+        let mut layer_manager = LayerManager::with_canvas_size(32, 32);
+        let shape = Shape::new(
+            ShapeKind::Star { points: 5, inner_ratio: 0.4 },
+            (4.0, 4.0),
+            (28.0, 28.0),
+            ShapeStyle::default(),
+        );
+        let layer_id = layer_manager.add_existing_layer(Layer::with_shape(shape.clone(), 32, 32));
+
+        FileHandler::save_native(&path, &canvas, &layer_manager).unwrap();
+        let document = FileHandler::load_native_document(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        let layer = document.layer_manager.get_layer(layer_id).unwrap();
+        assert_eq!(layer.read().shapes, vec![shape]);
+        assert!(layer.read().get_pixel(16, 16).unwrap().a > 0.99);
+    }
+
+    #[test]
+    fn test_version_1_file_loads() {
+        let path = std::env::temp_dir().join(format!("dc-format-{}.dcpaint", Uuid::new_v4()));
+        let mut header = DcPaintHeader::new(64, 48);
+        header.version = 1;
+        let mut data = bincode::serialize(&header).unwrap();
+        // Version 1 wrote unframed tile data after the header
+        data.extend_from_slice(&[0xAB; 16]);
+        std::fs::write(&path, data).unwrap();
+
+        let document = FileHandler::load_native_document(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(document.canvas.width(), 64);
+        assert!(document.history.is_none());
+    }
 }
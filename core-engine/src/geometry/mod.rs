@@ -144,6 +144,20 @@ impl Transform {
         }
     }
 
+    /// Skew transform (angles in radians)
+    pub fn skew(angle_x: f32, angle_y: f32) -> Self {
+        Self {
+            matrix: [1.0, angle_y.tan(), angle_x.tan(), 1.0, 0.0, 0.0],
+        }
+    }
+
+    /// Apply this transform around a pivot point instead of the origin
+    pub fn around(&self, pivot: Vec2) -> Transform {
+        Transform::translation(-pivot.x, -pivot.y)
+            .multiply(self)
+            .multiply(&Transform::translation(pivot.x, pivot.y))
+    }
+
     /// Combine two transforms
     pub fn multiply(&self, other: &Transform) -> Transform {
         let a = self.matrix;
@@ -200,6 +214,164 @@ impl Default for Transform {
     }
 }
 
+/// 2D projective transformation (homography)
+///
+/// Maps straight lines to straight lines but, unlike [`Transform`], not
+/// parallel lines to parallel lines, so it can give content perspective.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Homography {
+    /// Matrix elements [a, b, c, d, e, f, g, h, i]
+    /// | a  b  c |
+    /// | d  e  f |
+    /// | g  h  i |
+    /// applied to column vectors (x, y, 1) and divided by the third row
+    pub matrix: [f32; 9],
+}
+
+impl Homography {
+    /// Identity homography
+    pub fn identity() -> Self {
+        Self {
+            matrix: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+        }
+    }
+
+    /// Homography doing the same as an affine transform
+    pub fn from_transform(transform: &Transform) -> Self {
+        let m = &transform.matrix;
+        Self {
+            matrix: [m[0], m[2], m[4], m[1], m[3], m[5], 0.0, 0.0, 1.0],
+        }
+    }
+
+    /// Homography taking the corners of `from` to the corners of `to`
+    ///
+    /// Both quads are ordered top-left, top-right, bottom-right,
+    /// bottom-left. Returns `None` if either is degenerate, e.g. has three
+    /// corners on a line.
+    pub fn from_quads(from: [Vec2; 4], to: [Vec2; 4]) -> Option<Self> {
+        let from = square_to_quad(from)?;
+        let to = square_to_quad(to)?;
+        Some(Self::from_f64(multiply3(&to, &invert3(&from)?)))
+    }
+
+    /// Combine two homographies, applying `self` first
+    pub fn multiply(&self, other: &Homography) -> Homography {
+        Self::from_f64(multiply3(&other.to_f64(), &self.to_f64()))
+    }
+
+    /// Transform a point
+    ///
+    /// Returns `None` for points on or beyond the horizon line, which have
+    /// no finite image.
+    pub fn project(&self, point: Vec2) -> Option<Vec2> {
+        let m = &self.matrix;
+        let w = m[6] * point.x + m[7] * point.y + m[8];
+        if w.abs() < 1e-6 {
+            return None;
+        }
+        Some(Vec2::new(
+            (m[0] * point.x + m[1] * point.y + m[2]) / w,
+            (m[3] * point.x + m[4] * point.y + m[5]) / w,
+        ))
+    }
+
+    /// Get inverse homography
+    pub fn inverse(&self) -> Option<Homography> {
+        invert3(&self.to_f64()).map(Self::from_f64)
+    }
+
+    /// The equivalent affine transform, if there is no perspective
+    pub fn to_transform(&self) -> Option<Transform> {
+        let m = &self.matrix;
+        if m[6].abs() > 1e-7 || m[7].abs() > 1e-7 || m[8].abs() < 1e-7 {
+            return None;
+        }
+        let i = m[8];
+        Some(Transform {
+            matrix: [m[0] / i, m[3] / i, m[1] / i, m[4] / i, m[2] / i, m[5] / i],
+        })
+    }
+
+    fn to_f64(self) -> [f64; 9] {
+        self.matrix.map(|v| v as f64)
+    }
+
+    /// Scale so the matrix stays well inside f32 range
+    fn from_f64(m: [f64; 9]) -> Self {
+        let scale = m.iter().fold(0.0f64, |max, v| max.max(v.abs()));
+        let scale = if scale > 0.0 { scale } else { 1.0 };
+        let scale = if m[8] < 0.0 { -scale } else { scale };
+        Self {
+            matrix: m.map(|v| (v / scale) as f32),
+        }
+    }
+}
+
+impl Default for Homography {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+/// Homography from the unit square to a quad (Heckbert's closed form)
+fn square_to_quad(quad: [Vec2; 4]) -> Option<[f64; 9]> {
+    let [(x0, y0), (x1, y1), (x2, y2), (x3, y3)] = quad.map(|p| (p.x as f64, p.y as f64));
+    let (sx, sy) = (x0 - x1 + x2 - x3, y0 - y1 + y2 - y3);
+    let (dx1, dx2, dy1, dy2) = (x1 - x2, x3 - x2, y1 - y2, y3 - y2);
+    let det = dx1 * dy2 - dx2 * dy1;
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let g = (sx * dy2 - dx2 * sy) / det;
+    let h = (dx1 * sy - sx * dy1) / det;
+    let m = [
+        x1 - x0 + g * x1,
+        x3 - x0 + h * x3,
+        x0,
+        y1 - y0 + g * y1,
+        y3 - y0 + h * y3,
+        y0,
+        g,
+        h,
+        1.0,
+    ];
+    invert3(&m).map(|_| m)
+}
+
+fn multiply3(a: &[f64; 9], b: &[f64; 9]) -> [f64; 9] {
+    let mut out = [0.0; 9];
+    for row in 0..3 {
+        for col in 0..3 {
+            out[row * 3 + col] = (0..3).map(|k| a[row * 3 + k] * b[k * 3 + col]).sum();
+        }
+    }
+    out
+}
+
+fn invert3(m: &[f64; 9]) -> Option<[f64; 9]> {
+    let cofactor = |r0: usize, r1: usize, c0: usize, c1: usize| {
+        m[r0 * 3 + c0] * m[r1 * 3 + c1] - m[r0 * 3 + c1] * m[r1 * 3 + c0]
+    };
+    let adjugate = [
+        cofactor(1, 2, 1, 2),
+        -cofactor(0, 2, 1, 2),
+        cofactor(0, 1, 1, 2),
+        -cofactor(1, 2, 0, 2),
+        cofactor(0, 2, 0, 2),
+        -cofactor(0, 1, 0, 2),
+        cofactor(1, 2, 0, 1),
+        -cofactor(0, 2, 0, 1),
+        cofactor(0, 1, 0, 1),
+    ];
+    let det = m[0] * adjugate[0] + m[1] * adjugate[3] + m[2] * adjugate[6];
+    let size = m.iter().fold(0.0f64, |max, v| max.max(v.abs()));
+    if det.abs() <= 1e-12 * size.powi(3) || det == 0.0 {
+        return None;
+    }
+    Some(adjugate.map(|v| v / det))
+}
+
 /// Bezier curve utilities
 pub struct Bezier;
 
@@ -262,6 +434,36 @@ mod tests {
         assert_eq!(intersection.width, 50.0);
     }
 
+    #[test]
+    fn test_homography_maps_quad_corners() {
+        let square = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)].map(|(x, y)| Vec2::new(x, y));
+        let quad = [(2.0, 1.0), (30.0, 4.0), (25.0, 20.0), (0.0, 12.0)].map(|(x, y)| Vec2::new(x, y));
+        let h = Homography::from_quads(square, quad).unwrap();
+        for (from, to) in square.iter().zip(quad) {
+            assert!((h.project(*from).unwrap() - to).length() < 1e-3);
+        }
+
+        let back = h.multiply(&h.inverse().unwrap());
+        let p = Vec2::new(3.0, 7.0);
+        assert!((back.project(p).unwrap() - p).length() < 1e-3);
+        assert!(h.to_transform().is_none());
+
+        let collinear = [(0.0, 0.0), (5.0, 0.0), (10.0, 0.0), (0.0, 10.0)].map(|(x, y)| Vec2::new(x, y));
+        assert!(Homography::from_quads(square, collinear).is_none());
+    }
+
+    #[test]
+    fn test_homography_from_transform_round_trips() {
+        let transform = Transform::rotation(0.3).multiply(&Transform::translation(5.0, -2.0));
+        let h = Homography::from_transform(&transform);
+        let p = Vec2::new(4.0, 9.0);
+        assert!((h.project(p).unwrap() - transform.transform_point(p)).length() < 1e-4);
+        let back = h.to_transform().unwrap();
+        for (a, b) in back.matrix.iter().zip(transform.matrix) {
+            assert!((a - b).abs() < 1e-4);
+        }
+    }
+
     #[test]
     fn test_transform_point() {
         let translate = Transform::translation(10.0, 20.0);
@@ -0,0 +1,479 @@
+//! Gradient Module
+//!
+//! Multi-stop gradient definitions and rendering. A gradient has separate
+//! color and opacity stops, each with a midpoint that moves where the
+//! transition between two stops is halfway done. Rendering maps every pixel
+//! to a position along the gradient according to the shape and repeat mode,
+//! optionally dithers the result to hide banding, and blends it into a layer.
+
+use crate::color::Color;
+use crate::layer::{BlendMode, Layer};
+use serde::{Deserialize, Serialize};
+
+/// Number of entries in the lookup table used while rendering
+const LUT_SIZE: usize = 4096;
+
+/// 4x4 Bayer matrix used for ordered dithering
+const BAYER_4X4: [[f32; 4]; 4] = [
+    [0.0, 8.0, 2.0, 10.0],
+    [12.0, 4.0, 14.0, 6.0],
+    [3.0, 11.0, 1.0, 9.0],
+    [15.0, 7.0, 13.0, 5.0],
+];
+
+/// How positions map onto the gradient
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GradientShape {
+    /// Along the drag direction
+    #[default]
+    Linear,
+    /// Outwards from the start point
+    Radial,
+    /// Sweeping around the start point, starting at the drag direction
+    Angle,
+    /// Along the drag direction, mirrored behind the start point
+    Reflected,
+    /// Outwards from the start point in a diamond
+    Diamond,
+}
+
+/// What happens past the end of the gradient
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GradientRepeat {
+    /// Extend the end colors
+    #[default]
+    None,
+    /// Start over from the beginning
+    Repeat,
+    /// Run back and forth
+    Reflect,
+}
+
+/// Color of a gradient stop
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum StopColor {
+    /// A fixed color
+    Fixed(Color),
+    /// The primary color at the time of rendering
+    Foreground,
+    /// The secondary color at the time of rendering
+    Background,
+}
+
+impl StopColor {
+    fn resolve(&self, foreground: Color, background: Color) -> Color {
+        match self {
+            StopColor::Fixed(color) => *color,
+            StopColor::Foreground => foreground,
+            StopColor::Background => background,
+        }
+    }
+}
+
+/// Color stop of a gradient
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ColorStop {
+    /// Position along the gradient (0.0 - 1.0)
+    pub position: f32,
+    /// Where between this stop and the next the colors are mixed evenly,
+    /// as a fraction of the distance (0.0 - 1.0)
+    pub midpoint: f32,
+    /// Stop color
+    pub color: StopColor,
+}
+
+impl ColorStop {
+    /// Create a color stop with a centered midpoint
+    pub fn new(position: f32, color: StopColor) -> Self {
+        Self {
+            position: position.clamp(0.0, 1.0),
+            midpoint: 0.5,
+            color,
+        }
+    }
+}
+
+/// Opacity stop of a gradient
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct OpacityStop {
+    /// Position along the gradient (0.0 - 1.0)
+    pub position: f32,
+    /// Where between this stop and the next the opacities are mixed evenly,
+    /// as a fraction of the distance (0.0 - 1.0)
+    pub midpoint: f32,
+    /// Opacity (0.0 - 1.0)
+    pub opacity: f32,
+}
+
+impl OpacityStop {
+    /// Create an opacity stop with a centered midpoint
+    pub fn new(position: f32, opacity: f32) -> Self {
+        Self {
+            position: position.clamp(0.0, 1.0),
+            midpoint: 0.5,
+            opacity: opacity.clamp(0.0, 1.0),
+        }
+    }
+}
+
+/// A multi-stop gradient
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Gradient {
+    /// Gradient name
+    pub name: String,
+    /// Color stops, sorted by position
+    pub color_stops: Vec<ColorStop>,
+    /// Opacity stops, sorted by position
+    pub opacity_stops: Vec<OpacityStop>,
+}
+
+impl Default for Gradient {
+    fn default() -> Self {
+        Self::foreground_to_background()
+    }
+}
+
+impl Gradient {
+    /// Create a gradient from its stops
+    pub fn new(
+        name: impl Into<String>,
+        color_stops: Vec<ColorStop>,
+        opacity_stops: Vec<OpacityStop>,
+    ) -> Self {
+        let mut gradient = Self {
+            name: name.into(),
+            color_stops,
+            opacity_stops,
+        };
+        gradient.sort_stops();
+        gradient
+    }
+
+    /// Primary to secondary color
+    pub fn foreground_to_background() -> Self {
+        Self::new(
+            "Foreground to Background",
+            vec![
+                ColorStop::new(0.0, StopColor::Foreground),
+                ColorStop::new(1.0, StopColor::Background),
+            ],
+            vec![OpacityStop::new(0.0, 1.0), OpacityStop::new(1.0, 1.0)],
+        )
+    }
+
+    /// Primary color fading out
+    pub fn foreground_to_transparent() -> Self {
+        Self::new(
+            "Foreground to Transparent",
+            vec![
+                ColorStop::new(0.0, StopColor::Foreground),
+                ColorStop::new(1.0, StopColor::Foreground),
+            ],
+            vec![OpacityStop::new(0.0, 1.0), OpacityStop::new(1.0, 0.0)],
+        )
+    }
+
+    /// Between two fixed colors
+    pub fn two_color(from: Color, to: Color) -> Self {
+        Self::new(
+            "Custom",
+            vec![
+                ColorStop::new(0.0, StopColor::Fixed(from)),
+                ColorStop::new(1.0, StopColor::Fixed(to)),
+            ],
+            vec![OpacityStop::new(0.0, 1.0), OpacityStop::new(1.0, 1.0)],
+        )
+    }
+
+    /// Add a color stop, keeping the stops sorted
+    pub fn add_color_stop(&mut self, stop: ColorStop) {
+        self.color_stops.push(stop);
+        self.sort_stops();
+    }
+
+    /// Add an opacity stop, keeping the stops sorted
+    pub fn add_opacity_stop(&mut self, stop: OpacityStop) {
+        self.opacity_stops.push(stop);
+        self.sort_stops();
+    }
+
+    /// Remove a color stop, returning it
+    pub fn remove_color_stop(&mut self, index: usize) -> Option<ColorStop> {
+        (index < self.color_stops.len()).then(|| self.color_stops.remove(index))
+    }
+
+    /// Remove an opacity stop, returning it
+    pub fn remove_opacity_stop(&mut self, index: usize) -> Option<OpacityStop> {
+        (index < self.opacity_stops.len()).then(|| self.opacity_stops.remove(index))
+    }
+
+    /// Sort the stops by position, e.g. after editing them directly
+    pub fn sort_stops(&mut self) {
+        self.color_stops
+            .sort_by(|a, b| a.position.total_cmp(&b.position));
+        self.opacity_stops
+            .sort_by(|a, b| a.position.total_cmp(&b.position));
+    }
+
+    /// Color at a position (0.0 - 1.0), resolving foreground and background stops
+    ///
+    /// Without color stops the gradient is black; without opacity stops it is
+    /// opaque.
+    pub fn sample(&self, t: f32, foreground: Color, background: Color) -> Color {
+        let t = t.clamp(0.0, 1.0);
+        let color = match stop_span(&self.color_stops, t, |stop| stop.position, |stop| stop.midpoint) {
+            Some((from, to, mix)) => from
+                .color
+                .resolve(foreground, background)
+                .lerp(&to.color.resolve(foreground, background), mix),
+            None => Color::black(),
+        };
+        let opacity = match stop_span(&self.opacity_stops, t, |stop| stop.position, |stop| stop.midpoint) {
+            Some((from, to, mix)) => from.opacity + (to.opacity - from.opacity) * mix,
+            None => 1.0,
+        };
+        color.with_alpha(color.a * opacity)
+    }
+
+    /// Sample the gradient at evenly spaced positions
+    pub fn lookup_table(&self, size: usize, foreground: Color, background: Color) -> Vec<Color> {
+        let last = size.saturating_sub(1).max(1) as f32;
+        (0..size)
+            .map(|i| self.sample(i as f32 / last, foreground, background))
+            .collect()
+    }
+}
+
+/// The two stops around `t` and how far `t` is between them, midpoint adjusted
+fn stop_span<S>(
+    stops: &[S],
+    t: f32,
+    position: impl Fn(&S) -> f32,
+    midpoint: impl Fn(&S) -> f32,
+) -> Option<(&S, &S, f32)> {
+    let first = stops.first()?;
+    let last = stops.last()?;
+    if t <= position(first) {
+        return Some((first, first, 0.0));
+    }
+    if t >= position(last) {
+        return Some((last, last, 0.0));
+    }
+
+    let next = stops.iter().position(|stop| position(stop) > t)?;
+    let (from, to) = (&stops[next - 1], &stops[next]);
+    let span = position(to) - position(from);
+    if span <= f32::EPSILON {
+        return Some((to, to, 0.0));
+    }
+    let local = (t - position(from)) / span;
+    let mid = midpoint(from).clamp(0.01, 0.99);
+    let mix = if local < mid {
+        0.5 * local / mid
+    } else {
+        0.5 + 0.5 * (local - mid) / (1.0 - mid)
+    };
+    Some((from, to, mix))
+}
+
+/// How a gradient is drawn
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GradientOptions {
+    /// Gradient to draw
+    pub gradient: Gradient,
+    /// Gradient shape
+    pub shape: GradientShape,
+    /// Behavior past the end point
+    pub repeat: GradientRepeat,
+    /// Run the gradient from end to start
+    pub reverse: bool,
+    /// Add ordered noise below one 8-bit step to avoid banding
+    pub dither: bool,
+    /// How the gradient blends with the existing pixels
+    pub blend_mode: BlendMode,
+}
+
+impl Default for GradientOptions {
+    fn default() -> Self {
+        Self {
+            gradient: Gradient::default(),
+            shape: GradientShape::Linear,
+            repeat: GradientRepeat::None,
+            reverse: false,
+            dither: true,
+            blend_mode: BlendMode::Normal,
+        }
+    }
+}
+
+impl GradientOptions {
+    /// Gradient position of a point for a drag from `start` to `end`
+    ///
+    /// Returns `None` when the drag is too short to define a gradient.
+    pub fn position(&self, start: (f32, f32), end: (f32, f32), point: (f32, f32)) -> Option<f32> {
+        let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+        let length_squared = dx * dx + dy * dy;
+        if length_squared < 1.0 {
+            return None;
+        }
+        let length = length_squared.sqrt();
+        let (px, py) = (point.0 - start.0, point.1 - start.1);
+        // Coordinates along and across the drag, in drag lengths
+        let along = (px * dx + py * dy) / length_squared;
+        let across = (py * dx - px * dy) / length_squared;
+
+        let t = match self.shape {
+            GradientShape::Linear => along,
+            GradientShape::Radial => px.hypot(py) / length,
+            GradientShape::Angle => {
+                let angle = across.atan2(along);
+                (-angle / std::f32::consts::TAU).rem_euclid(1.0)
+            }
+            GradientShape::Reflected => along.abs(),
+            GradientShape::Diamond => along.abs() + across.abs(),
+        };
+
+        let t = match self.repeat {
+            GradientRepeat::None => t.clamp(0.0, 1.0),
+            GradientRepeat::Repeat => t.rem_euclid(1.0),
+            GradientRepeat::Reflect => {
+                let folded = t.rem_euclid(2.0);
+                if folded > 1.0 {
+                    2.0 - folded
+                } else {
+                    folded
+                }
+            }
+        };
+        Some(if self.reverse { 1.0 - t } else { t })
+    }
+
+    /// Draw the gradient for a drag from `start` to `end` into a layer
+    ///
+    /// Only pixels inside `area` (x, y, width, height) are touched, and
+    /// `opacity` scales the gradient's own opacity. Returns false when the
+    /// drag is too short.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render(
+        &self,
+        layer: &mut Layer,
+        area: (u32, u32, u32, u32),
+        start: (f32, f32),
+        end: (f32, f32),
+        foreground: Color,
+        background: Color,
+        opacity: f32,
+    ) -> bool {
+        if self.position(start, end, start).is_none() {
+            return false;
+        }
+        let lut = self.gradient.lookup_table(LUT_SIZE, foreground, background);
+        let scale = (LUT_SIZE - 1) as f32;
+        let (x0, y0, width, height) = area;
+
+        for y in y0..y0 + height {
+            for x in x0..x0 + width {
+                let Some(base) = layer.get_pixel(x, y) else {
+                    continue;
+                };
+                let t = self
+                    .position(start, end, (x as f32 + 0.5, y as f32 + 0.5))
+                    .unwrap_or(0.0);
+                let mut color = lut[(t * scale).round() as usize];
+                if self.dither {
+                    let noise = (BAYER_4X4[(y % 4) as usize][(x % 4) as usize] + 0.5) / 16.0 - 0.5;
+                    let noise = noise / 255.0;
+                    color = Color::from_rgba(
+                        (color.r + noise).clamp(0.0, 1.0),
+                        (color.g + noise).clamp(0.0, 1.0),
+                        (color.b + noise).clamp(0.0, 1.0),
+                        (color.a + noise).clamp(0.0, 1.0),
+                    );
+                }
+                let color = color.with_alpha(color.a * opacity);
+                layer.set_pixel(x, y, self.blend_mode.blend(base, color));
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_stops_and_midpoints() {
+        let mut gradient = Gradient::two_color(Color::black(), Color::white());
+        assert!((gradient.sample(0.5, Color::black(), Color::white()).r - 0.5).abs() < 1e-4);
+
+        // Moving the midpoint towards the start brightens the middle
+        gradient.color_stops[0].midpoint = 0.25;
+        assert!((gradient.sample(0.25, Color::black(), Color::white()).r - 0.5).abs() < 1e-4);
+        assert!(gradient.sample(0.5, Color::black(), Color::white()).r > 0.6);
+
+        let fade = Gradient::foreground_to_transparent();
+        let red = Color::from_rgb(1.0, 0.0, 0.0);
+        let sample = fade.sample(0.5, red, Color::white());
+        assert_eq!(sample.r, 1.0);
+        assert!((sample.a - 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_shapes_and_repeat_modes() {
+        let mut options = GradientOptions::default();
+        let (start, end) = ((0.0, 0.0), (10.0, 0.0));
+        let at = |options: &GradientOptions, x: f32, y: f32| options.position(start, end, (x, y)).unwrap();
+
+        assert!((at(&options, 5.0, 3.0) - 0.5).abs() < 1e-5);
+        assert_eq!(at(&options, -5.0, 0.0), 0.0);
+        assert!(options.position(start, (0.5, 0.0), (1.0, 1.0)).is_none());
+
+        options.shape = GradientShape::Radial;
+        assert!((at(&options, 0.0, 5.0) - 0.5).abs() < 1e-5);
+
+        options.shape = GradientShape::Reflected;
+        assert!((at(&options, -5.0, 0.0) - 0.5).abs() < 1e-5);
+
+        options.shape = GradientShape::Diamond;
+        assert!((at(&options, 3.0, 4.0) - 0.7).abs() < 1e-5);
+
+        options.shape = GradientShape::Angle;
+        assert!(at(&options, 5.0, 0.0).abs() < 1e-5);
+        assert!((at(&options, -5.0, 0.0) - 0.5).abs() < 1e-5);
+
+        options.shape = GradientShape::Linear;
+        options.repeat = GradientRepeat::Repeat;
+        assert!((at(&options, 12.5, 0.0) - 0.25).abs() < 1e-5);
+        options.repeat = GradientRepeat::Reflect;
+        assert!((at(&options, 12.5, 0.0) - 0.75).abs() < 1e-5);
+
+        options.reverse = true;
+        assert!((at(&options, 12.5, 0.0) - 0.25).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_render_dithers_flat_gradient() {
+        let mut layer = Layer::new("Test", 8, 8);
+        let mut options = GradientOptions {
+            gradient: Gradient::two_color(Color::from_rgb(0.5, 0.5, 0.5), Color::from_rgb(0.502, 0.502, 0.502)),
+            ..Default::default()
+        };
+        options.dither = false;
+        assert!(options.render(&mut layer, (0, 0, 8, 8), (0.0, 0.0), (8.0, 0.0), Color::black(), Color::white(), 1.0));
+        let plain: Vec<u8> = (0..8).map(|x| layer.get_pixel(x, 0).unwrap().to_rgba8().0).collect();
+
+        options.dither = true;
+        options.render(&mut layer, (0, 0, 8, 8), (0.0, 0.0), (8.0, 0.0), Color::black(), Color::white(), 1.0);
+        let dithered: Vec<u8> = (0..8)
+            .flat_map(|y| (0..8).map(move |x| (x, y)))
+            .map(|(x, y)| layer.get_pixel(x, y).unwrap().to_rgba8().0)
+            .collect();
+
+        // Without dithering the tiny step is a hard edge; with it both levels
+        // are interleaved across the whole gradient
+        assert!(plain.windows(2).filter(|pair| pair[0] != pair[1]).count() <= 1);
+        assert!(dithered[..8].windows(2).filter(|pair| pair[0] != pair[1]).count() > 1);
+    }
+}
@@ -0,0 +1,566 @@
+//! Content-aware fill
+//!
+//! Patch-based inpainting after Wexler et al., with the nearest neighbor
+//! search done by PatchMatch (Barnes et al.). The image is reduced to a
+//! pyramid until the hole is about one patch across. Starting from the
+//! coarsest level, every patch overlapping the hole is matched to the most
+//! similar patch lying wholly inside the sampling area, then each hole pixel
+//! becomes the similarity-weighted vote of the matched patches covering it.
+//! Matching and voting alternate a few times per level before matches and
+//! colors are carried to the next finer level.
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{EngineError, EngineResult};
+use crate::selection::SelectionMask;
+use crate::utils::simple_hash;
+
+/// Matching and voting rounds per pyramid level
+const EM_ITERATIONS: usize = 4;
+
+/// PatchMatch passes per round
+const SEARCH_PASSES: usize = 2;
+
+/// Most pyramid levels below the full image
+const MAX_LEVELS: usize = 8;
+
+/// Where content-aware fill writes its result
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ContentAwareOutput {
+    /// Into the active layer, blended by the selection coverage
+    #[default]
+    ActiveLayer,
+    /// Onto a new layer above the active layer
+    NewLayer,
+}
+
+/// Content-aware fill settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContentAwareOptions {
+    /// Sample the merged image instead of the active layer
+    pub sample_all_layers: bool,
+    /// Pixels patches may be taken from; everything outside the selection
+    /// when unset
+    pub sampling_area: Option<SelectionMask>,
+    /// Side of the square patches compared, in pixels (odd)
+    pub patch_size: u32,
+    /// Where the result goes
+    pub output: ContentAwareOutput,
+    /// Seed for the randomized search, so fills are repeatable
+    pub seed: u32,
+}
+
+impl Default for ContentAwareOptions {
+    fn default() -> Self {
+        Self {
+            sample_all_layers: false,
+            sampling_area: None,
+            patch_size: 7,
+            output: ContentAwareOutput::ActiveLayer,
+            seed: 0,
+        }
+    }
+}
+
+/// One level of the image pyramid
+struct Level {
+    width: usize,
+    height: usize,
+    colors: Vec<[f32; 4]>,
+    hole: Vec<bool>,
+    /// Pixels patches may be taken from
+    sample: Vec<bool>,
+}
+
+impl Level {
+    /// Half-size level; a pixel is in the hole if any pixel it covers is
+    fn downsample(&self) -> Level {
+        let (width, height) = (self.width.div_ceil(2), self.height.div_ceil(2));
+        let mut level = Level {
+            width,
+            height,
+            colors: vec![[0.0; 4]; width * height],
+            hole: vec![false; width * height],
+            sample: vec![true; width * height],
+        };
+
+        for y in 0..height {
+            for x in 0..width {
+                let i = y * width + x;
+                let mut known = ([0.0f32; 4], 0.0f32);
+                let mut all = ([0.0f32; 4], 0.0f32);
+                for (fx, fy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                    let (fx, fy) = (x * 2 + fx, y * 2 + fy);
+                    if fx >= self.width || fy >= self.height {
+                        continue;
+                    }
+                    let f = fy * self.width + fx;
+                    let sums = if self.hole[f] { &mut all } else { &mut known };
+                    for (sum, value) in sums.0.iter_mut().zip(self.colors[f]) {
+                        *sum += value;
+                    }
+                    sums.1 += 1.0;
+                    level.hole[i] |= self.hole[f];
+                    level.sample[i] &= self.sample[f];
+                }
+                let (sum, count) = if known.1 > 0.0 {
+                    known
+                } else {
+                    all
+                };
+                level.colors[i] = sum.map(|v| v / count.max(1.0));
+            }
+        }
+        level
+    }
+
+    /// Patch centers whose whole patch lies in the sampling area
+    fn valid_centers(&self, radius: usize) -> Vec<bool> {
+        let usable: Vec<bool> = (0..self.hole.len())
+            .map(|i| self.sample[i] && !self.hole[i])
+            .collect();
+        let unusable = window_counts(&usable, self.width, self.height, radius, false);
+        (0..self.hole.len())
+            .map(|i| {
+                let (x, y) = (i % self.width, i / self.width);
+                x >= radius
+                    && y >= radius
+                    && x + radius < self.width
+                    && y + radius < self.height
+                    && unusable[i] == 0
+            })
+            .collect()
+    }
+
+    /// Patch centers whose patch overlaps the hole
+    fn targets(&self, radius: usize) -> Vec<bool> {
+        window_counts(&self.hole, self.width, self.height, radius, true)
+            .into_iter()
+            .map(|count| count > 0)
+            .collect()
+    }
+
+    /// Largest side of the hole's bounding box
+    fn hole_extent(&self) -> usize {
+        let mut min = (usize::MAX, usize::MAX);
+        let mut max = (0, 0);
+        for (i, _) in self.hole.iter().enumerate().filter(|(_, &h)| h) {
+            let (x, y) = (i % self.width, i / self.width);
+            min = (min.0.min(x), min.1.min(y));
+            max = (max.0.max(x), max.1.max(y));
+        }
+        if min.0 > max.0 {
+            return 0;
+        }
+        (max.0 - min.0).max(max.1 - min.1) + 1
+    }
+
+    /// Give hole pixels a first guess by averaging inward from the edge
+    fn fill_inward(&mut self) {
+        let mut unknown = self.hole.clone();
+        loop {
+            let mut layer = Vec::new();
+            for i in (0..unknown.len()).filter(|&i| unknown[i]) {
+                let (x, y) = ((i % self.width) as isize, (i / self.width) as isize);
+                let mut sum = [0.0f32; 4];
+                let mut count = 0.0;
+                for (dx, dy) in [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)] {
+                    let (nx, ny) = (x + dx, y + dy);
+                    if nx < 0 || ny < 0 || nx >= self.width as isize || ny >= self.height as isize {
+                        continue;
+                    }
+                    let n = ny as usize * self.width + nx as usize;
+                    if !unknown[n] {
+                        for (total, value) in sum.iter_mut().zip(self.colors[n]) {
+                            *total += value;
+                        }
+                        count += 1.0;
+                    }
+                }
+                if count > 0.0 {
+                    layer.push((i, sum.map(|v| v / count)));
+                }
+            }
+            if layer.is_empty() {
+                break;
+            }
+            for (i, color) in layer {
+                self.colors[i] = color;
+                unknown[i] = false;
+            }
+        }
+    }
+}
+
+/// Number of set pixels (or unset, when `set` is false) in the square window
+/// of `radius` around every pixel, clipped to the image
+fn window_counts(values: &[bool], width: usize, height: usize, radius: usize, set: bool) -> Vec<u32> {
+    let stride = width + 1;
+    let mut table = vec![0u32; stride * (height + 1)];
+    for y in 0..height {
+        let mut row = 0;
+        for x in 0..width {
+            row += (values[y * width + x] == set) as u32;
+            table[(y + 1) * stride + x + 1] = table[y * stride + x + 1] + row;
+        }
+    }
+
+    (0..width * height)
+        .map(|i| {
+            let (x, y) = (i % width, i / width);
+            let (x0, y0) = (x.saturating_sub(radius), y.saturating_sub(radius));
+            let (x1, y1) = ((x + radius + 1).min(width), (y + radius + 1).min(height));
+            table[y1 * stride + x1] + table[y0 * stride + x0]
+                - table[y0 * stride + x1]
+                - table[y1 * stride + x0]
+        })
+        .collect()
+}
+
+/// Nearest neighbor field of one level
+struct Matcher {
+    radius: usize,
+    valid: Vec<bool>,
+    valid_list: Vec<usize>,
+    targets: Vec<usize>,
+    is_target: Vec<bool>,
+    /// Matched source center for every target pixel
+    nnf: Vec<usize>,
+    /// Patch distance of every match
+    cost: Vec<f32>,
+    seed: u32,
+}
+
+impl Matcher {
+    fn new(level: &Level, radius: usize, seed: u32) -> Option<Self> {
+        let valid = level.valid_centers(radius);
+        let valid_list: Vec<usize> = (0..valid.len()).filter(|&i| valid[i]).collect();
+        if valid_list.is_empty() {
+            return None;
+        }
+        let is_target = level.targets(radius);
+        let targets = (0..is_target.len()).filter(|&i| is_target[i]).collect();
+        let n = level.colors.len();
+        Some(Self {
+            radius,
+            valid,
+            valid_list,
+            targets,
+            is_target,
+            nnf: vec![0; n],
+            cost: vec![f32::MAX; n],
+            seed,
+        })
+    }
+
+    fn random(&self, i: usize, salt: u32) -> u32 {
+        simple_hash(i as u32, salt, self.seed)
+    }
+
+    fn random_center(&self, i: usize) -> usize {
+        self.valid_list[self.random(i, 0x9e37) as usize % self.valid_list.len()]
+    }
+
+    /// Match every target, preferring `guess` where it gives a valid center
+    fn initialize(&mut self, level: &Level, guess: impl Fn(usize) -> Option<usize>) {
+        for t in 0..self.targets.len() {
+            let p = self.targets[t];
+            let q = guess(p).filter(|&q| self.valid[q]).unwrap_or_else(|| self.random_center(p));
+            self.nnf[p] = q;
+        }
+        self.update_costs(level);
+    }
+
+    fn update_costs(&mut self, level: &Level) {
+        for t in 0..self.targets.len() {
+            let p = self.targets[t];
+            self.cost[p] = self.distance(level, p, self.nnf[p], f32::MAX);
+        }
+    }
+
+    /// Sum of squared differences between the patches at `p` and `q`,
+    /// stopping early once it reaches `limit`
+    fn distance(&self, level: &Level, p: usize, q: usize, limit: f32) -> f32 {
+        let (width, height) = (level.width as isize, level.height as isize);
+        let r = self.radius as isize;
+        let (px, py) = ((p % level.width) as isize, (p / level.width) as isize);
+        let offset = q as isize - p as isize;
+        let mut sum = 0.0;
+
+        for dy in -r..=r {
+            let y = py + dy;
+            if y < 0 || y >= height {
+                continue;
+            }
+            for dx in -r..=r {
+                let x = px + dx;
+                if x < 0 || x >= width {
+                    continue;
+                }
+                let t = (y * width + x) as usize;
+                let a = level.colors[t];
+                let b = level.colors[(t as isize + offset) as usize];
+                sum += (0..4).map(|c| (a[c] - b[c]) * (a[c] - b[c])).sum::<f32>();
+            }
+            if sum >= limit {
+                return sum;
+            }
+        }
+        sum
+    }
+
+    fn try_match(&mut self, level: &Level, p: usize, q: usize) {
+        if q != self.nnf[p] && self.valid[q] {
+            let d = self.distance(level, p, q, self.cost[p]);
+            if d < self.cost[p] {
+                self.nnf[p] = q;
+                self.cost[p] = d;
+            }
+        }
+    }
+
+    /// One PatchMatch pass: propagate good matches from neighbors, then
+    /// search randomly around the current match at shrinking radii
+    fn search(&mut self, level: &Level, pass: usize) {
+        let (width, height) = (level.width as isize, level.height as isize);
+        let forward = pass.is_multiple_of(2);
+        let step: isize = if forward { 1 } else { -1 };
+
+        for k in 0..self.targets.len() {
+            let t = if forward { k } else { self.targets.len() - 1 - k };
+            let p = self.targets[t];
+            let (px, py) = ((p as isize) % width, (p as isize) / width);
+
+            for (nx, ny) in [(px - step, py), (px, py - step)] {
+                if nx < 0 || ny < 0 || nx >= width || ny >= height {
+                    continue;
+                }
+                let n = (ny * width + nx) as usize;
+                if !self.is_target[n] {
+                    continue;
+                }
+                let (qx, qy) = ((self.nnf[n] as isize) % width + px - nx, (self.nnf[n] as isize) / width + py - ny);
+                if qx >= 0 && qy >= 0 && qx < width && qy < height {
+                    self.try_match(level, p, (qy * width + qx) as usize);
+                }
+            }
+
+            let mut reach = width.max(height);
+            let mut round = 0;
+            while reach >= 1 {
+                let best = self.nnf[p] as isize;
+                let (bx, by) = (best % width, best / width);
+                let salt = (pass as u32) << 16 | round;
+                let rx = self.random(p, salt * 2 + 1) as isize % (2 * reach + 1) - reach;
+                let ry = self.random(p, salt * 2 + 2) as isize % (2 * reach + 1) - reach;
+                let (qx, qy) = ((bx + rx).clamp(0, width - 1), (by + ry).clamp(0, height - 1));
+                self.try_match(level, p, (qy * width + qx) as usize);
+                reach /= 2;
+                round += 1;
+            }
+        }
+    }
+
+    /// New hole colors: every hole pixel averages the matched patches
+    /// covering it, weighted by how well each patch matched
+    fn vote(&self, level: &Level) -> Vec<(usize, [f32; 4])> {
+        let (width, height) = (level.width as isize, level.height as isize);
+        let r = self.radius as isize;
+
+        let mut costs: Vec<f32> = self.targets.iter().map(|&p| self.cost[p]).collect();
+        costs.sort_by(f32::total_cmp);
+        let spread = costs
+            .get(costs.len() * 3 / 4)
+            .copied()
+            .unwrap_or(1.0)
+            .max(1.0);
+
+        let mut colors = Vec::new();
+        for i in (0..level.hole.len()).filter(|&i| level.hole[i]) {
+            let (x, y) = ((i as isize) % width, (i as isize) / width);
+            let mut sum = [0.0f32; 4];
+            let mut total = 0.0;
+            for dy in -r..=r {
+                for dx in -r..=r {
+                    let (cx, cy) = (x - dx, y - dy);
+                    if cx < 0 || cy < 0 || cx >= width || cy >= height {
+                        continue;
+                    }
+                    let p = (cy * width + cx) as usize;
+                    if !self.is_target[p] {
+                        continue;
+                    }
+                    let q = self.nnf[p] as isize + dy * width + dx;
+                    let weight = (-self.cost[p] / (2.0 * spread)).exp().max(1e-6);
+                    for (s, value) in sum.iter_mut().zip(level.colors[q as usize]) {
+                        *s += value * weight;
+                    }
+                    total += weight;
+                }
+            }
+            if total > 0.0 {
+                colors.push((i, sum.map(|v| v / total)));
+            }
+        }
+        colors
+    }
+}
+
+/// Fill the `hole` pixels of straight RGBA `pixels` from the `sample` pixels
+///
+/// Uses the patch size and seed of `options`; the sampling area and output
+/// are up to the caller.
+///
+/// `progress` is called with the finished fraction of the work after every
+/// round of matching and voting.
+pub fn inpaint(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    hole: &[bool],
+    sample: &[bool],
+    options: &ContentAwareOptions,
+    progress: &mut dyn FnMut(f32),
+) -> EngineResult<Vec<u8>> {
+    let (patch_size, seed) = (options.patch_size.max(3), options.seed);
+    let n = (width * height) as usize;
+    if pixels.len() < n * 4 || hole.len() < n || sample.len() < n {
+        return Err(EngineError::InvalidOperation("Invalid pixel data".to_string()));
+    }
+    let radius = (patch_size / 2) as usize;
+
+    let mut levels = vec![Level {
+        width: width as usize,
+        height: height as usize,
+        colors: pixels.chunks_exact(4).take(n).map(|p| [0, 1, 2, 3].map(|c| p[c] as f32)).collect(),
+        hole: hole[..n].to_vec(),
+        sample: sample[..n].to_vec(),
+    }];
+    if !levels[0].valid_centers(radius).contains(&true) {
+        return Err(EngineError::InvalidOperation(
+            "Not enough area to sample from".to_string(),
+        ));
+    }
+    while levels.len() <= MAX_LEVELS {
+        let last = &levels[levels.len() - 1];
+        if last.hole_extent() <= patch_size as usize || last.width.min(last.height) < patch_size as usize * 4 {
+            break;
+        }
+        let next = last.downsample();
+        if !next.valid_centers(radius).contains(&true) {
+            break;
+        }
+        levels.push(next);
+    }
+
+    let total = (levels.len() * EM_ITERATIONS) as f32;
+    let mut done = 0.0;
+    // Matches of the next coarser level with its width
+    let mut coarser: Option<(Matcher, usize)> = None;
+
+    for index in (0..levels.len()).rev() {
+        let (finer, rest) = levels.split_at_mut(index + 1);
+        let level = &mut finer[index];
+        match rest.first() {
+            None => level.fill_inward(),
+            Some(coarse) => {
+                for i in (0..level.hole.len()).filter(|&i| level.hole[i]) {
+                    let (x, y) = (i % level.width, i / level.width);
+                    level.colors[i] = coarse.colors[(y / 2) * coarse.width + x / 2];
+                }
+            }
+        }
+
+        let mut matcher = Matcher::new(level, radius, seed.wrapping_add(index as u32))
+            .ok_or_else(|| EngineError::InvalidOperation("Not enough area to sample from".to_string()))?;
+        let (width, height) = (level.width, level.height);
+        matcher.initialize(level, |p| {
+            let (coarse, coarse_width) = coarser.as_ref()?;
+            let (x, y) = (p % width, p / width);
+            let c = (y / 2) * coarse_width + x / 2;
+            if !coarse.is_target[c] {
+                return None;
+            }
+            let q = coarse.nnf[c];
+            let (qx, qy) = ((q % coarse_width) * 2 + x % 2, (q / coarse_width) * 2 + y % 2);
+            (qx < width && qy < height).then_some(qy * width + qx)
+        });
+
+        for _ in 0..EM_ITERATIONS {
+            for pass in 0..SEARCH_PASSES {
+                matcher.search(level, pass);
+            }
+            for (i, color) in matcher.vote(level) {
+                level.colors[i] = color;
+            }
+            matcher.update_costs(level);
+            done += 1.0;
+            progress(done / total);
+        }
+        coarser = Some((matcher, width));
+    }
+
+    let finest = &levels[0];
+    let mut result = pixels[..n * 4].to_vec();
+    for i in (0..n).filter(|&i| finest.hole[i]) {
+        for c in 0..4 {
+            result[i * 4 + c] = finest.colors[i][c].round().clamp(0.0, 255.0) as u8;
+        }
+    }
+    progress(1.0);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Vertical stripes, 4 pixels red then 4 pixels blue, with a hole in the middle
+    fn stripes(size: u32) -> (Vec<u8>, Vec<bool>) {
+        let pixels = (0..size * size)
+            .flat_map(|i| if ((i % size) / 4).is_multiple_of(2) { [255, 0, 0, 255] } else { [0, 0, 255, 255] })
+            .collect();
+        let hole = (0..size * size)
+            .map(|i| (12..20).contains(&(i % size)) && (12..20).contains(&(i / size)))
+            .collect();
+        (pixels, hole)
+    }
+
+    fn options(patch_size: u32) -> ContentAwareOptions {
+        ContentAwareOptions {
+            patch_size,
+            seed: 1,
+            ..ContentAwareOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_inpaint_continues_texture() {
+        let (pixels, hole) = stripes(32);
+        let sample = vec![true; hole.len()];
+        let mut reports = Vec::new();
+        let filled = inpaint(&pixels, 32, 32, &hole, &sample, &options(7), &mut |p| reports.push(p)).unwrap();
+
+        let matching = (0..32 * 32)
+            .filter(|&i| hole[i] && filled[i * 4..i * 4 + 4] == pixels[i * 4..i * 4 + 4])
+            .count();
+        assert!(matching >= 56, "{} of 64 hole pixels match", matching);
+        assert_eq!(filled[..4], pixels[..4]);
+        assert!(reports.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(reports.last(), Some(&1.0));
+    }
+
+    #[test]
+    fn test_inpaint_uses_only_sampling_area() {
+        let (pixels, hole) = stripes(32);
+        // Only the red stripes may be sampled
+        let sample: Vec<bool> = pixels.chunks_exact(4).map(|p| p[0] == 255).collect();
+        assert!(inpaint(&pixels, 32, 32, &hole, &sample, &options(7), &mut |_| {}).is_err());
+
+        let sample: Vec<bool> = (0..32 * 32).map(|i| i % 32 < 8).collect();
+        let filled = inpaint(&pixels, 32, 32, &hole, &sample, &options(3), &mut |_| {}).unwrap();
+        let i = (16 * 32 + 16) * 4;
+        assert!(filled[i] == 255 || filled[i + 2] == 255);
+    }
+}
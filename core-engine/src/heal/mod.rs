@@ -0,0 +1,168 @@
+//! Healing Module
+//!
+//! Gradient-domain blending for the healing brush. Cloned pixels keep their
+//! texture, the differences between neighbors, while a smooth correction
+//! takes their overall color and luminance to the target's surroundings. The
+//! correction is the membrane that matches the target-minus-source
+//! difference on the edge of the healed area and is harmonic inside it,
+//! the solution of Poisson image editing's Laplace equation, found by
+//! successive over-relaxation.
+//!
+//! Content-aware fill synthesizes a selected area from patches of the rest
+//! of the image; see [`content_aware`].
+
+mod content_aware;
+
+pub use content_aware::{inpaint, ContentAwareOptions, ContentAwareOutput};
+
+/// Over-relaxation factor for the membrane solver
+const OVER_RELAXATION: f32 = 1.9;
+
+/// Largest per-channel change, in 8-bit steps, at which the solver stops
+const CONVERGED: f32 = 0.05;
+
+/// Blend `source` into `target` in the gradient domain
+///
+/// All three buffers cover the same `width` x `height` area, the pixels as
+/// straight RGBA. Pixels with coverage above zero are healed and the result
+/// is mixed with `target` by that coverage; uncovered pixels fix the edge the
+/// healed area has to meet, so the area should include a one pixel border of
+/// them wherever it does not touch the canvas edge.
+pub fn heal(target: &[u8], source: &[u8], coverage: &[f32], width: usize, height: usize) -> Vec<u8> {
+    let n = width * height;
+    let mut result = target.to_vec();
+    if target.len() < n * 4 || source.len() < n * 4 || coverage.len() < n {
+        return result;
+    }
+
+    let unknown: Vec<usize> = (0..n).filter(|&i| coverage[i] > 0.0).collect();
+    if unknown.is_empty() {
+        return result;
+    }
+
+    let difference = |i: usize| -> [f32; 4] {
+        [0, 1, 2, 3].map(|c| target[i * 4 + c] as f32 - source[i * 4 + c] as f32)
+    };
+    let mut membrane: Vec<[f32; 4]> = (0..n).map(difference).collect();
+
+    // Start the unknowns at the mean of the fixed edge around them, which
+    // takes out most of the error the relaxation would otherwise spread
+    let mut edge_sum = [0.0f32; 4];
+    let mut edge_count = 0.0;
+    for i in 0..n {
+        if coverage[i] <= 0.0 && neighbors(i, width, height).any(|j| coverage[j] > 0.0) {
+            for (sum, value) in edge_sum.iter_mut().zip(membrane[i]) {
+                *sum += value;
+            }
+            edge_count += 1.0;
+        }
+    }
+    let start = if edge_count > 0.0 {
+        edge_sum.map(|sum| sum / edge_count)
+    } else {
+        [0.0; 4]
+    };
+    for &i in &unknown {
+        membrane[i] = start;
+    }
+
+    let iterations = (width.min(height) * 2).clamp(16, 512);
+    for _ in 0..iterations {
+        let mut largest_change = 0.0f32;
+        for &i in &unknown {
+            let mut sum = [0.0f32; 4];
+            let mut count = 0.0;
+            for j in neighbors(i, width, height) {
+                for (total, value) in sum.iter_mut().zip(membrane[j]) {
+                    *total += value;
+                }
+                count += 1.0;
+            }
+            if count == 0.0 {
+                continue;
+            }
+            for (c, value) in membrane[i].iter_mut().enumerate() {
+                let change = OVER_RELAXATION * (sum[c] / count - *value);
+                *value += change;
+                largest_change = largest_change.max(change.abs());
+            }
+        }
+        if largest_change < CONVERGED {
+            break;
+        }
+    }
+
+    for &i in &unknown {
+        let weight = coverage[i].min(1.0);
+        for c in 0..4 {
+            let healed = (source[i * 4 + c] as f32 + membrane[i][c]).clamp(0.0, 255.0);
+            let original = target[i * 4 + c] as f32;
+            result[i * 4 + c] = (original + (healed - original) * weight).round() as u8;
+        }
+    }
+    result
+}
+
+/// Indices of the 4-connected neighbors of pixel `i` inside the area
+fn neighbors(i: usize, width: usize, height: usize) -> impl Iterator<Item = usize> {
+    let (x, y) = (i % width, i / width);
+    [
+        (x > 0).then(|| i - 1),
+        (x + 1 < width).then(|| i + 1),
+        (y > 0).then(|| i - width),
+        (y + 1 < height).then(|| i + width),
+    ]
+    .into_iter()
+    .flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Gray `level` with a one pixel checkerboard of +-`texture`
+    fn textured(level: u8, texture: u8, size: usize) -> Vec<u8> {
+        (0..size * size)
+            .flat_map(|i| {
+                let v = if (i % size + i / size).is_multiple_of(2) {
+                    level + texture
+                } else {
+                    level - texture
+                };
+                [v, v, v, 255]
+            })
+            .collect()
+    }
+
+    fn disc(size: usize, radius: f32) -> Vec<f32> {
+        let center = size as f32 / 2.0;
+        (0..size * size)
+            .map(|i| {
+                let (x, y) = ((i % size) as f32 + 0.5, (i / size) as f32 + 0.5);
+                if (x - center).hypot(y - center) < radius { 1.0 } else { 0.0 }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_heal_takes_tone_from_target_and_texture_from_source() {
+        let size = 24;
+        let target = vec![[100u8, 100, 100, 255]; size * size].concat();
+        let source = textured(200, 20, size);
+        let healed = heal(&target, &source, &disc(size, 8.0), size, size);
+
+        let center = (size / 2 * size + size / 2) * 4;
+        let next = center + 4;
+        let mean = (healed[center] as f32 + healed[next] as f32) / 2.0;
+        assert!((mean - 100.0).abs() < 3.0, "mean {}", mean);
+        assert!((healed[center] as i32 - healed[next] as i32).abs() >= 36);
+        assert_eq!(healed[0..4], target[0..4]);
+    }
+
+    #[test]
+    fn test_heal_with_matching_source_is_identity() {
+        let size = 12;
+        let image = textured(90, 30, size);
+        assert_eq!(heal(&image, &image, &disc(size, 4.0), size, size), image);
+    }
+}
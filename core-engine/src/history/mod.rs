@@ -0,0 +1,1752 @@
+//! History Management Module
+//!
+//! Provides undo/redo functionality through incremental layer snapshots.
+//! Canvas-level operations (resize, crop, rotate) go through the same
+//! history: their states also carry the canvas size and tiles, and full
+//! layer snapshots restore the layer dimensions they were taken at.
+//!
+//! ## History Tree
+//!
+//! History is a tree rather than a linear stack. Editing after an undo starts
+//! a new branch and keeps the old one, so any earlier state can be revisited.
+//! Named snapshots store a full copy of the document and are never evicted.
+//!
+//! ## Persistence
+//!
+//! The tree can be exported as a [`SavedHistory`] and stored inside .dcpaint
+//! documents, so reopening a file keeps its undo history.
+//!
+//! ## Selective Undo
+//!
+//! A single past step can be reverted while keeping later ones, as long as
+//! no later step changed the same pixels. See [`find_conflicts`].
+//!
+//! ## Disk Spill
+//!
+//! With [`HistoryManager::enable_disk_spill`], states beyond the RAM budget
+//! are paged out to a compressed on-disk cache instead of being evicted, and
+//! paged back in when undo or redo reaches them.
+//!
+//! ## Memory Optimization
+//!
+//! Instead of storing complete pixel buffers for each undo step, we use
+//! incremental snapshots that only store the modified region (dirty rect).
+//! This can reduce memory usage by 80-95% for typical drawing operations.
+
+mod persist;
+mod selective;
+mod spill;
+
+pub use persist::{SavedHistory, HISTORY_FORMAT_VERSION};
+pub use selective::{find_conflicts, HistoryConflict, SelectiveUndo};
+
+use crate::canvas::CanvasSnapshot;
+use crate::error::EngineResult;
+use crate::layer::Layer;
+use crate::shape::Shape;
+use spill::{HistorySpillCache, SpilledState};
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use uuid::Uuid;
+
+/// Maximum number of undo steps to keep
+const DEFAULT_MAX_UNDO_STEPS: usize = 50;
+
+/// Description of the root node of a fresh history tree
+const ROOT_DESCRIPTION: &str = "Initial State";
+
+/// Default limit for paged-out history on disk (4 GB)
+const DEFAULT_DISK_LIMIT: usize = 4 * 1024 * 1024 * 1024;
+
+/// A dirty rectangle representing a modified region
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct DirtyRect {
+    /// X coordinate of top-left corner
+    pub x: u32,
+    /// Y coordinate of top-left corner
+    pub y: u32,
+    /// Width of the dirty region
+    pub width: u32,
+    /// Height of the dirty region
+    pub height: u32,
+}
+
+impl DirtyRect {
+    /// Create a new dirty rect
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self { x, y, width, height }
+    }
+
+    /// Create a dirty rect from bounds (x1, y1, x2, y2)
+    pub fn from_bounds(x1: u32, y1: u32, x2: u32, y2: u32) -> Self {
+        Self {
+            x: x1,
+            y: y1,
+            width: x2.saturating_sub(x1),
+            height: y2.saturating_sub(y1),
+        }
+    }
+
+    /// Create a full-layer dirty rect
+    pub fn full(width: u32, height: u32) -> Self {
+        Self { x: 0, y: 0, width, height }
+    }
+
+    /// Expand the dirty rect to include a point
+    pub fn expand_to(&mut self, px: u32, py: u32) {
+        let x2 = self.x + self.width;
+        let y2 = self.y + self.height;
+
+        if px < self.x {
+            self.width += self.x - px;
+            self.x = px;
+        } else if px >= x2 {
+            self.width = px - self.x + 1;
+        }
+
+        if py < self.y {
+            self.height += self.y - py;
+            self.y = py;
+        } else if py >= y2 {
+            self.height = py - self.y + 1;
+        }
+    }
+
+    /// Expand the dirty rect to include another rect
+    pub fn union(&mut self, other: &DirtyRect) {
+        if other.width == 0 || other.height == 0 {
+            return;
+        }
+
+        let x1 = self.x.min(other.x);
+        let y1 = self.y.min(other.y);
+        let x2 = (self.x + self.width).max(other.x + other.width);
+        let y2 = (self.y + self.height).max(other.y + other.height);
+
+        self.x = x1;
+        self.y = y1;
+        self.width = x2 - x1;
+        self.height = y2 - y1;
+    }
+
+    /// Get the overlap with another rect (empty if they do not overlap)
+    pub fn intersection(&self, other: &DirtyRect) -> DirtyRect {
+        let x1 = self.x.max(other.x);
+        let y1 = self.y.max(other.y);
+        let x2 = (self.x + self.width).min(other.x + other.width);
+        let y2 = (self.y + self.height).min(other.y + other.height);
+        Self::from_bounds(x1, y1, x2.max(x1), y2.max(y1))
+    }
+
+    /// Check if the dirty rect is empty
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// Get the area of the dirty rect
+    pub fn area(&self) -> u64 {
+        self.width as u64 * self.height as u64
+    }
+
+    /// Add padding around the dirty rect (for brush radius)
+    pub fn pad(&mut self, padding: u32, max_width: u32, max_height: u32) {
+        let new_x = self.x.saturating_sub(padding);
+        let new_y = self.y.saturating_sub(padding);
+        let new_x2 = (self.x + self.width + padding).min(max_width);
+        let new_y2 = (self.y + self.height + padding).min(max_height);
+
+        self.x = new_x;
+        self.y = new_y;
+        self.width = new_x2 - new_x;
+        self.height = new_y2 - new_y;
+    }
+
+    /// Clamp the dirty rect to layer bounds
+    pub fn clamp(&mut self, layer_width: u32, layer_height: u32) {
+        if self.x >= layer_width || self.y >= layer_height {
+            self.width = 0;
+            self.height = 0;
+            return;
+        }
+
+        if self.x + self.width > layer_width {
+            self.width = layer_width - self.x;
+        }
+        if self.y + self.height > layer_height {
+            self.height = layer_height - self.y;
+        }
+    }
+}
+
+/// Snapshot type - full or incremental
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SnapshotData {
+    /// Full layer snapshot (used for large changes like fills)
+    Full {
+        /// Complete pixel data (optionally compressed)
+        pixels: Vec<u8>,
+        /// Whether the data is compressed
+        compressed: bool,
+    },
+    /// Incremental snapshot (only dirty region)
+    Incremental {
+        /// The dirty region bounds
+        dirty_rect: DirtyRect,
+        /// Pixel data for the dirty region only
+        pixels: Vec<u8>,
+        /// Whether the data is compressed
+        compressed: bool,
+    },
+}
+
+impl SnapshotData {
+    /// Get the memory size of this snapshot data
+    pub fn memory_size(&self) -> usize {
+        match self {
+            SnapshotData::Full { pixels, .. } => pixels.len(),
+            SnapshotData::Incremental { pixels, .. } => pixels.len(),
+        }
+    }
+}
+
+/// A snapshot of a layer's pixel data at a point in time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayerSnapshot {
+    /// The layer ID this snapshot belongs to
+    pub layer_id: Uuid,
+    /// The snapshot data
+    pub data: SnapshotData,
+    /// Layer dimensions (width, height)
+    pub dimensions: (u32, u32),
+}
+
+impl LayerSnapshot {
+    /// Create a new full layer snapshot (legacy compatibility)
+    pub fn new(layer_id: Uuid, pixels: Vec<u8>, width: u32, height: u32) -> Self {
+        Self {
+            layer_id,
+            data: SnapshotData::Full {
+                pixels,
+                compressed: false,
+            },
+            dimensions: (width, height),
+        }
+    }
+
+    /// Create an incremental snapshot from dirty region
+    pub fn incremental(
+        layer_id: Uuid,
+        layer_pixels: &[u8],
+        layer_width: u32,
+        layer_height: u32,
+        dirty_rect: DirtyRect,
+    ) -> Self {
+        let mut rect = dirty_rect;
+        rect.clamp(layer_width, layer_height);
+
+        if rect.is_empty() {
+            // Return empty incremental snapshot
+            return Self {
+                layer_id,
+                data: SnapshotData::Incremental {
+                    dirty_rect: rect,
+                    pixels: Vec::new(),
+                    compressed: false,
+                },
+                dimensions: (layer_width, layer_height),
+            };
+        }
+
+        // Extract only the dirty region pixels
+        let dirty_pixels = extract_region(
+            layer_pixels,
+            layer_width,
+            rect.x,
+            rect.y,
+            rect.width,
+            rect.height,
+        );
+
+        Self {
+            layer_id,
+            data: SnapshotData::Incremental {
+                dirty_rect: rect,
+                pixels: dirty_pixels,
+                compressed: false,
+            },
+            dimensions: (layer_width, layer_height),
+        }
+    }
+
+    /// Create a full snapshot with optional compression
+    pub fn full_compressed(
+        layer_id: Uuid,
+        pixels: Vec<u8>,
+        width: u32,
+        height: u32,
+        compress: bool,
+    ) -> Self {
+        let (data, compressed) = if compress {
+            match lz4_flex::compress_prepend_size(&pixels) {
+                compressed_data => (compressed_data, true),
+            }
+        } else {
+            (pixels, false)
+        };
+
+        Self {
+            layer_id,
+            data: SnapshotData::Full {
+                pixels: data,
+                compressed,
+            },
+            dimensions: (width, height),
+        }
+    }
+
+    /// Create an incremental snapshot with compression
+    pub fn incremental_compressed(
+        layer_id: Uuid,
+        layer_pixels: &[u8],
+        layer_width: u32,
+        layer_height: u32,
+        dirty_rect: DirtyRect,
+    ) -> Self {
+        let mut rect = dirty_rect;
+        rect.clamp(layer_width, layer_height);
+
+        if rect.is_empty() {
+            return Self {
+                layer_id,
+                data: SnapshotData::Incremental {
+                    dirty_rect: rect,
+                    pixels: Vec::new(),
+                    compressed: false,
+                },
+                dimensions: (layer_width, layer_height),
+            };
+        }
+
+        // Extract only the dirty region pixels
+        let dirty_pixels = extract_region(
+            layer_pixels,
+            layer_width,
+            rect.x,
+            rect.y,
+            rect.width,
+            rect.height,
+        );
+
+        // Compress if worthwhile (region > 1KB)
+        let (pixels, compressed) = if dirty_pixels.len() > 1024 {
+            (lz4_flex::compress_prepend_size(&dirty_pixels), true)
+        } else {
+            (dirty_pixels, false)
+        };
+
+        Self {
+            layer_id,
+            data: SnapshotData::Incremental {
+                dirty_rect: rect,
+                pixels,
+                compressed,
+            },
+            dimensions: (layer_width, layer_height),
+        }
+    }
+
+    /// Capture the same region of a layer as this snapshot covers
+    ///
+    /// Used to build the inverse of a history state before restoring it.
+    pub fn capture_same_region(&self, layer_pixels: &[u8], width: u32, height: u32) -> Self {
+        match &self.data {
+            SnapshotData::Full { .. } => {
+                Self::full_compressed(self.layer_id, layer_pixels.to_vec(), width, height, true)
+            }
+            SnapshotData::Incremental { dirty_rect, .. } => {
+                Self::incremental_compressed(self.layer_id, layer_pixels, width, height, *dirty_rect)
+            }
+        }
+    }
+
+    /// Restore pixels from snapshot to target buffer
+    pub fn restore_to(&self, target: &mut [u8], target_width: u32) {
+        match &self.data {
+            SnapshotData::Full { pixels, compressed } => {
+                let decompressed = if *compressed {
+                    lz4_flex::decompress_size_prepended(pixels)
+                        .unwrap_or_default()
+                } else {
+                    pixels.clone()
+                };
+
+                // Direct copy for full snapshot
+                if target.len() == decompressed.len() {
+                    target.copy_from_slice(&decompressed);
+                }
+            }
+            SnapshotData::Incremental { dirty_rect, pixels, compressed } => {
+                if dirty_rect.is_empty() || pixels.is_empty() {
+                    return;
+                }
+
+                let decompressed = if *compressed {
+                    lz4_flex::decompress_size_prepended(pixels)
+                        .unwrap_or_default()
+                } else {
+                    pixels.clone()
+                };
+
+                // Copy dirty region back
+                restore_region(
+                    &decompressed,
+                    target,
+                    target_width,
+                    dirty_rect.x,
+                    dirty_rect.y,
+                    dirty_rect.width,
+                    dirty_rect.height,
+                );
+            }
+        }
+    }
+
+    /// Restore pixels into a layer
+    ///
+    /// Full snapshots taken at other dimensions (before a canvas resize, crop
+    /// or rotation) also restore the layer size.
+    pub fn restore_layer(&self, layer: &mut Layer) {
+        let (width, height) = self.dimensions;
+        if self.is_full() && (layer.width(), layer.height()) != (width, height) {
+            layer.pixels = vec![0u8; (width * height * 4) as usize];
+            layer.bounds.2 = width;
+            layer.bounds.3 = height;
+        }
+
+        let target_width = layer.width();
+        self.restore_to(&mut layer.pixels, target_width);
+    }
+
+    /// Get memory size of this snapshot
+    pub fn memory_size(&self) -> usize {
+        std::mem::size_of::<Self>() + self.data.memory_size()
+    }
+
+    /// Get the dirty rect if this is an incremental snapshot
+    pub fn dirty_rect(&self) -> Option<DirtyRect> {
+        match &self.data {
+            SnapshotData::Full { .. } => None,
+            SnapshotData::Incremental { dirty_rect, .. } => Some(*dirty_rect),
+        }
+    }
+
+    /// Legacy accessor for pixels (decompresses if needed)
+    pub fn get_pixels(&self) -> Vec<u8> {
+        match &self.data {
+            SnapshotData::Full { pixels, compressed } => {
+                if *compressed {
+                    lz4_flex::decompress_size_prepended(pixels)
+                        .unwrap_or_default()
+                } else {
+                    pixels.clone()
+                }
+            }
+            SnapshotData::Incremental { .. } => {
+                // For incremental snapshots, we can't return full pixels
+                // This is a fallback that returns empty - caller should use restore_to
+                Vec::new()
+            }
+        }
+    }
+
+    /// Check if this is a full snapshot
+    pub fn is_full(&self) -> bool {
+        matches!(self.data, SnapshotData::Full { .. })
+    }
+
+    /// Get the region of the layer this snapshot covers
+    pub fn region(&self) -> DirtyRect {
+        match &self.data {
+            SnapshotData::Full { .. } => DirtyRect::full(self.dimensions.0, self.dimensions.1),
+            SnapshotData::Incremental { dirty_rect, .. } => *dirty_rect,
+        }
+    }
+
+    /// Get the uncompressed pixels of [`LayerSnapshot::region`]
+    pub fn region_pixels(&self) -> Vec<u8> {
+        let (pixels, compressed) = match &self.data {
+            SnapshotData::Full { pixels, compressed } => (pixels, *compressed),
+            SnapshotData::Incremental { pixels, compressed, .. } => (pixels, *compressed),
+        };
+
+        if compressed {
+            lz4_flex::decompress_size_prepended(pixels).unwrap_or_default()
+        } else {
+            pixels.clone()
+        }
+    }
+}
+
+/// Extract a region from a pixel buffer
+fn extract_region(
+    source: &[u8],
+    source_width: u32,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+) -> Vec<u8> {
+    let mut result = vec![0u8; (width * height * 4) as usize];
+    let src_stride = source_width as usize * 4;
+    let dst_stride = width as usize * 4;
+
+    for row in 0..height as usize {
+        let src_offset = ((y as usize + row) * source_width as usize + x as usize) * 4;
+        let dst_offset = row * dst_stride;
+
+        if src_offset + dst_stride <= source.len() && dst_offset + dst_stride <= result.len() {
+            result[dst_offset..dst_offset + dst_stride]
+                .copy_from_slice(&source[src_offset..src_offset + dst_stride]);
+        }
+    }
+
+    result
+}
+
+/// Restore a region to a pixel buffer
+fn restore_region(
+    source: &[u8],
+    target: &mut [u8],
+    target_width: u32,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+) {
+    let src_stride = width as usize * 4;
+    let dst_stride = target_width as usize * 4;
+
+    for row in 0..height as usize {
+        let src_offset = row * src_stride;
+        let dst_offset = ((y as usize + row) * target_width as usize + x as usize) * 4;
+
+        if src_offset + src_stride <= source.len() && dst_offset + src_stride <= target.len() {
+            target[dst_offset..dst_offset + src_stride]
+                .copy_from_slice(&source[src_offset..src_offset + src_stride]);
+        }
+    }
+}
+
+/// Layer list before an action that adds or removes layers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayerStackSnapshot {
+    /// Layer IDs from bottom to top
+    pub order: Vec<Uuid>,
+    /// Active layer ID
+    pub active: Option<Uuid>,
+    /// Listed layers that the action removed
+    ///
+    /// Their pixels are kept as full layer snapshots in the same state.
+    pub removed: Vec<Layer>,
+}
+
+impl LayerStackSnapshot {
+    /// Get memory size of this snapshot, not counting removed layer pixels
+    pub fn memory_size(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.order.len() * std::mem::size_of::<Uuid>()
+            + self.removed.len() * std::mem::size_of::<Layer>()
+    }
+}
+
+/// Shapes of a vector layer before an action
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShapesSnapshot {
+    /// The layer ID this snapshot belongs to
+    pub layer_id: Uuid,
+    /// The layer's shapes
+    pub shapes: Vec<Shape>,
+}
+
+impl ShapesSnapshot {
+    /// Get memory size of this snapshot
+    pub fn memory_size(&self) -> usize {
+        std::mem::size_of::<Self>() + self.shapes.len() * std::mem::size_of::<Shape>()
+    }
+}
+
+/// A history state containing snapshots of all affected layers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryState {
+    /// Description of the action
+    pub description: String,
+    /// Snapshots of layers before the action
+    pub layer_snapshots: Vec<LayerSnapshot>,
+    /// Canvas size and tiles before the action, for canvas-level operations
+    pub canvas: Option<CanvasSnapshot>,
+    /// Layer list before the action, for actions that add or remove layers
+    pub layer_stack: Option<LayerStackSnapshot>,
+    /// Shapes of vector layers before the action
+    pub shapes: Vec<ShapesSnapshot>,
+}
+
+impl HistoryState {
+    /// Create a new history state
+    pub fn new(description: impl Into<String>) -> Self {
+        Self {
+            description: description.into(),
+            layer_snapshots: Vec::new(),
+            canvas: None,
+            layer_stack: None,
+            shapes: Vec::new(),
+        }
+    }
+
+    /// Add a layer snapshot
+    pub fn add_snapshot(&mut self, snapshot: LayerSnapshot) {
+        self.layer_snapshots.push(snapshot);
+    }
+
+    /// Attach a canvas snapshot
+    pub fn set_canvas(&mut self, snapshot: CanvasSnapshot) {
+        self.canvas = Some(snapshot);
+    }
+
+    /// Attach a layer list snapshot
+    pub fn set_layer_stack(&mut self, snapshot: LayerStackSnapshot) {
+        self.layer_stack = Some(snapshot);
+    }
+
+    /// Add a snapshot of a vector layer's shapes
+    pub fn add_shapes(&mut self, snapshot: ShapesSnapshot) {
+        self.shapes.push(snapshot);
+    }
+
+    /// Get total memory size of all snapshots
+    pub fn memory_size(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.description.len()
+            + self.layer_snapshots.iter().map(|s| s.memory_size()).sum::<usize>()
+            + self.canvas.as_ref().map_or(0, |c| c.memory_size())
+            + self.layer_stack.as_ref().map_or(0, |s| s.memory_size())
+            + self.shapes.iter().map(|s| s.memory_size()).sum::<usize>()
+    }
+}
+
+/// Identifier of a node (or named snapshot) in the history tree
+pub type HistoryNodeId = u64;
+
+/// Small preview image attached to a history node or snapshot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryThumbnail {
+    /// Thumbnail width
+    pub width: u32,
+    /// Thumbnail height
+    pub height: u32,
+    /// Pixel data (RGBA)
+    pub pixels: Vec<u8>,
+}
+
+impl HistoryThumbnail {
+    /// Get memory size of the thumbnail
+    pub fn memory_size(&self) -> usize {
+        std::mem::size_of::<Self>() + self.pixels.len()
+    }
+}
+
+/// Description of a history node, as shown in a history panel
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryNodeInfo {
+    /// Node ID
+    pub id: HistoryNodeId,
+    /// Parent node ID (None for the root)
+    pub parent: Option<HistoryNodeId>,
+    /// Child node IDs, oldest first
+    pub children: Vec<HistoryNodeId>,
+    /// Description of the action that produced this node
+    pub description: String,
+    /// Creation time in seconds since UNIX epoch
+    pub timestamp: u64,
+    /// Optional preview of the document at this node
+    pub thumbnail: Option<HistoryThumbnail>,
+    /// Distance from the root
+    pub depth: usize,
+    /// Whether this is the current document state
+    pub is_current: bool,
+    /// Whether this node lies on the path from the root to the current state
+    pub is_active_branch: bool,
+}
+
+/// Description of a user-named snapshot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistorySnapshotInfo {
+    /// Snapshot ID
+    pub id: HistoryNodeId,
+    /// User-given name
+    pub name: String,
+    /// Creation time in seconds since UNIX epoch
+    pub timestamp: u64,
+    /// Optional preview of the document when the snapshot was taken
+    pub thumbnail: Option<HistoryThumbnail>,
+}
+
+/// Steps needed to move from the current node to another node
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryPath {
+    /// Number of undo steps up to the common ancestor
+    pub undo_steps: usize,
+    /// Nodes to redo into, in order, after the undo steps
+    pub redo_nodes: Vec<HistoryNodeId>,
+}
+
+/// RAM and disk usage of the history
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryMemoryStats {
+    /// Bytes held in RAM (tree states, thumbnails and named snapshots)
+    pub ram_bytes: usize,
+    /// Compressed bytes paged out to the disk cache
+    pub disk_bytes: usize,
+    /// Number of states held in RAM
+    pub resident_states: usize,
+    /// Number of states paged out to disk
+    pub spilled_states: usize,
+}
+
+/// Where a node's state currently lives
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum StoredState {
+    /// Held in RAM
+    Resident(HistoryState),
+    /// Paged out to the disk cache
+    Spilled(SpilledState),
+}
+
+impl StoredState {
+    /// Size of the state once loaded
+    fn memory_size(&self) -> usize {
+        match self {
+            StoredState::Resident(state) => state.memory_size(),
+            StoredState::Spilled(spilled) => spilled.memory_size(),
+        }
+    }
+
+    /// Bytes this state occupies in RAM right now
+    fn resident_size(&self) -> usize {
+        match self {
+            StoredState::Resident(state) => state.memory_size(),
+            StoredState::Spilled(_) => 0,
+        }
+    }
+}
+
+/// Which of a node's two states to address
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StateSlot {
+    Undo,
+    Redo,
+}
+
+/// A node in the history tree
+///
+/// Each non-root node holds exactly one of its two states at a time:
+/// `undo_state` while the document is at or below the node (it restores the
+/// parent), `redo_state` once the node has been undone (it restores the node).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct HistoryNode {
+    id: HistoryNodeId,
+    parent: Option<HistoryNodeId>,
+    children: Vec<HistoryNodeId>,
+    /// Child followed by a plain redo (most recently visited)
+    active_child: Option<HistoryNodeId>,
+    description: String,
+    timestamp: u64,
+    thumbnail: Option<HistoryThumbnail>,
+    undo_state: Option<StoredState>,
+    redo_state: Option<StoredState>,
+}
+
+impl HistoryNode {
+    fn new(id: HistoryNodeId, parent: Option<HistoryNodeId>, description: String) -> Self {
+        Self {
+            id,
+            parent,
+            children: Vec::new(),
+            active_child: None,
+            description,
+            timestamp: current_timestamp(),
+            thumbnail: None,
+            undo_state: None,
+            redo_state: None,
+        }
+    }
+
+    /// Size of the node with all states loaded
+    fn memory_size(&self) -> usize {
+        self.undo_state.as_ref().map_or(0, |s| s.memory_size())
+            + self.redo_state.as_ref().map_or(0, |s| s.memory_size())
+            + self.thumbnail.as_ref().map_or(0, |t| t.memory_size())
+    }
+
+    /// Bytes the node occupies in RAM right now
+    fn resident_size(&self) -> usize {
+        self.undo_state.as_ref().map_or(0, |s| s.resident_size())
+            + self.redo_state.as_ref().map_or(0, |s| s.resident_size())
+            + self.thumbnail.as_ref().map_or(0, |t| t.memory_size())
+    }
+
+    fn slot_mut(&mut self, slot: StateSlot) -> &mut Option<StoredState> {
+        match slot {
+            StateSlot::Undo => &mut self.undo_state,
+            StateSlot::Redo => &mut self.redo_state,
+        }
+    }
+}
+
+/// A user-named snapshot of the whole document
+///
+/// Snapshots live outside the tree, so step-limit and memory eviction never
+/// remove them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct NamedSnapshot {
+    id: HistoryNodeId,
+    name: String,
+    timestamp: u64,
+    thumbnail: Option<HistoryThumbnail>,
+    state: HistoryState,
+}
+
+impl NamedSnapshot {
+    fn memory_size(&self) -> usize {
+        self.state.memory_size() + self.thumbnail.as_ref().map_or(0, |t| t.memory_size())
+    }
+
+    fn info(&self) -> HistorySnapshotInfo {
+        HistorySnapshotInfo {
+            id: self.id,
+            name: self.name.clone(),
+            timestamp: self.timestamp,
+            thumbnail: self.thumbnail.clone(),
+        }
+    }
+}
+
+/// History manager for undo/redo operations
+///
+/// History is kept as a tree: a new action after an undo starts a new branch
+/// instead of discarding the redo branch, and any node can be reached with
+/// [`HistoryManager::path_to`].
+pub struct HistoryManager {
+    /// All nodes of the tree
+    nodes: HashMap<HistoryNodeId, HistoryNode>,
+    /// Oldest retained node
+    root: HistoryNodeId,
+    /// Node matching the current document state
+    current: HistoryNodeId,
+    /// Next ID to hand out (shared by nodes and snapshots)
+    next_id: HistoryNodeId,
+    /// Named snapshots
+    snapshots: Vec<NamedSnapshot>,
+    /// Maximum number of undo steps
+    max_steps: usize,
+    /// Memory limit for history (soft limit)
+    memory_limit: usize,
+    /// Current estimated RAM usage of the tree
+    current_memory: usize,
+    /// Disk cache for states beyond the memory limit
+    spill: Option<HistorySpillCache>,
+    /// Limit for paged-out states on disk
+    disk_limit: usize,
+}
+
+impl HistoryManager {
+    /// Create a new history manager
+    pub fn new() -> Self {
+        Self::with_max_steps(DEFAULT_MAX_UNDO_STEPS)
+    }
+
+    /// Create with custom max steps
+    pub fn with_max_steps(max_steps: usize) -> Self {
+        let mut nodes = HashMap::new();
+        nodes.insert(0, HistoryNode::new(0, None, ROOT_DESCRIPTION.into()));
+
+        Self {
+            nodes,
+            root: 0,
+            current: 0,
+            next_id: 1,
+            snapshots: Vec::new(),
+            max_steps,
+            memory_limit: 512 * 1024 * 1024, // 512 MB default
+            current_memory: 0,
+            spill: None,
+            disk_limit: DEFAULT_DISK_LIMIT,
+        }
+    }
+
+    /// Set memory limit for history
+    pub fn set_memory_limit(&mut self, limit: usize) {
+        self.memory_limit = limit;
+        self.enforce_limits();
+    }
+
+    /// Get current memory usage (tree and named snapshots)
+    pub fn memory_usage(&self) -> usize {
+        self.current_memory + self.snapshots.iter().map(|s| s.memory_size()).sum::<usize>()
+    }
+
+    /// Page states beyond the memory limit out to a cache directory under `base`
+    /// instead of evicting them
+    pub fn enable_disk_spill(&mut self, base: &Path) -> EngineResult<()> {
+        if self.spill.is_none() {
+            self.spill = Some(HistorySpillCache::new(base)?);
+            self.enforce_limits();
+        }
+        Ok(())
+    }
+
+    /// Check if states are paged out to disk under memory pressure
+    pub fn is_disk_spill_enabled(&self) -> bool {
+        self.spill.is_some()
+    }
+
+    /// Set the limit for paged-out states on disk; older steps are evicted beyond it
+    pub fn set_disk_limit(&mut self, limit: usize) {
+        self.disk_limit = limit;
+        self.enforce_limits();
+    }
+
+    /// Get RAM versus disk usage
+    pub fn memory_stats(&self) -> HistoryMemoryStats {
+        let mut stats = HistoryMemoryStats {
+            ram_bytes: self.memory_usage(),
+            disk_bytes: self.spill.as_ref().map_or(0, |c| c.disk_bytes()),
+            ..Default::default()
+        };
+        for state in self.nodes.values().flat_map(|n| n.undo_state.iter().chain(n.redo_state.iter())) {
+            match state {
+                StoredState::Resident(_) => stats.resident_states += 1,
+                StoredState::Spilled(_) => stats.spilled_states += 1,
+            }
+        }
+        stats
+    }
+
+    /// Push a new state as a child of the current node
+    ///
+    /// Other children of the current node are kept as alternative branches.
+    pub fn push_state(&mut self, state: HistoryState) {
+        let id = self.alloc_id();
+        let mut node = HistoryNode::new(id, Some(self.current), state.description.clone());
+        self.current_memory += state.memory_size();
+        node.undo_state = Some(StoredState::Resident(state));
+        self.nodes.insert(id, node);
+
+        if let Some(parent) = self.nodes.get_mut(&self.current) {
+            parent.children.push(id);
+            parent.active_child = Some(id);
+        }
+        self.current = id;
+
+        self.enforce_limits();
+    }
+
+    /// Move to the parent of the current node
+    /// Returns the state to restore and keeps `current_state` for redo
+    pub fn undo(&mut self, current_state: HistoryState) -> Option<HistoryState> {
+        let id = self.current;
+        let parent = self.nodes.get(&id)?.parent?;
+        let state = self.take_state(id, StateSlot::Undo)?;
+
+        // Update memory tracking
+        self.current_memory += current_state.memory_size();
+        if let Some(node) = self.nodes.get_mut(&id) {
+            node.redo_state = Some(StoredState::Resident(current_state));
+        }
+
+        if let Some(parent_node) = self.nodes.get_mut(&parent) {
+            parent_node.active_child = Some(id);
+        }
+        self.current = parent;
+        self.enforce_limits();
+        Some(state)
+    }
+
+    /// Move to the most recently visited child of the current node
+    /// Returns the state to restore and keeps `current_state` for undo
+    pub fn redo(&mut self, current_state: HistoryState) -> Option<HistoryState> {
+        let child = self.nodes.get(&self.current)?.active_child?;
+        self.redo_to(child, current_state)
+    }
+
+    /// Move to a specific child of the current node
+    /// Returns the state to restore and keeps `current_state` for undo
+    pub fn redo_to(&mut self, child: HistoryNodeId, current_state: HistoryState) -> Option<HistoryState> {
+        let current = self.current;
+        if self.nodes.get(&child)?.parent != Some(current) {
+            return None;
+        }
+        let state = self.take_state(child, StateSlot::Redo)?;
+
+        // Update memory tracking
+        self.current_memory += current_state.memory_size();
+        if let Some(node) = self.nodes.get_mut(&child) {
+            node.undo_state = Some(StoredState::Resident(current_state));
+        }
+
+        if let Some(parent_node) = self.nodes.get_mut(&current) {
+            parent_node.active_child = Some(child);
+        }
+        self.current = child;
+        self.enforce_limits();
+        Some(state)
+    }
+
+    /// Get the state the next undo would restore, paging it in if needed
+    pub fn peek_undo(&mut self) -> Option<&HistoryState> {
+        self.peek_state(self.current, StateSlot::Undo)
+    }
+
+    /// Get the child a plain redo would move to
+    pub fn next_redo_node(&self) -> Option<HistoryNodeId> {
+        self.nodes.get(&self.current)?.active_child
+    }
+
+    /// Get the state redoing into `child` would restore, paging it in if needed
+    pub fn peek_redo(&mut self, child: HistoryNodeId) -> Option<&HistoryState> {
+        self.peek_state(child, StateSlot::Redo)
+    }
+
+    /// Undo states of `node` and of every later step up to the current node,
+    /// oldest first
+    ///
+    /// Returns None unless `node` is a step on the current branch, at or
+    /// before the current node. Paged-out states are read without being
+    /// brought back into RAM.
+    pub fn states_since(&self, node: HistoryNodeId) -> Option<Vec<(HistoryNodeId, HistoryState)>> {
+        if node == self.root {
+            return None;
+        }
+
+        let path = self.ancestors(self.current);
+        let end = path.iter().position(|&id| id == node)?;
+        path[..=end]
+            .iter()
+            .rev()
+            .map(|&id| {
+                let stored = self.nodes.get(&id)?.undo_state.as_ref()?;
+                Some((id, self.load_state(stored)?))
+            })
+            .collect()
+    }
+
+    /// Compute the undo/redo steps leading from the current node to `target`
+    pub fn path_to(&self, target: HistoryNodeId) -> Option<HistoryPath> {
+        if !self.nodes.contains_key(&target) {
+            return None;
+        }
+
+        let current_path = self.ancestors(self.current);
+        let mut redo_nodes = Vec::new();
+        let mut node = target;
+        while !current_path.contains(&node) {
+            redo_nodes.push(node);
+            node = self.nodes.get(&node)?.parent?;
+        }
+        redo_nodes.reverse();
+
+        let undo_steps = current_path.iter().position(|&id| id == node)?;
+        Some(HistoryPath { undo_steps, redo_nodes })
+    }
+
+    /// Check if undo is available
+    pub fn can_undo(&self) -> bool {
+        self.current != self.root
+    }
+
+    /// Check if redo is available
+    pub fn can_redo(&self) -> bool {
+        self.nodes
+            .get(&self.current)
+            .is_some_and(|n| n.active_child.is_some())
+    }
+
+    /// Get the number of undo steps available
+    pub fn undo_count(&self) -> usize {
+        self.depth(self.current)
+    }
+
+    /// Get the number of redo steps available along the active branch
+    pub fn redo_count(&self) -> usize {
+        let mut count = 0;
+        let mut node = self.nodes.get(&self.current).and_then(|n| n.active_child);
+        while let Some(id) = node {
+            count += 1;
+            node = self.nodes.get(&id).and_then(|n| n.active_child);
+        }
+        count
+    }
+
+    /// Get the ID of the node matching the current document state
+    pub fn current_node(&self) -> HistoryNodeId {
+        self.current
+    }
+
+    /// Get the ID of the oldest retained node
+    pub fn root_node(&self) -> HistoryNodeId {
+        self.root
+    }
+
+    /// Get the total number of retained nodes, including abandoned branches
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Describe a single node
+    pub fn node(&self, id: HistoryNodeId) -> Option<HistoryNodeInfo> {
+        let active = self.ancestors(self.current);
+        self.nodes.get(&id).map(|n| self.node_info(n, &active))
+    }
+
+    /// Describe all nodes of the tree, in creation order
+    pub fn nodes(&self) -> Vec<HistoryNodeInfo> {
+        let active = self.ancestors(self.current);
+        let mut infos: Vec<_> = self.nodes.values().map(|n| self.node_info(n, &active)).collect();
+        infos.sort_by_key(|n| n.id);
+        infos
+    }
+
+    /// Attach a thumbnail to a node
+    pub fn set_thumbnail(&mut self, id: HistoryNodeId, thumbnail: HistoryThumbnail) -> bool {
+        if let Some(node) = self.nodes.get_mut(&id) {
+            if let Some(old) = node.thumbnail.take() {
+                self.current_memory = self.current_memory.saturating_sub(old.memory_size());
+            }
+            self.current_memory += thumbnail.memory_size();
+            node.thumbnail = Some(thumbnail);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Store a named snapshot of the whole document
+    ///
+    /// `state` must hold full snapshots of every layer.
+    pub fn add_snapshot(
+        &mut self,
+        name: impl Into<String>,
+        state: HistoryState,
+        thumbnail: Option<HistoryThumbnail>,
+    ) -> HistoryNodeId {
+        let id = self.alloc_id();
+        self.snapshots.push(NamedSnapshot {
+            id,
+            name: name.into(),
+            timestamp: current_timestamp(),
+            thumbnail,
+            state,
+        });
+        id
+    }
+
+    /// Get the stored state of a named snapshot
+    pub fn snapshot_state(&self, id: HistoryNodeId) -> Option<&HistoryState> {
+        self.snapshots.iter().find(|s| s.id == id).map(|s| &s.state)
+    }
+
+    /// Rename a named snapshot
+    pub fn rename_snapshot(&mut self, id: HistoryNodeId, name: impl Into<String>) -> bool {
+        if let Some(snapshot) = self.snapshots.iter_mut().find(|s| s.id == id) {
+            snapshot.name = name.into();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Delete a named snapshot
+    pub fn remove_snapshot(&mut self, id: HistoryNodeId) -> bool {
+        let before = self.snapshots.len();
+        self.snapshots.retain(|s| s.id != id);
+        self.snapshots.len() != before
+    }
+
+    /// Describe all named snapshots, oldest first
+    pub fn snapshots(&self) -> Vec<HistorySnapshotInfo> {
+        self.snapshots.iter().map(|s| s.info()).collect()
+    }
+
+    /// Clear all history (named snapshots are kept)
+    pub fn clear(&mut self) {
+        let id = self.alloc_id();
+        for node in std::mem::take(&mut self.nodes).into_values() {
+            self.discard_spilled(&node);
+        }
+        self.nodes.insert(id, HistoryNode::new(id, None, ROOT_DESCRIPTION.into()));
+        self.root = id;
+        self.current = id;
+        self.current_memory = 0;
+    }
+
+    fn alloc_id(&mut self) -> HistoryNodeId {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Remove a state from a node, reading it back from disk if paged out
+    fn take_state(&mut self, id: HistoryNodeId, slot: StateSlot) -> Option<HistoryState> {
+        let stored = self.nodes.get_mut(&id)?.slot_mut(slot).take()?;
+        match stored {
+            StoredState::Resident(state) => {
+                self.current_memory = self.current_memory.saturating_sub(state.memory_size());
+                Some(state)
+            }
+            StoredState::Spilled(spilled) => {
+                let cache = self.spill.as_mut()?;
+                match cache.load(&spilled) {
+                    Ok(state) => {
+                        cache.remove(&spilled);
+                        Some(state)
+                    }
+                    Err(e) => {
+                        log::error!("Failed to page in history state: {}", e);
+                        if let Some(node) = self.nodes.get_mut(&id) {
+                            *node.slot_mut(slot) = Some(StoredState::Spilled(spilled));
+                        }
+                        None
+                    }
+                }
+            }
+        }
+    }
+
+    /// Borrow a node's state, paging it back into RAM if needed
+    fn peek_state(&mut self, id: HistoryNodeId, slot: StateSlot) -> Option<&HistoryState> {
+        if let Some(StoredState::Spilled(_)) = self.nodes.get_mut(&id)?.slot_mut(slot) {
+            let state = self.take_state(id, slot)?;
+            self.current_memory += state.memory_size();
+            *self.nodes.get_mut(&id)?.slot_mut(slot) = Some(StoredState::Resident(state));
+        }
+
+        match self.nodes.get_mut(&id)?.slot_mut(slot) {
+            Some(StoredState::Resident(state)) => Some(state),
+            _ => None,
+        }
+    }
+
+    /// Read a node's state without changing where it lives
+    fn load_state(&self, stored: &StoredState) -> Option<HistoryState> {
+        match stored {
+            StoredState::Resident(state) => Some(state.clone()),
+            StoredState::Spilled(spilled) => match self.spill.as_ref()?.load(spilled) {
+                Ok(state) => Some(state),
+                Err(e) => {
+                    log::error!("Failed to read paged-out history state: {}", e);
+                    None
+                }
+            },
+        }
+    }
+
+    /// Page the resident state furthest from the current node out to disk
+    ///
+    /// Distance is counted in tree edges, so states on abandoned branches
+    /// and deep in the undo stack go first; ties go to the oldest node.
+    /// States the next undo or redo would need are kept in RAM.
+    fn spill_oldest(&mut self) -> bool {
+        let current = self.current;
+        let distances = self.distances_from(current);
+        let candidate = self
+            .nodes
+            .values()
+            .flat_map(|n| {
+                let undo = matches!(n.undo_state, Some(StoredState::Resident(_))) && n.id != current;
+                let redo = matches!(n.redo_state, Some(StoredState::Resident(_))) && n.parent != Some(current);
+                [(n.id, StateSlot::Undo, undo), (n.id, StateSlot::Redo, redo)]
+            })
+            .filter(|&(_, _, eligible)| eligible)
+            .map(|(id, slot, _)| (id, slot))
+            .max_by_key(|&(id, _)| {
+                let distance = distances.get(&id).copied().unwrap_or(usize::MAX);
+                (distance, std::cmp::Reverse(id))
+            });
+
+        let Some((id, slot)) = candidate else {
+            return false;
+        };
+        let Some(StoredState::Resident(state)) = self.nodes.get_mut(&id).and_then(|n| n.slot_mut(slot).take()) else {
+            return false;
+        };
+        let Some(cache) = self.spill.as_mut() else {
+            return false;
+        };
+
+        match cache.store(&state) {
+            Ok(spilled) => {
+                self.current_memory = self.current_memory.saturating_sub(state.memory_size());
+                if let Some(node) = self.nodes.get_mut(&id) {
+                    *node.slot_mut(slot) = Some(StoredState::Spilled(spilled));
+                }
+                true
+            }
+            Err(e) => {
+                log::error!("Failed to page out history state: {}", e);
+                if let Some(node) = self.nodes.get_mut(&id) {
+                    *node.slot_mut(slot) = Some(StoredState::Resident(state));
+                }
+                false
+            }
+        }
+    }
+
+    /// Delete the disk copies of a node's paged-out states
+    fn discard_spilled(&mut self, node: &HistoryNode) {
+        if let Some(cache) = self.spill.as_mut() {
+            for state in node.undo_state.iter().chain(node.redo_state.iter()) {
+                if let StoredState::Spilled(spilled) = state {
+                    cache.remove(spilled);
+                }
+            }
+        }
+    }
+
+    /// Node IDs from `id` up to the root (inclusive)
+    fn ancestors(&self, id: HistoryNodeId) -> Vec<HistoryNodeId> {
+        let mut path = Vec::new();
+        let mut node = Some(id);
+        while let Some(n) = node {
+            path.push(n);
+            node = self.nodes.get(&n).and_then(|n| n.parent);
+        }
+        path
+    }
+
+    /// Number of tree edges from `start` to every node
+    fn distances_from(&self, start: HistoryNodeId) -> HashMap<HistoryNodeId, usize> {
+        let mut distances = HashMap::from([(start, 0)]);
+        let mut queue = std::collections::VecDeque::from([start]);
+        while let Some(id) = queue.pop_front() {
+            let Some(node) = self.nodes.get(&id) else {
+                continue;
+            };
+            let distance = distances[&id] + 1;
+            for next in node.parent.iter().chain(&node.children) {
+                if !distances.contains_key(next) {
+                    distances.insert(*next, distance);
+                    queue.push_back(*next);
+                }
+            }
+        }
+        distances
+    }
+
+    fn depth(&self, id: HistoryNodeId) -> usize {
+        self.ancestors(id).len().saturating_sub(1)
+    }
+
+    fn node_info(&self, node: &HistoryNode, active: &[HistoryNodeId]) -> HistoryNodeInfo {
+        HistoryNodeInfo {
+            id: node.id,
+            parent: node.parent,
+            children: node.children.clone(),
+            description: node.description.clone(),
+            timestamp: node.timestamp,
+            thumbnail: node.thumbnail.clone(),
+            depth: self.depth(node.id),
+            is_current: node.id == self.current,
+            is_active_branch: active.contains(&node.id),
+        }
+    }
+
+    /// Evict or page out old steps until the step, memory and disk limits are met
+    fn enforce_limits(&mut self) {
+        while self.nodes.len() - 1 > self.max_steps {
+            if !self.evict_oldest() {
+                break;
+            }
+        }
+
+        while self.current_memory > self.memory_limit {
+            if self.spill.is_some() && self.spill_oldest() {
+                continue;
+            }
+            if self.nodes.len() <= 2 || !self.evict_oldest() {
+                break;
+            }
+        }
+
+        while self.spill.as_ref().is_some_and(|c| c.disk_bytes() > self.disk_limit) && self.nodes.len() > 2 {
+            if !self.evict_oldest() {
+                break;
+            }
+        }
+    }
+
+    /// Evict the oldest step: either an abandoned leaf or the root edge
+    fn evict_oldest(&mut self) -> bool {
+        let active = self.ancestors(self.current);
+
+        let oldest_leaf = self
+            .nodes
+            .values()
+            .filter(|n| n.children.is_empty() && !active.contains(&n.id))
+            .map(|n| n.id)
+            .min();
+        // The root's child on the active branch becomes the new root
+        let root_child = active.iter().rev().nth(1).copied();
+
+        match (oldest_leaf, root_child) {
+            (Some(leaf), Some(child)) if child < leaf => self.collapse_root(child),
+            (Some(leaf), _) => self.remove_node(leaf),
+            (None, Some(child)) => self.collapse_root(child),
+            (None, None) => return false,
+        }
+        true
+    }
+
+    /// Drop the root and every branch except the one through `new_root`
+    fn collapse_root(&mut self, new_root: HistoryNodeId) {
+        let old_root = self.root;
+        let siblings: Vec<_> = self
+            .nodes
+            .get(&old_root)
+            .map(|n| n.children.iter().copied().filter(|&c| c != new_root).collect())
+            .unwrap_or_default();
+        for sibling in siblings {
+            self.remove_subtree(sibling);
+        }
+        self.remove_node(old_root);
+
+        if let Some(node) = self.nodes.get_mut(&new_root) {
+            node.parent = None;
+        }
+        if let Some(state) = self.nodes.get_mut(&new_root).and_then(|n| n.undo_state.take()) {
+            self.current_memory = self.current_memory.saturating_sub(state.resident_size());
+            if let (StoredState::Spilled(spilled), Some(cache)) = (&state, self.spill.as_mut()) {
+                cache.remove(spilled);
+            }
+        }
+        self.root = new_root;
+    }
+
+    fn remove_subtree(&mut self, id: HistoryNodeId) {
+        let children = self.nodes.get(&id).map(|n| n.children.clone()).unwrap_or_default();
+        for child in children {
+            self.remove_subtree(child);
+        }
+        self.remove_node(id);
+    }
+
+    /// Remove a single node and unlink it from its parent
+    fn remove_node(&mut self, id: HistoryNodeId) {
+        let Some(node) = self.nodes.remove(&id) else {
+            return;
+        };
+        self.current_memory = self.current_memory.saturating_sub(node.resident_size());
+        self.discard_spilled(&node);
+
+        if let Some(parent) = node.parent.and_then(|p| self.nodes.get_mut(&p)) {
+            parent.children.retain(|&c| c != id);
+            if parent.active_child == Some(id) {
+                parent.active_child = parent.children.last().copied();
+            }
+        }
+    }
+}
+
+impl Default for HistoryManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Get current timestamp in seconds since UNIX epoch
+fn current_timestamp() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dirty_rect_expand() {
+        let mut rect = DirtyRect::new(10, 10, 5, 5);
+        rect.expand_to(20, 20);
+        assert_eq!(rect.x, 10);
+        assert_eq!(rect.y, 10);
+        assert_eq!(rect.width, 11);
+        assert_eq!(rect.height, 11);
+    }
+
+    #[test]
+    fn test_dirty_rect_union() {
+        let mut rect1 = DirtyRect::new(10, 10, 10, 10);
+        let rect2 = DirtyRect::new(5, 15, 10, 10);
+        rect1.union(&rect2);
+        assert_eq!(rect1.x, 5);
+        assert_eq!(rect1.y, 10);
+        assert_eq!(rect1.width, 15);
+        assert_eq!(rect1.height, 15);
+    }
+
+    #[test]
+    fn test_incremental_snapshot() {
+        // Create a 100x100 layer with some pixels
+        let layer_width = 100u32;
+        let layer_height = 100u32;
+        let mut pixels = vec![0u8; (layer_width * layer_height * 4) as usize];
+
+        // Fill a 10x10 region with red
+        for y in 20..30 {
+            for x in 20..30 {
+                let idx = ((y * layer_width + x) * 4) as usize;
+                pixels[idx] = 255;     // R
+                pixels[idx + 1] = 0;   // G
+                pixels[idx + 2] = 0;   // B
+                pixels[idx + 3] = 255; // A
+            }
+        }
+
+        // Create incremental snapshot of just the red region
+        let dirty = DirtyRect::new(20, 20, 10, 10);
+        let snapshot = LayerSnapshot::incremental(
+            Uuid::new_v4(),
+            &pixels,
+            layer_width,
+            layer_height,
+            dirty,
+        );
+
+        // Snapshot should be much smaller than full (400 bytes vs 40000 bytes)
+        assert!(snapshot.memory_size() < 1000);
+
+        // Restore to a fresh buffer
+        let mut restored = vec![0u8; (layer_width * layer_height * 4) as usize];
+        snapshot.restore_to(&mut restored, layer_width);
+
+        // Check restored region has red pixels
+        for y in 20..30 {
+            for x in 20..30 {
+                let idx = ((y * layer_width + x) * 4) as usize;
+                assert_eq!(restored[idx], 255, "Red channel should be 255");
+                assert_eq!(restored[idx + 3], 255, "Alpha channel should be 255");
+            }
+        }
+    }
+
+    #[test]
+    fn test_push_and_undo() {
+        let mut manager = HistoryManager::new();
+
+        // Create a state
+        let state1 = HistoryState::new("Draw stroke 1");
+        manager.push_state(state1);
+
+        assert!(manager.can_undo());
+        assert!(!manager.can_redo());
+
+        // Undo with current state
+        let current = HistoryState::new("Current");
+        let restored = manager.undo(current);
+        assert!(restored.is_some());
+        assert_eq!(restored.unwrap().description, "Draw stroke 1");
+
+        assert!(!manager.can_undo());
+        assert!(manager.can_redo());
+    }
+
+    #[test]
+    fn test_undo_redo_cycle() {
+        let mut manager = HistoryManager::new();
+
+        manager.push_state(HistoryState::new("Action 1"));
+        manager.push_state(HistoryState::new("Action 2"));
+
+        assert_eq!(manager.undo_count(), 2);
+        assert_eq!(manager.redo_count(), 0);
+
+        // Undo
+        let current = HistoryState::new("Current after 2");
+        manager.undo(current);
+
+        assert_eq!(manager.undo_count(), 1);
+        assert_eq!(manager.redo_count(), 1);
+
+        // Redo
+        let current = HistoryState::new("Current after undo");
+        manager.redo(current);
+
+        assert_eq!(manager.undo_count(), 2);
+        assert_eq!(manager.redo_count(), 0);
+    }
+
+    #[test]
+    fn test_max_steps() {
+        let mut manager = HistoryManager::with_max_steps(3);
+
+        for i in 0..5 {
+            manager.push_state(HistoryState::new(format!("Action {}", i)));
+        }
+
+        // Should only keep last 3
+        assert_eq!(manager.undo_count(), 3);
+    }
+
+    #[test]
+    fn test_new_edit_keeps_abandoned_branch() {
+        let mut manager = HistoryManager::new();
+
+        manager.push_state(HistoryState::new("Action 1"));
+        manager.push_state(HistoryState::new("Action 2"));
+        let abandoned = manager.current_node();
+
+        manager.undo(HistoryState::new("Current"));
+        manager.push_state(HistoryState::new("Action 3"));
+
+        // Root, Action 1, Action 2 and Action 3 are all kept
+        assert_eq!(manager.node_count(), 4);
+        assert!(!manager.can_redo());
+
+        let path = manager.path_to(abandoned).unwrap();
+        assert_eq!(path.undo_steps, 1);
+        assert_eq!(path.redo_nodes, vec![abandoned]);
+
+        // Walk over to the abandoned branch
+        assert!(manager.undo(HistoryState::new("Current")).is_some());
+        let restored = manager.redo_to(abandoned, HistoryState::new("Current"));
+        assert_eq!(restored.unwrap().description, "Current");
+        assert_eq!(manager.current_node(), abandoned);
+    }
+
+    #[test]
+    fn test_node_listing() {
+        let mut manager = HistoryManager::new();
+        manager.push_state(HistoryState::new("Stroke"));
+        let id = manager.current_node();
+        manager.set_thumbnail(
+            id,
+            HistoryThumbnail { width: 16, height: 8, pixels: vec![255; 16 * 8 * 4] },
+        );
+
+        let nodes = manager.nodes();
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0].description, ROOT_DESCRIPTION);
+        assert_eq!(nodes[1].description, "Stroke");
+        assert!(nodes[1].is_current);
+        assert_eq!(nodes[1].depth, 1);
+
+        let thumb = nodes[1].thumbnail.as_ref().unwrap();
+        assert_eq!((thumb.width, thumb.height), (16, 8));
+    }
+
+    #[test]
+    fn test_snapshots_survive_eviction() {
+        let mut manager = HistoryManager::with_max_steps(2);
+        let snapshot = manager.add_snapshot("before coloring", HistoryState::new("Snapshot"), None);
+
+        for i in 0..5 {
+            manager.push_state(HistoryState::new(format!("Action {}", i)));
+        }
+
+        assert_eq!(manager.undo_count(), 2);
+        assert_eq!(manager.snapshots().len(), 1);
+        assert_eq!(manager.snapshots()[0].name, "before coloring");
+        assert!(manager.snapshot_state(snapshot).is_some());
+    }
+
+    #[test]
+    fn test_eviction_prefers_oldest_step() {
+        let mut manager = HistoryManager::with_max_steps(3);
+
+        manager.push_state(HistoryState::new("Action 1"));
+        manager.push_state(HistoryState::new("Action 2"));
+        manager.undo(HistoryState::new("Current"));
+        manager.push_state(HistoryState::new("Action 3"));
+        manager.push_state(HistoryState::new("Action 4"));
+
+        // Action 1 is older than the abandoned Action 2, so the root edge goes first
+        assert_eq!(manager.node_count(), 4);
+        assert_eq!(manager.undo_count(), 2);
+    }
+
+    #[test]
+    fn test_disk_spill_round_trip() {
+        let layer_id = Uuid::new_v4();
+        let pixels: Vec<u8> = (0..64 * 64 * 4).map(|i| (i % 251) as u8).collect();
+        let state = |description: String| {
+            let mut state = HistoryState::new(description);
+            state.add_snapshot(LayerSnapshot::incremental(layer_id, &pixels, 64, 64, DirtyRect::full(64, 64)));
+            state
+        };
+        let step_size = state(String::new()).memory_size();
+
+        let mut manager = HistoryManager::new();
+        manager.set_memory_limit(step_size * 2);
+        manager.enable_disk_spill(&std::env::temp_dir()).unwrap();
+        for i in 0..5 {
+            manager.push_state(state(format!("Stroke {}", i)));
+        }
+
+        // Nothing is evicted, older steps live on disk
+        let stats = manager.memory_stats();
+        assert_eq!(manager.undo_count(), 5);
+        assert_eq!(stats.resident_states + stats.spilled_states, 5);
+        assert!(stats.spilled_states >= 3);
+        assert!(stats.disk_bytes > 0);
+        assert!(stats.ram_bytes <= step_size * 2);
+
+        // Undo pages states back in transparently
+        for i in (0..5).rev() {
+            assert_eq!(manager.peek_undo().unwrap().description, format!("Stroke {}", i));
+            let restored = manager.undo(state("Current".into())).unwrap();
+            assert_eq!(restored.layer_snapshots[0].dirty_rect().unwrap().area(), 64 * 64);
+        }
+        assert!(!manager.can_undo());
+        assert!(manager.memory_usage() <= step_size * 2);
+    }
+
+    #[test]
+    fn test_spill_pages_out_furthest_state_first() {
+        let layer_id = Uuid::new_v4();
+        let pixels = vec![7u8; 16 * 16 * 4];
+        let state = |description: &str| {
+            let mut state = HistoryState::new(description);
+            state.add_snapshot(LayerSnapshot::incremental(layer_id, &pixels, 16, 16, DirtyRect::full(16, 16)));
+            state
+        };
+
+        let mut manager = HistoryManager::new();
+        manager.enable_disk_spill(&std::env::temp_dir()).unwrap();
+        for i in 0..5 {
+            manager.push_state(state(&format!("Stroke {}", i)));
+        }
+        for _ in 0..4 {
+            manager.undo(state("Current")).unwrap();
+        }
+
+        // Near the root, the redo state at the far end of the chain goes first
+        assert!(manager.spill_oldest());
+        let spilled: Vec<_> = manager
+            .nodes
+            .values()
+            .filter(|n| {
+                [&n.undo_state, &n.redo_state]
+                    .iter()
+                    .any(|slot| matches!(slot, Some(StoredState::Spilled(_))))
+            })
+            .map(|n| n.id)
+            .collect();
+        let distances = manager.distances_from(manager.current);
+        let furthest = manager.nodes.keys().map(|id| distances[id]).max().unwrap();
+        assert_eq!(spilled.len(), 1);
+        assert_eq!(distances[&spilled[0]], furthest);
+        assert!(furthest >= 3);
+    }
+}
@@ -0,0 +1,411 @@
+//! History persistence
+//!
+//! Converts the history tree to and from a serializable form that documents
+//! store next to their layers. Snapshots keep their compressed dirty-region
+//! deltas, so saved history stays small for typical brush work.
+
+use super::spill::SpilledState;
+use legacy::{Resident, V1State, V2State};
+use super::{
+    HistoryManager, HistoryNode, HistoryNodeId, HistoryState, HistoryThumbnail, LayerSnapshot,
+    NamedSnapshot, StoredState,
+};
+use crate::error::{EngineError, EngineResult};
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Version of the saved history layout
+///
+/// Bump this when [`SavedHistory`] changes shape, and keep a decoder for the
+/// previous layouts in [`SavedHistory::decode`].
+///
+/// - v1: layer snapshots only, every state resident
+/// - v2: node states may be paged out to the disk cache
+/// - v3: states may also carry a canvas snapshot
+/// - v4: states may also carry a layer list and vector shapes
+pub const HISTORY_FORMAT_VERSION: u32 = 4;
+
+/// History tree in its saved form
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedHistory {
+    root: HistoryNodeId,
+    current: HistoryNodeId,
+    next_id: HistoryNodeId,
+    nodes: Vec<HistoryNode>,
+    snapshots: Vec<NamedSnapshot>,
+}
+
+impl SavedHistory {
+    /// Number of saved nodes, including the root
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Number of saved named snapshots
+    pub fn snapshot_count(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    /// Encode as a version tag followed by the bincode payload
+    pub fn encode(&self) -> EngineResult<Vec<u8>> {
+        let mut data = HISTORY_FORMAT_VERSION.to_le_bytes().to_vec();
+        data.extend_from_slice(&bincode::serialize(self)?);
+        Ok(data)
+    }
+
+    /// Decode data written by [`SavedHistory::encode`]
+    pub fn decode(data: &[u8]) -> EngineResult<Self> {
+        if data.len() < 4 {
+            return Err(EngineError::SerializationError("Truncated history data".into()));
+        }
+
+        let version = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+        match version {
+            1 => {
+                let saved: legacy::SavedHistory<V1State, Resident<V1State>> = bincode::deserialize(&data[4..])?;
+                Ok(saved.into())
+            }
+            2 => Ok(bincode::deserialize::<legacy::SavedHistory<V1State>>(&data[4..])?.into()),
+            3 => Ok(bincode::deserialize::<legacy::SavedHistory<V2State>>(&data[4..])?.into()),
+            4 => Ok(bincode::deserialize(&data[4..])?),
+            _ => Err(EngineError::UnsupportedFormat(format!(
+                "History format version {} (supported: {})",
+                version, HISTORY_FORMAT_VERSION
+            ))),
+        }
+    }
+}
+
+impl HistoryManager {
+    /// Export the history tree, keeping at most `max_bytes` of state data
+    ///
+    /// The active branch is kept newest-first, then named snapshots, then
+    /// abandoned branches. Steps that do not fit are dropped from the oldest
+    /// end, so the saved tree may be rooted below the in-memory root.
+    pub fn export_history(&self, max_bytes: usize) -> SavedHistory {
+        let mut kept = HashSet::new();
+        let mut used = 0usize;
+
+        // The current node always survives, it matches the saved pixels
+        kept.insert(self.current);
+        let active = self.ancestors(self.current);
+        for pair in active.windows(2) {
+            let (child, parent) = (pair[0], pair[1]);
+            let size = self.nodes.get(&child).map_or(0, |n| n.memory_size());
+            if used + size > max_bytes {
+                break;
+            }
+            used += size;
+            kept.insert(parent);
+        }
+
+        let mut snapshots = Vec::new();
+        for snapshot in &self.snapshots {
+            let size = snapshot.memory_size();
+            if used + size <= max_bytes {
+                used += size;
+                snapshots.push(snapshot.clone());
+            }
+        }
+
+        // Children always have higher IDs than their parents
+        let mut branch_ids: Vec<_> = self.nodes.keys().copied().filter(|id| !kept.contains(id)).collect();
+        branch_ids.sort_unstable();
+        for id in branch_ids {
+            let Some(node) = self.nodes.get(&id) else {
+                continue;
+            };
+            let size = node.memory_size();
+            if node.parent.is_some_and(|p| kept.contains(&p)) && used + size <= max_bytes {
+                used += size;
+                kept.insert(id);
+            }
+        }
+
+        let root = active.iter().rev().copied().find(|id| kept.contains(id)).unwrap_or(self.current);
+        let mut nodes: Vec<_> = kept
+            .iter()
+            .filter_map(|id| self.nodes.get(id))
+            .map(|node| {
+                let mut node = self.resident_copy(node);
+                node.children.retain(|c| kept.contains(c));
+                if node.active_child.is_some_and(|c| !kept.contains(&c)) {
+                    node.active_child = node.children.last().copied();
+                }
+                if node.id == root {
+                    node.parent = None;
+                    node.undo_state = None;
+                }
+                node
+            })
+            .collect();
+        nodes.sort_by_key(|n| n.id);
+
+        SavedHistory {
+            root,
+            current: self.current,
+            next_id: self.next_id,
+            nodes,
+            snapshots,
+        }
+    }
+
+    /// Rebuild a history manager from saved history
+    pub fn import_history(saved: SavedHistory, max_steps: usize) -> EngineResult<Self> {
+        let mut manager = Self::with_max_steps(max_steps);
+        manager.restore_saved(saved)?;
+        Ok(manager)
+    }
+
+    /// Replace the tree and named snapshots with saved history, keeping this
+    /// manager's limits and disk cache
+    pub fn restore_saved(&mut self, saved: SavedHistory) -> EngineResult<()> {
+        let nodes: HashMap<_, _> = saved.nodes.into_iter().map(|n| (n.id, n)).collect();
+        if !nodes.contains_key(&saved.root) || !nodes.contains_key(&saved.current) {
+            return Err(EngineError::SerializationError(
+                "Saved history is missing its root or current node".into(),
+            ));
+        }
+
+        self.clear();
+        self.current_memory = nodes.values().map(|n| n.resident_size()).sum();
+        self.nodes = nodes;
+        self.root = saved.root;
+        self.current = saved.current;
+        self.next_id = self.next_id.max(saved.next_id);
+        self.snapshots = saved.snapshots;
+        self.enforce_limits();
+        Ok(())
+    }
+
+    /// Clone a node with any paged-out states read back from disk
+    fn resident_copy(&self, node: &HistoryNode) -> HistoryNode {
+        let load = |stored: &Option<StoredState>| {
+            stored
+                .as_ref()
+                .and_then(|s| self.load_state(s))
+                .map(StoredState::Resident)
+        };
+
+        HistoryNode {
+            id: node.id,
+            parent: node.parent,
+            children: node.children.clone(),
+            active_child: node.active_child,
+            description: node.description.clone(),
+            timestamp: node.timestamp,
+            thumbnail: node.thumbnail.clone(),
+            undo_state: load(&node.undo_state),
+            redo_state: load(&node.redo_state),
+        }
+    }
+}
+
+/// Layouts of saved history before the current version
+///
+/// Only the state layout and how nodes hold their states changed between
+/// versions, so the tree types are shared and take both as parameters.
+mod legacy {
+    use super::*;
+    use crate::canvas::CanvasSnapshot;
+
+    #[derive(Deserialize)]
+    pub(super) struct SavedHistory<S, N = Stored<S>> {
+        root: HistoryNodeId,
+        current: HistoryNodeId,
+        next_id: HistoryNodeId,
+        nodes: Vec<HistoryNode<N>>,
+        snapshots: Vec<NamedSnapshot<S>>,
+    }
+
+    /// Version 1 state, before states carried a canvas snapshot
+    #[derive(Deserialize)]
+    pub(super) struct V1State {
+        description: String,
+        layer_snapshots: Vec<LayerSnapshot>,
+    }
+
+    /// Version 2 state, before states carried layer lists and shapes
+    #[derive(Deserialize)]
+    pub(super) struct V2State {
+        description: String,
+        layer_snapshots: Vec<LayerSnapshot>,
+        canvas: Option<CanvasSnapshot>,
+    }
+
+    /// Version 1 node state, before states could be paged out to disk
+    #[derive(Deserialize)]
+    #[serde(transparent)]
+    pub(super) struct Resident<S>(S);
+
+    #[derive(Deserialize)]
+    pub(super) enum Stored<S> {
+        Resident(S),
+        Spilled(SpilledState),
+    }
+
+    #[derive(Deserialize)]
+    struct HistoryNode<N> {
+        id: HistoryNodeId,
+        parent: Option<HistoryNodeId>,
+        children: Vec<HistoryNodeId>,
+        active_child: Option<HistoryNodeId>,
+        description: String,
+        timestamp: u64,
+        thumbnail: Option<HistoryThumbnail>,
+        undo_state: Option<N>,
+        redo_state: Option<N>,
+    }
+
+    #[derive(Deserialize)]
+    struct NamedSnapshot<S> {
+        id: HistoryNodeId,
+        name: String,
+        timestamp: u64,
+        thumbnail: Option<HistoryThumbnail>,
+        state: S,
+    }
+
+    impl From<V1State> for HistoryState {
+        fn from(state: V1State) -> Self {
+            let mut converted = HistoryState::new(state.description);
+            converted.layer_snapshots = state.layer_snapshots;
+            converted
+        }
+    }
+
+    impl From<V2State> for HistoryState {
+        fn from(state: V2State) -> Self {
+            let mut converted = HistoryState::new(state.description);
+            converted.layer_snapshots = state.layer_snapshots;
+            converted.canvas = state.canvas;
+            converted
+        }
+    }
+
+    impl<S: Into<HistoryState>> From<Resident<S>> for StoredState {
+        fn from(resident: Resident<S>) -> Self {
+            StoredState::Resident(resident.0.into())
+        }
+    }
+
+    impl<S: Into<HistoryState>> From<Stored<S>> for StoredState {
+        fn from(stored: Stored<S>) -> Self {
+            match stored {
+                Stored::Resident(state) => StoredState::Resident(state.into()),
+                Stored::Spilled(spilled) => StoredState::Spilled(spilled),
+            }
+        }
+    }
+
+    impl<S: Into<HistoryState>, N: Into<StoredState>> From<SavedHistory<S, N>> for super::SavedHistory {
+        fn from(saved: SavedHistory<S, N>) -> Self {
+            let nodes = saved
+                .nodes
+                .into_iter()
+                .map(|n| super::HistoryNode {
+                    id: n.id,
+                    parent: n.parent,
+                    children: n.children,
+                    active_child: n.active_child,
+                    description: n.description,
+                    timestamp: n.timestamp,
+                    thumbnail: n.thumbnail,
+                    undo_state: n.undo_state.map(Into::into),
+                    redo_state: n.redo_state.map(Into::into),
+                })
+                .collect();
+
+            let snapshots = saved
+                .snapshots
+                .into_iter()
+                .map(|s| super::NamedSnapshot {
+                    id: s.id,
+                    name: s.name,
+                    timestamp: s.timestamp,
+                    thumbnail: s.thumbnail,
+                    state: s.state.into(),
+                })
+                .collect();
+
+            Self {
+                root: saved.root,
+                current: saved.current,
+                next_id: saved.next_id,
+                nodes,
+                snapshots,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::{DirtyRect, HistoryState, LayerSnapshot};
+    use uuid::Uuid;
+
+    fn stroke_state(layer_id: Uuid, description: &str) -> HistoryState {
+        let pixels = vec![128u8; 64 * 64 * 4];
+        let mut state = HistoryState::new(description);
+        state.add_snapshot(LayerSnapshot::incremental(layer_id, &pixels, 64, 64, DirtyRect::new(0, 0, 16, 16)));
+        state
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let layer_id = Uuid::new_v4();
+        let mut manager = HistoryManager::new();
+        manager.push_state(stroke_state(layer_id, "Stroke 1"));
+        manager.push_state(stroke_state(layer_id, "Stroke 2"));
+
+        let data = manager.export_history(usize::MAX).encode().unwrap();
+        let saved = SavedHistory::decode(&data).unwrap();
+        let mut restored = HistoryManager::import_history(saved, 50).unwrap();
+
+        assert_eq!(restored.undo_count(), 2);
+        assert_eq!(restored.peek_undo().unwrap().description, "Stroke 2");
+        assert_eq!(restored.current_node(), manager.current_node());
+    }
+
+    #[test]
+    fn test_budget_keeps_newest_steps() {
+        let layer_id = Uuid::new_v4();
+        let mut manager = HistoryManager::new();
+        for i in 0..10 {
+            manager.push_state(stroke_state(layer_id, &format!("Stroke {}", i)));
+        }
+
+        let step_size = manager.peek_undo().unwrap().memory_size();
+        let saved = manager.export_history(step_size * 3);
+        let mut restored = HistoryManager::import_history(saved, 50).unwrap();
+
+        assert_eq!(restored.undo_count(), 3);
+        assert_eq!(restored.peek_undo().unwrap().description, "Stroke 9");
+    }
+
+    #[test]
+    fn test_decode_v1_fixture() {
+        // Written by the version 1 encoder: two strokes on one 8x8 layer
+        let data = include_bytes!("fixtures/history_v1.bin");
+        assert_eq!(data[..4], 1u32.to_le_bytes());
+
+        let saved = SavedHistory::decode(data).unwrap();
+        let mut restored = HistoryManager::import_history(saved, 50).unwrap();
+
+        assert_eq!(restored.undo_count(), 2);
+        let state = restored.peek_undo().unwrap();
+        assert_eq!(state.description, "Stroke 2");
+        assert_eq!(state.layer_snapshots[0].layer_id, Uuid::from_u128(0x1234));
+        assert!(restored.undo(HistoryState::new("Stroke 2")).is_some());
+        assert_eq!(restored.peek_undo().unwrap().description, "Stroke 1");
+    }
+
+    #[test]
+    fn test_unknown_version_rejected() {
+        let mut data = HistoryManager::new().export_history(0).encode().unwrap();
+        data[..4].copy_from_slice(&(HISTORY_FORMAT_VERSION + 1).to_le_bytes());
+        assert!(SavedHistory::decode(&data).is_err());
+    }
+}
@@ -0,0 +1,264 @@
+//! Selective (non-linear) undo
+//!
+//! Reverting one past step means writing its stored before-pixels back while
+//! later steps stay applied. That is only safe where no later step changed
+//! the same pixels. To check, the later steps' deltas are replayed newest
+//! first over the target step's region, starting from the current layer
+//! pixels; every replayed delta that differs from the pixels it replaces
+//! marks pixels a later step changed, and is reported as a conflict.
+
+use super::{extract_region, DirtyRect, HistoryNodeId, HistoryState, LayerSnapshot};
+use crate::layer::LayerManager;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A later step that changed pixels a selective undo would overwrite
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryConflict {
+    /// History node of the later step
+    pub node: HistoryNodeId,
+    /// Description of the later step
+    pub description: String,
+    /// Affected layer (None for canvas-level operations)
+    pub layer_id: Option<Uuid>,
+    /// Bounds of the overlapping pixels
+    pub bounds: DirtyRect,
+    /// Number of overlapping pixels
+    pub pixel_count: u64,
+}
+
+/// Outcome of a selective undo
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SelectiveUndo {
+    /// The step was reverted by a new history node
+    Reverted(HistoryNodeId),
+    /// Later steps changed the same pixels; nothing was modified
+    Conflicts(Vec<HistoryConflict>),
+}
+
+/// Find later steps that changed pixels `target` would restore
+///
+/// `later` holds the undo states of the steps applied after `target`,
+/// oldest first, as returned by
+/// [`HistoryManager::states_since`](super::HistoryManager::states_since).
+pub fn find_conflicts(
+    target: &HistoryState,
+    later: &[(HistoryNodeId, HistoryState)],
+    layers: &LayerManager,
+) -> Vec<HistoryConflict> {
+    let mut conflicts = Vec::new();
+
+    // Canvas tiles are snapshotted whole, so any later canvas-level step conflicts
+    if let Some(canvas) = &target.canvas {
+        for (node, state) in later.iter().filter(|(_, s)| s.canvas.is_some()) {
+            let bounds = DirtyRect::full(canvas.width, canvas.height);
+            conflicts.push(HistoryConflict {
+                node: *node,
+                description: state.description.clone(),
+                layer_id: None,
+                bounds,
+                pixel_count: bounds.area(),
+            });
+        }
+    }
+
+    for snapshot in &target.layer_snapshots {
+        conflicts.extend(layer_conflicts(snapshot, later, layers));
+    }
+
+    conflicts
+}
+
+/// Conflicts on the layer of one snapshot of the target step
+fn layer_conflicts(
+    target: &LayerSnapshot,
+    later: &[(HistoryNodeId, HistoryState)],
+    layers: &LayerManager,
+) -> Vec<HistoryConflict> {
+    let Some(layer_arc) = layers.get_layer(target.layer_id) else {
+        return Vec::new();
+    };
+    let layer = layer_arc.read();
+
+    let later_snapshots: Vec<_> = later
+        .iter()
+        .rev()
+        .flat_map(|(node, state)| {
+            state
+                .layer_snapshots
+                .iter()
+                .filter(|s| s.layer_id == target.layer_id)
+                .map(move |s| (*node, &state.description, s))
+        })
+        .collect();
+
+    // A step whose before-size differs from the size after it resized the layer;
+    // pixel positions no longer line up, so the whole region conflicts
+    let mut conflicts = Vec::new();
+    let mut size_after = (layer.width(), layer.height());
+    for &(node, description, snapshot) in &later_snapshots {
+        if snapshot.dimensions != size_after {
+            let bounds = target.region();
+            conflicts.push(HistoryConflict {
+                node,
+                description: description.clone(),
+                layer_id: Some(target.layer_id),
+                bounds,
+                pixel_count: bounds.area(),
+            });
+        }
+        size_after = snapshot.dimensions;
+    }
+    if !conflicts.is_empty() {
+        return conflicts;
+    }
+
+    // The target step resized the layer itself; reverting it discards every later edit
+    if size_after != target.dimensions {
+        return later_snapshots
+            .into_iter()
+            .map(|(node, description, snapshot)| {
+                let bounds = snapshot.region();
+                HistoryConflict {
+                    node,
+                    description: description.clone(),
+                    layer_id: Some(target.layer_id),
+                    bounds,
+                    pixel_count: bounds.area(),
+                }
+            })
+            .collect();
+    }
+
+    let region = target.region();
+    let mut scratch = extract_region(&layer.pixels, layer.width(), region.x, region.y, region.width, region.height);
+
+    for (node, description, snapshot) in later_snapshots {
+        let overlap = region.intersection(&snapshot.region());
+        if overlap.is_empty() {
+            continue;
+        }
+
+        let before = snapshot.region_pixels();
+        let source = snapshot.region();
+        let mut changed: Option<DirtyRect> = None;
+        let mut pixel_count = 0u64;
+
+        for y in overlap.y..overlap.y + overlap.height {
+            for x in overlap.x..overlap.x + overlap.width {
+                let src = (((y - source.y) * source.width + (x - source.x)) * 4) as usize;
+                let dst = (((y - region.y) * region.width + (x - region.x)) * 4) as usize;
+                let (Some(old), Some(current)) = (before.get(src..src + 4), scratch.get_mut(dst..dst + 4)) else {
+                    continue;
+                };
+
+                if old != current {
+                    current.copy_from_slice(old);
+                    pixel_count += 1;
+                    match &mut changed {
+                        Some(rect) => rect.expand_to(x, y),
+                        None => changed = Some(DirtyRect::new(x, y, 1, 1)),
+                    }
+                }
+            }
+        }
+
+        if let Some(bounds) = changed {
+            conflicts.push(HistoryConflict {
+                node,
+                description: description.clone(),
+                layer_id: Some(target.layer_id),
+                bounds,
+                pixel_count,
+            });
+        }
+    }
+
+    conflicts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::HistoryManager;
+
+    fn paint(layers: &LayerManager, layer_id: Uuid, rect: DirtyRect, value: u8) -> HistoryState {
+        let layer_arc = layers.get_layer(layer_id).unwrap();
+        let mut layer = layer_arc.write();
+        let (width, height) = (layer.width(), layer.height());
+
+        let mut state = HistoryState::new("Paint");
+        state.add_snapshot(LayerSnapshot::incremental_compressed(layer_id, &layer.pixels, width, height, rect));
+        for y in rect.y..rect.y + rect.height {
+            for x in rect.x..rect.x + rect.width {
+                let idx = ((y * width + x) * 4) as usize;
+                layer.pixels[idx..idx + 4].fill(value);
+            }
+        }
+        state
+    }
+
+    fn setup() -> (LayerManager, Uuid) {
+        let mut layers = LayerManager::new();
+        layers.set_canvas_size(32, 32);
+        let id = layers.add_layer("Layer 1");
+        (layers, id)
+    }
+
+    #[test]
+    fn test_disjoint_edits_do_not_conflict() {
+        let (layers, id) = setup();
+        let mut history = HistoryManager::new();
+        history.push_state(paint(&layers, id, DirtyRect::new(0, 0, 8, 8), 200));
+        let target = history.current_node();
+        history.push_state(paint(&layers, id, DirtyRect::new(16, 16, 8, 8), 100));
+
+        let mut states = history.states_since(target).unwrap();
+        let (_, state) = states.remove(0);
+        assert!(find_conflicts(&state, &states, &layers).is_empty());
+    }
+
+    #[test]
+    fn test_overlapping_edit_conflicts() {
+        let (layers, id) = setup();
+        let mut history = HistoryManager::new();
+        history.push_state(paint(&layers, id, DirtyRect::new(0, 0, 8, 8), 200));
+        let target = history.current_node();
+        history.push_state(paint(&layers, id, DirtyRect::new(4, 4, 8, 8), 100));
+        let later = history.current_node();
+
+        let mut states = history.states_since(target).unwrap();
+        let (_, state) = states.remove(0);
+        let conflicts = find_conflicts(&state, &states, &layers);
+
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].node, later);
+        assert_eq!(conflicts[0].pixel_count, 16);
+        assert_eq!((conflicts[0].bounds.x, conflicts[0].bounds.y), (4, 4));
+    }
+
+    #[test]
+    fn test_unchanged_overlap_does_not_conflict() {
+        let (layers, id) = setup();
+        let mut history = HistoryManager::new();
+        history.push_state(paint(&layers, id, DirtyRect::new(0, 0, 8, 8), 200));
+        let target = history.current_node();
+
+        // The later step's dirty rect overlaps, but it only changed pixels outside
+        let layer_arc = layers.get_layer(id).unwrap();
+        let before = layer_arc.read().pixels.clone();
+        let mut state = HistoryState::new("Paint");
+        state.add_snapshot(LayerSnapshot::incremental(id, &before, 32, 32, DirtyRect::new(4, 4, 12, 12)));
+        {
+            let mut layer = layer_arc.write();
+            let idx = ((14 * 32 + 14) * 4) as usize;
+            layer.pixels[idx..idx + 4].fill(50);
+        }
+        history.push_state(state);
+
+        let mut states = history.states_since(target).unwrap();
+        let (_, state) = states.remove(0);
+        assert!(find_conflicts(&state, &states, &layers).is_empty());
+    }
+}
@@ -0,0 +1,104 @@
+//! On-disk cache for history states
+//!
+//! When history outgrows its RAM budget, the states furthest from the
+//! current node are serialized, LZ4-compressed and written to a private
+//! cache directory. They are read back transparently when undo or redo
+//! reaches them. The directory is removed when the cache is dropped.
+
+use super::HistoryState;
+use crate::error::{EngineError, EngineResult};
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// Handle to a history state stored in the disk cache
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct SpilledState {
+    /// Cache entry key
+    key: u64,
+    /// Size the state occupies once loaded
+    memory_size: usize,
+    /// Compressed size on disk
+    disk_size: usize,
+}
+
+impl SpilledState {
+    /// Size the state occupies once loaded
+    pub(super) fn memory_size(&self) -> usize {
+        self.memory_size
+    }
+}
+
+/// Directory of paged-out history states
+#[derive(Debug)]
+pub(super) struct HistorySpillCache {
+    /// Private directory holding the cache files
+    dir: PathBuf,
+    /// Next entry key
+    next_key: u64,
+    /// Total compressed bytes on disk
+    disk_bytes: usize,
+}
+
+impl HistorySpillCache {
+    /// Create a cache in a fresh subdirectory of `base`
+    pub(super) fn new(base: &Path) -> EngineResult<Self> {
+        let dir = base.join(format!("history-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            next_key: 0,
+            disk_bytes: 0,
+        })
+    }
+
+    /// Total compressed bytes on disk
+    pub(super) fn disk_bytes(&self) -> usize {
+        self.disk_bytes
+    }
+
+    /// Write a state to disk
+    pub(super) fn store(&mut self, state: &HistoryState) -> EngineResult<SpilledState> {
+        let encoded = bincode::serialize(state)?;
+        let compressed = lz4_flex::compress_prepend_size(&encoded);
+
+        let key = self.next_key;
+        std::fs::write(self.entry_path(key), &compressed)?;
+        self.next_key += 1;
+        self.disk_bytes += compressed.len();
+
+        Ok(SpilledState {
+            key,
+            memory_size: state.memory_size(),
+            disk_size: compressed.len(),
+        })
+    }
+
+    /// Read a state back without removing it from disk
+    pub(super) fn load(&self, spilled: &SpilledState) -> EngineResult<HistoryState> {
+        let compressed = std::fs::read(self.entry_path(spilled.key))?;
+        let encoded = lz4_flex::decompress_size_prepended(&compressed)
+            .map_err(|e| EngineError::CompressionError(e.to_string()))?;
+        Ok(bincode::deserialize(&encoded)?)
+    }
+
+    /// Delete a state from disk
+    pub(super) fn remove(&mut self, spilled: &SpilledState) {
+        if std::fs::remove_file(self.entry_path(spilled.key)).is_ok() {
+            self.disk_bytes = self.disk_bytes.saturating_sub(spilled.disk_size);
+        }
+    }
+
+    fn entry_path(&self, key: u64) -> PathBuf {
+        self.dir.join(format!("{}.hist", key))
+    }
+}
+
+impl Drop for HistorySpillCache {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.dir) {
+            log::warn!("Failed to remove history cache {:?}: {}", self.dir, e);
+        }
+    }
+}
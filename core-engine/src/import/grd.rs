@@ -0,0 +1,548 @@
+//! GRD (Photoshop Gradient) file parser
+//!
+//! Supports the legacy binary layout (version 3) and the action descriptor
+//! layout written by Photoshop 6 and later (version 5). Only custom
+//! (solid) gradients are imported; noise gradients are skipped.
+
+use crate::color::Color;
+use crate::error::{EngineError, EngineResult};
+use crate::gradient::{ColorStop, Gradient, OpacityStop, StopColor};
+use crate::import::swatch::SwatchParser;
+use std::io::{Cursor, Read};
+
+/// Stop locations in GRD files run from 0 to this value
+const GRD_LOCATION_SCALE: f32 = 4096.0;
+
+/// Value in a Photoshop action descriptor
+#[derive(Debug, Clone)]
+enum DescriptorValue {
+    Object(Vec<(String, DescriptorValue)>),
+    List(Vec<DescriptorValue>),
+    Double(f64),
+    UnitFloat(f64),
+    Long(i32),
+    Text(String),
+    Enum(String),
+    Other,
+}
+
+impl DescriptorValue {
+    fn get(&self, key: &str) -> Option<&DescriptorValue> {
+        match self {
+            DescriptorValue::Object(items) => items
+                .iter()
+                .find(|(item_key, _)| item_key == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            DescriptorValue::Double(v) | DescriptorValue::UnitFloat(v) => Some(*v),
+            DescriptorValue::Long(v) => Some(*v as f64),
+            _ => None,
+        }
+    }
+
+    fn as_list(&self) -> &[DescriptorValue] {
+        match self {
+            DescriptorValue::List(items) => items,
+            _ => &[],
+        }
+    }
+}
+
+/// GRD file parser
+pub struct GrdParser;
+
+impl GrdParser {
+    /// Parse a GRD file from bytes
+    pub fn parse(data: &[u8]) -> EngineResult<Vec<Gradient>> {
+        if data.len() < 6 || &data[0..4] != b"8BGR" {
+            return Err(EngineError::ImportError("Invalid GRD signature".into()));
+        }
+
+        let mut cursor = Cursor::new(data);
+        cursor.set_position(4);
+        let version = read_u16(&mut cursor)?;
+
+        match version {
+            3 => Self::parse_v3(&mut cursor),
+            5 => Self::parse_v5(&mut cursor),
+            _ => Err(EngineError::ImportError(format!(
+                "Unsupported GRD version: {}",
+                version
+            ))),
+        }
+    }
+
+    /// Parse the legacy binary layout
+    fn parse_v3(cursor: &mut Cursor<&[u8]>) -> EngineResult<Vec<Gradient>> {
+        let count = read_u16(cursor)?;
+        let mut gradients = Vec::with_capacity(count as usize);
+
+        for _ in 0..count {
+            let name = read_pascal_string(cursor)?;
+
+            let color_count = read_u16(cursor)?;
+            let mut color_stops = Vec::with_capacity(color_count as usize);
+            for _ in 0..color_count {
+                let location = read_u32(cursor)?;
+                let midpoint = read_u32(cursor)?;
+                let model = read_u16(cursor)?;
+                let mut values = [0u16; 4];
+                for value in &mut values {
+                    *value = read_u16(cursor)?;
+                }
+                let color_type = read_u16(cursor)?;
+
+                let color = match color_type {
+                    1 => StopColor::Foreground,
+                    2 => StopColor::Background,
+                    _ => {
+                        let [r, g, b, a] = SwatchParser::convert_aco_color(model, values)?;
+                        StopColor::Fixed(Color::from_rgba8(r, g, b, a))
+                    }
+                };
+                color_stops.push(ColorStop {
+                    position: location as f32 / GRD_LOCATION_SCALE,
+                    midpoint: midpoint as f32 / 100.0,
+                    color,
+                });
+            }
+
+            let opacity_count = read_u16(cursor)?;
+            let mut opacity_stops = Vec::with_capacity(opacity_count as usize);
+            for _ in 0..opacity_count {
+                let location = read_u32(cursor)?;
+                let midpoint = read_u32(cursor)?;
+                let opacity = read_u16(cursor)?;
+                opacity_stops.push(OpacityStop {
+                    position: location as f32 / GRD_LOCATION_SCALE,
+                    midpoint: midpoint as f32 / 100.0,
+                    opacity: opacity as f32 / 255.0,
+                });
+            }
+
+            // Smoothness and the noise gradient settings, then padding
+            let _expansion_count = read_u16(cursor)?;
+            let _interpolation = read_u16(cursor)?;
+            let length = read_u16(cursor)? as u64;
+            cursor.set_position(cursor.position() + length + 2);
+
+            gradients.push(Gradient::new(name, color_stops, opacity_stops));
+        }
+
+        Ok(gradients)
+    }
+
+    /// Parse the action descriptor layout
+    fn parse_v5(cursor: &mut Cursor<&[u8]>) -> EngineResult<Vec<Gradient>> {
+        let descriptor_version = read_u32(cursor)?;
+        if descriptor_version != 16 {
+            return Err(EngineError::ImportError(format!(
+                "Unsupported GRD descriptor version: {}",
+                descriptor_version
+            )));
+        }
+
+        let root = read_descriptor(cursor)?;
+        let list = root
+            .get("GrdL")
+            .ok_or_else(|| EngineError::ImportError("GRD file has no gradient list".into()))?;
+
+        let mut gradients = Vec::new();
+        for (i, item) in list.as_list().iter().enumerate() {
+            let Some(gradient) = item.get("Grad") else {
+                continue;
+            };
+            match Self::descriptor_gradient(gradient) {
+                Some(gradient) => gradients.push(gradient),
+                None => log::warn!("Skipping gradient {}: not a custom gradient", i),
+            }
+        }
+        Ok(gradients)
+    }
+
+    /// Build a gradient from a `Grad` descriptor
+    fn descriptor_gradient(descriptor: &DescriptorValue) -> Option<Gradient> {
+        if let Some(DescriptorValue::Enum(form)) = descriptor.get("GrdF") {
+            if form != "CstS" {
+                return None;
+            }
+        }
+
+        let name = match descriptor.get("Nm  ") {
+            Some(DescriptorValue::Text(name)) => name.clone(),
+            _ => String::new(),
+        };
+
+        let color_stops = descriptor
+            .get("Clrs")?
+            .as_list()
+            .iter()
+            .map(|stop| {
+                let color = match stop.get("Type") {
+                    Some(DescriptorValue::Enum(kind)) if kind == "FrgC" => StopColor::Foreground,
+                    Some(DescriptorValue::Enum(kind)) if kind == "BckC" => StopColor::Background,
+                    _ => StopColor::Fixed(
+                        stop.get("Clr ").map(descriptor_color).unwrap_or_else(Color::black),
+                    ),
+                };
+                ColorStop {
+                    position: location(stop),
+                    midpoint: midpoint(stop),
+                    color,
+                }
+            })
+            .collect();
+
+        let opacity_stops = descriptor
+            .get("Trns")
+            .map(|stops| {
+                stops
+                    .as_list()
+                    .iter()
+                    .map(|stop| OpacityStop {
+                        position: location(stop),
+                        midpoint: midpoint(stop),
+                        opacity: stop
+                            .get("Opct")
+                            .and_then(DescriptorValue::as_f64)
+                            .map_or(1.0, |percent| (percent / 100.0) as f32),
+                    })
+                    .collect()
+            })
+            .unwrap_or_else(|| vec![OpacityStop::new(0.0, 1.0), OpacityStop::new(1.0, 1.0)]);
+
+        Some(Gradient::new(name, color_stops, opacity_stops))
+    }
+}
+
+/// Stop location of a descriptor stop
+fn location(stop: &DescriptorValue) -> f32 {
+    let location = stop.get("Lctn").and_then(DescriptorValue::as_f64).unwrap_or(0.0);
+    (location as f32 / GRD_LOCATION_SCALE).clamp(0.0, 1.0)
+}
+
+/// Stop midpoint of a descriptor stop
+fn midpoint(stop: &DescriptorValue) -> f32 {
+    let midpoint = stop.get("Mdpn").and_then(DescriptorValue::as_f64).unwrap_or(50.0);
+    (midpoint as f32 / 100.0).clamp(0.0, 1.0)
+}
+
+/// Convert a descriptor color object to a color
+fn descriptor_color(color: &DescriptorValue) -> Color {
+    let value = |key: &str| color.get(key).and_then(DescriptorValue::as_f64).unwrap_or(0.0) as f32;
+
+    if color.get("Rd  ").is_some() {
+        Color::from_rgb(value("Rd  ") / 255.0, value("Grn ") / 255.0, value("Bl  ") / 255.0)
+    } else if color.get("H   ").is_some() {
+        let (r, g, b) = SwatchParser::hsb_to_rgb(value("H   "), value("Strt") / 100.0, value("Brgh") / 100.0);
+        Color::from_rgba8(r, g, b, 255)
+    } else if color.get("Cyn ").is_some() {
+        let k = value("Blck") / 100.0;
+        Color::from_rgb(
+            (1.0 - value("Cyn ") / 100.0) * (1.0 - k),
+            (1.0 - value("Mgnt") / 100.0) * (1.0 - k),
+            (1.0 - value("Ylw ") / 100.0) * (1.0 - k),
+        )
+    } else if color.get("Lmnc").is_some() {
+        let (r, g, b) = SwatchParser::lab_to_rgb(value("Lmnc"), value("A   "), value("B   "));
+        Color::from_rgba8(r, g, b, 255)
+    } else if color.get("Gry ").is_some() {
+        let gray = 1.0 - value("Gry ") / 100.0;
+        Color::from_rgb(gray, gray, gray)
+    } else {
+        Color::black()
+    }
+}
+
+/// Read a descriptor: class name, class ID, then its items
+fn read_descriptor(cursor: &mut Cursor<&[u8]>) -> EngineResult<DescriptorValue> {
+    let _class_name = read_unicode_string(cursor)?;
+    let _class_id = read_key(cursor)?;
+    let count = read_u32(cursor)?;
+
+    let mut items = Vec::new();
+    for _ in 0..count {
+        let key = read_key(cursor)?;
+        let value = read_value(cursor)?;
+        items.push((key, value));
+    }
+    Ok(DescriptorValue::Object(items))
+}
+
+/// Read a typed descriptor value
+fn read_value(cursor: &mut Cursor<&[u8]>) -> EngineResult<DescriptorValue> {
+    let mut kind = [0u8; 4];
+    cursor.read_exact(&mut kind)?;
+
+    Ok(match &kind {
+        b"Objc" | b"GlbO" => read_descriptor(cursor)?,
+        b"VlLs" => {
+            let count = read_u32(cursor)?;
+            let mut items = Vec::new();
+            for _ in 0..count {
+                items.push(read_value(cursor)?);
+            }
+            DescriptorValue::List(items)
+        }
+        b"doub" => DescriptorValue::Double(read_f64(cursor)?),
+        b"UntF" => {
+            let mut unit = [0u8; 4];
+            cursor.read_exact(&mut unit)?;
+            DescriptorValue::UnitFloat(read_f64(cursor)?)
+        }
+        b"long" => DescriptorValue::Long(read_u32(cursor)? as i32),
+        b"TEXT" => DescriptorValue::Text(read_unicode_string(cursor)?),
+        b"enum" => {
+            let _type_id = read_key(cursor)?;
+            DescriptorValue::Enum(read_key(cursor)?)
+        }
+        b"bool" => {
+            let mut value = [0u8; 1];
+            cursor.read_exact(&mut value)?;
+            DescriptorValue::Other
+        }
+        b"type" | b"GlbC" => {
+            let _class_name = read_unicode_string(cursor)?;
+            let _class_id = read_key(cursor)?;
+            DescriptorValue::Other
+        }
+        b"tdta" => {
+            let length = read_u32(cursor)? as u64;
+            cursor.set_position(cursor.position() + length);
+            DescriptorValue::Other
+        }
+        _ => {
+            return Err(EngineError::ImportError(format!(
+                "Unsupported descriptor value type: {}",
+                String::from_utf8_lossy(&kind)
+            )))
+        }
+    })
+}
+
+/// Read a descriptor key: a length, or zero followed by a four character code
+fn read_key(cursor: &mut Cursor<&[u8]>) -> EngineResult<String> {
+    let length = match read_u32(cursor)? {
+        0 => 4,
+        length => length as usize,
+    };
+    let remaining = cursor.get_ref().len() as u64 - cursor.position();
+    if length as u64 > remaining {
+        return Err(EngineError::ImportError("Descriptor key past end of file".into()));
+    }
+    let mut key = vec![0u8; length];
+    cursor.read_exact(&mut key)?;
+    Ok(String::from_utf8_lossy(&key).into_owned())
+}
+
+/// Read a UTF-16BE string prefixed by its length in characters
+fn read_unicode_string(cursor: &mut Cursor<&[u8]>) -> EngineResult<String> {
+    let length = read_u32(cursor)? as u64;
+    let remaining = cursor.get_ref().len() as u64 - cursor.position();
+    if length * 2 > remaining {
+        return Err(EngineError::ImportError("String past end of file".into()));
+    }
+
+    let mut units = Vec::with_capacity(length as usize);
+    for _ in 0..length {
+        units.push(read_u16(cursor)?);
+    }
+    Ok(String::from_utf16_lossy(&units)
+        .trim_end_matches('\0')
+        .to_string())
+}
+
+/// Read a byte-length prefixed string
+fn read_pascal_string(cursor: &mut Cursor<&[u8]>) -> EngineResult<String> {
+    let mut length = [0u8; 1];
+    cursor.read_exact(&mut length)?;
+    let mut bytes = vec![0u8; length[0] as usize];
+    cursor.read_exact(&mut bytes)?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn read_u16(cursor: &mut Cursor<&[u8]>) -> EngineResult<u16> {
+    let mut buf = [0u8; 2];
+    cursor.read_exact(&mut buf)?;
+    Ok(u16::from_be_bytes(buf))
+}
+
+fn read_u32(cursor: &mut Cursor<&[u8]>) -> EngineResult<u32> {
+    let mut buf = [0u8; 4];
+    cursor.read_exact(&mut buf)?;
+    Ok(u32::from_be_bytes(buf))
+}
+
+fn read_f64(cursor: &mut Cursor<&[u8]>) -> EngineResult<f64> {
+    let mut buf = [0u8; 8];
+    cursor.read_exact(&mut buf)?;
+    Ok(f64::from_be_bytes(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(out: &mut Vec<u8>, id: &str) {
+        if id.len() == 4 {
+            out.extend_from_slice(&0u32.to_be_bytes());
+        } else {
+            out.extend_from_slice(&(id.len() as u32).to_be_bytes());
+        }
+        out.extend_from_slice(id.as_bytes());
+    }
+
+    fn unicode(out: &mut Vec<u8>, text: &str) {
+        out.extend_from_slice(&(text.len() as u32 + 1).to_be_bytes());
+        for unit in text.encode_utf16().chain([0]) {
+            out.extend_from_slice(&unit.to_be_bytes());
+        }
+    }
+
+    fn object_header(out: &mut Vec<u8>, class: &str, count: u32) {
+        unicode(out, "");
+        key(out, class);
+        out.extend_from_slice(&count.to_be_bytes());
+    }
+
+    fn double(out: &mut Vec<u8>, name: &str, value: f64) {
+        key(out, name);
+        out.extend_from_slice(b"doub");
+        out.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn long(out: &mut Vec<u8>, name: &str, value: i32) {
+        key(out, name);
+        out.extend_from_slice(b"long");
+        out.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn enumerated(out: &mut Vec<u8>, name: &str, type_id: &str, value: &str) {
+        key(out, name);
+        out.extend_from_slice(b"enum");
+        key(out, type_id);
+        key(out, value);
+    }
+
+    #[test]
+    fn test_invalid_signature() {
+        assert!(GrdParser::parse(b"8BPT\0\x05").is_err());
+        assert!(GrdParser::parse(b"8BGR\0\x07").is_err());
+    }
+
+    #[test]
+    fn test_parse_v3() {
+        let mut data = b"8BGR".to_vec();
+        data.extend_from_slice(&3u16.to_be_bytes());
+        data.extend_from_slice(&1u16.to_be_bytes());
+        data.push(4);
+        data.extend_from_slice(b"Fade");
+
+        data.extend_from_slice(&2u16.to_be_bytes());
+        // Red stop at the start, then the background color at the end
+        data.extend_from_slice(&0u32.to_be_bytes());
+        data.extend_from_slice(&25u32.to_be_bytes());
+        data.extend_from_slice(&0u16.to_be_bytes());
+        for value in [65535u16, 0, 0, 0] {
+            data.extend_from_slice(&value.to_be_bytes());
+        }
+        data.extend_from_slice(&0u16.to_be_bytes());
+        data.extend_from_slice(&4096u32.to_be_bytes());
+        data.extend_from_slice(&50u32.to_be_bytes());
+        data.extend_from_slice(&[0; 10]);
+        data.extend_from_slice(&2u16.to_be_bytes());
+
+        data.extend_from_slice(&1u16.to_be_bytes());
+        data.extend_from_slice(&2048u32.to_be_bytes());
+        data.extend_from_slice(&50u32.to_be_bytes());
+        data.extend_from_slice(&51u16.to_be_bytes());
+
+        data.extend_from_slice(&2u16.to_be_bytes());
+        data.extend_from_slice(&4096u16.to_be_bytes());
+        data.extend_from_slice(&32u16.to_be_bytes());
+        data.extend_from_slice(&[0; 34]);
+
+        let gradients = GrdParser::parse(&data).unwrap();
+        assert_eq!(gradients.len(), 1);
+        let gradient = &gradients[0];
+        assert_eq!(gradient.name, "Fade");
+        assert_eq!(gradient.color_stops.len(), 2);
+        assert_eq!(gradient.color_stops[0].color, StopColor::Fixed(Color::from_rgba8(255, 0, 0, 255)));
+        assert!((gradient.color_stops[0].midpoint - 0.25).abs() < 1e-6);
+        assert_eq!(gradient.color_stops[1].color, StopColor::Background);
+        assert_eq!(gradient.color_stops[1].position, 1.0);
+        assert!((gradient.opacity_stops[0].opacity - 0.2).abs() < 1e-6);
+        assert_eq!(gradient.opacity_stops[0].position, 0.5);
+    }
+
+    #[test]
+    fn test_parse_v5_descriptor() {
+        let mut data = b"8BGR".to_vec();
+        data.extend_from_slice(&5u16.to_be_bytes());
+        data.extend_from_slice(&16u32.to_be_bytes());
+        object_header(&mut data, "null", 1);
+        key(&mut data, "GrdL");
+        data.extend_from_slice(b"VlLs");
+        data.extend_from_slice(&1u32.to_be_bytes());
+
+        data.extend_from_slice(b"Objc");
+        object_header(&mut data, "Grdn", 1);
+        key(&mut data, "Grad");
+        data.extend_from_slice(b"Objc");
+        object_header(&mut data, "Grdn", 5);
+
+        key(&mut data, "Nm  ");
+        data.extend_from_slice(b"TEXT");
+        unicode(&mut data, "Blue Fade");
+        enumerated(&mut data, "GrdF", "GrdF", "CstS");
+        double(&mut data, "Intr", 4096.0);
+
+        key(&mut data, "Clrs");
+        data.extend_from_slice(b"VlLs");
+        data.extend_from_slice(&2u32.to_be_bytes());
+        // Fixed blue, then the foreground color
+        data.extend_from_slice(b"Objc");
+        object_header(&mut data, "Clrt", 4);
+        key(&mut data, "Clr ");
+        data.extend_from_slice(b"Objc");
+        object_header(&mut data, "RGBC", 3);
+        double(&mut data, "Rd  ", 0.0);
+        double(&mut data, "Grn ", 0.0);
+        double(&mut data, "Bl  ", 255.0);
+        enumerated(&mut data, "Type", "Clry", "UsrS");
+        long(&mut data, "Lctn", 0);
+        long(&mut data, "Mdpn", 50);
+        data.extend_from_slice(b"Objc");
+        object_header(&mut data, "Clrt", 3);
+        enumerated(&mut data, "Type", "Clry", "FrgC");
+        long(&mut data, "Lctn", 4096);
+        long(&mut data, "Mdpn", 50);
+
+        key(&mut data, "Trns");
+        data.extend_from_slice(b"VlLs");
+        data.extend_from_slice(&1u32.to_be_bytes());
+        data.extend_from_slice(b"Objc");
+        object_header(&mut data, "TrnS", 3);
+        key(&mut data, "Opct");
+        data.extend_from_slice(b"UntF");
+        data.extend_from_slice(b"#Prc");
+        data.extend_from_slice(&50.0f64.to_be_bytes());
+        long(&mut data, "Lctn", 1024);
+        long(&mut data, "Mdpn", 50);
+
+        let gradients = GrdParser::parse(&data).unwrap();
+        assert_eq!(gradients.len(), 1);
+        let gradient = &gradients[0];
+        assert_eq!(gradient.name, "Blue Fade");
+        assert_eq!(gradient.color_stops[0].color, StopColor::Fixed(Color::from_rgb(0.0, 0.0, 1.0)));
+        assert_eq!(gradient.color_stops[1].color, StopColor::Foreground);
+        assert_eq!(gradient.color_stops[1].position, 1.0);
+        assert_eq!(gradient.opacity_stops[0].position, 0.25);
+        assert!((gradient.opacity_stops[0].opacity - 0.5).abs() < 1e-6);
+    }
+}
@@ -4,11 +4,14 @@
 //! - ABR: Photoshop brush files
 //! - PAT: Photoshop pattern files
 //! - ACO/ASE: Color swatch files
+//! - GRD: Photoshop gradient files
 
 pub mod abr;
+pub mod grd;
 pub mod pat;
 pub mod swatch;
 
 pub use abr::{AbrParser, ImportedBrush};
+pub use grd::GrdParser;
 pub use pat::{PatParser, ImportedPattern};
 pub use swatch::{SwatchParser, ColorSwatch};
@@ -170,7 +170,7 @@ impl SwatchParser {
     }
 
     /// Convert ACO color values to RGBA
-    fn convert_aco_color(color_space: u16, values: [u16; 4]) -> EngineResult<[u8; 4]> {
+    pub(crate) fn convert_aco_color(color_space: u16, values: [u16; 4]) -> EngineResult<[u8; 4]> {
         let space = AcoColorSpace::try_from(color_space);
 
         match space {
@@ -401,7 +401,7 @@ impl SwatchParser {
     }
 
     /// Convert HSB to RGB
-    fn hsb_to_rgb(h: f32, s: f32, b: f32) -> (u8, u8, u8) {
+    pub(crate) fn hsb_to_rgb(h: f32, s: f32, b: f32) -> (u8, u8, u8) {
         if s == 0.0 {
             let v = (b * 255.0) as u8;
             return (v, v, v);
@@ -432,7 +432,7 @@ impl SwatchParser {
     }
 
     /// Convert Lab to RGB
-    fn lab_to_rgb(l: f32, a: f32, b: f32) -> (u8, u8, u8) {
+    pub(crate) fn lab_to_rgb(l: f32, a: f32, b: f32) -> (u8, u8, u8) {
         // Lab to XYZ
         let y = (l + 16.0) / 116.0;
         let x = a / 500.0 + y;
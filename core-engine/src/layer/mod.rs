@@ -17,6 +17,7 @@ pub use mask::{LayerMask, MaskMode};
 
 use crate::color::Color;
 use crate::error::{EngineError, EngineResult};
+use crate::shape::Shape;
 
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
@@ -110,6 +111,9 @@ pub struct Layer {
     /// Thumbnail data
     #[serde(skip)]
     pub thumbnail: Option<Vec<u8>>,
+    /// Shapes of a vector layer, rendered into its pixels
+    #[serde(skip)]
+    pub shapes: Vec<Shape>,
 }
 
 impl Layer {
@@ -130,6 +134,7 @@ impl Layer {
             mask: None,
             pixels: vec![0; pixel_count * 4],
             thumbnail: None,
+            shapes: Vec::new(),
         }
     }
 
@@ -215,6 +220,27 @@ impl Layer {
         }
     }
 
+    /// Create a vector layer holding one shape
+    pub fn with_shape(shape: Shape, width: u32, height: u32) -> Self {
+        let mut layer = Self::with_type(shape.kind.name(), LayerType::Vector, width, height);
+        layer.shapes.push(shape);
+        layer.render_shapes();
+        layer
+    }
+
+    /// Redraw a vector layer's pixels from its shapes
+    pub fn render_shapes(&mut self) {
+        if !self.lock.can_draw() {
+            return;
+        }
+        self.pixels.fill(0);
+        let shapes = std::mem::take(&mut self.shapes);
+        for shape in &shapes {
+            shape.render(self, 1.0);
+        }
+        self.shapes = shapes;
+    }
+
     /// Clear the layer (make fully transparent)
     pub fn clear(&mut self) {
         if !self.lock.can_draw() {
@@ -337,6 +363,11 @@ impl LayerManager {
         manager
     }
 
+    /// Canvas dimensions as (width, height)
+    pub fn canvas_size(&self) -> (u32, u32) {
+        (self.canvas_width, self.canvas_height)
+    }
+
     /// Set canvas dimensions (for layers created after this call)
     pub fn set_canvas_size(&mut self, width: u32, height: u32) {
         self.canvas_width = width;
@@ -409,6 +440,43 @@ impl LayerManager {
         self.layers.len()
     }
 
+    /// Get the active layer ID
+    pub fn active_layer_id(&self) -> Option<Uuid> {
+        self.active_layer_id
+    }
+
+    /// Rebuild the layer list in `order` (bottom to top)
+    ///
+    /// Layers not listed are dropped. Listed layers that no longer exist are
+    /// taken from `restored`, with blank pixels if theirs were not kept.
+    pub fn restore_order(
+        &mut self,
+        order: &[Uuid],
+        mut restored: Vec<Layer>,
+        active: Option<Uuid>,
+    ) {
+        let mut layers = Vec::with_capacity(order.len());
+        for &id in order {
+            if let Some(existing) = self.get_layer(id) {
+                layers.push(existing);
+            } else if let Some(pos) = restored.iter().position(|l| l.id == id) {
+                let mut layer = restored.swap_remove(pos);
+                let len = (layer.width() * layer.height() * 4) as usize;
+                if layer.pixels.len() != len {
+                    layer.pixels = vec![0; len];
+                }
+                layers.push(Arc::new(RwLock::new(layer)));
+            }
+        }
+        self.layers = layers;
+
+        let exists = |id: &Uuid| self.layers.iter().any(|l| l.read().id == *id);
+        self.active_layer_id = active
+            .filter(exists)
+            .or_else(|| self.layers.last().map(|l| l.read().id));
+        self.selection.retain(|id| self.layers.iter().any(|l| l.read().id == *id));
+    }
+
     /// Move layer to new position
     pub fn move_layer(&mut self, id: Uuid, new_index: usize) -> EngineResult<()> {
         let current_pos = self
@@ -520,7 +588,7 @@ impl LayerManager {
     }
 
     /// Flatten all visible layers
-    pub fn flatten(&mut self) -> Layer {
+    pub fn flatten(&self) -> Layer {
         let mut result = Layer::new("Flattened", self.canvas_width, self.canvas_height);
 
         // Fill with white background
@@ -556,6 +624,94 @@ impl LayerManager {
         result
     }
 
+    /// Composite color of the visible layers at a pixel, over transparency
+    ///
+    /// Honors each layer's blend mode, opacity and mask, and skips layers
+    /// inside hidden groups. With `below` set, only the layers under that
+    /// layer are composited.
+    pub fn composite_pixel(&self, x: u32, y: u32, below: Option<Uuid>) -> Color {
+        let mut result = Color::transparent();
+
+        for layer_arc in &self.layers {
+            let layer = layer_arc.read();
+            if Some(layer.id) == below {
+                break;
+            }
+            let Some(group_opacity) = self.group_opacity(layer.parent_id) else {
+                continue;
+            };
+            if !layer.visible {
+                continue;
+            }
+            let Some(color) = layer.get_pixel(x, y) else {
+                continue;
+            };
+
+            let coverage = match &layer.mask {
+                Some(mask) if mask.enabled => mask.get(x, y),
+                _ => 1.0,
+            };
+            let alpha = color.a * layer.opacity * group_opacity * coverage;
+            if alpha <= 0.0 {
+                continue;
+            }
+
+            // Blend against the opaque color below, then fade that toward
+            // the plain layer color where there is little below
+            let mixed = layer
+                .blend_mode
+                .blend(result.with_alpha(1.0), color.with_alpha(1.0));
+            let mix = |own: f32, blended: f32| own * (1.0 - result.a) + blended * result.a;
+            let source = Color::from_rgba(
+                mix(color.r, mixed.r),
+                mix(color.g, mixed.g),
+                mix(color.b, mixed.b),
+                alpha,
+            );
+            result = result.blend_over(source);
+        }
+
+        result
+    }
+
+    /// RGBA pixels of the visible layers composited over transparency, at
+    /// canvas size
+    ///
+    /// Unlike [`LayerManager::flatten`] this keeps alpha, so areas no layer
+    /// covers stay transparent.
+    pub fn composite(&self) -> Vec<u8> {
+        let (width, height) = (self.canvas_width, self.canvas_height);
+        let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let (r, g, b, a) = self.composite_pixel(x, y, None).to_rgba8();
+                pixels.extend_from_slice(&[r, g, b, a]);
+            }
+        }
+        pixels
+    }
+
+    /// Combined opacity of a group and its ancestors, or `None` if any of
+    /// them is hidden
+    fn group_opacity(&self, mut group_id: Option<Uuid>) -> Option<f32> {
+        let mut opacity = 1.0;
+        // Bounded by the group count in case of a cycle
+        for _ in 0..=self.groups.len() {
+            let Some(id) = group_id else {
+                return Some(opacity);
+            };
+            let Some(group) = self.groups.iter().find(|g| g.id == id) else {
+                return Some(opacity);
+            };
+            if !group.visible {
+                return None;
+            }
+            opacity *= group.opacity;
+            group_id = group.parent_id;
+        }
+        Some(opacity)
+    }
+
     /// Create a new group
     pub fn create_group(&mut self, name: impl Into<String>) -> Uuid {
         let group = LayerGroup::new(name);
@@ -647,4 +803,39 @@ mod tests {
         assert_eq!(layers[2].read().id, id1);
         assert_eq!(layers[0].read().id, id2);
     }
+
+    #[test]
+    fn test_composite_pixel_blend_mode_mask_and_groups() {
+        let mut manager = LayerManager::with_canvas_size(4, 4);
+        let base = manager.add_layer("Base");
+        let multiply = manager.add_layer("Multiply");
+        let hidden = manager.add_layer("Hidden");
+        manager.get_layer(base).unwrap().write().fill(Color::from_rgba(1.0, 1.0, 0.0, 1.0));
+        {
+            let layer = manager.get_layer(multiply).unwrap();
+            let mut layer = layer.write();
+            layer.fill(Color::from_rgba(0.0, 1.0, 1.0, 1.0));
+            layer.blend_mode = BlendMode::Multiply;
+            let mut mask = LayerMask::new(4, 4);
+            mask.set(0, 0, 0.0);
+            layer.mask = Some(mask);
+        }
+        manager.get_layer(hidden).unwrap().write().fill(Color::from_rgba(1.0, 0.0, 0.0, 1.0));
+        let group = manager.create_group("Group");
+        manager.add_to_group(hidden, group).unwrap();
+        manager.groups[0].visible = false;
+
+        // Yellow multiplied by cyan is green, except where the mask hides it
+        let green = manager.composite_pixel(1, 1, None);
+        assert!(green.r < 0.01 && green.g > 0.99 && green.b < 0.01);
+        let yellow = manager.composite_pixel(0, 0, None);
+        assert!(yellow.r > 0.99 && yellow.g > 0.99);
+
+        // Multiplying over nothing keeps the layer's own color
+        let below = manager.composite_pixel(1, 1, Some(base));
+        assert_eq!(below.a, 0.0);
+        manager.get_layer(base).unwrap().write().visible = false;
+        let cyan = manager.composite_pixel(1, 1, None);
+        assert!(cyan.r < 0.01 && cyan.g > 0.99 && cyan.b > 0.99);
+    }
 }
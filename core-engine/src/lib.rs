@@ -28,15 +28,21 @@ pub mod brush;
 pub mod canvas;
 pub mod color;
 pub mod error;
+pub mod fill;
 pub mod format;
 pub mod geometry;
+pub mod gradient;
+pub mod heal;
 pub mod history;
 pub mod import;
 pub mod layer;
+pub mod liquify;
+pub mod measure;
 pub mod optimize;
 pub mod plugin;
 pub mod render;
 pub mod selection;
+pub mod shape;
 pub mod stroke;
 pub mod tools;
 pub mod utils;
@@ -48,17 +54,39 @@ pub mod transform;
 
 // Re-exports for convenience
 pub use brush::{Brush, BrushEngine, BrushMode, BrushPreset, BrushSettings};
-pub use canvas::{Canvas, CanvasSettings, TileManager};
-pub use color::{Color, ColorSpace, ColorManager};
+pub use canvas::{Canvas, CanvasSettings, CanvasSnapshot, TileManager};
+pub use color::{Color, ColorSpace, ColorManager, PickerOptions, SampleLayers, SampleSize};
 pub use error::{EngineError, EngineResult};
-pub use history::{HistoryManager, HistoryState, LayerSnapshot, DirtyRect};
+pub use fill::{BucketContent, BucketOptions, FillContent, FillOptions, FillPattern};
+pub use gradient::{
+    ColorStop, Gradient, GradientOptions, GradientRepeat, GradientShape, OpacityStop, StopColor,
+};
+pub use heal::{ContentAwareOptions, ContentAwareOutput};
+pub use history::{
+    HistoryManager, HistoryState, LayerSnapshot, DirtyRect, HistoryNodeId, HistoryNodeInfo,
+    HistorySnapshotInfo, HistoryThumbnail, HistoryMemoryStats, HistoryConflict, SelectiveUndo,
+    LayerStackSnapshot, ShapesSnapshot,
+};
 pub use layer::{Layer, LayerManager, BlendMode, LayerType};
+pub use liquify::{LiquifyMesh, LiquifyMode, LiquifyOptions, LiquifySession};
+pub use measure::{MeasureOptions, MeasureReadout, MeasureUnit, Measurement};
 pub use render::{RenderPipeline, RenderContext};
-pub use selection::{Selection, SelectionManager, SelectionMode, SelectionInfo};
+pub use selection::{
+    ColorRange, ColorRangeSpace, ColorRangeTarget, FloatingSelection, FloatingWarp,
+    FreeTransformMode, FreeTransformOptions, MagneticLasso, QuickMask, SampleSource, Selection,
+    SelectionChannel, SelectionInfo, SelectionManager, SelectionMask, SelectionMode,
+    SelectionOutline, WandOptions,
+};
+pub use shape::{Shape, ShapeKind, ShapeOptions, ShapeOutput, ShapeStyle};
 pub use stroke::{Stroke, StrokePoint, StrokeBuilder};
+pub use tools::{Modifiers, Tool, ToolEvent, ToolManager, ToolOptions, ToolPreview, ToolType};
 
+use format::FileHandler;
+use std::path::Path;
+use transform::{Anchor, CropRegion, ImageData, Interpolation, TransformResult};
 use std::sync::Arc;
 use parking_lot::RwLock;
+use tools::PixelEdit;
 
 /// Engine version information
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -72,6 +100,9 @@ pub const DEFAULT_TILE_SIZE: u32 = 256;
 /// Maximum pressure level (8192)
 pub const MAX_PRESSURE_LEVEL: u32 = 8192;
 
+/// Longest side of history thumbnails, in pixels
+pub const HISTORY_THUMBNAIL_SIZE: u32 = 96;
+
 /// Core engine configuration
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct EngineConfig {
@@ -87,6 +118,12 @@ pub struct EngineConfig {
     pub gpu_enabled: bool,
     /// Memory limit in bytes
     pub memory_limit: usize,
+    /// RAM budget for undo history in bytes (capped by `memory_limit`)
+    pub history_ram_budget: usize,
+    /// Disk budget for paged-out undo history in bytes
+    pub history_disk_budget: usize,
+    /// Directory for paged-out undo history (None = evict instead of paging out)
+    pub history_spill_dir: Option<std::path::PathBuf>,
 }
 
 impl Default for EngineConfig {
@@ -98,6 +135,9 @@ impl Default for EngineConfig {
             max_undo_steps: 100,
             gpu_enabled: true,
             memory_limit: 4 * 1024 * 1024 * 1024, // 4GB
+            history_ram_budget: 512 * 1024 * 1024, // 512MB
+            history_disk_budget: 4 * 1024 * 1024 * 1024, // 4GB
+            history_spill_dir: cfg!(feature = "native").then(std::env::temp_dir),
         }
     }
 }
@@ -108,18 +148,25 @@ pub struct DrawEngine {
     canvas: Arc<RwLock<Canvas>>,
     layer_manager: Arc<RwLock<LayerManager>>,
     brush_engine: Arc<RwLock<BrushEngine>>,
-    color_manager: Arc<ColorManager>,
+    color_manager: Arc<RwLock<ColorManager>>,
     render_pipeline: Arc<RwLock<RenderPipeline>>,
     history_manager: Arc<RwLock<HistoryManager>>,
     selection_manager: Arc<RwLock<SelectionManager>>,
+    tool_manager: Arc<RwLock<ToolManager>>,
     // 增量笔触状态
     current_stroke: Arc<RwLock<Option<Stroke>>>,
     // 笔触开始前的像素备份（用于创建增量快照）
     stroke_before_pixels: Arc<RwLock<Option<Vec<u8>>>>,
+    // 未经选区裁剪的笔触像素（羽化边缘只按覆盖率混合一次）
+    stroke_unclipped_pixels: Arc<RwLock<Option<Vec<u8>>>>,
     stroke_layer_id: Arc<RwLock<Option<uuid::Uuid>>>,
     stroke_layer_dims: Arc<RwLock<Option<(u32, u32)>>>,
     // 脏区域追踪（用于增量快照）
     stroke_dirty_rect: Arc<RwLock<Option<DirtyRect>>>,
+    /// Pending liquify session and the edit it renders into
+    liquify: Arc<RwLock<Option<(LiquifySession, PixelEdit)>>>,
+    /// Ruler lines kept for redrawing
+    measurements: Arc<RwLock<Vec<Measurement>>>,
 }
 
 impl DrawEngine {
@@ -138,9 +185,9 @@ impl DrawEngine {
 
         let layer_manager = Arc::new(RwLock::new(LayerManager::new()));
         let brush_engine = Arc::new(RwLock::new(BrushEngine::new()));
-        let color_manager = Arc::new(ColorManager::new());
+        let color_manager = Arc::new(RwLock::new(ColorManager::new()));
         let render_pipeline = Arc::new(RwLock::new(RenderPipeline::new(config.gpu_enabled)?));
-        let history_manager = Arc::new(RwLock::new(HistoryManager::with_max_steps(config.max_undo_steps)));
+        let history_manager = Arc::new(RwLock::new(Self::create_history(&config)));
         let selection_manager = Arc::new(RwLock::new(SelectionManager::new()));
 
         Ok(Self {
@@ -152,14 +199,31 @@ impl DrawEngine {
             render_pipeline,
             history_manager,
             selection_manager,
+            tool_manager: Arc::new(RwLock::new(ToolManager::new())),
             current_stroke: Arc::new(RwLock::new(None)),
             stroke_before_pixels: Arc::new(RwLock::new(None)),
+            stroke_unclipped_pixels: Arc::new(RwLock::new(None)),
             stroke_layer_id: Arc::new(RwLock::new(None)),
             stroke_layer_dims: Arc::new(RwLock::new(None)),
             stroke_dirty_rect: Arc::new(RwLock::new(None)),
+            liquify: Arc::new(RwLock::new(None)),
+            measurements: Arc::new(RwLock::new(Vec::new())),
         })
     }
 
+    /// Create a history manager honoring the configured memory and disk budgets
+    fn create_history(config: &EngineConfig) -> HistoryManager {
+        let mut history = HistoryManager::with_max_steps(config.max_undo_steps);
+        history.set_memory_limit(config.history_ram_budget.min(config.memory_limit));
+        history.set_disk_limit(config.history_disk_budget);
+        if let Some(dir) = &config.history_spill_dir {
+            if let Err(e) = history.enable_disk_spill(dir) {
+                log::warn!("Undo history will not be paged out to {:?}: {}", dir, e);
+            }
+        }
+        history
+    }
+
     /// Get the engine configuration
     pub fn config(&self) -> &EngineConfig {
         &self.config
@@ -181,7 +245,7 @@ impl DrawEngine {
     }
 
     /// Get access to the color manager
-    pub fn color_manager(&self) -> Arc<ColorManager> {
+    pub fn color_manager(&self) -> Arc<RwLock<ColorManager>> {
         Arc::clone(&self.color_manager)
     }
 
@@ -200,21 +264,72 @@ impl DrawEngine {
         Arc::clone(&self.selection_manager)
     }
 
+    /// Get access to the tool manager
+    pub fn tool_manager(&self) -> Arc<RwLock<ToolManager>> {
+        Arc::clone(&self.tool_manager)
+    }
+
+    /// Switch tools, first applying the current tool's pending work
+    ///
+    /// An operation still in progress, such as a stroke whose pointer never
+    /// came up, is cancelled.
+    pub fn set_tool(&self, tool: ToolType) -> EngineResult<()> {
+        let mut tool_manager = self.tool_manager.write();
+        if tool_manager.current_tool() == tool {
+            return Ok(());
+        }
+        if tool_manager.state() == tools::ToolState::Active {
+            tool_manager.cancel(self)?;
+        }
+        tool_manager.commit(self)?;
+        tool_manager.set_tool(tool);
+        Ok(())
+    }
+
+    /// Send a pointer press to the current tool
+    pub fn pointer_down(&self, event: ToolEvent) -> EngineResult<()> {
+        self.tool_manager.write().pointer_down(self, &event)
+    }
+
+    /// Send a pointer move to the current tool, pressed or hovering
+    pub fn pointer_move(&self, event: ToolEvent) -> EngineResult<()> {
+        self.tool_manager.write().pointer_move(self, &event)
+    }
+
+    /// Send a pointer release to the current tool
+    pub fn pointer_up(&self, event: ToolEvent) -> EngineResult<()> {
+        self.tool_manager.write().pointer_up(self, &event)
+    }
+
+    /// Apply the current tool's pending work, e.g. a pen path or free transform
+    pub fn commit_tool(&self) -> EngineResult<()> {
+        self.tool_manager.write().commit(self)
+    }
+
+    /// Cancel the current tool's operation, undoing its unfinished edits
+    pub fn cancel_tool(&self) -> EngineResult<()> {
+        self.tool_manager.write().cancel(self)
+    }
+
+    /// Overlay the frontend should draw for the current tool
+    pub fn tool_preview(&self) -> ToolPreview {
+        self.tool_manager.read().preview()
+    }
+
     /// Begin a new stroke for incremental drawing
     pub fn begin_stroke(&self) -> EngineResult<()> {
         // Initialize dirty rect tracking
         *self.stroke_dirty_rect.write() = None;
 
         // Save BEFORE state - copy current layer pixels for undo
-        let layer_manager = self.layer_manager.read();
-        if let Some(active_layer) = layer_manager.active_layer() {
+        if let Some(active_layer) = self.edit_target() {
             let layer = active_layer.read();
             // Store the original pixels before any modification
             *self.stroke_before_pixels.write() = Some(layer.pixels.clone());
+            *self.stroke_unclipped_pixels.write() = Some(layer.pixels.clone());
             *self.stroke_layer_id.write() = Some(layer.id);
             *self.stroke_layer_dims.write() = Some((layer.width(), layer.height()));
         }
-        drop(layer_manager);
 
         // Start new stroke
         *self.current_stroke.write() = Some(Stroke::new());
@@ -269,10 +384,10 @@ impl DrawEngine {
 
                     // 渲染部分笔触
                     let mut brush = self.brush_engine.write();
-                    let layer_manager = self.layer_manager.read();
-                    if let Some(active_layer) = layer_manager.active_layer() {
+                    if let Some(active_layer) = self.edit_target() {
                         let mut layer = active_layer.write();
-                        brush.render_stroke_to_layer(&partial_stroke, &mut layer)?;
+                        let partial = &partial_stroke;
+                        self.render_partial_stroke(&mut brush, partial, &mut layer, brush_radius)?;
                     }
                 }
             }
@@ -280,11 +395,57 @@ impl DrawEngine {
         Ok(())
     }
 
+    /// Render the newest points of a stroke into the layer
+    ///
+    /// Dabs build up on the unclipped stroke pixels, which are then faded by
+    /// selection coverage once per pixel, so overlapping dabs keep their full
+    /// strength across feathered edges.
+    fn render_partial_stroke(
+        &self,
+        brush: &mut BrushEngine,
+        stroke: &Stroke,
+        layer: &mut Layer,
+        brush_radius: u32,
+    ) -> EngineResult<()> {
+        let before = self.stroke_before_pixels.read();
+        let mut unclipped = self.stroke_unclipped_pixels.write();
+        let (Some(before), Some(unclipped)) = (before.as_deref(), unclipped.as_mut()) else {
+            return brush.render_stroke_to_layer(stroke, layer);
+        };
+        if unclipped.len() != layer.pixels.len() {
+            return brush.render_stroke_to_layer(stroke, layer);
+        }
+
+        std::mem::swap(&mut layer.pixels, unclipped);
+        let rendered = brush.render_stroke_to_layer(stroke, layer);
+        std::mem::swap(&mut layer.pixels, unclipped);
+        rendered?;
+
+        let mut area: Option<DirtyRect> = None;
+        for p in &stroke.points {
+            let rect = DirtyRect::new(
+                (p.position.x as u32).saturating_sub(brush_radius),
+                (p.position.y as u32).saturating_sub(brush_radius),
+                brush_radius * 2 + 1,
+                brush_radius * 2 + 1,
+            );
+            area.get_or_insert(rect).union(&rect);
+        }
+        if let Some(mut area) = area {
+            let width = layer.width();
+            area.clamp(width, layer.height());
+            tools::copy_area(unclipped, &mut layer.pixels, width, area);
+            self.clip_to_selection(layer, before, area);
+        }
+        Ok(())
+    }
+
     /// End the current stroke and commit to history
     pub fn end_stroke(&self) -> EngineResult<()> {
         // Get dirty rect and stored BEFORE state
         let dirty_rect = self.stroke_dirty_rect.write().take();
         let before_pixels = self.stroke_before_pixels.write().take();
+        *self.stroke_unclipped_pixels.write() = None;
         let layer_id = self.stroke_layer_id.write().take();
         let layer_dims = self.stroke_layer_dims.write().take();
 
@@ -317,12 +478,26 @@ impl DrawEngine {
         Ok(())
     }
 
+    /// Abandon the current stroke, restoring the pixels it painted over
+    pub fn cancel_stroke(&self) {
+        *self.stroke_dirty_rect.write() = None;
+        *self.stroke_layer_dims.write() = None;
+        *self.stroke_unclipped_pixels.write() = None;
+        let before_pixels = self.stroke_before_pixels.write().take();
+        let layer_id = self.stroke_layer_id.write().take();
+        if let (Some(before), Some(id)) = (before_pixels, layer_id) {
+            if let Some(layer_arc) = self.find_layer(id) {
+                layer_arc.write().pixels = before;
+            }
+        }
+        *self.current_stroke.write() = None;
+    }
+
     /// Process a stroke on the current layer (with undo support)
     pub fn process_stroke(&self, stroke: &Stroke) -> EngineResult<()> {
         let mut brush = self.brush_engine.write();
-        let layer_manager = self.layer_manager.read();
 
-        if let Some(active_layer) = layer_manager.active_layer() {
+        if let Some(active_layer) = self.edit_target() {
             let mut layer = active_layer.write();
 
             // Save current state for undo before modifying
@@ -337,46 +512,36 @@ impl DrawEngine {
             self.history_manager.write().push_state(state);
 
             // Apply the stroke
+            let before = layer.pixels.clone();
             brush.render_stroke_to_layer(stroke, &mut layer)?;
+            let area = DirtyRect::full(layer.width(), layer.height());
+            self.clip_to_selection(&mut layer, &before, area);
         }
 
         Ok(())
     }
 
     /// Undo the last action
+    ///
+    /// A pending floating selection or liquify session is cancelled first,
+    /// without counting as the step.
     pub fn undo(&self) -> EngineResult<bool> {
-        let layer_manager = self.layer_manager.read();
+        self.cancel_pending_edits()?;
+        self.undo_step()
+    }
 
-        // Create current state to save for redo
-        let mut current_state = HistoryState::new("Current");
-        for layer_arc in layer_manager.layers() {
-            let layer = layer_arc.read();
-            let snapshot = LayerSnapshot::new(
-                layer.id,
-                layer.pixels.clone(),
-                layer.width(),
-                layer.height(),
-            );
-            current_state.add_snapshot(snapshot);
-        }
-        drop(layer_manager);
+    /// Move the history cursor one step back
+    fn undo_step(&self) -> EngineResult<bool> {
+        // Capture the regions about to be overwritten, to save for redo
+        let current_state = match self.history_manager.write().peek_undo() {
+            Some(state) => self.capture_inverse_state(state),
+            None => return Ok(false),
+        };
 
         // Get state to restore
-        let mut history = self.history_manager.write();
-        if let Some(state_to_restore) = history.undo(current_state) {
-            drop(history);
-
-            // Restore layer states using restore_to (handles both full and incremental)
-            let layer_manager = self.layer_manager.read();
-            for snapshot in state_to_restore.layer_snapshots {
-                if let Some(layer_arc) = layer_manager.get_layer(snapshot.layer_id) {
-                    let mut layer = layer_arc.write();
-                    // Get width before mutable borrow of pixels
-                    let width = layer.width();
-                    // Use restore_to which handles incremental snapshots properly
-                    snapshot.restore_to(&mut layer.pixels, width);
-                }
-            }
+        let state_to_restore = self.history_manager.write().undo(current_state);
+        if let Some(state) = state_to_restore {
+            self.restore_state(state)?;
             Ok(true)
         } else {
             Ok(false)
@@ -385,42 +550,147 @@ impl DrawEngine {
 
     /// Redo the last undone action
     pub fn redo(&self) -> EngineResult<bool> {
-        let layer_manager = self.layer_manager.read();
+        let child = self.history_manager.read().next_redo_node();
+        match child {
+            Some(child) => self.redo_into(child),
+            None => Ok(false),
+        }
+    }
 
-        // Create current state to save for undo
-        let mut current_state = HistoryState::new("Current");
-        for layer_arc in layer_manager.layers() {
-            let layer = layer_arc.read();
-            let snapshot = LayerSnapshot::new(
-                layer.id,
-                layer.pixels.clone(),
-                layer.width(),
-                layer.height(),
-            );
-            current_state.add_snapshot(snapshot);
+    /// Jump to any node of the history tree, including abandoned branches
+    pub fn jump_to_history_node(&self, node_id: HistoryNodeId) -> EngineResult<bool> {
+        self.cancel_pending_edits()?;
+        let path = self.history_manager.read().path_to(node_id);
+        let Some(path) = path else {
+            return Ok(false);
+        };
+
+        for _ in 0..path.undo_steps {
+            if !self.undo_step()? {
+                return Ok(false);
+            }
         }
-        drop(layer_manager);
 
-        // Get state to restore
+        for child in path.redo_nodes {
+            if !self.redo_into(child)? {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Revert one past step on the current branch while keeping later steps
+    ///
+    /// The revert is recorded as a new, undoable step. If later steps changed
+    /// any of the same pixels, nothing is modified and the conflicts are returned.
+    pub fn revert_history_node(&self, node_id: HistoryNodeId) -> EngineResult<SelectiveUndo> {
+        let mut states = self.history_manager.read().states_since(node_id).ok_or_else(|| {
+            EngineError::InvalidOperation(format!(
+                "History node {} is not an earlier step on the current branch",
+                node_id
+            ))
+        })?;
+        let (_, target) = states.remove(0);
+
+        let conflicts = history::find_conflicts(&target, &states, &self.layer_manager.read());
+        if !conflicts.is_empty() {
+            return Ok(SelectiveUndo::Conflicts(conflicts));
+        }
+
+        let mut before = self.capture_inverse_state(&target);
+        before.description = format!("Revert {}", target.description);
+        self.history_manager.write().push_state(before);
+        self.restore_state(target)?;
+        Ok(SelectiveUndo::Reverted(self.history_manager.read().current_node()))
+    }
+
+    /// Get RAM versus disk usage of the undo history
+    pub fn history_memory_stats(&self) -> HistoryMemoryStats {
+        self.history_manager.read().memory_stats()
+    }
+
+    /// List all history nodes with descriptions, timestamps and thumbnails
+    pub fn history_nodes(&self) -> Vec<HistoryNodeInfo> {
+        self.history_manager.read().nodes()
+    }
+
+    /// Render a thumbnail of the current document and attach it to the current history node
+    pub fn capture_history_thumbnail(&self, max_size: u32) -> EngineResult<()> {
+        let thumbnail = self.render_thumbnail(max_size)?;
         let mut history = self.history_manager.write();
-        if let Some(state_to_restore) = history.redo(current_state) {
-            drop(history);
-
-            // Restore layer states using restore_to (handles both full and incremental)
-            let layer_manager = self.layer_manager.read();
-            for snapshot in state_to_restore.layer_snapshots {
-                if let Some(layer_arc) = layer_manager.get_layer(snapshot.layer_id) {
-                    let mut layer = layer_arc.write();
-                    // Get width before mutable borrow of pixels
-                    let width = layer.width();
-                    // Use restore_to which handles incremental snapshots properly
-                    snapshot.restore_to(&mut layer.pixels, width);
-                }
+        let current = history.current_node();
+        history.set_thumbnail(current, thumbnail);
+        Ok(())
+    }
+
+    /// Store a named snapshot of the whole document (exempt from step-limit eviction)
+    pub fn create_history_snapshot(&self, name: impl Into<String>) -> EngineResult<HistoryNodeId> {
+        let name = name.into();
+        let state = self.capture_document_state(name.clone());
+        let thumbnail = self.render_thumbnail(HISTORY_THUMBNAIL_SIZE).ok();
+        Ok(self.history_manager.write().add_snapshot(name, state, thumbnail))
+    }
+
+    /// Restore a named snapshot as a new, undoable history step
+    pub fn restore_history_snapshot(&self, snapshot_id: HistoryNodeId) -> EngineResult<()> {
+        let history = self.history_manager.read();
+        let snapshot = history
+            .snapshot_state(snapshot_id)
+            .cloned()
+            .ok_or_else(|| EngineError::InvalidOperation(format!("Snapshot {} not found", snapshot_id)))?;
+        drop(history);
+
+        let before = self.capture_document_state(format!("Restore {}", snapshot.description));
+        self.history_manager.write().push_state(before);
+        self.restore_state(snapshot)?;
+        Ok(())
+    }
+
+    /// Record the canvas tiles as an undo step before drawing on the canvas directly
+    pub fn record_canvas_state(&self, description: impl Into<String>) {
+        let mut state = HistoryState::new(description);
+        state.set_canvas(self.canvas.read().snapshot());
+        self.history_manager.write().push_state(state);
+    }
+
+    /// Resize the canvas and every layer, placing the content by `anchor`
+    pub fn resize_canvas(
+        &self,
+        width: u32,
+        height: u32,
+        anchor: Anchor,
+        fill: Color,
+    ) -> EngineResult<()> {
+        self.transform_document("Resize Canvas", |image| {
+            transform::canvas_resize(image, width, height, anchor, fill)
+        })
+    }
+
+    /// Crop the canvas and every layer to a region
+    pub fn crop_canvas(&self, region: CropRegion) -> EngineResult<()> {
+        self.transform_document("Crop", |image| transform::crop_image(image, region))
+    }
+
+    /// Rotate the canvas and every layer clockwise by `angle` degrees
+    ///
+    /// Multiples of 90 degrees are lossless; other angles grow the canvas to
+    /// fit the rotated content.
+    pub fn rotate_canvas(&self, angle: f32) -> EngineResult<()> {
+        let angle = angle.rem_euclid(360.0);
+        if angle.fract() == 0.0 {
+            let quarter_turn: Option<fn(&ImageData) -> ImageData> = match angle as u32 {
+                0 => return Ok(()),
+                90 => Some(transform::rotate_90_cw),
+                180 => Some(transform::rotate_180),
+                270 => Some(transform::rotate_90_ccw),
+                _ => None,
+            };
+            if let Some(rotate) = quarter_turn {
+                return self.transform_document("Rotate Canvas", |image| Ok(rotate(image)));
             }
-            Ok(true)
-        } else {
-            Ok(false)
         }
+        self.transform_document("Rotate Canvas", |image| transform::rotate_arbitrary(image, angle))
     }
 
     /// Check if undo is available
@@ -433,101 +703,1040 @@ impl DrawEngine {
         self.history_manager.read().can_redo()
     }
 
-    /// Pick color at position from the rendered canvas
-    pub fn pick_color(&self, x: u32, y: u32) -> EngineResult<Color> {
+    /// Pick a color at position and record it in the color history
+    ///
+    /// Transparent picks are returned but not recorded. See
+    /// [`DrawEngine::sample_color`] for how the color is found.
+    pub fn pick_color(&self, x: u32, y: u32, options: &PickerOptions) -> EngineResult<Color> {
+        let color = self.sample_color(x, y, options)?.ok_or_else(|| {
+            EngineError::InvalidOperation(format!("({}, {}) is outside the canvas", x, y))
+        })?;
+        if color.a > 0.0 {
+            self.color_manager.write().add_to_history(color);
+        }
+        Ok(color)
+    }
+
+    /// Sample the color at position without touching the color history
+    ///
+    /// Averages the area given by `options.sample_size`, clipped to the
+    /// canvas, from the active layer's pixels or from the visible layers
+    /// composited with their blend modes, masks and group visibility.
+    /// Returns `None` outside the canvas.
+    pub fn sample_color(&self, x: u32, y: u32, options: &PickerOptions) -> EngineResult<Option<Color>> {
+        let (width, height) = {
+            let canvas = self.canvas.read();
+            (canvas.width(), canvas.height())
+        };
+        if x >= width || y >= height {
+            return Ok(None);
+        }
+
         let layer_manager = self.layer_manager.read();
+        let active = layer_manager.active_layer().cloned();
+        let radius = options.sample_size.radius();
+        let (x0, y0) = (x.saturating_sub(radius), y.saturating_sub(radius));
+        let (x1, y1) = ((x + radius).min(width - 1), (y + radius).min(height - 1));
+        let points = (y0..=y1).flat_map(|py| (x0..=x1).map(move |px| (px, py)));
+
+        let color = match options.layers {
+            SampleLayers::Current => {
+                let layer = active
+                    .ok_or_else(|| EngineError::InvalidOperation("No active layer".to_string()))?;
+                let layer = layer.read();
+                color::average_colors(
+                    points.map(|(px, py)| layer.get_pixel(px, py).unwrap_or_else(Color::transparent)),
+                )
+            }
+            SampleLayers::All => {
+                color::average_colors(points.map(|(px, py)| layer_manager.composite_pixel(px, py, None)))
+            }
+            SampleLayers::Below => {
+                let below = active.map(|layer| layer.read().id);
+                color::average_colors(points.map(|(px, py)| layer_manager.composite_pixel(px, py, below)))
+            }
+        };
+        Ok(Some(color))
+    }
 
-        // Get the composite color from all visible layers
-        let mut result_color = Color::transparent();
+    /// Flood fill at position with color
+    ///
+    /// `tolerance` is a 0-1 fraction of the channel range. The region is
+    /// found in the layer being filled, with hard edges; see
+    /// [`DrawEngine::bucket_fill`] for the other options.
+    pub fn flood_fill(&self, x: u32, y: u32, fill_color: Color, tolerance: f32) -> EngineResult<()> {
+        let options = FillOptions {
+            tolerance: tolerance * 255.0,
+            anti_alias: false,
+            ..FillOptions::default()
+        };
+        self.bucket_fill(x, y, &FillContent::Color(fill_color), &options)
+    }
 
-        for layer_arc in layer_manager.layers() {
-            let layer = layer_arc.read();
-            if !layer.visible {
-                continue;
+    /// Fill the region around (x, y) with color, a pattern or a gradient
+    ///
+    /// The region is found in `options.source` like the magic wand does and
+    /// painted into the edit target inside the selection. Sampling the
+    /// active layer samples the quick mask surface while it is being edited.
+    /// Only the changed area is recorded in history.
+    pub fn bucket_fill(
+        &self,
+        x: u32,
+        y: u32,
+        content: &FillContent,
+        options: &FillOptions,
+    ) -> EngineResult<()> {
+        let sampled = match options.source {
+            SampleSource::ActiveLayer => None,
+            source => Some(self.sample_pixels(source)?),
+        };
+
+        let mut edit = PixelEdit::begin(self, "Fill")?;
+        let (width, height) = edit.size();
+        let region = match &sampled {
+            Some((pixels, w, h)) => fill::fill_region(pixels, *w, *h, x, y, options)?,
+            None => fill::fill_region(edit.before(), width, height, x, y, options)?,
+        };
+        let region = region.resized(width, height);
+        let Some(area) = region.bounds() else {
+            edit.abort();
+            return Ok(());
+        };
+
+        let (ax, ay, aw, ah) = area;
+        edit.apply(self, DirtyRect::new(ax, ay, aw, ah), |layer, before| {
+            fill::render(layer, before, &region, area, content, options.opacity);
+        });
+        edit.finish(self);
+        Ok(())
+    }
+
+    /// Paste RGBA pixels onto the edit target with their top-left corner at (x, y)
+    ///
+    /// The content is composited over the layer as one undoable step and,
+    /// like painting, clipped by the selection coverage.
+    pub fn paste_pixels(&self, pixels: &[u8], width: u32, height: u32, x: i32, y: i32) -> EngineResult<()> {
+        let image = ImageData::from_pixels(pixels.to_vec(), width, height)?;
+        let target = self
+            .edit_target()
+            .ok_or_else(|| EngineError::InvalidOperation("No active layer".to_string()))?;
+        let mut layer = target.write();
+        if !layer.lock.can_draw() {
+            return Err(EngineError::InvalidOperation("Layer is locked".to_string()));
+        }
+        let (layer_width, layer_height) = (layer.width(), layer.height());
+
+        let x0 = x.clamp(0, layer_width as i32);
+        let y0 = y.clamp(0, layer_height as i32);
+        let x1 = x.saturating_add(width as i32).clamp(0, layer_width as i32);
+        let y1 = y.saturating_add(height as i32).clamp(0, layer_height as i32);
+        if x0 >= x1 || y0 >= y1 {
+            return Err(EngineError::InvalidOperation(
+                "Pasted content lies outside the layer".to_string(),
+            ));
+        }
+        let area = DirtyRect::new(x0 as u32, y0 as u32, (x1 - x0) as u32, (y1 - y0) as u32);
+
+        let before = layer.pixels.clone();
+        let mut state = HistoryState::new("Paste");
+        state.add_snapshot(LayerSnapshot::incremental_compressed(
+            layer.id,
+            &before,
+            layer_width,
+            layer_height,
+            area,
+        ));
+        self.history_manager.write().push_state(state);
+
+        composite_over(&mut layer, &image, x, y);
+        self.clip_to_selection(&mut layer, &before, area);
+        Ok(())
+    }
+
+    /// Select from a layer's transparency, combined with the given mode
+    pub fn select_layer_transparency(
+        &self,
+        layer_id: uuid::Uuid,
+        mode: SelectionMode,
+    ) -> EngineResult<()> {
+        let (pixels, width, height) = self.sample_pixels(SampleSource::Layer(layer_id))?;
+        self.selection_manager.write().select_layer_alpha(&pixels, width, height, mode)
+    }
+
+    /// Magic wand selection at a position, sampling colors from `source`
+    pub fn select_magic_wand(&self, x: u32, y: u32, source: SampleSource) -> EngineResult<()> {
+        let (pixels, width, height) = self.sample_pixels(source)?;
+        self.selection_manager.write().select_magic_wand(x, y, &pixels, width, height)
+    }
+
+    /// Start a magnetic lasso outline that snaps to edges in `source`
+    pub fn begin_magnetic_lasso(&self, source: SampleSource) -> EngineResult<()> {
+        let (pixels, width, height) = self.sample_pixels(source)?;
+        self.selection_manager.write().begin_magnetic_lasso(&pixels, width, height)
+    }
+
+    /// Lift the selected pixels off the active layer into a floating selection
+    ///
+    /// The pixels can then be transformed through
+    /// [`SelectionManager::floating_mut`] and previewed with
+    /// [`DrawEngine::render_view`] until committed or cancelled.
+    pub fn float_selection(&self) -> EngineResult<()> {
+        let mut selection_manager = self.selection_manager.write();
+        if selection_manager.is_quick_mask() {
+            return Err(EngineError::InvalidOperation(
+                "Cannot float a selection in quick mask mode".to_string(),
+            ));
+        }
+        if selection_manager.is_floating() {
+            return Err(EngineError::InvalidOperation(
+                "A floating selection is already pending".to_string(),
+            ));
+        }
+
+        let active_layer = self
+            .layer_manager
+            .read()
+            .active_layer()
+            .cloned()
+            .ok_or_else(|| EngineError::InvalidOperation("No active layer".to_string()))?;
+        let mut layer = active_layer.write();
+        if !layer.lock.can_draw() {
+            return Err(EngineError::InvalidOperation("Layer is locked".to_string()));
+        }
+
+        let (id, width, height) = (layer.id, layer.width(), layer.height());
+        let floating = FloatingSelection::lift(
+            id,
+            &mut layer.pixels,
+            width,
+            height,
+            selection_manager.selection(),
+        )?;
+        selection_manager.set_floating(floating)
+    }
+
+    /// Resample the floating selection into its layer with `interpolation`
+    ///
+    /// The lift, transform and drop become one undo step, and the selection
+    /// follows the transformed pixels.
+    pub fn commit_floating_selection(&self, interpolation: Interpolation) -> EngineResult<()> {
+        let mut selection_manager = self.selection_manager.write();
+        let floating = selection_manager
+            .floating()
+            .ok_or_else(|| EngineError::InvalidOperation("No floating selection".to_string()))?;
+        let image = floating.render(interpolation)?;
+        let mask = floating.transformed_mask(interpolation)?;
+        let floating = selection_manager.take_floating().expect("checked above");
+
+        if let Some(layer_arc) = self.layer_manager.read().get_layer(floating.layer_id()) {
+            let mut layer = layer_arc.write();
+            let (width, height) = (layer.width(), layer.height());
+            let mut state = HistoryState::new("Transform Selection");
+            state.add_snapshot(LayerSnapshot::new(
+                layer.id,
+                floating.original_pixels().to_vec(),
+                width,
+                height,
+            ));
+            self.history_manager.write().push_state(state);
+
+            composite_over(&mut layer, &image, 0, 0);
+        }
+
+        let feather = floating.original_selection().feather;
+        *selection_manager.selection_mut() = Selection::from_mask(mask);
+        selection_manager.selection_mut().feather = feather;
+        Ok(())
+    }
+
+    /// Put the floating pixels back unchanged and restore the selection
+    pub fn cancel_floating_selection(&self) -> EngineResult<()> {
+        let mut selection_manager = self.selection_manager.write();
+        let floating = selection_manager
+            .take_floating()
+            .ok_or_else(|| EngineError::InvalidOperation("No floating selection".to_string()))?;
+
+        if let Some(layer_arc) = self.layer_manager.read().get_layer(floating.layer_id()) {
+            layer_arc.write().pixels = floating.original_pixels().to_vec();
+        }
+        *selection_manager.selection_mut() = floating.original_selection().clone();
+        Ok(())
+    }
+
+    /// Fill the magic wand region at a position on the active layer
+    ///
+    /// The region is found in `source` with the current wand options, so
+    /// flats can be filled on their own layer from line art on another. Soft
+    /// region edges and any active selection scale the fill's opacity.
+    pub fn fill_wand_region(
+        &self,
+        x: u32,
+        y: u32,
+        fill_color: Color,
+        source: SampleSource,
+    ) -> EngineResult<()> {
+        let options = *self.selection_manager.read().wand_options();
+        self.fill_region(x, y, fill_color, source, &options)
+    }
+
+    /// Fill the region found in `source` with explicit wand options
+    ///
+    /// Like [`DrawEngine::fill_wand_region`], without changing the selection
+    /// manager's wand settings.
+    pub fn fill_region(
+        &self,
+        x: u32,
+        y: u32,
+        fill_color: Color,
+        source: SampleSource,
+        options: &WandOptions,
+    ) -> EngineResult<()> {
+        let options = FillOptions::from_wand(source, options);
+        self.bucket_fill(x, y, &FillContent::Color(fill_color), &options)
+    }
+
+    /// Coverage of the magic wand region at a position in `source`
+    pub fn wand_mask(
+        &self,
+        x: u32,
+        y: u32,
+        source: SampleSource,
+        options: &WandOptions,
+    ) -> EngineResult<SelectionMask> {
+        let (pixels, width, height) = self.sample_pixels(source)?;
+        selection::magic_wand(&pixels, width, height, x, y, options)
+    }
+
+    /// Add a vector layer holding `shape` above the active layer
+    pub fn add_shape_layer(&self, shape: Shape, opacity: f32) -> EngineResult<uuid::Uuid> {
+        let (width, height) = {
+            let canvas = self.canvas.read();
+            (canvas.width(), canvas.height())
+        };
+        let mut layer = Layer::with_shape(shape, width, height);
+        layer.opacity = opacity.clamp(0.0, 1.0);
+
+        let mut state = HistoryState::new("Shape Layer");
+        state.set_layer_stack(self.capture_layer_stack());
+        let id = self.insert_above_active(layer)?;
+        self.history_manager.write().push_state(state);
+        Ok(id)
+    }
+
+    /// Add a layer directly above the active layer
+    fn insert_above_active(&self, layer: Layer) -> EngineResult<uuid::Uuid> {
+        let mut layer_manager = self.layer_manager.write();
+        let above = layer_manager.active_layer().and_then(|active| {
+            layer_manager
+                .layers()
+                .iter()
+                .position(|layer| Arc::ptr_eq(layer, active))
+        });
+        let id = layer_manager.add_existing_layer(layer);
+        if let Some(index) = above {
+            layer_manager.move_layer(id, index + 1)?;
+        }
+        Ok(id)
+    }
+
+    /// Fill the selection with content synthesized from the rest of the image
+    ///
+    /// Patches are matched against the sampling area, or everything outside
+    /// the selection, of the active layer or the merged image. `progress`
+    /// receives the finished fraction of the work as it runs. The result is
+    /// blended into the active layer by the selection coverage, or placed on
+    /// a new layer whose id is returned; either way it is one undo step.
+    pub fn content_aware_fill(
+        &self,
+        options: &ContentAwareOptions,
+        mut progress: impl FnMut(f32),
+    ) -> EngineResult<Option<uuid::Uuid>> {
+        let hole = {
+            let selection_manager = self.selection_manager.read();
+            let selection = selection_manager.selection();
+            if !selection.is_active || selection.mask.is_empty() {
+                return Err(EngineError::InvalidOperation(
+                    "Content-aware fill needs a selection".to_string(),
+                ));
+            }
+            selection.mask.clone()
+        };
+        let target = self
+            .edit_target()
+            .ok_or_else(|| EngineError::InvalidOperation("No active layer".to_string()))?;
+        let (pixels, width, height) = if options.sample_all_layers {
+            self.sample_pixels(SampleSource::Merged)?
+        } else {
+            let layer = target.read();
+            (layer.pixels.clone(), layer.width(), layer.height())
+        };
+
+        let hole = hole.resized(width, height);
+        let sampling_area = options
+            .sampling_area
+            .as_ref()
+            .map(|area| area.resized(width, height));
+        let in_hole: Vec<bool> = hole.data().iter().map(|&v| v > 0).collect();
+        let sample: Vec<bool> = match &sampling_area {
+            Some(area) => area.data().iter().map(|&v| v > 0).collect(),
+            None => vec![true; in_hole.len()],
+        };
+        let filled = heal::inpaint(
+            &pixels,
+            width,
+            height,
+            &in_hole,
+            &sample,
+            options,
+            &mut progress,
+        )?;
+        let Some((x, y, w, h)) = hole.bounds() else {
+            return Ok(None);
+        };
+        let area = DirtyRect::new(x, y, w, h);
+
+        if options.output == ContentAwareOutput::NewLayer {
+            let mut layer = Layer::new("Content-Aware Fill", width, height);
+            for py in y..y + h {
+                for px in x..x + w {
+                    let idx = ((py * width + px) * 4) as usize;
+                    let alpha = filled[idx + 3] as u32 * hole.get(px, py) as u32 / 255;
+                    layer.pixels[idx..idx + 3].copy_from_slice(&filled[idx..idx + 3]);
+                    layer.pixels[idx + 3] = alpha as u8;
+                }
+            }
+            let mut state = HistoryState::new("Content-Aware Fill");
+            state.set_layer_stack(self.capture_layer_stack());
+            let id = self.insert_above_active(layer)?;
+            self.history_manager.write().push_state(state);
+            return Ok(Some(id));
+        }
+
+        let mut edit = PixelEdit::begin(self, "Content-Aware Fill")?;
+        if edit.size() != (width, height) {
+            edit.abort();
+            return Err(EngineError::InvalidOperation(
+                "Merged image does not match layer size".to_string(),
+            ));
+        }
+        edit.apply(self, area, |layer, _| {
+            for py in y..y + h {
+                let start = ((py * width + x) * 4) as usize;
+                let end = start + (w * 4) as usize;
+                layer.pixels[start..end].copy_from_slice(&filled[start..end]);
             }
+        });
+        edit.finish(self);
+        Ok(None)
+    }
 
-            if let Some(color) = layer.get_pixel(x, y) {
-                // Blend with result
-                result_color = result_color.blend_over(color);
+    /// Start liquifying the active layer
+    ///
+    /// Dabs warp the layer live through [`DrawEngine::liquify_dab`] until the
+    /// session is committed as one undo step or cancelled. Any selection
+    /// limits where the layer changes.
+    pub fn begin_liquify(&self) -> EngineResult<()> {
+        let mut liquify = self.liquify.write();
+        if liquify.is_some() {
+            return Err(EngineError::InvalidOperation(
+                "A liquify session is already pending".to_string(),
+            ));
+        }
+        let edit = PixelEdit::begin(self, "Liquify")?;
+        let (width, height) = edit.size();
+        *liquify = Some((LiquifySession::new(width, height), edit));
+        Ok(())
+    }
+
+    /// Whether a liquify session is pending
+    pub fn is_liquifying(&self) -> bool {
+        self.liquify.read().is_some()
+    }
+
+    /// Apply one liquify brush dab and update the layer under it
+    ///
+    /// `strength` should already include pen pressure; `motion` is how far
+    /// the pointer moved since the previous dab and drives push.
+    pub fn liquify_dab(
+        &self,
+        mode: LiquifyMode,
+        center: (f32, f32),
+        radius: f32,
+        strength: f32,
+        motion: (f32, f32),
+    ) -> EngineResult<()> {
+        let mut liquify = self.liquify.write();
+        let (session, edit) = liquify
+            .as_mut()
+            .ok_or_else(|| EngineError::InvalidOperation("No liquify session".to_string()))?;
+        if let Some(area) = session.dab(mode, center, radius, strength, motion) {
+            edit.apply(self, area, |layer, before| session.render(before, &mut layer.pixels, area));
+        }
+        Ok(())
+    }
+
+    /// Move the whole unfrozen mesh `amount` (0-1) of the way back to the
+    /// original layer
+    pub fn reconstruct_liquify(&self, amount: f32) -> EngineResult<()> {
+        let mut liquify = self.liquify.write();
+        let (session, edit) = liquify
+            .as_mut()
+            .ok_or_else(|| EngineError::InvalidOperation("No liquify session".to_string()))?;
+        if let Some(area) = session.reconstruct(amount) {
+            edit.apply(self, area, |layer, before| session.render(before, &mut layer.pixels, area));
+        }
+        Ok(())
+    }
+
+    /// Keep the liquified layer, recording the session as one undo step
+    pub fn commit_liquify(&self) -> EngineResult<()> {
+        let (_, edit) = self
+            .liquify
+            .write()
+            .take()
+            .ok_or_else(|| EngineError::InvalidOperation("No liquify session".to_string()))?;
+        edit.finish(self);
+        Ok(())
+    }
+
+    /// Discard the liquify session and restore the layer
+    pub fn cancel_liquify(&self) -> EngineResult<()> {
+        let (_, edit) = self
+            .liquify
+            .write()
+            .take()
+            .ok_or_else(|| EngineError::InvalidOperation("No liquify session".to_string()))?;
+        edit.abort();
+        Ok(())
+    }
+
+    /// Store a ruler line between two canvas points, returning its id
+    pub fn add_measurement(&self, start: (f32, f32), end: (f32, f32)) -> uuid::Uuid {
+        let measurement = Measurement::new(start, end);
+        self.measurements.write().push(measurement);
+        measurement.id
+    }
+
+    /// Move the endpoints of a stored ruler line
+    pub fn update_measurement(&self, id: uuid::Uuid, start: (f32, f32), end: (f32, f32)) -> EngineResult<()> {
+        let mut measurements = self.measurements.write();
+        let measurement = measurements
+            .iter_mut()
+            .find(|m| m.id == id)
+            .ok_or_else(|| EngineError::InvalidOperation(format!("No measurement {}", id)))?;
+        measurement.start = start;
+        measurement.end = end;
+        Ok(())
+    }
+
+    /// Remove a stored ruler line
+    pub fn remove_measurement(&self, id: uuid::Uuid) -> bool {
+        let mut measurements = self.measurements.write();
+        let count = measurements.len();
+        measurements.retain(|m| m.id != id);
+        measurements.len() != count
+    }
+
+    /// Remove every stored ruler line
+    pub fn clear_measurements(&self) {
+        self.measurements.write().clear();
+    }
+
+    /// Stored ruler lines, oldest first
+    pub fn measurements(&self) -> Vec<Measurement> {
+        self.measurements.read().clone()
+    }
+
+    /// Stored ruler lines read out in `unit` at the document resolution
+    pub fn measurement_readouts(&self, unit: MeasureUnit) -> Vec<MeasureReadout> {
+        let dpi = self.canvas.read().settings().dpi;
+        self.measurements
+            .read()
+            .iter()
+            .map(|m| m.readout(unit, dpi))
+            .collect()
+    }
+
+    /// Rotate the active layer so a ruler line becomes horizontal, or
+    /// vertical if it is closer to vertical, returning the clockwise
+    /// rotation in degrees
+    ///
+    /// The layer turns around its center and keeps its size; corners
+    /// rotated out of the layer are lost. Any selection limits where the
+    /// layer changes, and the rotation is one undo step.
+    pub fn straighten_layer(&self, id: uuid::Uuid) -> EngineResult<f32> {
+        let angle = self
+            .measurements
+            .read()
+            .iter()
+            .find(|m| m.id == id)
+            .map(|m| m.straighten_angle())
+            .ok_or_else(|| EngineError::InvalidOperation(format!("No measurement {}", id)))?;
+        if angle.abs() < 1e-3 {
+            return Ok(0.0);
+        }
+
+        let mut edit = PixelEdit::begin(self, "Straighten Layer")?;
+        let (width, height) = edit.size();
+        let straightened = ImageData::from_pixels(edit.before().to_vec(), width, height)
+            .and_then(|image| transform::rotate_arbitrary(&image, angle))
+            .and_then(|rotated| {
+                transform::canvas_resize(&rotated, width, height, Anchor::MiddleCenter, Color::transparent())
+            });
+        let straightened = match straightened {
+            Ok(image) => image,
+            Err(e) => {
+                edit.abort();
+                return Err(e.into());
             }
+        };
+        edit.apply(self, DirtyRect::new(0, 0, width, height), |layer, _| {
+            layer.pixels.copy_from_slice(&straightened.pixels);
+        });
+        edit.finish(self);
+        Ok(angle)
+    }
+
+    /// Replace a vector layer's shapes and redraw it
+    pub fn set_layer_shapes(&self, layer_id: uuid::Uuid, shapes: Vec<Shape>) -> EngineResult<()> {
+        let layer_arc = self
+            .layer_manager
+            .read()
+            .get_layer(layer_id)
+            .ok_or(EngineError::LayerNotFound(layer_id))?;
+        let mut layer = layer_arc.write();
+        if layer.layer_type != LayerType::Vector {
+            return Err(EngineError::InvalidOperation("Not a vector layer".into()));
+        }
+        if !layer.lock.can_draw() {
+            return Err(EngineError::InvalidOperation("Layer is locked".to_string()));
         }
 
-        Ok(result_color)
+        let mut state = HistoryState::new("Edit Shapes");
+        state.add_snapshot(LayerSnapshot::full_compressed(
+            layer.id,
+            layer.pixels.clone(),
+            layer.width(),
+            layer.height(),
+            true,
+        ));
+        let previous = std::mem::replace(&mut layer.shapes, shapes);
+        state.add_shapes(ShapesSnapshot { layer_id, shapes: previous });
+        layer.render_shapes();
+        drop(layer);
+        self.history_manager.write().push_state(state);
+        Ok(())
     }
 
-    /// Flood fill at position with color
-    pub fn flood_fill(&self, x: u32, y: u32, fill_color: Color, tolerance: f32) -> EngineResult<()> {
+    /// Apply a saved selection channel as a layer's mask
+    pub fn channel_to_layer_mask(
+        &self,
+        channel_id: uuid::Uuid,
+        layer_id: uuid::Uuid,
+    ) -> EngineResult<()> {
+        let mut mask = self
+            .selection_manager
+            .read()
+            .channel(channel_id)
+            .map(|channel| channel.to_layer_mask())
+            .ok_or_else(|| {
+                let message = format!("Selection channel not found: {}", channel_id);
+                EngineError::InvalidOperation(message)
+            })?;
+
+        let layer_arc = self
+            .layer_manager
+            .read()
+            .get_layer(layer_id)
+            .ok_or(EngineError::LayerNotFound(layer_id))?;
+        let mut layer = layer_arc.write();
+        if (mask.width, mask.height) != (layer.width(), layer.height()) {
+            mask.resize(layer.width(), layer.height());
+        }
+        layer.mask = Some(mask);
+        Ok(())
+    }
+
+    /// Save a layer's mask as a new selection channel
+    pub fn layer_mask_to_channel(
+        &self,
+        layer_id: uuid::Uuid,
+        name: impl Into<String>,
+    ) -> EngineResult<uuid::Uuid> {
+        let layer_arc = self
+            .layer_manager
+            .read()
+            .get_layer(layer_id)
+            .ok_or(EngineError::LayerNotFound(layer_id))?;
+        let channel = {
+            let layer = layer_arc.read();
+            let mask = layer.mask.as_ref().ok_or_else(|| {
+                EngineError::InvalidOperation("Layer has no mask".to_string())
+            })?;
+            SelectionChannel::from_layer_mask(name, mask)
+        };
+        Ok(self.selection_manager.write().add_channel(channel))
+    }
+
+    /// Save the document in native format with its selection channels
+    ///
+    /// Up to `max_history_bytes` of undo history is stored as well.
+    pub fn save_document(&self, path: &Path, max_history_bytes: usize) -> EngineResult<()> {
+        let canvas = self.canvas.read();
         let layer_manager = self.layer_manager.read();
+        let selection_manager = self.selection_manager.read();
+        let history = self.history_manager.read();
+        FileHandler::save_native_document(
+            path,
+            &canvas,
+            &layer_manager,
+            selection_manager.channels(),
+            Some((&history, max_history_bytes)),
+        )
+    }
 
-        if let Some(active_layer) = layer_manager.active_layer() {
-            let mut layer = active_layer.write();
-            let (_, _, width, height) = layer.bounds;
+    /// Replace the current document with a native file, restoring its channels and history
+    pub fn open_document(&self, path: &Path) -> EngineResult<()> {
+        let document = FileHandler::load_native_document(path)?;
+        let mut history = Self::create_history(&self.config);
+        if let Some(saved) = document.history {
+            history.restore_saved(saved)?;
+        }
 
-            // Get the target color at the click position
-            let target_color = match layer.get_pixel(x, y) {
-                Some(c) => c,
-                None => return Err(EngineError::InvalidOperation("Position out of bounds".into())),
-            };
+        let (width, height) = (document.canvas.width(), document.canvas.height());
+        *self.canvas.write() = document.canvas;
+        *self.layer_manager.write() = document.layer_manager;
+        *self.history_manager.write() = history;
+        {
+            let mut selection_manager = self.selection_manager.write();
+            selection_manager.set_canvas_size(width, height);
+            selection_manager.set_channels(document.channels);
+        }
+        *self.current_stroke.write() = None;
+        *self.liquify.write() = None;
+        self.measurements.write().clear();
+        self.tool_manager.write().reset();
+        Ok(())
+    }
 
-            // Save current state for undo before modifying
-            let snapshot = LayerSnapshot::new(
+    /// Render the current canvas state
+    pub fn render(&self) -> EngineResult<Vec<u8>> {
+        let canvas = self.canvas.read();
+        let layer_manager = self.layer_manager.read();
+        let render_pipeline = self.render_pipeline.read();
+
+        render_pipeline.render(&canvas, &layer_manager)
+    }
+
+    /// Render the canvas for display, with editing overlays such as quick
+    /// mask, the liquify freeze mask and a live preview of a floating
+    /// selection
+    pub fn render_view(&self) -> EngineResult<Vec<u8>> {
+        let mut output = self.render()?;
+        let selection_manager = self.selection_manager.read();
+        let width = self.canvas.read().width();
+
+        if let Some(floating) = selection_manager.floating() {
+            let preview = floating.render(selection::PREVIEW_INTERPOLATION)?;
+            for (i, px) in preview.pixels.chunks_exact(4).enumerate() {
+                if px[3] == 0 {
+                    continue;
+                }
+                let (x, y) = (i as u32 % preview.width, i as u32 / preview.width);
+                let idx = ((y * width + x) * 4) as usize;
+                if x < width {
+                    if let Some(out) = output.get_mut(idx..idx + 4) {
+                        let below = Color::from_rgba8(out[0], out[1], out[2], out[3]);
+                        let (r, g, b, a) =
+                            below.blend_over(Color::from_rgba8(px[0], px[1], px[2], px[3])).to_rgba8();
+                        out.copy_from_slice(&[r, g, b, a]);
+                    }
+                }
+            }
+        }
+
+        if let Some((session, _)) = self.liquify.read().as_ref() {
+            let freeze = session.freeze_mask();
+            if !freeze.is_empty() {
+                let thawed: Vec<u8> = freeze.data().iter().map(|&v| 255 - v).collect();
+                let mask = layer::LayerMask::from_u8(freeze.width(), freeze.height(), &thawed);
+                let tint = Color::from_rgba(1.0, 0.0, 0.0, 0.5);
+                self.render_pipeline.read().render_mask_overlay(&mut output, width, &mask, tint);
+            }
+        }
+
+        if let Some(quick_mask) = selection_manager.quick_mask() {
+            let mask = quick_mask.to_layer_mask();
+            self.render_pipeline.read().render_mask_overlay(&mut output, width, &mask, quick_mask.tint);
+        }
+        Ok(output)
+    }
+
+    /// Layer that painting, fills, filters and adjustments should edit
+    ///
+    /// This is the active layer, or the quick mask surface while quick mask
+    /// mode is on.
+    pub fn edit_target(&self) -> Option<Arc<RwLock<Layer>>> {
+        if let Some(quick_mask) = self.selection_manager.read().quick_mask() {
+            return Some(quick_mask.surface());
+        }
+        self.layer_manager.read().active_layer().cloned()
+    }
+
+    /// Redo into a specific child of the current history node
+    fn redo_into(&self, child: HistoryNodeId) -> EngineResult<bool> {
+        self.cancel_pending_edits()?;
+
+        // Capture the regions about to be overwritten, to save for undo
+        let current_state = match self.history_manager.write().peek_redo(child) {
+            Some(state) => self.capture_inverse_state(state),
+            None => return Ok(false),
+        };
+
+        // Get state to restore
+        let state_to_restore = self.history_manager.write().redo_to(child, current_state);
+        if let Some(state) = state_to_restore {
+            self.restore_state(state)?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Cancel edits that are not in history yet
+    ///
+    /// A floating selection restores the pixels it was lifted from when it is
+    /// cancelled or committed, and a liquify session renders from the pixels
+    /// it started with, so neither may outlive a change of history.
+    fn cancel_pending_edits(&self) -> EngineResult<()> {
+        if self.selection_manager.read().is_floating() {
+            self.cancel_floating_selection()?;
+        }
+        if self.is_liquifying() {
+            self.cancel_liquify()?;
+        }
+        Ok(())
+    }
+
+    /// Capture the current pixels of every region a history state would overwrite
+    fn capture_inverse_state(&self, state: &HistoryState) -> HistoryState {
+        let mut inverse = HistoryState::new(state.description.clone());
+        if state.canvas.is_some() {
+            inverse.set_canvas(self.canvas.read().snapshot());
+        }
+
+        for snapshot in &state.layer_snapshots {
+            if let Some(layer_arc) = self.find_layer(snapshot.layer_id) {
+                let layer = layer_arc.read();
+                inverse.add_snapshot(snapshot.capture_same_region(
+                    &layer.pixels,
+                    layer.width(),
+                    layer.height(),
+                ));
+            }
+        }
+
+        for snapshot in &state.shapes {
+            if let Some(layer_arc) = self.find_layer(snapshot.layer_id) {
+                inverse.add_shapes(ShapesSnapshot {
+                    layer_id: snapshot.layer_id,
+                    shapes: layer_arc.read().shapes.clone(),
+                });
+            }
+        }
+
+        // Layers the restore is about to drop are kept whole, to bring back
+        if let Some(stack) = &state.layer_stack {
+            let mut current = self.capture_layer_stack();
+            for layer_arc in self.layer_manager.read().layers() {
+                let layer = layer_arc.read();
+                if stack.order.contains(&layer.id) {
+                    continue;
+                }
+                inverse.add_snapshot(LayerSnapshot::full_compressed(
+                    layer.id,
+                    layer.pixels.clone(),
+                    layer.width(),
+                    layer.height(),
+                    true,
+                ));
+                if !layer.shapes.is_empty() {
+                    let shapes = layer.shapes.clone();
+                    inverse.add_shapes(ShapesSnapshot { layer_id: layer.id, shapes });
+                }
+                current.removed.push(layer.clone());
+            }
+            inverse.set_layer_stack(current);
+        }
+        inverse
+    }
+
+    /// Snapshot the layer list, so undo can add or remove layers
+    fn capture_layer_stack(&self) -> LayerStackSnapshot {
+        let layer_manager = self.layer_manager.read();
+        LayerStackSnapshot {
+            order: layer_manager.layers().iter().map(|l| l.read().id).collect(),
+            active: layer_manager.active_layer_id(),
+            removed: Vec::new(),
+        }
+    }
+
+    /// Snapshot the canvas and every layer into a history state
+    fn capture_document_state(&self, description: impl Into<String>) -> HistoryState {
+        let canvas = self.canvas.read();
+        let layer_manager = self.layer_manager.read();
+        let mut state = HistoryState::new(description);
+        state.set_canvas(canvas.snapshot());
+        for layer_arc in layer_manager.layers() {
+            let layer = layer_arc.read();
+            let snapshot = LayerSnapshot::full_compressed(
                 layer.id,
                 layer.pixels.clone(),
                 layer.width(),
                 layer.height(),
+                true,
             );
-            let mut state = HistoryState::new("Fill");
             state.add_snapshot(snapshot);
-            self.history_manager.write().push_state(state);
+        }
+        state
+    }
 
-            // Use a simple flood fill algorithm (scanline fill would be more efficient for large areas)
-            let mut visited = vec![false; (width * height) as usize];
-            let mut stack = vec![(x, y)];
+    /// Restore the canvas and layer pixels from a history state
+    fn restore_state(&self, state: HistoryState) -> EngineResult<()> {
+        if let Some(snapshot) = &state.canvas {
+            self.canvas.write().restore(snapshot)?;
+            self.layer_manager.write().set_canvas_size(snapshot.width, snapshot.height);
+            self.selection_manager.write().set_canvas_size(snapshot.width, snapshot.height);
+        }
 
-            while let Some((cx, cy)) = stack.pop() {
-                if cx >= width || cy >= height {
-                    continue;
-                }
+        // The layer list comes first, so restored layers get their pixels
+        if let Some(stack) = state.layer_stack {
+            self.layer_manager.write().restore_order(&stack.order, stack.removed, stack.active);
+        }
 
-                let idx = (cy * width + cx) as usize;
-                if visited[idx] {
-                    continue;
-                }
+        // restore_layer handles full, incremental and resized snapshots
+        for snapshot in state.layer_snapshots {
+            if let Some(layer_arc) = self.find_layer(snapshot.layer_id) {
+                snapshot.restore_layer(&mut layer_arc.write());
+            }
+        }
+        for snapshot in state.shapes {
+            if let Some(layer_arc) = self.find_layer(snapshot.layer_id) {
+                layer_arc.write().shapes = snapshot.shapes;
+            }
+        }
+        Ok(())
+    }
 
-                if let Some(current_color) = layer.get_pixel(cx, cy) {
-                    // Check if colors are similar within tolerance
-                    if colors_similar(&current_color, &target_color, tolerance) {
-                        visited[idx] = true;
-                        layer.set_pixel(cx, cy, fill_color);
-
-                        // Add neighbors
-                        if cx > 0 {
-                            stack.push((cx - 1, cy));
-                        }
-                        if cx + 1 < width {
-                            stack.push((cx + 1, cy));
-                        }
-                        if cy > 0 {
-                            stack.push((cx, cy - 1));
-                        }
-                        if cy + 1 < height {
-                            stack.push((cx, cy + 1));
-                        }
-                    }
-                }
+    /// Apply a transform to the canvas and every layer as one undoable step
+    ///
+    /// The new canvas size is taken from the transformed canvas image. Every
+    /// image is transformed before anything is written back, so a failing
+    /// transform leaves the document untouched.
+    fn transform_document<F>(&self, description: &str, transform: F) -> EngineResult<()>
+    where
+        F: Fn(&ImageData) -> TransformResult<ImageData>,
+    {
+        let before = self.capture_document_state(description);
+
+        let mut canvas = self.canvas.write();
+        let mut layer_manager = self.layer_manager.write();
+
+        let canvas_pixels = ImageData::from_pixels(canvas.get_pixels(), canvas.width(), canvas.height())?;
+        let canvas_image = transform(&canvas_pixels)?;
+        let mut layer_images = Vec::with_capacity(layer_manager.layers().len());
+        for layer_arc in layer_manager.layers() {
+            let layer = layer_arc.read();
+            let image = ImageData::from_pixels(layer.pixels.clone(), layer.width(), layer.height())?;
+            layer_images.push(transform(&image)?);
+        }
+
+        let (width, height) = (canvas_image.width, canvas_image.height);
+        canvas.resize(width, height)?;
+        canvas.clear();
+        for (i, px) in canvas_image.pixels.chunks_exact(4).enumerate() {
+            if px[3] > 0 {
+                let (x, y) = (i as u32 % width, i as u32 / width);
+                canvas.set_pixel(x, y, Color::from_rgba8(px[0], px[1], px[2], px[3]))?;
             }
         }
 
+        for (layer_arc, image) in layer_manager.layers().iter().zip(layer_images) {
+            let mut layer = layer_arc.write();
+            layer.pixels = image.pixels;
+            layer.bounds.2 = image.width;
+            layer.bounds.3 = image.height;
+        }
+        layer_manager.set_canvas_size(width, height);
+        drop(layer_manager);
+        drop(canvas);
+
+        self.selection_manager.write().set_canvas_size(width, height);
+        self.history_manager.write().push_state(before);
         Ok(())
     }
 
-    /// Render the current canvas state
-    pub fn render(&self) -> EngineResult<Vec<u8>> {
-        let canvas = self.canvas.read();
+    /// Find a document layer, or the quick mask surface, by id
+    fn find_layer(&self, id: uuid::Uuid) -> Option<Arc<RwLock<Layer>>> {
+        if let Some(layer) = self.layer_manager.read().get_layer(id) {
+            return Some(layer);
+        }
+        let selection_manager = self.selection_manager.read();
+        let surface = selection_manager.quick_mask()?.surface();
+        let is_surface = surface.read().id == id;
+        is_surface.then_some(surface)
+    }
+
+    /// RGBA pixels and dimensions of a selection sampling source
+    fn sample_pixels(&self, source: SampleSource) -> EngineResult<(Vec<u8>, u32, u32)> {
         let layer_manager = self.layer_manager.read();
-        let render_pipeline = self.render_pipeline.read();
+        let layer = match source {
+            SampleSource::ActiveLayer => layer_manager
+                .active_layer()
+                .cloned()
+                .ok_or_else(|| EngineError::InvalidOperation("No active layer".to_string()))?,
+            SampleSource::Layer(id) => {
+                layer_manager.get_layer(id).ok_or(EngineError::LayerNotFound(id))?
+            }
+            SampleSource::Merged => {
+                let (width, height) = layer_manager.canvas_size();
+                return Ok((layer_manager.composite(), width, height));
+            }
+        };
 
-        render_pipeline.render(&canvas, &layer_manager)
+        let layer = layer.read();
+        Ok((layer.pixels.clone(), layer.width(), layer.height()))
+    }
+
+    /// Blend painted pixels in `area` back toward `before` by selection coverage
+    pub(crate) fn clip_to_selection(&self, layer: &mut Layer, before: &[u8], mut area: DirtyRect) {
+        let selection_manager = self.selection_manager.read();
+        let selection = selection_manager.selection();
+        if !selection.is_active {
+            return;
+        }
+
+        let width = layer.width();
+        area.clamp(width, layer.height());
+        for y in area.y..area.y + area.height {
+            for x in area.x..area.x + area.width {
+                let idx = ((y * width + x) * 4) as usize;
+                if let (Some(original), Some(painted)) =
+                    (before.get(idx..idx + 4), layer.pixels.get_mut(idx..idx + 4))
+                {
+                    selection::blend_by_coverage(original, painted, selection.mask.get(x, y));
+                }
+            }
+        }
+    }
+
+    /// Render the document and downscale it to a history thumbnail
+    fn render_thumbnail(&self, max_size: u32) -> EngineResult<HistoryThumbnail> {
+        let canvas = self.canvas.read();
+        let layer_manager = self.layer_manager.read();
+        let (pixels, width, height) =
+            self.render_pipeline.read().render_thumbnail(&canvas, &layer_manager, max_size)?;
+        Ok(HistoryThumbnail { width, height, pixels })
     }
 }
 
@@ -537,16 +1746,22 @@ impl Default for DrawEngine {
     }
 }
 
-/// Helper function to check if two colors are similar within tolerance
-fn colors_similar(a: &Color, b: &Color, tolerance: f32) -> bool {
-    let dr = (a.r - b.r).abs();
-    let dg = (a.g - b.g).abs();
-    let db = (a.b - b.b).abs();
-    let da = (a.a - b.a).abs();
-
-    // Use Euclidean distance in RGBA space
-    let distance = (dr * dr + dg * dg + db * db + da * da).sqrt();
-    distance <= tolerance
+/// Composite an image over layer pixels with its top-left corner at (x, y)
+fn composite_over(layer: &mut Layer, image: &ImageData, x: i32, y: i32) {
+    for (i, px) in image.pixels.chunks_exact(4).enumerate() {
+        if px[3] == 0 {
+            continue;
+        }
+        let lx = x + (i as u32 % image.width) as i32;
+        let ly = y + (i as u32 / image.width) as i32;
+        if lx < 0 || ly < 0 {
+            continue;
+        }
+        if let Some(existing) = layer.get_pixel(lx as u32, ly as u32) {
+            let color = Color::from_rgba8(px[0], px[1], px[2], px[3]);
+            layer.set_pixel(lx as u32, ly as u32, existing.blend_over(color));
+        }
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,439 @@
+//! Liquify Module
+//!
+//! Interactive local warping. A liquify session keeps a mesh over the layer
+//! being edited whose vertices record how far from their own position they
+//! sample the original pixels; pixels between vertices interpolate the
+//! displacements of the vertices around them. Push, twirl, pinch and bloat
+//! dabs warp the mesh, reconstruct dabs relax it back toward the original and
+//! a freeze mask protects areas from all of them. Pixels are always resampled
+//! from the original layer, so an area can be warped and reconstructed any
+//! number of times without blurring.
+
+use serde::{Deserialize, Serialize};
+
+use crate::history::DirtyRect;
+use crate::selection::SelectionMask;
+
+/// Distance between mesh vertices in pixels
+const MESH_SPACING: u32 = 4;
+
+/// Twirl angle of one full-strength dab at the brush center, in radians
+const TWIRL_ANGLE: f32 = 0.1;
+
+/// Scale change of one full-strength pinch or bloat dab at the brush center
+const PINCH_SCALE: f32 = 0.05;
+
+/// Share of the displacement one full-strength reconstruct dab removes
+const RECONSTRUCT_RATE: f32 = 0.25;
+
+/// What a liquify dab does
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LiquifyMode {
+    /// Push pixels along the pointer movement
+    #[default]
+    Push,
+    /// Rotate pixels clockwise around the brush center
+    TwirlClockwise,
+    /// Rotate pixels counterclockwise around the brush center
+    TwirlCounterclockwise,
+    /// Pull pixels toward the brush center
+    Pinch,
+    /// Push pixels away from the brush center
+    Bloat,
+    /// Return pixels toward their original positions
+    Reconstruct,
+    /// Paint the freeze mask
+    Freeze,
+    /// Erase the freeze mask
+    Thaw,
+}
+
+impl LiquifyMode {
+    /// Mode used with Alt held: the opposite twirl, pinch, bloat or freeze
+    pub fn alternate(self) -> Self {
+        match self {
+            LiquifyMode::TwirlClockwise => LiquifyMode::TwirlCounterclockwise,
+            LiquifyMode::TwirlCounterclockwise => LiquifyMode::TwirlClockwise,
+            LiquifyMode::Pinch => LiquifyMode::Bloat,
+            LiquifyMode::Bloat => LiquifyMode::Pinch,
+            LiquifyMode::Freeze => LiquifyMode::Thaw,
+            LiquifyMode::Thaw => LiquifyMode::Freeze,
+            mode => mode,
+        }
+    }
+}
+
+/// Liquify settings of the liquify tool
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LiquifyOptions {
+    /// What the brush does
+    pub mode: LiquifyMode,
+    /// Brush diameter in pixels
+    pub size: f32,
+    /// Brush strength (0-1), scaled by pen pressure
+    pub strength: f32,
+}
+
+impl Default for LiquifyOptions {
+    fn default() -> Self {
+        Self {
+            mode: LiquifyMode::Push,
+            size: 100.0,
+            strength: 0.5,
+        }
+    }
+}
+
+/// Grid of displacements from the warped layer back into the original
+#[derive(Debug, Clone, PartialEq)]
+pub struct LiquifyMesh {
+    width: u32,
+    height: u32,
+    columns: usize,
+    rows: usize,
+    /// Per vertex, row by row, the offset to the position it samples
+    offsets: Vec<(f32, f32)>,
+}
+
+impl LiquifyMesh {
+    /// Create an undistorted mesh over a `width` x `height` layer
+    pub fn new(width: u32, height: u32) -> Self {
+        let columns = width.div_ceil(MESH_SPACING) as usize + 1;
+        let rows = height.div_ceil(MESH_SPACING) as usize + 1;
+        Self {
+            width,
+            height,
+            columns,
+            rows,
+            offsets: vec![(0.0, 0.0); columns * rows],
+        }
+    }
+
+    /// Whether no vertex is displaced
+    pub fn is_identity(&self) -> bool {
+        self.offsets.iter().all(|&(dx, dy)| dx == 0.0 && dy == 0.0)
+    }
+
+    /// Position in the original pixels that a position on the layer shows
+    pub fn source_at(&self, x: f32, y: f32) -> (f32, f32) {
+        let (dx, dy) = self.offset_at(x, y);
+        (x + dx, y + dy)
+    }
+
+    /// Displacement at a position, interpolated between the vertices
+    fn offset_at(&self, x: f32, y: f32) -> (f32, f32) {
+        let spacing = MESH_SPACING as f32;
+        let gx = (x / spacing).clamp(0.0, (self.columns - 1) as f32);
+        let gy = (y / spacing).clamp(0.0, (self.rows - 1) as f32);
+        let (c0, r0) = (gx.floor() as usize, gy.floor() as usize);
+        let (c1, r1) = ((c0 + 1).min(self.columns - 1), (r0 + 1).min(self.rows - 1));
+        let (tx, ty) = (gx - c0 as f32, gy - r0 as f32);
+
+        let at = |c: usize, r: usize| self.offsets[r * self.columns + c];
+        let lerp = |a: (f32, f32), b: (f32, f32), t: f32| (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t);
+        lerp(lerp(at(c0, r0), at(c1, r0), tx), lerp(at(c0, r1), at(c1, r1), tx), ty)
+    }
+
+    /// Vertex range within `radius` of `center` as (c0, r0, c1, r1), inclusive
+    fn vertices_near(&self, center: (f32, f32), radius: f32) -> Option<(usize, usize, usize, usize)> {
+        let spacing = MESH_SPACING as f32;
+        let c0 = ((center.0 - radius) / spacing).ceil().max(0.0) as usize;
+        let r0 = ((center.1 - radius) / spacing).ceil().max(0.0) as usize;
+        let c1 = ((center.0 + radius) / spacing).floor();
+        let r1 = ((center.1 + radius) / spacing).floor();
+        if c1 < 0.0 || r1 < 0.0 {
+            return None;
+        }
+        let c1 = (c1 as usize).min(self.columns - 1);
+        let r1 = (r1 as usize).min(self.rows - 1);
+        (c0 <= c1 && r0 <= r1).then_some((c0, r0, c1, r1))
+    }
+
+    /// Layer pixels that interpolate between the vertices of a range
+    fn pixel_area(&self, (c0, r0, c1, r1): (usize, usize, usize, usize)) -> DirtyRect {
+        let spacing = MESH_SPACING as usize;
+        let x0 = (c0.saturating_sub(1) * spacing) as u32;
+        let y0 = (r0.saturating_sub(1) * spacing) as u32;
+        let x1 = (((c1 + 1) * spacing) as u32).min(self.width);
+        let y1 = (((r1 + 1) * spacing) as u32).min(self.height);
+        DirtyRect::from_bounds(x0, y0, x1.max(x0), y1.max(y0))
+    }
+}
+
+/// A liquify edit in progress: the mesh and the freeze mask
+#[derive(Debug, Clone)]
+pub struct LiquifySession {
+    mesh: LiquifyMesh,
+    freeze: SelectionMask,
+}
+
+impl LiquifySession {
+    /// Start liquifying a `width` x `height` layer
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            mesh: LiquifyMesh::new(width, height),
+            freeze: SelectionMask::new(width, height),
+        }
+    }
+
+    /// The displacement mesh
+    pub fn mesh(&self) -> &LiquifyMesh {
+        &self.mesh
+    }
+
+    /// Coverage of the frozen areas
+    pub fn freeze_mask(&self) -> &SelectionMask {
+        &self.freeze
+    }
+
+    /// Apply one brush dab
+    ///
+    /// `strength` already includes pen pressure and `motion` is how far the
+    /// pointer moved since the previous dab, used by push. Returns the layer
+    /// area that has to be rendered again, if the dab moved any pixels.
+    pub fn dab(
+        &mut self,
+        mode: LiquifyMode,
+        center: (f32, f32),
+        radius: f32,
+        strength: f32,
+        motion: (f32, f32),
+    ) -> Option<DirtyRect> {
+        let strength = strength.clamp(0.0, 1.0);
+        if radius <= 0.0 || strength == 0.0 {
+            return None;
+        }
+        if matches!(mode, LiquifyMode::Freeze | LiquifyMode::Thaw) {
+            self.paint_freeze(mode == LiquifyMode::Freeze, center, radius, strength);
+            return None;
+        }
+
+        let range = self.mesh.vertices_near(center, radius)?;
+        let (c0, r0, c1, r1) = range;
+        let spacing = MESH_SPACING as f32;
+
+        // Find every new offset from the mesh as it was before the dab,
+        // since moved vertices sample their neighbors' old displacements
+        let mut updates = Vec::new();
+        for r in r0..=r1 {
+            for c in c0..=c1 {
+                let p = (c as f32 * spacing, r as f32 * spacing);
+                let distance = (p.0 - center.0).hypot(p.1 - center.1);
+                let weight = falloff(distance, radius) * strength * (1.0 - self.frozen_at(c, r));
+                if weight <= 0.0 {
+                    continue;
+                }
+
+                let index = r * self.mesh.columns + c;
+                let offset = if mode == LiquifyMode::Reconstruct {
+                    let (dx, dy) = self.mesh.offsets[index];
+                    let keep = 1.0 - weight * RECONSTRUCT_RATE;
+                    (dx * keep, dy * keep)
+                } else {
+                    let from = warp_source(mode, p, center, weight, motion);
+                    let (sx, sy) = self.mesh.source_at(from.0, from.1);
+                    (sx - p.0, sy - p.1)
+                };
+                updates.push((index, offset));
+            }
+        }
+        if updates.is_empty() {
+            return None;
+        }
+        for (index, offset) in updates {
+            self.mesh.offsets[index] = offset;
+        }
+        Some(self.mesh.pixel_area(range))
+    }
+
+    /// Move every unfrozen vertex `amount` (0-1) of the way back to where it
+    /// started, returning the area to render again
+    pub fn reconstruct(&mut self, amount: f32) -> Option<DirtyRect> {
+        let amount = amount.clamp(0.0, 1.0);
+        if amount == 0.0 || self.mesh.is_identity() {
+            return None;
+        }
+        let columns = self.mesh.columns;
+        for i in 0..self.mesh.offsets.len() {
+            let keep = 1.0 - amount * (1.0 - self.frozen_at(i % columns, i / columns));
+            let offset = &mut self.mesh.offsets[i];
+            *offset = (offset.0 * keep, offset.1 * keep);
+        }
+        Some(DirtyRect::new(0, 0, self.mesh.width, self.mesh.height))
+    }
+
+    /// Resample `area` of the layer from its `original` pixels through the
+    /// mesh with bilinear interpolation
+    pub fn render(&self, original: &[u8], pixels: &mut [u8], area: DirtyRect) {
+        let (width, height) = (self.mesh.width, self.mesh.height);
+        let x1 = (area.x + area.width).min(width);
+        let y1 = (area.y + area.height).min(height);
+        for y in area.y..y1 {
+            for x in area.x..x1 {
+                let idx = ((y * width + x) * 4) as usize;
+                let (cx, cy) = (x as f32 + 0.5, y as f32 + 0.5);
+                let (sx, sy) = self.mesh.source_at(cx, cy);
+                let rgba = if (sx - cx).abs() < 1e-3 && (sy - cy).abs() < 1e-3 {
+                    [original[idx], original[idx + 1], original[idx + 2], original[idx + 3]]
+                } else {
+                    sample_bilinear(original, width, height, sx - 0.5, sy - 0.5)
+                };
+                pixels[idx..idx + 4].copy_from_slice(&rgba);
+            }
+        }
+    }
+
+    /// How frozen (0-1) the vertex in a column and row is
+    fn frozen_at(&self, column: usize, row: usize) -> f32 {
+        let x = (column as u32 * MESH_SPACING).min(self.freeze.width().saturating_sub(1));
+        let y = (row as u32 * MESH_SPACING).min(self.freeze.height().saturating_sub(1));
+        self.freeze.get(x, y) as f32 / 255.0
+    }
+
+    fn paint_freeze(&mut self, freeze: bool, center: (f32, f32), radius: f32, strength: f32) {
+        let width = self.freeze.width();
+        let height = self.freeze.height();
+        let x0 = (center.0 - radius).floor().max(0.0) as u32;
+        let y0 = (center.1 - radius).floor().max(0.0) as u32;
+        let x1 = ((center.0 + radius).ceil().max(0.0) as u32).min(width);
+        let y1 = ((center.1 + radius).ceil().max(0.0) as u32).min(height);
+        let data = self.freeze.data_mut();
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let distance = (x as f32 + 0.5 - center.0).hypot(y as f32 + 0.5 - center.1);
+                let amount = falloff(distance, radius) * strength * 255.0;
+                let value = &mut data[(y * width + x) as usize];
+                let changed = if freeze { *value as f32 + amount } else { *value as f32 - amount };
+                *value = changed.round().clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+}
+
+/// Smooth brush weight, 1 at the center and 0 at `radius`
+fn falloff(distance: f32, radius: f32) -> f32 {
+    if distance >= radius {
+        return 0.0;
+    }
+    let t = distance / radius;
+    (1.0 - t * t).powi(2)
+}
+
+/// Position whose pixels a vertex at `p` shows after a warp dab
+fn warp_source(mode: LiquifyMode, p: (f32, f32), center: (f32, f32), weight: f32, motion: (f32, f32)) -> (f32, f32) {
+    let (vx, vy) = (p.0 - center.0, p.1 - center.1);
+    match mode {
+        LiquifyMode::Push => (p.0 - motion.0 * weight, p.1 - motion.1 * weight),
+        LiquifyMode::TwirlClockwise | LiquifyMode::TwirlCounterclockwise => {
+            // With y pointing down a positive angle turns clockwise; the
+            // content turns one way by sampling from the other
+            let angle = if mode == LiquifyMode::TwirlClockwise { -TWIRL_ANGLE } else { TWIRL_ANGLE } * weight;
+            let (sin, cos) = angle.sin_cos();
+            (center.0 + vx * cos - vy * sin, center.1 + vx * sin + vy * cos)
+        }
+        LiquifyMode::Pinch => {
+            let scale = 1.0 + PINCH_SCALE * weight;
+            (center.0 + vx * scale, center.1 + vy * scale)
+        }
+        LiquifyMode::Bloat => {
+            let scale = 1.0 - PINCH_SCALE * weight;
+            (center.0 + vx * scale, center.1 + vy * scale)
+        }
+        _ => p,
+    }
+}
+
+/// Bilinear sample of straight RGBA at a position where integers are pixel
+/// centers, interpolating premultiplied colors and clamping to the edge
+fn sample_bilinear(pixels: &[u8], width: u32, height: u32, x: f32, y: f32) -> [u8; 4] {
+    let x = x.clamp(0.0, (width - 1) as f32);
+    let y = y.clamp(0.0, (height - 1) as f32);
+    let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+    let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+    let (tx, ty) = (x - x0 as f32, y - y0 as f32);
+
+    let mut sum = [0.0f32; 4];
+    for (px, py, weight) in [
+        (x0, y0, (1.0 - tx) * (1.0 - ty)),
+        (x1, y0, tx * (1.0 - ty)),
+        (x0, y1, (1.0 - tx) * ty),
+        (x1, y1, tx * ty),
+    ] {
+        let idx = ((py * width + px) * 4) as usize;
+        let alpha = pixels[idx + 3] as f32;
+        for c in 0..3 {
+            sum[c] += pixels[idx + c] as f32 * alpha * weight;
+        }
+        sum[3] += alpha * weight;
+    }
+    if sum[3] < 0.5 {
+        return [0; 4];
+    }
+    let alpha = sum[3];
+    [
+        (sum[0] / alpha).round().clamp(0.0, 255.0) as u8,
+        (sum[1] / alpha).round().clamp(0.0, 255.0) as u8,
+        (sum[2] / alpha).round().clamp(0.0, 255.0) as u8,
+        alpha.round().clamp(0.0, 255.0) as u8,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 32x32 opaque pixels with a vertical black stripe at x 14..18 on white
+    fn stripe() -> Vec<u8> {
+        (0..32 * 32)
+            .flat_map(|i| if (14..18).contains(&(i % 32)) { [0, 0, 0, 255] } else { [255; 4] })
+            .collect()
+    }
+
+    #[test]
+    fn test_push_moves_pixels_along_motion() {
+        let original = stripe();
+        let mut pixels = original.clone();
+        let mut session = LiquifySession::new(32, 32);
+        let area = session.dab(LiquifyMode::Push, (16.0, 16.0), 12.0, 1.0, (4.0, 0.0)).unwrap();
+        session.render(&original, &mut pixels, area);
+
+        let at = |x: u32, y: u32| pixels[((y * 32 + x) * 4) as usize];
+        assert!(at(19, 16) < 64, "stripe pushed right");
+        assert!(at(14, 16) > 192, "left edge uncovered");
+        assert_eq!(at(15, 2), 0, "outside the brush unchanged");
+    }
+
+    #[test]
+    fn test_reconstruct_restores_original() {
+        let original = stripe();
+        let mut pixels = original.clone();
+        let mut session = LiquifySession::new(32, 32);
+        for mode in [LiquifyMode::TwirlClockwise, LiquifyMode::Bloat] {
+            session.dab(mode, (16.0, 16.0), 10.0, 1.0, (0.0, 0.0));
+        }
+        assert!(!session.mesh().is_identity());
+
+        let area = session.reconstruct(1.0).unwrap();
+        session.render(&original, &mut pixels, area);
+        assert!(session.mesh().is_identity());
+        assert_eq!(pixels, original);
+    }
+
+    #[test]
+    fn test_freeze_protects_and_pinch_bloat_are_opposite() {
+        let mut session = LiquifySession::new(32, 32);
+        session.dab(LiquifyMode::Freeze, (16.0, 16.0), 20.0, 1.0, (0.0, 0.0));
+        session.dab(LiquifyMode::Freeze, (16.0, 16.0), 20.0, 1.0, (0.0, 0.0));
+        assert_eq!(session.freeze_mask().get(16, 16), 255);
+        assert!(session.dab(LiquifyMode::Push, (16.0, 16.0), 4.0, 1.0, (3.0, 0.0)).is_none());
+
+        let mut session = LiquifySession::new(32, 32);
+        session.dab(LiquifyMode::Pinch, (16.0, 16.0), 10.0, 1.0, (0.0, 0.0));
+        let (sx, _) = session.mesh().source_at(20.0, 16.0);
+        assert!(sx > 20.0, "pinch samples from farther out");
+        session.dab(LiquifyMode::Bloat, (16.0, 16.0), 10.0, 1.0, (0.0, 0.0));
+        let (sx, _) = session.mesh().source_at(20.0, 16.0);
+        assert!((sx - 20.0).abs() < 0.1);
+    }
+}
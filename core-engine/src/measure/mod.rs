@@ -0,0 +1,169 @@
+//! Measure Module
+//!
+//! Ruler lines drawn over the canvas. A measurement is stored in pixels;
+//! readouts convert its length to physical units with the document
+//! resolution. Angles follow the usual ruler convention: degrees
+//! counterclockwise from the positive x axis, between -180 and 180, even
+//! though canvas y grows downward.
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Millimeters per inch
+const MM_PER_INCH: f32 = 25.4;
+
+/// Unit a measurement is read out in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MeasureUnit {
+    /// Pixels
+    #[default]
+    Pixels,
+    /// Millimeters
+    Millimeters,
+    /// Centimeters
+    Centimeters,
+    /// Inches
+    Inches,
+}
+
+impl MeasureUnit {
+    /// Convert a length in pixels at `dpi` to this unit
+    pub fn from_pixels(&self, pixels: f32, dpi: u32) -> f32 {
+        let inches = pixels / dpi.max(1) as f32;
+        match self {
+            MeasureUnit::Pixels => pixels,
+            MeasureUnit::Millimeters => inches * MM_PER_INCH,
+            MeasureUnit::Centimeters => inches * MM_PER_INCH / 10.0,
+            MeasureUnit::Inches => inches,
+        }
+    }
+
+    /// Short label for readouts
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            MeasureUnit::Pixels => "px",
+            MeasureUnit::Millimeters => "mm",
+            MeasureUnit::Centimeters => "cm",
+            MeasureUnit::Inches => "in",
+        }
+    }
+}
+
+/// Ruler settings
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MeasureOptions {
+    /// Unit lengths are read out in
+    pub unit: MeasureUnit,
+}
+
+/// A ruler line between two canvas points
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Measurement {
+    /// Unique measurement identifier
+    pub id: Uuid,
+    /// Where the line starts, in canvas pixels
+    pub start: (f32, f32),
+    /// Where the line ends, in canvas pixels
+    pub end: (f32, f32),
+}
+
+impl Measurement {
+    /// Create a measurement between two points
+    pub fn new(start: (f32, f32), end: (f32, f32)) -> Self {
+        Self { id: Uuid::new_v4(), start, end }
+    }
+
+    /// Length in pixels
+    pub fn distance(&self) -> f32 {
+        let (dx, dy) = self.delta();
+        dx.hypot(dy)
+    }
+
+    /// Angle in degrees, counterclockwise from the positive x axis
+    pub fn angle(&self) -> f32 {
+        let (dx, dy) = self.delta();
+        if dx == 0.0 && dy == 0.0 {
+            return 0.0;
+        }
+        (-dy).atan2(dx).to_degrees()
+    }
+
+    /// Clockwise rotation in degrees that turns the line horizontal, or
+    /// vertical when it is closer to vertical
+    pub fn straighten_angle(&self) -> f32 {
+        let angle = self.angle();
+        angle - (angle / 90.0).round() * 90.0
+    }
+
+    /// Length, extents and angle with lengths in `unit`
+    pub fn readout(&self, unit: MeasureUnit, dpi: u32) -> MeasureReadout {
+        let (dx, dy) = self.delta();
+        MeasureReadout {
+            id: self.id,
+            start: self.start,
+            end: self.end,
+            unit,
+            distance: unit.from_pixels(self.distance(), dpi),
+            width: unit.from_pixels(dx.abs(), dpi),
+            height: unit.from_pixels(dy.abs(), dpi),
+            angle: self.angle(),
+        }
+    }
+
+    fn delta(&self) -> (f32, f32) {
+        (self.end.0 - self.start.0, self.end.1 - self.start.1)
+    }
+}
+
+/// A measurement as shown to the user
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MeasureReadout {
+    /// Measurement identifier
+    pub id: Uuid,
+    /// Where the line starts, in canvas pixels
+    pub start: (f32, f32),
+    /// Where the line ends, in canvas pixels
+    pub end: (f32, f32),
+    /// Unit of `distance`, `width` and `height`
+    pub unit: MeasureUnit,
+    /// Length of the line
+    pub distance: f32,
+    /// Horizontal extent
+    pub width: f32,
+    /// Vertical extent
+    pub height: f32,
+    /// Angle in degrees, counterclockwise from the positive x axis
+    pub angle: f32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_units_follow_resolution() {
+        let measurement = Measurement::new((0.0, 0.0), (300.0, 0.0));
+        let inches = measurement.readout(MeasureUnit::Inches, 300);
+        assert!((inches.distance - 1.0).abs() < 1e-6);
+        let mm = measurement.readout(MeasureUnit::Millimeters, 150);
+        assert!((mm.distance - 50.8).abs() < 1e-4);
+        assert!((MeasureUnit::Centimeters.from_pixels(300.0, 300) - 2.54).abs() < 1e-5);
+        assert_eq!(MeasureUnit::Pixels.from_pixels(12.5, 72), 12.5);
+    }
+
+    #[test]
+    fn test_angle_and_straighten() {
+        // Up and to the right on screen is a positive angle
+        let rising = Measurement::new((0.0, 10.0), (10.0, 0.0));
+        assert!((rising.angle() - 45.0).abs() < 1e-4);
+        let tilted = Measurement::new((0.0, 0.0), (100.0, -10.0));
+        assert!((tilted.straighten_angle() - tilted.angle()).abs() < 1e-4);
+
+        // Nearly vertical lines straighten to vertical
+        let steep = Measurement::new((0.0, 0.0), (10.0, 100.0));
+        assert!((steep.angle() + 84.29).abs() < 0.01);
+        assert!((steep.straighten_angle() - 5.71).abs() < 0.01);
+        assert_eq!(Measurement::new((3.0, 3.0), (3.0, 3.0)).angle(), 0.0);
+    }
+}
@@ -4,11 +4,13 @@
 //! - Tile-based rendering for large canvases
 //! - Layer compositing
 //! - Real-time preview
+//! - Quick mask overlay
 //! - Export rendering
 
 use crate::canvas::Canvas;
+use crate::color::Color;
 use crate::error::{EngineError, EngineResult};
-use crate::layer::LayerManager;
+use crate::layer::{LayerManager, LayerMask};
 
 /// Render context for a frame
 pub struct RenderContext {
@@ -153,6 +155,15 @@ impl RenderPipeline {
         &self.context
     }
 
+    /// Set zoom and pan, leaving the rest of the context unchanged
+    ///
+    /// A canvas point `p` is shown on screen at `p * zoom + pan`.
+    pub fn set_view(&mut self, zoom: f32, pan_x: f32, pan_y: f32) {
+        self.context.zoom = zoom;
+        self.context.pan_x = pan_x;
+        self.context.pan_y = pan_y;
+    }
+
     /// Mark a region as dirty
     pub fn mark_dirty(&mut self, region: DirtyRegion) {
         // Try to merge with existing regions
@@ -309,6 +320,31 @@ impl RenderPipeline {
         }
     }
 
+    /// Tint the unselected areas of a quick mask over rendered output
+    ///
+    /// Tint strength scales with how unselected each pixel is, so partially
+    /// selected pixels show a lighter tint.
+    pub fn render_mask_overlay(
+        &self,
+        output: &mut [u8],
+        width: u32,
+        mask: &LayerMask,
+        tint: Color,
+    ) {
+        for (i, pixel) in output.chunks_exact_mut(4).enumerate() {
+            let (x, y) = (i as u32 % width, i as u32 / width);
+            let strength = tint.a * (1.0 - mask.get(x, y));
+            if strength <= 0.0 {
+                continue;
+            }
+
+            for (channel, target) in pixel.iter_mut().zip([tint.r, tint.g, tint.b]) {
+                let value = *channel as f32 / 255.0;
+                *channel = ((value + (target - value) * strength) * 255.0).round() as u8;
+            }
+        }
+    }
+
     /// Render to image for export
     pub fn render_export(
         &self,
@@ -0,0 +1,63 @@
+//! Saved selection channels
+//!
+//! A channel is a named selection mask stored with the document, so complex
+//! selections can be reloaded in later sessions and combined with the active
+//! selection. Channels convert to and from layer masks.
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::SelectionMask;
+use crate::layer::LayerMask;
+
+/// A named selection stored in the document
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SelectionChannel {
+    /// Unique channel identifier
+    pub id: Uuid,
+    /// Channel name
+    pub name: String,
+    /// Stored coverage
+    pub mask: SelectionMask,
+}
+
+impl SelectionChannel {
+    /// Create a channel from a coverage mask
+    pub fn new(name: impl Into<String>, mask: SelectionMask) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            name: name.into(),
+            mask,
+        }
+    }
+
+    /// Create a channel from a layer mask (white is selected)
+    pub fn from_layer_mask(name: impl Into<String>, mask: &LayerMask) -> Self {
+        Self::new(name, SelectionMask::from_layer_mask(mask))
+    }
+
+    /// Convert the channel to a layer mask that reveals the selected area
+    pub fn to_layer_mask(&self) -> LayerMask {
+        self.mask.to_layer_mask()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layer_mask_round_trip() {
+        let mask = SelectionMask::ellipse(16, 16, 0.0, 0.0, 16.0, 16.0);
+        let channel = SelectionChannel::new("Face", mask.clone());
+
+        let mut layer_mask = channel.to_layer_mask();
+        assert_eq!(SelectionChannel::from_layer_mask("Copy", &layer_mask).mask, mask);
+
+        // Inverted layer masks hide the selection instead
+        layer_mask.inverted = true;
+        let inverted = SelectionChannel::from_layer_mask("Inverted", &layer_mask);
+        assert_eq!(inverted.mask.get(8, 8), 0);
+        assert_eq!(inverted.mask.get(0, 0), 255);
+    }
+}
@@ -0,0 +1,335 @@
+//! Color-based selections
+//!
+//! Color Range selects pixels by how close their color is to sampled colors,
+//! a tonal range or skin tones. Select Similar and Grow extend an existing
+//! selection to pixels resembling the colors already selected, across the
+//! whole image or through neighboring pixels respectively.
+//!
+//! Every match is soft: a pixel whose color distance is within half the
+//! fuzziness (or tolerance) is fully selected, and coverage fades linearly to
+//! nothing at the full fuzziness. Coverage is also scaled by pixel alpha, so
+//! transparent areas are never picked up.
+
+use serde::{Deserialize, Serialize};
+
+use super::SelectionMask;
+use crate::color::{Color, ColorConverter};
+use crate::error::{EngineError, EngineResult};
+
+/// Color space used to measure the distance between colors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ColorRangeSpace {
+    /// Euclidean distance between 8-bit RGB values
+    #[default]
+    Rgb,
+    /// CIE76 delta E between Lab values, closer to perceived difference
+    Lab,
+}
+
+/// Which colors a Color Range selection matches
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ColorRangeTarget {
+    /// Colors near any of the sampled colors
+    Sampled(Vec<Color>),
+    /// Bright tones (upper quarter of the luminance range)
+    Highlights,
+    /// Middle tones
+    Midtones,
+    /// Dark tones (lower quarter of the luminance range)
+    Shadows,
+    /// Typical human skin tones, independent of brightness
+    SkinTones,
+}
+
+/// Color Range selection settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColorRange {
+    /// Colors to match
+    pub target: ColorRangeTarget,
+    /// Distance at which coverage reaches zero
+    ///
+    /// In 8-bit RGB units or delta E depending on `space`; tonal ranges and
+    /// skin tones use the same scale for how far their soft edge extends.
+    pub fuzziness: f32,
+    /// Space in which color distance is measured
+    pub space: ColorRangeSpace,
+}
+
+impl ColorRange {
+    /// Match colors near the sampled colors
+    pub fn sampled(colors: Vec<Color>, fuzziness: f32) -> Self {
+        Self::new(ColorRangeTarget::Sampled(colors), fuzziness)
+    }
+
+    /// Match a preset target
+    pub fn new(target: ColorRangeTarget, fuzziness: f32) -> Self {
+        Self {
+            target,
+            fuzziness: fuzziness.max(0.0),
+            space: ColorRangeSpace::default(),
+        }
+    }
+
+    /// Measure distances in another color space
+    pub fn with_space(mut self, space: ColorRangeSpace) -> Self {
+        self.space = space;
+        self
+    }
+
+    /// Coverage of an 8-bit RGB color as 0.0..=1.0
+    pub fn coverage(&self, r: u8, g: u8, b: u8) -> f32 {
+        let distance = match &self.target {
+            ColorRangeTarget::Sampled(samples) => samples
+                .iter()
+                .map(|sample| self.color_distance(sample.to_rgb8(), (r, g, b)))
+                .fold(f32::INFINITY, f32::min),
+            ColorRangeTarget::Highlights => (192.0 - self.tone(r, g, b)).max(0.0),
+            ColorRangeTarget::Midtones => ((self.tone(r, g, b) - 128.0).abs() - 32.0).max(0.0),
+            ColorRangeTarget::Shadows => (self.tone(r, g, b) - 64.0).max(0.0),
+            ColorRangeTarget::SkinTones => skin_distance(r, g, b),
+        };
+        soft_match(distance, self.fuzziness)
+    }
+
+    /// Build a coverage mask over RGBA pixels
+    pub fn select(&self, pixels: &[u8], width: u32, height: u32) -> EngineResult<SelectionMask> {
+        check_pixels(pixels, width, height)?;
+        if matches!(&self.target, ColorRangeTarget::Sampled(samples) if samples.is_empty()) {
+            return Err(EngineError::InvalidOperation("No sample colors".to_string()));
+        }
+
+        let data = pixels
+            .chunks_exact(4)
+            .map(|p| to_byte(self.coverage(p[0], p[1], p[2]) * p[3] as f32 / 255.0))
+            .collect();
+        SelectionMask::from_data(width, height, data)
+    }
+
+    /// Brightness on a 0..=255 scale in the configured space
+    fn tone(&self, r: u8, g: u8, b: u8) -> f32 {
+        match self.space {
+            ColorRangeSpace::Rgb => Color::from_rgb8(r, g, b).luminance() * 255.0,
+            ColorRangeSpace::Lab => ColorConverter::rgb_to_lab(Color::from_rgb8(r, g, b)).0 * 2.55,
+        }
+    }
+
+    fn color_distance(&self, a: (u8, u8, u8), b: (u8, u8, u8)) -> f32 {
+        match self.space {
+            ColorRangeSpace::Rgb => {
+                let dr = a.0 as f32 - b.0 as f32;
+                let dg = a.1 as f32 - b.1 as f32;
+                let db = a.2 as f32 - b.2 as f32;
+                (dr * dr + dg * dg + db * db).sqrt()
+            }
+            ColorRangeSpace::Lab => {
+                let (l1, a1, b1) = ColorConverter::rgb_to_lab(Color::from_rgb8(a.0, a.1, a.2));
+                let (l2, a2, b2) = ColorConverter::rgb_to_lab(Color::from_rgb8(b.0, b.1, b.2));
+                ((l1 - l2).powi(2) + (a1 - a2).powi(2) + (b1 - b2).powi(2)).sqrt()
+            }
+        }
+    }
+}
+
+/// Per-channel bounds of the colors inside a selection
+///
+/// Select Similar and Grow match against this box rather than individual
+/// colors, so selecting part of a gradient picks up the whole span.
+#[derive(Debug, Clone, Copy)]
+struct ColorBox {
+    min: [u8; 3],
+    max: [u8; 3],
+}
+
+impl ColorBox {
+    /// Colors of the mostly selected pixels, or of any selected pixel if
+    /// none is more than half selected
+    fn from_selection(mask: &SelectionMask, pixels: &[u8]) -> Option<Self> {
+        Self::collect(mask, pixels, 127).or_else(|| Self::collect(mask, pixels, 0))
+    }
+
+    fn collect(mask: &SelectionMask, pixels: &[u8], threshold: u8) -> Option<Self> {
+        let mut bounds: Option<Self> = None;
+        for (&coverage, p) in mask.data().iter().zip(pixels.chunks_exact(4)) {
+            if coverage <= threshold || p[3] == 0 {
+                continue;
+            }
+            let b = bounds.get_or_insert(Self {
+                min: [p[0], p[1], p[2]],
+                max: [p[0], p[1], p[2]],
+            });
+            for (c, &value) in p[..3].iter().enumerate() {
+                b.min[c] = b.min[c].min(value);
+                b.max[c] = b.max[c].max(value);
+            }
+        }
+        bounds
+    }
+
+    /// Largest per-channel distance from the box
+    fn distance(&self, p: &[u8]) -> f32 {
+        (0..3)
+            .map(|c| {
+                let below = self.min[c].saturating_sub(p[c]);
+                let above = p[c].saturating_sub(self.max[c]);
+                below.max(above) as f32
+            })
+            .fold(0.0, f32::max)
+    }
+
+    /// Soft match of an RGBA pixel, scaled by its alpha
+    fn coverage(&self, p: &[u8], tolerance: f32) -> u8 {
+        to_byte(soft_match(self.distance(p), tolerance) * p[3] as f32 / 255.0)
+    }
+}
+
+/// Extend `mask` to every pixel in the image with a similar color
+pub(crate) fn select_similar(
+    mask: &mut SelectionMask,
+    pixels: &[u8],
+    tolerance: f32,
+) -> EngineResult<()> {
+    check_pixels(pixels, mask.width(), mask.height())?;
+    let colors = ColorBox::from_selection(mask, pixels)
+        .ok_or_else(|| EngineError::InvalidOperation("No selected pixels to match".to_string()))?;
+
+    for (value, p) in mask.data_mut().iter_mut().zip(pixels.chunks_exact(4)) {
+        *value = (*value).max(colors.coverage(p, tolerance));
+    }
+    Ok(())
+}
+
+/// Extend `mask` to similarly colored pixels connected to the selection
+///
+/// Growth spreads through 4-connected neighbors whose color matches at all,
+/// so a soft match can carry the selection across a gentle gradient.
+pub(crate) fn grow(mask: &mut SelectionMask, pixels: &[u8], tolerance: f32) -> EngineResult<()> {
+    let (width, height) = (mask.width(), mask.height());
+    check_pixels(pixels, width, height)?;
+    let colors = ColorBox::from_selection(mask, pixels)
+        .ok_or_else(|| EngineError::InvalidOperation("No selected pixels to match".to_string()))?;
+
+    let mut stack: Vec<u32> = mask
+        .data()
+        .iter()
+        .enumerate()
+        .filter(|(_, &v)| v > 0)
+        .map(|(i, _)| i as u32)
+        .collect();
+    let mut visited: Vec<bool> = mask.data().iter().map(|&v| v > 0).collect();
+    let data = mask.data_mut();
+
+    while let Some(i) = stack.pop() {
+        let (x, y) = (i % width, i / width);
+        let neighbors = [
+            (x > 0).then(|| i - 1),
+            (x + 1 < width).then(|| i + 1),
+            (y > 0).then(|| i - width),
+            (y + 1 < height).then(|| i + width),
+        ];
+
+        for n in neighbors.into_iter().flatten() {
+            let n = n as usize;
+            if visited[n] {
+                continue;
+            }
+            visited[n] = true;
+            let coverage = colors.coverage(&pixels[n * 4..n * 4 + 4], tolerance);
+            if coverage > 0 {
+                data[n] = coverage;
+                stack.push(n as u32);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Linear falloff: full coverage within half the fuzziness, none beyond it
+fn soft_match(distance: f32, fuzziness: f32) -> f32 {
+    if fuzziness <= 0.0 {
+        return if distance <= 0.0 { 1.0 } else { 0.0 };
+    }
+    ((fuzziness - distance) / (fuzziness * 0.5)).clamp(0.0, 1.0)
+}
+
+/// Distance outside the skin-tone region of the CbCr chroma plane
+///
+/// The region is an ellipse around the classic skin cluster
+/// (Cb 77..127, Cr 133..173), so brightness does not affect the match.
+fn skin_distance(r: u8, g: u8, b: u8) -> f32 {
+    let (r, g, b) = (r as f32, g as f32, b as f32);
+    let cb = 128.0 - 0.168736 * r - 0.331264 * g + 0.5 * b;
+    let cr = 128.0 + 0.5 * r - 0.418688 * g - 0.081312 * b;
+
+    let (du, dv) = ((cb - 102.0) / 25.0, (cr - 153.0) / 20.0);
+    let radius = (du * du + dv * dv).sqrt();
+    // Scale back to chroma units so fuzziness behaves like an RGB distance
+    ((radius - 1.0) * 20.0).max(0.0)
+}
+
+fn check_pixels(pixels: &[u8], width: u32, height: u32) -> EngineResult<()> {
+    if pixels.len() < (width * height * 4) as usize {
+        return Err(EngineError::InvalidOperation("Invalid pixel data".to_string()));
+    }
+    Ok(())
+}
+
+fn to_byte(coverage: f32) -> u8 {
+    (coverage.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(colors: &[[u8; 4]]) -> Vec<u8> {
+        colors.iter().flatten().copied().collect()
+    }
+
+    #[test]
+    fn test_sampled_range_is_soft() {
+        let pixels = image(&[[200, 0, 0, 255], [170, 0, 0, 255], [140, 0, 0, 255], [0, 0, 200, 255]]);
+        let range = ColorRange::sampled(vec![Color::from_rgb8(200, 0, 0)], 40.0);
+        let mask = range.select(&pixels, 4, 1).unwrap();
+
+        assert_eq!(mask.get(0, 0), 255);
+        assert!((1..255).contains(&mask.get(1, 0)));
+        assert_eq!(mask.get(2, 0), 0);
+        assert_eq!(mask.get(3, 0), 0);
+    }
+
+    #[test]
+    fn test_tonal_ranges() {
+        let pixels = image(&[[10, 10, 10, 255], [128, 128, 128, 255], [245, 245, 245, 255]]);
+        let select = |target| ColorRange::new(target, 20.0).select(&pixels, 3, 1).unwrap();
+
+        assert_eq!(select(ColorRangeTarget::Shadows).data(), &[255, 0, 0]);
+        assert_eq!(select(ColorRangeTarget::Midtones).data(), &[0, 255, 0]);
+        assert_eq!(select(ColorRangeTarget::Highlights).data(), &[0, 0, 255]);
+    }
+
+    #[test]
+    fn test_skin_tones_ignore_brightness() {
+        let range = ColorRange::new(ColorRangeTarget::SkinTones, 20.0).with_space(ColorRangeSpace::Lab);
+        assert_eq!(range.coverage(224, 172, 140), 1.0);
+        assert_eq!(range.coverage(141, 85, 36), 1.0);
+        assert_eq!(range.coverage(40, 90, 200), 0.0);
+    }
+
+    #[test]
+    fn test_similar_and_grow() {
+        // Two red regions separated by a blue column
+        let red = [200, 20, 20, 255];
+        let pixels = image(&[red, red, [0, 0, 255, 255], red, [205, 20, 20, 255]]);
+
+        let mut seed = SelectionMask::new(5, 1);
+        seed.set(0, 0, 255);
+
+        let mut grown = seed.clone();
+        grow(&mut grown, &pixels, 32.0).unwrap();
+        assert_eq!(grown.data(), &[255, 255, 0, 0, 0]);
+
+        let mut similar = seed;
+        select_similar(&mut similar, &pixels, 32.0).unwrap();
+        assert_eq!(similar.data(), &[255, 255, 0, 255, 255]);
+    }
+}
@@ -0,0 +1,524 @@
+//! Floating selections
+//!
+//! Lifting the selected pixels off a layer turns them into a floating
+//! selection that can be moved, scaled, rotated, skewed and flipped together
+//! with its coverage, given perspective, distorted by its corners or bent
+//! with a mesh. Edits only update an affine transform and a [`FloatingWarp`]
+//! below it, so the content is resampled once on commit with the chosen
+//! [`Interpolation`] no matter how often it was adjusted; previews use a fast
+//! interpolation instead.
+
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{Selection, SelectionMask};
+use crate::error::{EngineError, EngineResult};
+use crate::geometry::{Homography, Transform};
+use crate::transform::{self, ImageData, Interpolation, WarpMesh};
+
+/// Interpolation used for live previews
+pub const PREVIEW_INTERPOLATION: Interpolation = Interpolation::Bilinear;
+
+/// What dragging does in the free transform tool
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FreeTransformMode {
+    /// Move, rotate and scale
+    #[default]
+    Free,
+    /// Corner drags move the neighboring corner the opposite way
+    Perspective,
+    /// Corner drags move that corner alone
+    Distort,
+    /// Drags bend the content by the points of a mesh
+    Warp,
+}
+
+/// Free transform settings
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FreeTransformOptions {
+    /// What dragging does
+    pub mode: FreeTransformMode,
+    /// Interpolation used when the transform is applied
+    pub interpolation: Interpolation,
+    /// Mesh cells across in warp mode
+    pub warp_columns: u32,
+    /// Mesh cells down in warp mode
+    pub warp_rows: u32,
+}
+
+impl Default for FreeTransformOptions {
+    fn default() -> Self {
+        Self {
+            mode: FreeTransformMode::Free,
+            interpolation: Interpolation::Bicubic,
+            warp_columns: 3,
+            warp_rows: 3,
+        }
+    }
+}
+
+/// Non-affine part of a free transform
+///
+/// It maps content pixel coordinates to the coordinates the affine
+/// transform then takes to the layer.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FloatingWarp {
+    /// Projective transform, from perspective and distort
+    Perspective(Homography),
+    /// Control-point mesh
+    Mesh(WarpMesh),
+}
+
+/// Corners after dragging corner `index` to `to` in perspective mode
+///
+/// Corners are ordered top-left, top-right, bottom-right, bottom-left. A
+/// mostly horizontal drag moves the corner sharing the horizontal edge the
+/// opposite way, a mostly vertical one the corner sharing the vertical edge,
+/// so the edge narrows or widens symmetrically.
+pub fn perspective_corners(corners: [(f32, f32); 4], index: usize, to: (f32, f32)) -> [(f32, f32); 4] {
+    let mut corners = corners;
+    let index = index % 4;
+    let (dx, dy) = (to.0 - corners[index].0, to.1 - corners[index].1);
+    if dx.abs() >= dy.abs() {
+        let partner = [1, 0, 3, 2][index];
+        corners[index].0 += dx;
+        corners[partner].0 -= dx;
+    } else {
+        let partner = [3, 2, 1, 0][index];
+        corners[index].1 += dy;
+        corners[partner].1 -= dy;
+    }
+    corners
+}
+
+/// Selected pixels lifted off a layer, pending a transform
+#[derive(Debug, Clone)]
+pub struct FloatingSelection {
+    /// Layer the pixels were lifted from
+    layer_id: Uuid,
+    /// Lifted pixels cropped to the selection bounds, coverage folded into alpha
+    content: ImageData,
+    /// Selection coverage over the lifted area
+    mask: SelectionMask,
+    /// Maps warped content coordinates to layer coordinates
+    transform: Transform,
+    /// Perspective or mesh applied to the content before `transform`
+    warp: Option<FloatingWarp>,
+    /// Where the content was lifted from, in layer coordinates
+    origin: (u32, u32),
+    /// Layer pixels before lifting, for cancel and undo
+    original_pixels: Vec<u8>,
+    /// Selection before lifting, for cancel
+    original_selection: Selection,
+    /// Layer width
+    width: u32,
+    /// Layer height
+    height: u32,
+}
+
+impl FloatingSelection {
+    /// Lift the selected pixels out of a layer
+    ///
+    /// Selected coverage moves from `pixels` into the floating content, so
+    /// partially selected pixels are split between the two.
+    pub fn lift(
+        layer_id: Uuid,
+        pixels: &mut [u8],
+        width: u32,
+        height: u32,
+        selection: &Selection,
+    ) -> EngineResult<Self> {
+        if pixels.len() != (width * height * 4) as usize {
+            return Err(EngineError::InvalidOperation(
+                "Pixel data does not match layer size".to_string(),
+            ));
+        }
+        let coverage_mask = selection.mask.resized(width, height);
+        let (x0, y0, bounds_width, bounds_height) = selection
+            .is_active
+            .then(|| coverage_mask.bounds())
+            .flatten()
+            .ok_or_else(|| EngineError::InvalidOperation("No active selection".to_string()))?;
+
+        let original_pixels = pixels.to_vec();
+        let mut content = ImageData::new(bounds_width, bounds_height);
+        let mut mask = SelectionMask::new(bounds_width, bounds_height);
+
+        for y in 0..bounds_height {
+            for x in 0..bounds_width {
+                let coverage = coverage_mask.get(x0 + x, y0 + y) as u32;
+                if coverage == 0 {
+                    continue;
+                }
+                mask.set(x, y, coverage as u8);
+
+                let src = (((y0 + y) * width + x0 + x) * 4) as usize;
+                let dst = ((y * bounds_width + x) * 4) as usize;
+                let alpha = pixels[src + 3] as u32;
+                content.pixels[dst..dst + 3].copy_from_slice(&pixels[src..src + 3]);
+                content.pixels[dst + 3] = ((alpha * coverage + 127) / 255) as u8;
+                pixels[src + 3] = ((alpha * (255 - coverage) + 127) / 255) as u8;
+            }
+        }
+
+        Ok(Self {
+            layer_id,
+            content,
+            mask,
+            transform: Transform::translation(x0 as f32, y0 as f32),
+            warp: None,
+            origin: (x0, y0),
+            original_pixels,
+            original_selection: selection.clone(),
+            width,
+            height,
+        })
+    }
+
+    /// Layer the pixels were lifted from
+    pub fn layer_id(&self) -> Uuid {
+        self.layer_id
+    }
+
+    /// Current affine transform to layer coordinates, applied after any warp
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    /// Perspective or mesh applied before the affine transform
+    pub fn warp(&self) -> Option<&FloatingWarp> {
+        self.warp.as_ref()
+    }
+
+    /// Replace the whole transform, e.g. from a UI that tracks its own handles
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+
+    /// Undo all adjustments, putting the content back where it was lifted from
+    pub fn reset(&mut self) {
+        self.transform = Transform::translation(self.origin.0 as f32, self.origin.1 as f32);
+        self.warp = None;
+    }
+
+    /// Apply a further transform in layer coordinates
+    pub fn apply(&mut self, transform: &Transform) {
+        self.transform = self.transform.multiply(transform);
+    }
+
+    /// Move by an offset in pixels
+    pub fn translate(&mut self, dx: f32, dy: f32) {
+        self.apply(&Transform::translation(dx, dy));
+    }
+
+    /// Scale around the content center
+    pub fn scale(&mut self, sx: f32, sy: f32) {
+        self.apply(&Transform::scale(sx, sy).around(self.center()));
+    }
+
+    /// Rotate clockwise by `angle` degrees around the content center
+    pub fn rotate(&mut self, angle: f32) {
+        self.apply(&Transform::rotation(angle.to_radians()).around(self.center()));
+    }
+
+    /// Skew by angles in degrees around the content center
+    pub fn skew(&mut self, angle_x: f32, angle_y: f32) {
+        let skew = Transform::skew(angle_x.to_radians(), angle_y.to_radians());
+        self.apply(&skew.around(self.center()));
+    }
+
+    /// Mirror left to right around the content center
+    pub fn flip_horizontal(&mut self) {
+        self.scale(-1.0, 1.0);
+    }
+
+    /// Mirror top to bottom around the content center
+    pub fn flip_vertical(&mut self) {
+        self.scale(1.0, -1.0);
+    }
+
+    /// Move the corners to arbitrary positions, replacing any warp
+    ///
+    /// The corners are ordered as [`FloatingSelection::corners`] returns
+    /// them and must form a convex quad.
+    pub fn distort(&mut self, corners: [(f32, f32); 4]) -> EngineResult<()> {
+        let (w, h) = (self.content.width as f32, self.content.height as f32);
+        let from = [(0.0, 0.0), (w, 0.0), (w, h), (0.0, h)].map(|(x, y)| Vec2::new(x, y));
+        let to = corners.map(|(x, y)| Vec2::new(x, y));
+        let homography = Homography::from_quads(from, to)
+            .filter(|_| is_convex(&to))
+            .ok_or_else(|| EngineError::InvalidOperation("Corners must form a convex quad".to_string()))?;
+        self.transform = Transform::identity();
+        self.warp = Some(FloatingWarp::Perspective(homography));
+        Ok(())
+    }
+
+    /// Drag one corner in perspective mode; see [`perspective_corners`]
+    pub fn perspective(&mut self, index: usize, to: (f32, f32)) -> EngineResult<()> {
+        self.distort(perspective_corners(self.corners(), index, to))
+    }
+
+    /// Replace the whole transform with a homography from content to layer
+    /// coordinates
+    pub fn set_homography(&mut self, homography: Homography) -> EngineResult<()> {
+        let (w, h) = (self.content.width as f32, self.content.height as f32);
+        let m = &homography.matrix;
+        let corners = [(0.0, 0.0), (w, 0.0), (w, h), (0.0, h)].map(|(x, y)| Vec2::new(x, y));
+        if corners.iter().any(|p| m[6] * p.x + m[7] * p.y + m[8] <= 1e-6) {
+            return Err(EngineError::InvalidOperation(
+                "Perspective reaches past the horizon".to_string(),
+            ));
+        }
+        self.transform = Transform::identity();
+        self.warp = Some(FloatingWarp::Perspective(homography));
+        Ok(())
+    }
+
+    /// Switch to a mesh of `columns` x `rows` cells following the current
+    /// shape of the content
+    pub fn begin_mesh(&mut self, columns: u32, rows: u32) {
+        let (w, h) = (self.content.width as f32, self.content.height as f32);
+        let mesh = WarpMesh::new(columns, rows, w, h).mapped(|p| self.map_point(p));
+        self.transform = Transform::identity();
+        self.warp = Some(FloatingWarp::Mesh(mesh));
+    }
+
+    /// Move a mesh control point to a layer position
+    pub fn move_mesh_point(&mut self, column: u32, row: u32, to: (f32, f32)) -> EngineResult<()> {
+        let inverse = self
+            .transform
+            .inverse()
+            .ok_or_else(|| EngineError::InvalidOperation("Transform is not invertible".to_string()))?;
+        let Some(FloatingWarp::Mesh(mesh)) = &mut self.warp else {
+            return Err(EngineError::InvalidOperation("No mesh to warp".to_string()));
+        };
+        let to = inverse.transform_point(Vec2::new(to.0, to.1));
+        mesh.set_point(column, row, (to.x, to.y))?;
+        Ok(())
+    }
+
+    /// Mesh control points in layer coordinates, row by row, if warping
+    /// with a mesh
+    pub fn mesh_points(&self) -> Option<Vec<(f32, f32)>> {
+        let Some(FloatingWarp::Mesh(mesh)) = &self.warp else {
+            return None;
+        };
+        let points = mesh.mapped(|p| self.transform.transform_point(p));
+        Some(points.points().to_vec())
+    }
+
+    /// Layer position of a content position
+    pub fn map_point(&self, point: Vec2) -> Vec2 {
+        let warped = match &self.warp {
+            None => point,
+            Some(FloatingWarp::Perspective(homography)) => homography.project(point).unwrap_or(point),
+            Some(FloatingWarp::Mesh(mesh)) => mesh.position(
+                point.x / self.content.width as f32,
+                point.y / self.content.height as f32,
+            ),
+        };
+        self.transform.transform_point(warped)
+    }
+
+    /// Center of the transformed content in layer coordinates
+    pub fn center(&self) -> Vec2 {
+        let center = Vec2::new(self.content.width as f32, self.content.height as f32) / 2.0;
+        self.map_point(center)
+    }
+
+    /// Corners of the transformed content for drawing handles
+    ///
+    /// Ordered top-left, top-right, bottom-right, bottom-left before the
+    /// transform.
+    pub fn corners(&self) -> [(f32, f32); 4] {
+        let (w, h) = (self.content.width as f32, self.content.height as f32);
+        [(0.0, 0.0), (w, 0.0), (w, h), (0.0, h)].map(|(x, y)| {
+            let p = self.map_point(Vec2::new(x, y));
+            (p.x, p.y)
+        })
+    }
+
+    /// Render the transformed content into a layer-sized buffer
+    ///
+    /// Only the area the content lands in is resampled, so previews with a
+    /// fast interpolation stay cheap.
+    pub fn render(&self, interpolation: Interpolation) -> EngineResult<ImageData> {
+        self.warp_image(&self.content, interpolation)
+    }
+
+    /// Transformed selection coverage at layer size
+    pub fn transformed_mask(&self, interpolation: Interpolation) -> EngineResult<SelectionMask> {
+        if self.warp.is_none() {
+            return self.mask.warped(&self.transform, self.width, self.height, interpolation);
+        }
+        let pixels = self.mask.data().iter().flat_map(|&v| [255, 255, 255, v]).collect();
+        let image = ImageData::from_pixels(pixels, self.mask.width(), self.mask.height())?;
+        let warped = self.warp_image(&image, interpolation)?;
+        SelectionMask::from_alpha(self.width, self.height, &warped.pixels)
+    }
+
+    /// Resample content-sized pixels through the warp and transform
+    fn warp_image(&self, image: &ImageData, interpolation: Interpolation) -> EngineResult<ImageData> {
+        let (width, height) = (self.width, self.height);
+        let image = match &self.warp {
+            None => transform::warp_affine(image, &self.transform, width, height, interpolation)?,
+            Some(FloatingWarp::Perspective(homography)) => {
+                let full = homography.multiply(&Homography::from_transform(&self.transform));
+                transform::warp_perspective(image, &full, width, height, interpolation)?
+            }
+            Some(FloatingWarp::Mesh(mesh)) => {
+                let mesh = mesh.mapped(|p| self.transform.transform_point(p));
+                transform::warp_mesh(image, &mesh, width, height, interpolation)?
+            }
+        };
+        Ok(image)
+    }
+
+    /// Layer pixels as they were before lifting
+    pub fn original_pixels(&self) -> &[u8] {
+        &self.original_pixels
+    }
+
+    /// Selection as it was before lifting
+    pub fn original_selection(&self) -> &Selection {
+        &self.original_selection
+    }
+}
+
+/// Whether a quad's corners turn the same way all around
+fn is_convex(corners: &[Vec2; 4]) -> bool {
+    let turns: Vec<f32> = (0..4)
+        .map(|i| {
+            let (a, b, c) = (corners[i], corners[(i + 1) % 4], corners[(i + 2) % 4]);
+            (b - a).perp_dot(c - b)
+        })
+        .collect();
+    turns.iter().all(|&t| t > 0.0) || turns.iter().all(|&t| t < 0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer_pixels(width: u32, height: u32) -> Vec<u8> {
+        [200u8, 50, 10, 255].repeat((width * height) as usize)
+    }
+
+    #[test]
+    fn test_lift_splits_partial_coverage() {
+        let mut pixels = layer_pixels(8, 8);
+        let selection = Selection::rectangle(2.0, 2.0, 2.5, 2.0, 8, 8);
+        let floating = FloatingSelection::lift(Uuid::new_v4(), &mut pixels, 8, 8, &selection).unwrap();
+
+        // Fully selected pixels leave the layer, the half-covered column is split
+        assert_eq!(pixels[((2 * 8 + 2) * 4 + 3) as usize], 0);
+        assert_eq!(pixels[((2 * 8 + 4) * 4 + 3) as usize], 127);
+        assert_eq!(floating.content.pixels[3], 255);
+        assert_eq!(floating.content.get_pixel(2, 0).to_rgba8().3, 128);
+        assert_eq!(floating.corners()[0], (2.0, 2.0));
+    }
+
+    #[test]
+    fn test_transform_moves_content_and_mask() {
+        let mut pixels = layer_pixels(10, 10);
+        let selection = Selection::rectangle(0.0, 0.0, 2.0, 2.0, 10, 10);
+        let mut floating =
+            FloatingSelection::lift(Uuid::new_v4(), &mut pixels, 10, 10, &selection).unwrap();
+
+        floating.translate(6.0, 3.0);
+        floating.flip_horizontal();
+        let image = floating.render(Interpolation::Nearest).unwrap();
+        assert_eq!(image.get_pixel(6, 3).to_rgba8(), (200, 50, 10, 255));
+        assert_eq!(image.get_pixel(0, 0).a, 0.0);
+
+        let mask = floating.transformed_mask(Interpolation::Nearest).unwrap();
+        assert_eq!(mask.bounds(), Some((6, 3, 2, 2)));
+
+        floating.reset();
+        assert_eq!(floating.center(), Vec2::new(1.0, 1.0));
+    }
+
+    #[test]
+    fn test_rotate_and_skew_around_center() {
+        let mut pixels = layer_pixels(10, 10);
+        let selection = Selection::rectangle(2.0, 2.0, 4.0, 2.0, 10, 10);
+        let mut floating =
+            FloatingSelection::lift(Uuid::new_v4(), &mut pixels, 10, 10, &selection).unwrap();
+
+        floating.rotate(90.0);
+        floating.skew(10.0, 0.0);
+        let center = floating.center();
+        assert!((center.x - 4.0).abs() < 1e-4 && (center.y - 3.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_distort_and_perspective_move_corners() {
+        let mut pixels = layer_pixels(20, 20);
+        let selection = Selection::rectangle(5.0, 5.0, 10.0, 10.0, 20, 20);
+        let mut floating =
+            FloatingSelection::lift(Uuid::new_v4(), &mut pixels, 20, 20, &selection).unwrap();
+
+        floating.perspective(0, (8.0, 5.0)).unwrap();
+        let corners = floating.corners();
+        assert!((corners[0].0 - 8.0).abs() < 1e-3 && (corners[1].0 - 12.0).abs() < 1e-3);
+        assert!((corners[2].0 - 15.0).abs() < 1e-3);
+
+        let image = floating.render(Interpolation::Bilinear).unwrap();
+        assert_eq!(image.get_pixel(6, 6).a, 0.0);
+        assert_eq!(image.get_pixel(10, 6).to_rgba8(), (200, 50, 10, 255));
+        assert_eq!(image.get_pixel(6, 13).to_rgba8(), (200, 50, 10, 255));
+
+        // Moving afterwards keeps the perspective
+        floating.translate(2.0, 0.0);
+        assert!((floating.corners()[0].0 - 10.0).abs() < 1e-3);
+
+        let bow_tie = [(5.0, 5.0), (15.0, 15.0), (15.0, 5.0), (5.0, 15.0)];
+        assert!(floating.distort(bow_tie).is_err());
+    }
+
+    #[test]
+    fn test_mesh_follows_current_shape() {
+        let mut pixels = layer_pixels(20, 20);
+        let selection = Selection::rectangle(4.0, 4.0, 8.0, 8.0, 20, 20);
+        let mut floating =
+            FloatingSelection::lift(Uuid::new_v4(), &mut pixels, 20, 20, &selection).unwrap();
+
+        floating.translate(2.0, 0.0);
+        floating.begin_mesh(2, 2);
+        let points = floating.mesh_points().unwrap();
+        assert_eq!(points.len(), 9);
+        assert_eq!(points[0], (6.0, 4.0));
+
+        // Pulling the middle of the right edge out bulges the content
+        floating.move_mesh_point(2, 1, (18.0, 8.0)).unwrap();
+        assert!(floating.move_mesh_point(5, 1, (0.0, 0.0)).is_err());
+        let mask = floating.transformed_mask(Interpolation::Bilinear).unwrap();
+        assert!(mask.get(16, 7) > 200);
+        assert_eq!(mask.get(15, 4), 0);
+
+        floating.reset();
+        assert!(floating.warp().is_none());
+        assert_eq!(floating.corners()[0], (4.0, 4.0));
+    }
+
+    #[test]
+    fn test_lift_with_selection_of_another_size() {
+        // A selection made on a larger canvas is cut to the layer
+        let mut pixels = layer_pixels(8, 8);
+        let selection = Selection::rectangle(4.0, 4.0, 8.0, 8.0, 16, 16);
+        let floating = FloatingSelection::lift(Uuid::new_v4(), &mut pixels, 8, 8, &selection).unwrap();
+        assert_eq!((floating.content.width, floating.content.height), (4, 4));
+        assert_eq!(floating.content.get_pixel(3, 3).to_rgba8().3, 255);
+        assert_eq!(pixels[((7 * 8 + 7) * 4 + 3) as usize], 0);
+        assert_eq!(pixels[((3 * 8 + 3) * 4 + 3) as usize], 255);
+    }
+
+    #[test]
+    fn test_lift_requires_selection() {
+        let mut pixels = layer_pixels(4, 4);
+        let result = FloatingSelection::lift(Uuid::new_v4(), &mut pixels, 4, 4, &Selection::new());
+        assert!(result.is_err());
+    }
+}
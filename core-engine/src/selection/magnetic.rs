@@ -0,0 +1,352 @@
+//! Magnetic lasso
+//!
+//! The magnetic lasso traces a live wire between anchor points: the cheapest
+//! 8-connected path through a cost map that is low on strong edges, so the
+//! outline clings to object boundaries. Edge strength is the Sobel gradient
+//! magnitude used by [`FindEdges`](crate::filters::stylize::FindEdges),
+//! taken over premultiplied color so transparency edges count too.
+//!
+//! The lasso is driven incrementally: add anchors as the user clicks, ask
+//! for a preview path to the cursor while it moves, then close the outline
+//! back to the first anchor to get the lasso polygon.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use crate::error::{EngineError, EngineResult};
+
+/// Extra room around the anchors for the live wire to detour, in pixels
+const SEARCH_MARGIN: u32 = 32;
+
+/// Cost of crossing a pixel with no edge at all; edge pixels approach zero
+const FLAT_COST: f32 = 1.0;
+
+/// Cost floor so paths along edges still prefer being short
+const MIN_COST: f32 = 0.02;
+
+/// Edges weaker than this (relative to the strongest) are not snapped to
+const SNAP_THRESHOLD: f32 = 0.1;
+
+const NEIGHBORS_8: [(i64, i64); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+/// Live-wire lasso state for one outline
+#[derive(Debug, Clone)]
+pub struct MagneticLasso {
+    width: u32,
+    height: u32,
+    /// Edge strength per pixel, normalized to 0-1
+    edges: Vec<f32>,
+    /// Pixels anchors and the cursor snap across to reach the strongest edge
+    pub snap_radius: u32,
+    /// Anchor pixels in the order they were placed
+    anchors: Vec<(u32, u32)>,
+    /// Traced path from each anchor to the next, endpoints included
+    segments: Vec<Vec<(u32, u32)>>,
+}
+
+impl MagneticLasso {
+    /// Default snap radius in pixels
+    pub const DEFAULT_SNAP_RADIUS: u32 = 5;
+
+    /// Prepare a lasso over RGBA pixels
+    pub fn new(pixels: &[u8], width: u32, height: u32) -> EngineResult<Self> {
+        if width == 0 || height == 0 || pixels.len() < (width * height * 4) as usize {
+            return Err(EngineError::InvalidOperation("Invalid pixel data".to_string()));
+        }
+
+        Ok(Self {
+            width,
+            height,
+            edges: edge_strength(pixels, width, height),
+            snap_radius: Self::DEFAULT_SNAP_RADIUS,
+            anchors: Vec::new(),
+            segments: Vec::new(),
+        })
+    }
+
+    /// Place an anchor near (x, y), tracing the edge from the previous one
+    pub fn add_anchor(&mut self, x: f32, y: f32) {
+        let anchor = self.snap(x, y);
+        if let Some(&last) = self.anchors.last() {
+            if last == anchor {
+                return;
+            }
+            self.segments.push(self.live_wire(last, anchor));
+        }
+        self.anchors.push(anchor);
+    }
+
+    /// Remove the most recent anchor and the path leading to it
+    ///
+    /// Returns false when there was no anchor to remove.
+    pub fn remove_last_anchor(&mut self) -> bool {
+        if self.anchors.pop().is_none() {
+            return false;
+        }
+        self.segments.truncate(self.anchors.len().saturating_sub(1));
+        true
+    }
+
+    /// Number of anchors placed
+    pub fn anchor_count(&self) -> usize {
+        self.anchors.len()
+    }
+
+    /// Anchor positions at pixel centers
+    pub fn anchors(&self) -> Vec<(f32, f32)> {
+        self.anchors.iter().map(|&p| pixel_center(p)).collect()
+    }
+
+    /// The path traced through all placed anchors
+    pub fn path(&self) -> Vec<(f32, f32)> {
+        self.join(self.segments.iter().map(Vec::as_slice))
+    }
+
+    /// The placed path extended along the live wire to the cursor
+    pub fn preview(&self, x: f32, y: f32) -> Vec<(f32, f32)> {
+        let Some(&last) = self.anchors.last() else {
+            return Vec::new();
+        };
+        let wire = self.live_wire(last, self.snap(x, y));
+        self.join(self.segments.iter().map(Vec::as_slice).chain([wire.as_slice()]))
+    }
+
+    /// Close the outline back to the first anchor, returning the polygon
+    pub fn close(&self) -> EngineResult<Vec<(f32, f32)>> {
+        if self.anchors.len() < 2 {
+            return Err(EngineError::InvalidOperation(
+                "Magnetic lasso needs at least two anchors".to_string(),
+            ));
+        }
+
+        let closing = self.live_wire(self.anchors[self.anchors.len() - 1], self.anchors[0]);
+        let segments = self.segments.iter().map(Vec::as_slice);
+        let mut polygon = self.join(segments.chain([closing.as_slice()]));
+        // The closing path ends back on the first point
+        polygon.pop();
+        Ok(polygon)
+    }
+
+    /// Concatenate segments, dropping the point each shares with the previous
+    fn join<'a>(&self, segments: impl Iterator<Item = &'a [(u32, u32)]>) -> Vec<(f32, f32)> {
+        let mut points: Vec<(f32, f32)> =
+            self.anchors.first().map(|&p| pixel_center(p)).into_iter().collect();
+        for segment in segments {
+            points.extend(segment.iter().skip(1).map(|&p| pixel_center(p)));
+        }
+        points
+    }
+
+    /// Move a point to the strongest nearby edge pixel
+    fn snap(&self, x: f32, y: f32) -> (u32, u32) {
+        let clamp = |v: f32, size: u32| (v.max(0.0) as u32).min(size - 1);
+        let (cx, cy) = (clamp(x, self.width), clamp(y, self.height));
+        let radius = self.snap_radius as i64;
+
+        let mut best = (cx, cy);
+        let mut best_score = SNAP_THRESHOLD;
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                let (nx, ny) = (cx as i64 + dx, cy as i64 + dy);
+                let distance_sq = dx * dx + dy * dy;
+                if nx < 0 || ny < 0 || nx >= self.width as i64 || ny >= self.height as i64
+                    || distance_sq > radius * radius
+                {
+                    continue;
+                }
+                // Prefer the nearer of two similar edges
+                let strength = self.edges[(ny * self.width as i64 + nx) as usize];
+                let score = strength - distance_sq as f32 * 1e-4;
+                if score > best_score {
+                    best_score = score;
+                    best = (nx as u32, ny as u32);
+                }
+            }
+        }
+        best
+    }
+
+    /// Cheapest path from `start` to `end`, both endpoints included
+    ///
+    /// Dijkstra's search is confined to the box around both points plus a
+    /// margin, which keeps previews interactive on large canvases.
+    fn live_wire(&self, start: (u32, u32), end: (u32, u32)) -> Vec<(u32, u32)> {
+        let left = start.0.min(end.0).saturating_sub(SEARCH_MARGIN);
+        let top = start.1.min(end.1).saturating_sub(SEARCH_MARGIN);
+        let right = (start.0.max(end.0) + SEARCH_MARGIN).min(self.width - 1);
+        let bottom = (start.1.max(end.1) + SEARCH_MARGIN).min(self.height - 1);
+        let box_width = (right - left + 1) as usize;
+        let box_height = (bottom - top + 1) as usize;
+        let local = |(x, y): (u32, u32)| (y - top) as usize * box_width + (x - left) as usize;
+
+        let mut distance = vec![f32::INFINITY; box_width * box_height];
+        let mut previous = vec![usize::MAX; box_width * box_height];
+        let (source, target) = (local(start), local(end));
+        distance[source] = 0.0;
+
+        // Non-negative floats order the same as their bit patterns
+        let mut queue = BinaryHeap::new();
+        queue.push(Reverse((0f32.to_bits(), source)));
+        while let Some(Reverse((bits, i))) = queue.pop() {
+            if i == target {
+                break;
+            }
+            if f32::from_bits(bits) > distance[i] {
+                continue;
+            }
+
+            let (x, y) = ((i % box_width) as i64, (i / box_width) as i64);
+            for (dx, dy) in NEIGHBORS_8 {
+                let (nx, ny) = (x + dx, y + dy);
+                if nx < 0 || ny < 0 || nx >= box_width as i64 || ny >= box_height as i64 {
+                    continue;
+                }
+                let n = ny as usize * box_width + nx as usize;
+                let pixel = (ny as u32 + top) * self.width + nx as u32 + left;
+                let step = if dx != 0 && dy != 0 { std::f32::consts::SQRT_2 } else { 1.0 };
+                let cost = distance[i] + step * self.pixel_cost(pixel as usize);
+                if cost < distance[n] {
+                    distance[n] = cost;
+                    previous[n] = i;
+                    queue.push(Reverse((cost.to_bits(), n)));
+                }
+            }
+        }
+
+        let mut path = vec![end];
+        let mut i = target;
+        while i != source {
+            i = previous[i];
+            path.push(((i % box_width) as u32 + left, (i / box_width) as u32 + top));
+        }
+        path.reverse();
+        path
+    }
+
+    fn pixel_cost(&self, index: usize) -> f32 {
+        (FLAT_COST * (1.0 - self.edges[index])).max(MIN_COST)
+    }
+}
+
+/// Sobel gradient magnitude of premultiplied RGBA, normalized to 0-1
+fn edge_strength(pixels: &[u8], width: u32, height: u32) -> Vec<f32> {
+    let (w, h) = (width as i64, height as i64);
+    let premultiplied: Vec<[f32; 4]> = pixels
+        .chunks_exact(4)
+        .take((width * height) as usize)
+        .map(|p| {
+            let alpha = p[3] as f32 / 255.0;
+            [p[0] as f32 * alpha, p[1] as f32 * alpha, p[2] as f32 * alpha, p[3] as f32]
+        })
+        .collect();
+    // Edge pixels repeat their border neighbor
+    let at = |x: i64, y: i64| {
+        &premultiplied[(y.clamp(0, h - 1) * w + x.clamp(0, w - 1)) as usize]
+    };
+
+    let mut edges = vec![0.0f32; premultiplied.len()];
+    for y in 0..h {
+        for x in 0..w {
+            let mut magnitude = 0.0f32;
+            for c in 0..4 {
+                let gx = -at(x - 1, y - 1)[c] + at(x + 1, y - 1)[c] - 2.0 * at(x - 1, y)[c]
+                    + 2.0 * at(x + 1, y)[c]
+                    - at(x - 1, y + 1)[c]
+                    + at(x + 1, y + 1)[c];
+                let gy = -at(x - 1, y - 1)[c] - 2.0 * at(x, y - 1)[c] - at(x + 1, y - 1)[c]
+                    + at(x - 1, y + 1)[c]
+                    + 2.0 * at(x, y + 1)[c]
+                    + at(x + 1, y + 1)[c];
+                magnitude = magnitude.max((gx * gx + gy * gy).sqrt());
+            }
+            edges[(y * w + x) as usize] = magnitude;
+        }
+    }
+
+    let max = edges.iter().copied().fold(0.0f32, f32::max);
+    if max > 0.0 {
+        edges.iter_mut().for_each(|e| *e /= max);
+    }
+    edges
+}
+
+fn pixel_center((x, y): (u32, u32)) -> (f32, f32) {
+    (x as f32 + 0.5, y as f32 + 0.5)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 40x40 white image with a black disc of radius 12 at the center
+    fn disc() -> Vec<u8> {
+        let mut pixels = vec![255u8; 40 * 40 * 4];
+        for y in 0..40 {
+            for x in 0..40 {
+                let (dx, dy) = (x as f32 - 19.5, y as f32 - 19.5);
+                if dx * dx + dy * dy <= 144.0 {
+                    let i = (y * 40 + x) * 4;
+                    pixels[i..i + 3].copy_from_slice(&[0, 0, 0]);
+                }
+            }
+        }
+        pixels
+    }
+
+    fn distance_from_center((x, y): (f32, f32)) -> f32 {
+        ((x - 20.0).powi(2) + (y - 20.0).powi(2)).sqrt()
+    }
+
+    #[test]
+    fn test_anchors_snap_to_edges() {
+        let mut lasso = MagneticLasso::new(&disc(), 40, 40).unwrap();
+        lasso.add_anchor(20.0, 5.0);
+        let anchor = lasso.anchors()[0];
+        assert!((distance_from_center(anchor) - 12.0).abs() <= 1.5);
+    }
+
+    #[test]
+    fn test_live_wire_follows_edge() {
+        let mut lasso = MagneticLasso::new(&disc(), 40, 40).unwrap();
+        lasso.add_anchor(20.0, 8.0);
+        lasso.add_anchor(32.0, 20.0);
+        lasso.add_anchor(20.0, 32.0);
+
+        // A straight line between anchors would cut well inside the disc
+        let path = lasso.preview(8.0, 20.0);
+        assert!(path.len() > 20);
+        for point in &path {
+            assert!((distance_from_center(*point) - 12.0).abs() <= 1.5, "{:?}", point);
+        }
+
+        let polygon = lasso.close().unwrap();
+        assert!(polygon.len() > path.len());
+        assert_ne!(polygon.first(), polygon.last());
+    }
+
+    #[test]
+    fn test_remove_last_anchor() {
+        let mut lasso = MagneticLasso::new(&disc(), 40, 40).unwrap();
+        lasso.add_anchor(20.0, 8.0);
+        lasso.add_anchor(32.0, 20.0);
+        let traced = lasso.path().len();
+        lasso.add_anchor(20.0, 32.0);
+
+        assert!(lasso.remove_last_anchor());
+        assert_eq!(lasso.anchor_count(), 2);
+        assert_eq!(lasso.path().len(), traced);
+        assert!(lasso.close().is_ok());
+
+        assert!(lasso.remove_last_anchor());
+        assert!(lasso.close().is_err());
+    }
+}
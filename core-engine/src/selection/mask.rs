@@ -0,0 +1,417 @@
+//! Selection coverage masks
+//!
+//! Every selection is stored as an 8-bit coverage mask at canvas resolution:
+//! 0 is unselected, 255 fully selected and values in between partially
+//! selected. Shapes are rasterized with anti-aliasing, so their edges carry
+//! fractional coverage that painting, filters and adjustments honor.
+
+use super::SelectionMode;
+use crate::error::{EngineError, EngineResult};
+use crate::geometry::Transform;
+use crate::layer::LayerMask;
+use crate::transform::{self, ImageData, Interpolation};
+
+use serde::{Deserialize, Serialize};
+
+/// Sub-scanlines per pixel row when rasterizing polygons
+const POLYGON_SUBSAMPLES: u32 = 4;
+
+/// 8-bit selection coverage at canvas resolution
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SelectionMask {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+}
+
+impl SelectionMask {
+    /// Create an empty (fully unselected) mask
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            data: vec![0; (width * height) as usize],
+        }
+    }
+
+    /// Create a fully selected mask
+    pub fn full(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            data: vec![255; (width * height) as usize],
+        }
+    }
+
+    /// Create a mask from raw coverage values (one byte per pixel)
+    pub fn from_data(width: u32, height: u32, data: Vec<u8>) -> EngineResult<Self> {
+        if data.len() != (width * height) as usize {
+            return Err(EngineError::InvalidOperation(format!(
+                "Mask data has {} bytes, expected {}x{}",
+                data.len(),
+                width,
+                height
+            )));
+        }
+        Ok(Self { width, height, data })
+    }
+
+    /// Create a mask from the alpha channel of RGBA pixels
+    pub fn from_alpha(width: u32, height: u32, pixels: &[u8]) -> EngineResult<Self> {
+        let data: Vec<u8> = pixels.chunks_exact(4).map(|p| p[3]).collect();
+        Self::from_data(width, height, data)
+    }
+
+    /// Create a mask from a layer mask, honoring its inversion and density
+    pub fn from_layer_mask(mask: &LayerMask) -> Self {
+        let mut selection = Self::new(mask.width, mask.height);
+        for y in 0..mask.height {
+            for x in 0..mask.width {
+                selection.set(x, y, to_coverage(mask.get(x, y)));
+            }
+        }
+        selection
+    }
+
+    /// Convert to a grayscale layer mask where white is selected
+    pub fn to_layer_mask(&self) -> LayerMask {
+        LayerMask::from_u8(self.width, self.height, &self.data)
+    }
+
+    /// Rasterize an anti-aliased rectangle
+    pub fn rectangle(width: u32, height: u32, x: f32, y: f32, rect_width: f32, rect_height: f32) -> Self {
+        let mut mask = Self::new(width, height);
+        let (x1, y1) = (x + rect_width, y + rect_height);
+
+        let (px0, px1) = pixel_span(x, x1, width);
+        let (py0, py1) = pixel_span(y, y1, height);
+        for py in py0..py1 {
+            let cov_y = overlap(py as f32, y, y1);
+            for px in px0..px1 {
+                let cov = overlap(px as f32, x, x1) * cov_y;
+                mask.data[(py * width + px) as usize] = to_coverage(cov);
+            }
+        }
+        mask
+    }
+
+    /// Rasterize an anti-aliased ellipse inscribed in a rectangle
+    pub fn ellipse(width: u32, height: u32, x: f32, y: f32, rect_width: f32, rect_height: f32) -> Self {
+        let mut mask = Self::new(width, height);
+        let (rx, ry) = (rect_width / 2.0, rect_height / 2.0);
+        if rx <= 0.0 || ry <= 0.0 {
+            return mask;
+        }
+        let (cx, cy) = (x + rx, y + ry);
+
+        let (px0, px1) = pixel_span(x - 1.0, x + rect_width + 1.0, width);
+        let (py0, py1) = pixel_span(y - 1.0, y + rect_height + 1.0, height);
+        for py in py0..py1 {
+            let dy = py as f32 + 0.5 - cy;
+            for px in px0..px1 {
+                let dx = px as f32 + 0.5 - cx;
+
+                // Implicit function divided by its gradient approximates the
+                // signed distance to the outline in pixels
+                let f = (dx / rx).powi(2) + (dy / ry).powi(2) - 1.0;
+                let grad = 2.0 * ((dx / (rx * rx)).powi(2) + (dy / (ry * ry)).powi(2)).sqrt();
+                let distance = if grad > f32::EPSILON { f / grad } else { f32::MIN };
+
+                mask.data[(py * width + px) as usize] = to_coverage(0.5 - distance);
+            }
+        }
+        mask
+    }
+
+    /// Rasterize an anti-aliased polygon (even-odd fill)
+    ///
+    /// Coverage is exact horizontally and sampled on
+    /// [`POLYGON_SUBSAMPLES`] sub-scanlines per row vertically.
+    pub fn polygon(width: u32, height: u32, points: &[(f32, f32)]) -> Self {
+        let mut mask = Self::new(width, height);
+        if points.len() < 3 || width == 0 {
+            return mask;
+        }
+
+        let min_y = points.iter().map(|p| p.1).fold(f32::MAX, f32::min);
+        let max_y = points.iter().map(|p| p.1).fold(f32::MIN, f32::max);
+        let (py0, py1) = pixel_span(min_y, max_y, height);
+
+        let weight = 1.0 / POLYGON_SUBSAMPLES as f32;
+        let mut row = vec![0.0f32; width as usize];
+        let mut crossings = Vec::new();
+
+        for py in py0..py1 {
+            row.iter_mut().for_each(|v| *v = 0.0);
+
+            for sub in 0..POLYGON_SUBSAMPLES {
+                let sy = py as f32 + (sub as f32 + 0.5) * weight;
+                crossings.clear();
+                for (i, &(x0, y0)) in points.iter().enumerate() {
+                    let (x1, y1) = points[(i + 1) % points.len()];
+                    if (y0 <= sy) != (y1 <= sy) {
+                        crossings.push(x0 + (sy - y0) * (x1 - x0) / (y1 - y0));
+                    }
+                }
+                crossings.sort_by(|a, b| a.total_cmp(b));
+
+                for span in crossings.chunks_exact(2) {
+                    accumulate_span(&mut row, span[0], span[1], weight);
+                }
+            }
+
+            let offset = (py * width) as usize;
+            for (dst, &cov) in mask.data[offset..offset + width as usize].iter_mut().zip(&row) {
+                *dst = to_coverage(cov);
+            }
+        }
+        mask
+    }
+
+    /// Mask width
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Mask height
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Raw coverage values, row-major
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Mutable raw coverage values, row-major
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    /// Coverage at a pixel (0 outside the mask)
+    pub fn get(&self, x: u32, y: u32) -> u8 {
+        if x >= self.width || y >= self.height {
+            return 0;
+        }
+        self.data[(y * self.width + x) as usize]
+    }
+
+    /// Set coverage at a pixel
+    pub fn set(&mut self, x: u32, y: u32, value: u8) {
+        if x < self.width && y < self.height {
+            self.data[(y * self.width + x) as usize] = value;
+        }
+    }
+
+    /// Coverage at a pixel as 0.0..=1.0
+    pub fn coverage(&self, x: u32, y: u32) -> f32 {
+        self.get(x, y) as f32 / 255.0
+    }
+
+    /// Check whether no pixel is selected
+    pub fn is_empty(&self) -> bool {
+        self.data.iter().all(|&v| v == 0)
+    }
+
+    /// Bounding box of all partially or fully selected pixels (x, y, width, height)
+    pub fn bounds(&self) -> Option<(u32, u32, u32, u32)> {
+        let mut min = (self.width, self.height);
+        let mut max = (0u32, 0u32);
+        let mut found = false;
+
+        for (i, _) in self.data.iter().enumerate().filter(|(_, &v)| v > 0) {
+            let (x, y) = (i as u32 % self.width, i as u32 / self.width);
+            min = (min.0.min(x), min.1.min(y));
+            max = (max.0.max(x), max.1.max(y));
+            found = true;
+        }
+
+        found.then(|| (min.0, min.1, max.0 - min.0 + 1, max.1 - min.1 + 1))
+    }
+
+    /// Combine another mask into this one
+    ///
+    /// Add takes the larger coverage, Intersect the smaller, and Subtract
+    /// scales this mask by the other's uncovered fraction. Pixels outside the
+    /// other mask count as unselected.
+    pub fn combine(&mut self, other: &SelectionMask, mode: SelectionMode) {
+        let width = self.width;
+        for (i, value) in self.data.iter_mut().enumerate() {
+            let b = other.get(i as u32 % width, i as u32 / width);
+            *value = match mode {
+                SelectionMode::Replace => b,
+                SelectionMode::Add => (*value).max(b),
+                SelectionMode::Subtract => ((*value as u32 * (255 - b as u32) + 127) / 255) as u8,
+                SelectionMode::Intersect => (*value).min(b),
+            };
+        }
+    }
+
+    /// Invert coverage
+    pub fn invert(&mut self) {
+        self.data.iter_mut().for_each(|v| *v = 255 - *v);
+    }
+
+    /// Copy into a mask of another size, anchored at the top-left corner
+    pub fn resized(&self, width: u32, height: u32) -> Self {
+        let mut mask = Self::new(width, height);
+        let copy_width = self.width.min(width) as usize;
+        for y in 0..self.height.min(height) {
+            let src = (y * self.width) as usize;
+            let dst = (y * width) as usize;
+            mask.data[dst..dst + copy_width].copy_from_slice(&self.data[src..src + copy_width]);
+        }
+        mask
+    }
+
+    /// Resample through an affine transform into a `width` x `height` mask
+    ///
+    /// `transform` maps this mask's pixel coordinates to the new mask's.
+    pub fn warped(
+        &self,
+        transform: &Transform,
+        width: u32,
+        height: u32,
+        interpolation: Interpolation,
+    ) -> EngineResult<Self> {
+        let pixels = self.data.iter().flat_map(|&v| [255, 255, 255, v]).collect();
+        let image = ImageData::from_pixels(pixels, self.width, self.height)?;
+        let warped = transform::warp_affine(&image, transform, width, height, interpolation)?;
+        Self::from_alpha(width, height, &warped.pixels)
+    }
+
+    /// Blend edited RGBA pixels with the originals by coverage
+    ///
+    /// Fully selected pixels keep the edit, unselected ones revert to
+    /// `original` and partially selected ones are mixed. Both buffers are
+    /// `width` pixels wide and positioned at the canvas origin.
+    pub fn apply_to_pixels(&self, original: &[u8], edited: &mut [u8], width: u32) {
+        for (i, (out, orig)) in edited.chunks_exact_mut(4).zip(original.chunks_exact(4)).enumerate() {
+            let coverage = self.get(i as u32 % width, i as u32 / width);
+            blend_by_coverage(orig, out, coverage);
+        }
+    }
+}
+
+/// Mix an edited RGBA pixel back toward its original by selection coverage
+pub(crate) fn blend_by_coverage(original: &[u8], edited: &mut [u8], coverage: u8) {
+    match coverage {
+        255 => {}
+        0 => edited.copy_from_slice(original),
+        c => {
+            let c = c as u32;
+            for (out, &orig) in edited.iter_mut().zip(original) {
+                *out = ((*out as u32 * c + orig as u32 * (255 - c) + 127) / 255) as u8;
+            }
+        }
+    }
+}
+
+/// Fraction of the pixel [p, p + 1) covered by [start, end)
+fn overlap(p: f32, start: f32, end: f32) -> f32 {
+    ((p + 1.0).min(end) - p.max(start)).max(0.0)
+}
+
+/// Pixel range touched by [start, end), clamped to 0..limit
+fn pixel_span(start: f32, end: f32, limit: u32) -> (u32, u32) {
+    let first = start.floor().max(0.0) as u32;
+    let last = (end.ceil().max(0.0) as u32).min(limit);
+    (first.min(last), last)
+}
+
+/// Add the horizontal coverage of [x0, x1) to a row
+fn accumulate_span(row: &mut [f32], x0: f32, x1: f32, weight: f32) {
+    let (first, last) = pixel_span(x0, x1, row.len() as u32);
+    for px in first..last {
+        row[px as usize] += overlap(px as f32, x0, x1) * weight;
+    }
+}
+
+pub(super) fn to_coverage(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rectangle_edges_are_partial() {
+        let mask = SelectionMask::rectangle(10, 10, 2.5, 2.0, 4.0, 3.0);
+        assert_eq!(mask.get(3, 3), 255);
+        assert_eq!(mask.get(2, 3), 128);
+        assert_eq!(mask.get(6, 3), 128);
+        assert_eq!(mask.get(1, 3), 0);
+        assert_eq!(mask.bounds(), Some((2, 2, 5, 3)));
+    }
+
+    #[test]
+    fn test_ellipse_coverage() {
+        let mask = SelectionMask::ellipse(20, 20, 0.0, 0.0, 20.0, 20.0);
+        assert_eq!(mask.get(10, 10), 255);
+        assert_eq!(mask.get(0, 0), 0);
+
+        // Somewhere along the outline coverage is fractional
+        assert!((0..20).any(|x| (1..255).contains(&mask.get(x, 2))));
+    }
+
+    #[test]
+    fn test_polygon_matches_rectangle() {
+        let points = [(2.0, 2.0), (8.0, 2.0), (8.0, 6.0), (2.0, 6.0)];
+        let polygon = SelectionMask::polygon(10, 10, &points);
+        let rect = SelectionMask::rectangle(10, 10, 2.0, 2.0, 6.0, 4.0);
+        assert_eq!(polygon, rect);
+    }
+
+    #[test]
+    fn test_polygon_diagonal_is_antialiased() {
+        let mask = SelectionMask::polygon(10, 10, &[(0.0, 0.0), (10.0, 0.0), (0.0, 10.0)]);
+        assert_eq!(mask.get(1, 1), 255);
+        assert_eq!(mask.get(8, 8), 0);
+        assert!((1..255).contains(&mask.get(4, 5)));
+    }
+
+    #[test]
+    fn test_combine_across_shapes() {
+        let mut mask = SelectionMask::rectangle(20, 20, 0.0, 0.0, 10.0, 20.0);
+        let ellipse = SelectionMask::ellipse(20, 20, 5.0, 5.0, 10.0, 10.0);
+
+        let mut union = mask.clone();
+        union.combine(&ellipse, SelectionMode::Add);
+        assert_eq!(union.get(12, 10), 255);
+
+        let mut intersection = mask.clone();
+        intersection.combine(&ellipse, SelectionMode::Intersect);
+        assert_eq!(intersection.get(2, 10), 0);
+        assert_eq!(intersection.get(8, 10), 255);
+
+        mask.combine(&ellipse, SelectionMode::Subtract);
+        assert_eq!(mask.get(8, 10), 0);
+        assert_eq!(mask.get(2, 10), 255);
+    }
+
+    #[test]
+    fn test_warped_moves_coverage() {
+        let mask = SelectionMask::rectangle(10, 10, 0.0, 0.0, 4.0, 4.0);
+        let moved = mask
+            .warped(&Transform::translation(5.0, 5.0), 10, 10, Interpolation::Bilinear)
+            .unwrap();
+        assert_eq!(moved.bounds(), Some((5, 5, 4, 4)));
+        assert_eq!(moved.get(6, 6), 255);
+    }
+
+    #[test]
+    fn test_apply_to_pixels_blends_partial_coverage() {
+        let mut mask = SelectionMask::new(3, 1);
+        mask.set(0, 0, 255);
+        mask.set(1, 0, 128);
+
+        let original = [0u8; 12];
+        let mut edited = [200u8; 12];
+        mask.apply_to_pixels(&original, &mut edited, 3);
+
+        assert_eq!(edited[0], 200);
+        assert_eq!(edited[4], 100);
+        assert_eq!(edited[8], 0);
+    }
+}
@@ -1,9 +1,44 @@
 //! Selection system for DrawConnect
 //!
-//! Provides selection tools including rectangle, lasso, and magic wand selection.
+//! Every [`Selection`] is an anti-aliased coverage mask (see
+//! [`SelectionMask`]), so shapes, lassos and color-based selections combine
+//! freely and keep soft edges. The [`SelectionManager`] owns the active
+//! selection and applies new selections to it with a [`SelectionMode`].
+//!
+//! The submodules build on the mask: modifiers reshape it, outlines trace it
+//! for display, and floating selections, quick mask mode and saved channels
+//! move it between the selection, layer pixels and the document.
+
+mod channel;
+mod color_range;
+mod floating;
+mod magnetic;
+mod mask;
+mod modify;
+mod outline;
+mod quick_mask;
+mod wand;
+
+pub use channel::SelectionChannel;
+pub use color_range::{ColorRange, ColorRangeSpace, ColorRangeTarget};
+pub use floating::{
+    perspective_corners, FloatingSelection, FloatingWarp, FreeTransformMode, FreeTransformOptions,
+    PREVIEW_INTERPOLATION,
+};
+pub use magnetic::MagneticLasso;
+pub use mask::SelectionMask;
+pub use outline::SelectionOutline;
+pub use quick_mask::QuickMask;
+pub use wand::{SampleSource, WandOptions};
+pub(crate) use mask::blend_by_coverage;
+pub(crate) use wand::magic_wand;
 
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 use crate::error::{EngineError, EngineResult};
+use crate::geometry::Transform;
+use crate::transform::Interpolation;
+use glam::Vec2;
 
 /// Selection mode for combining selections
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -24,7 +59,10 @@ impl Default for SelectionMode {
     }
 }
 
-/// Selection shape type
+/// How a selection was created
+///
+/// The coverage mask is the selection itself; the shape is kept so the UI can
+/// draw rectangles, ellipses and lassos precisely.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum SelectionShape {
     /// No selection
@@ -36,26 +74,29 @@ pub enum SelectionShape {
         width: f32,
         height: f32,
     },
+    /// Ellipse selection inscribed in bounds (x, y, width, height)
+    Ellipse {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+    },
     /// Lasso selection with polygon points
     Lasso {
         points: Vec<(f32, f32)>,
     },
-    /// Bitmap mask selection (for magic wand results)
-    Mask {
-        x: u32,
-        y: u32,
-        width: u32,
-        height: u32,
-        data: Vec<u8>,
-    },
+    /// Any other coverage: magic wand results, combined or inverted selections
+    Mask,
 }
 
 /// Selection data structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Selection {
-    /// The shape of the selection
+    /// The shape the selection was created from
     pub shape: SelectionShape,
-    /// Feather radius for soft edges
+    /// Coverage mask at canvas resolution
+    pub mask: SelectionMask,
+    /// Combined feather radius already applied to the mask
     pub feather: f32,
     /// Whether the selection is active
     pub is_active: bool,
@@ -65,6 +106,7 @@ impl Default for Selection {
     fn default() -> Self {
         Self {
             shape: SelectionShape::None,
+            mask: SelectionMask::default(),
             feather: 0.0,
             is_active: false,
         }
@@ -77,120 +119,115 @@ impl Selection {
         Self::default()
     }
 
-    /// Create a rectangle selection
-    pub fn rectangle(x: f32, y: f32, width: f32, height: f32) -> Self {
-        // Normalize negative dimensions
-        let (x, width) = if width < 0.0 {
-            (x + width, -width)
-        } else {
-            (x, width)
-        };
-        let (y, height) = if height < 0.0 {
-            (y + height, -height)
-        } else {
-            (y, height)
-        };
-
-        Self {
-            shape: SelectionShape::Rectangle { x, y, width, height },
-            feather: 0.0,
-            is_active: true,
-        }
-    }
-
-    /// Create a lasso selection from points
-    pub fn lasso(points: Vec<(f32, f32)>) -> Self {
+    /// Create an anti-aliased rectangle selection on a canvas
+    pub fn rectangle(
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        canvas_width: u32,
+        canvas_height: u32,
+    ) -> Self {
+        let (x, y, width, height) = normalize_rect(x, y, width, height);
+        Self::with_shape(
+            SelectionShape::Rectangle { x, y, width, height },
+            SelectionMask::rectangle(canvas_width, canvas_height, x, y, width, height),
+        )
+    }
+
+    /// Create an anti-aliased ellipse selection inscribed in a rectangle
+    pub fn ellipse(
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        canvas_width: u32,
+        canvas_height: u32,
+    ) -> Self {
+        let (x, y, width, height) = normalize_rect(x, y, width, height);
+        Self::with_shape(
+            SelectionShape::Ellipse { x, y, width, height },
+            SelectionMask::ellipse(canvas_width, canvas_height, x, y, width, height),
+        )
+    }
+
+    /// Create an anti-aliased lasso selection from points
+    pub fn lasso(points: Vec<(f32, f32)>, canvas_width: u32, canvas_height: u32) -> Self {
         if points.len() < 3 {
             return Self::default();
         }
+        let mask = SelectionMask::polygon(canvas_width, canvas_height, &points);
+        Self::with_shape(SelectionShape::Lasso { points }, mask)
+    }
+
+    /// Create a selection from a coverage mask
+    pub fn from_mask(mask: SelectionMask) -> Self {
+        Self::with_shape(SelectionShape::Mask, mask)
+    }
+
+    fn with_shape(shape: SelectionShape, mask: SelectionMask) -> Self {
+        let is_active = !mask.is_empty();
         Self {
-            shape: SelectionShape::Lasso { points },
+            shape: if is_active { shape } else { SelectionShape::None },
+            mask,
             feather: 0.0,
-            is_active: true,
+            is_active,
         }
     }
 
     /// Clear the selection
     pub fn clear(&mut self) {
         self.shape = SelectionShape::None;
+        self.mask = SelectionMask::default();
+        self.feather = 0.0;
         self.is_active = false;
     }
 
-    /// Check if a point is inside the selection
+    /// Selection coverage at a pixel as 0.0..=1.0
+    pub fn coverage(&self, px: u32, py: u32) -> f32 {
+        if !self.is_active {
+            return 0.0;
+        }
+        self.mask.coverage(px, py)
+    }
+
+    /// Check if a point is at least half selected
     pub fn contains(&self, px: f32, py: f32) -> bool {
-        match &self.shape {
-            SelectionShape::None => false,
-            SelectionShape::Rectangle { x, y, width, height } => {
-                px >= *x && px < x + width && py >= *y && py < y + height
-            }
-            SelectionShape::Lasso { points } => {
-                Self::point_in_polygon(px, py, points)
-            }
-            SelectionShape::Mask { x, y, width, height, data } => {
-                let ix = px as i32 - *x as i32;
-                let iy = py as i32 - *y as i32;
-                if ix < 0 || iy < 0 || ix >= *width as i32 || iy >= *height as i32 {
-                    return false;
-                }
-                let idx = (iy as usize * *width as usize + ix as usize);
-                idx < data.len() && data[idx] > 127
-            }
+        if !self.is_active || px < 0.0 || py < 0.0 {
+            return false;
         }
+        self.mask.get(px as u32, py as u32) > 127
     }
 
     /// Get the bounding box of the selection (x, y, width, height)
     pub fn bounds(&self) -> Option<(f32, f32, f32, f32)> {
         match &self.shape {
             SelectionShape::None => None,
-            SelectionShape::Rectangle { x, y, width, height } => {
-                Some((*x, *y, *width, *height))
-            }
-            SelectionShape::Lasso { points } => {
-                if points.is_empty() {
-                    return None;
-                }
-                let mut min_x = f32::MAX;
-                let mut min_y = f32::MAX;
-                let mut max_x = f32::MIN;
-                let mut max_y = f32::MIN;
-                for (px, py) in points {
-                    min_x = min_x.min(*px);
-                    min_y = min_y.min(*py);
-                    max_x = max_x.max(*px);
-                    max_y = max_y.max(*py);
+            SelectionShape::Rectangle { x, y, width, height }
+            | SelectionShape::Ellipse { x, y, width, height } => Some((*x, *y, *width, *height)),
+            SelectionShape::Lasso { .. } | SelectionShape::Mask => self
+                .mask
+                .bounds()
+                .map(|(x, y, w, h)| (x as f32, y as f32, w as f32, h as f32)),
+        }
+    }
+
+    /// Combine another selection into this one
+    pub fn combine(&mut self, other: &Selection, mode: SelectionMode) {
+        match mode {
+            SelectionMode::Replace => *self = other.clone(),
+            _ if !self.is_active => {
+                // Only adding to nothing yields a selection
+                if mode == SelectionMode::Add {
+                    *self = other.clone();
                 }
-                Some((min_x, min_y, max_x - min_x, max_y - min_y))
             }
-            SelectionShape::Mask { x, y, width, height, data } => {
-                // Calculate actual bounding box of selected pixels
-                let mut min_x = *width;
-                let mut min_y = *height;
-                let mut max_x = 0u32;
-                let mut max_y = 0u32;
-                let mut has_selection = false;
-
-                for py in 0..*height {
-                    for px in 0..*width {
-                        let idx = (py * *width + px) as usize;
-                        if idx < data.len() && data[idx] > 127 {
-                            has_selection = true;
-                            min_x = min_x.min(px);
-                            min_y = min_y.min(py);
-                            max_x = max_x.max(px);
-                            max_y = max_y.max(py);
-                        }
-                    }
-                }
-
-                if has_selection {
-                    Some((
-                        (*x + min_x) as f32,
-                        (*y + min_y) as f32,
-                        (max_x - min_x + 1) as f32,
-                        (max_y - min_y + 1) as f32,
-                    ))
-                } else {
-                    None
+            _ => {
+                self.mask.combine(&other.mask, mode);
+                self.shape = SelectionShape::Mask;
+                self.is_active = !self.mask.is_empty();
+                if !self.is_active {
+                    self.clear();
                 }
             }
         }
@@ -198,119 +235,148 @@ impl Selection {
 
     /// Invert the selection within canvas bounds
     pub fn invert(&mut self, canvas_width: u32, canvas_height: u32) {
-        match &self.shape {
-            SelectionShape::None => {
-                // Select all
-                self.shape = SelectionShape::Rectangle {
-                    x: 0.0,
-                    y: 0.0,
-                    width: canvas_width as f32,
-                    height: canvas_height as f32,
-                };
-                self.is_active = true;
-            }
-            SelectionShape::Rectangle { x, y, width, height } => {
-                // Convert to mask and invert
-                let mut mask = vec![255u8; (canvas_width * canvas_height) as usize];
-                let x1 = (*x as u32).min(canvas_width);
-                let y1 = (*y as u32).min(canvas_height);
-                let x2 = ((x + width) as u32).min(canvas_width);
-                let y2 = ((y + height) as u32).min(canvas_height);
-
-                for py in y1..y2 {
-                    for px in x1..x2 {
-                        mask[(py * canvas_width + px) as usize] = 0;
-                    }
-                }
+        if !self.is_active {
+            *self = Self::from_mask(SelectionMask::full(canvas_width, canvas_height));
+            return;
+        }
 
-                self.shape = SelectionShape::Mask {
-                    x: 0,
-                    y: 0,
-                    width: canvas_width,
-                    height: canvas_height,
-                    data: mask,
-                };
+        let mut mask = self.mask.resized(canvas_width, canvas_height);
+        mask.invert();
+        let feather = self.feather;
+        *self = Self::from_mask(mask);
+        self.feather = feather;
+    }
+
+    /// Fit the mask to a new canvas size, anchored at the top-left corner
+    pub fn set_canvas_size(&mut self, canvas_width: u32, canvas_height: u32) {
+        let size = (self.mask.width(), self.mask.height());
+        if self.is_active && size != (canvas_width, canvas_height) {
+            self.mask = self.mask.resized(canvas_width, canvas_height);
+            self.shape = SelectionShape::Mask;
+            self.is_active = !self.mask.is_empty();
+        }
+    }
+
+    /// Apply an affine transform to the selection shape
+    ///
+    /// Rectangles and ellipses stay exact while the transform keeps them
+    /// axis-aligned, rectangles and lassos are re-rasterized from their
+    /// transformed outline, and everything else, including feathered
+    /// selections, resamples the mask with `interpolation`.
+    pub fn transform(&mut self, transform: &Transform, interpolation: Interpolation) -> EngineResult<()> {
+        if !self.is_active {
+            return Ok(());
+        }
+        let (width, height) = (self.mask.width(), self.mask.height());
+        let m = transform.matrix;
+        let axis_aligned = m[1] == 0.0 && m[2] == 0.0;
+        let map = |x: f32, y: f32| {
+            let p = transform.transform_point(Vec2::new(x, y));
+            (p.x, p.y)
+        };
+
+        let transformed = match &self.shape {
+            _ if self.feather > 0.0 => None,
+            SelectionShape::Rectangle { x, y, width: w, height: h } if axis_aligned => {
+                let (x0, y0) = map(*x, *y);
+                let (x1, y1) = map(x + w, y + h);
+                Some(Self::rectangle(x0, y0, x1 - x0, y1 - y0, width, height))
+            }
+            SelectionShape::Ellipse { x, y, width: w, height: h } if axis_aligned => {
+                let (x0, y0) = map(*x, *y);
+                let (x1, y1) = map(x + w, y + h);
+                Some(Self::ellipse(x0, y0, x1 - x0, y1 - y0, width, height))
+            }
+            SelectionShape::Rectangle { x, y, width: w, height: h } => {
+                let corners = [(*x, *y), (x + w, *y), (x + w, y + h), (*x, y + h)];
+                Some(Self::lasso(corners.map(|(px, py)| map(px, py)).to_vec(), width, height))
             }
             SelectionShape::Lasso { points } => {
-                // Convert to mask and invert
-                let mut mask = vec![255u8; (canvas_width * canvas_height) as usize];
-                for py in 0..canvas_height {
-                    for px in 0..canvas_width {
-                        if Self::point_in_polygon(px as f32, py as f32, points) {
-                            mask[(py * canvas_width + px) as usize] = 0;
-                        }
-                    }
-                }
-                self.shape = SelectionShape::Mask {
-                    x: 0,
-                    y: 0,
-                    width: canvas_width,
-                    height: canvas_height,
-                    data: mask,
-                };
+                let points = points.iter().map(|&(px, py)| map(px, py)).collect();
+                Some(Self::lasso(points, width, height))
             }
-            SelectionShape::Mask { width, height, data, .. } => {
-                let inverted: Vec<u8> = data.iter().map(|v| 255 - v).collect();
-                self.shape = SelectionShape::Mask {
-                    x: 0,
-                    y: 0,
-                    width: *width,
-                    height: *height,
-                    data: inverted,
-                };
+            _ => None,
+        };
+
+        match transformed {
+            Some(selection) => *self = selection,
+            None => {
+                let feather = self.feather;
+                *self = Self::from_mask(self.mask.warped(transform, width, height, interpolation)?);
+                self.feather = feather;
             }
         }
+        Ok(())
     }
 
     /// Expand the selection by given pixels
     pub fn expand(&mut self, pixels: f32) {
-        if let SelectionShape::Rectangle { x, y, width, height } = &mut self.shape {
-            *x -= pixels;
-            *y -= pixels;
-            *width += pixels * 2.0;
-            *height += pixels * 2.0;
-        }
+        self.modify(|mask| mask.grow(pixels));
     }
 
     /// Contract the selection by given pixels
     pub fn contract(&mut self, pixels: f32) {
-        if let SelectionShape::Rectangle { x, y, width, height } = &mut self.shape {
-            *x += pixels;
-            *y += pixels;
-            *width = (*width - pixels * 2.0).max(0.0);
-            *height = (*height - pixels * 2.0).max(0.0);
-        }
+        self.modify(|mask| mask.shrink(pixels));
     }
 
-    /// Set feather radius
-    pub fn set_feather(&mut self, radius: f32) {
-        self.feather = radius.max(0.0);
+    /// Replace the selection with a band of given width around its edge
+    pub fn border(&mut self, width: f32) {
+        self.modify(|mask| mask.border(width));
     }
 
-    /// Check if point is in polygon using ray casting algorithm
-    fn point_in_polygon(px: f32, py: f32, polygon: &[(f32, f32)]) -> bool {
-        let n = polygon.len();
-        if n < 3 {
-            return false;
-        }
-
-        let mut inside = false;
-        let mut j = n - 1;
+    /// Smooth jagged edges and remove specks up to given radius
+    pub fn smooth(&mut self, radius: f32) {
+        self.modify(|mask| mask.smooth(radius));
+    }
 
-        for i in 0..n {
-            let (xi, yi) = polygon[i];
-            let (xj, yj) = polygon[j];
+    /// Feather the selection edge by given radius
+    ///
+    /// Feathering accumulates: two passes of radius r equal one pass of
+    /// radius r * sqrt(2), and `feather` reports the combined radius.
+    pub fn feather(&mut self, radius: f32) {
+        if radius <= 0.0 {
+            return;
+        }
+        self.modify(|mask| mask.feather(radius));
+        if self.is_active {
+            self.feather = (self.feather * self.feather + radius * radius).sqrt();
+        }
+    }
 
-            if ((yi > py) != (yj > py)) && (px < (xj - xi) * (py - yi) / (yj - yi) + xi) {
-                inside = !inside;
-            }
-            j = i;
+    /// Reshape an active mask; the result no longer matches the original shape
+    fn modify(&mut self, operation: impl FnOnce(&mut SelectionMask)) {
+        if !self.is_active {
+            return;
         }
+        operation(&mut self.mask);
+        self.shape = SelectionShape::Mask;
+        self.is_active = !self.mask.is_empty();
+        if !self.is_active {
+            self.clear();
+        }
+    }
 
-        inside
+    /// Blend edited RGBA pixels with the originals by selection coverage
+    ///
+    /// Without an active selection the edit is kept everywhere.
+    pub fn apply_to_pixels(&self, original: &[u8], edited: &mut [u8], width: u32) {
+        if self.is_active {
+            self.mask.apply_to_pixels(original, edited, width);
+        }
     }
 }
 
+fn channel_not_found(id: Uuid) -> EngineError {
+    EngineError::InvalidOperation(format!("Selection channel not found: {}", id))
+}
+
+/// Normalize negative rectangle dimensions
+fn normalize_rect(x: f32, y: f32, width: f32, height: f32) -> (f32, f32, f32, f32) {
+    let (x, width) = if width < 0.0 { (x + width, -width) } else { (x, width) };
+    let (y, height) = if height < 0.0 { (y + height, -height) } else { (y, height) };
+    (x, y, width, height)
+}
+
 /// Selection manager for handling selection operations
 #[derive(Debug)]
 pub struct SelectionManager {
@@ -318,10 +384,16 @@ pub struct SelectionManager {
     current: Selection,
     /// Selection mode for next operation
     mode: SelectionMode,
-    /// Magic wand tolerance
-    tolerance: f32,
-    /// Contiguous selection for magic wand
-    contiguous: bool,
+    /// Magic wand settings
+    wand: WandOptions,
+    /// Editing surface while in quick mask mode
+    quick_mask: Option<QuickMask>,
+    /// Magnetic lasso outline being traced
+    magnetic_lasso: Option<MagneticLasso>,
+    /// Pixels lifted off a layer, pending a transform
+    floating: Option<FloatingSelection>,
+    /// Saved selection channels
+    channels: Vec<SelectionChannel>,
     /// Canvas width for mask operations
     canvas_width: u32,
     /// Canvas height for mask operations
@@ -340,8 +412,11 @@ impl SelectionManager {
         Self {
             current: Selection::new(),
             mode: SelectionMode::Replace,
-            tolerance: 32.0,
-            contiguous: true,
+            wand: WandOptions::default(),
+            quick_mask: None,
+            magnetic_lasso: None,
+            floating: None,
+            channels: Vec::new(),
             canvas_width: 1920,
             canvas_height: 1080,
         }
@@ -351,6 +426,13 @@ impl SelectionManager {
     pub fn set_canvas_size(&mut self, width: u32, height: u32) {
         self.canvas_width = width;
         self.canvas_height = height;
+        self.current.set_canvas_size(width, height);
+        if let Some(quick_mask) = &self.quick_mask {
+            quick_mask.set_canvas_size(width, height);
+        }
+        for channel in &mut self.channels {
+            channel.mask = channel.mask.resized(width, height);
+        }
     }
 
     /// Get the current selection
@@ -380,119 +462,75 @@ impl SelectionManager {
 
     /// Set magic wand tolerance
     pub fn set_tolerance(&mut self, tolerance: f32) {
-        self.tolerance = tolerance.clamp(0.0, 255.0);
+        self.wand.tolerance = tolerance.clamp(0.0, 255.0);
     }
 
     /// Get magic wand tolerance
     pub fn tolerance(&self) -> f32 {
-        self.tolerance
+        self.wand.tolerance
     }
 
     /// Set contiguous mode for magic wand
     pub fn set_contiguous(&mut self, contiguous: bool) {
-        self.contiguous = contiguous;
+        self.wand.contiguous = contiguous;
     }
 
-    /// Convert a selection to a bitmap mask
-    fn selection_to_mask(&self, selection: &Selection, width: u32, height: u32) -> Vec<u8> {
-        let mut mask = vec![0u8; (width * height) as usize];
-
-        match &selection.shape {
-            SelectionShape::None => {},
-            SelectionShape::Rectangle { x, y, width: w, height: h } => {
-                let x1 = (*x as u32).min(width);
-                let y1 = (*y as u32).min(height);
-                let x2 = ((x + w) as u32).min(width);
-                let y2 = ((y + h) as u32).min(height);
-
-                for py in y1..y2 {
-                    for px in x1..x2 {
-                        mask[(py * width + px) as usize] = 255;
-                    }
-                }
-            },
-            SelectionShape::Lasso { points } => {
-                for py in 0..height {
-                    for px in 0..width {
-                        if Selection::point_in_polygon(px as f32, py as f32, points) {
-                            mask[(py * width + px) as usize] = 255;
-                        }
-                    }
-                }
-            },
-            SelectionShape::Mask { x, y, width: mw, height: mh, data } => {
-                for my in 0..*mh {
-                    for mx in 0..*mw {
-                        let tx = *x + mx;
-                        let ty = *y + my;
-                        if tx < width && ty < height {
-                            let src_idx = (my * mw + mx) as usize;
-                            let dst_idx = (ty * width + tx) as usize;
-                            if src_idx < data.len() {
-                                mask[dst_idx] = data[src_idx];
-                            }
-                        }
-                    }
-                }
-            }
-        }
-
-        mask
-    }
-
-    /// Create a selection from a mask
-    fn mask_to_selection(&self, mask: Vec<u8>, width: u32, height: u32) -> Selection {
-        // Check if mask has any selection
-        let has_selection = mask.iter().any(|&v| v > 127);
-        if !has_selection {
-            return Selection::new();
-        }
-
-        Selection {
-            shape: SelectionShape::Mask {
-                x: 0,
-                y: 0,
-                width,
-                height,
-                data: mask,
-            },
-            feather: 0.0,
-            is_active: true,
-        }
+    /// Get magic wand settings
+    pub fn wand_options(&self) -> &WandOptions {
+        &self.wand
     }
 
-    /// Union two masks (OR operation)
-    fn union_masks(&self, a: &[u8], b: &[u8]) -> Vec<u8> {
-        a.iter().zip(b.iter()).map(|(&va, &vb)| va.max(vb)).collect()
-    }
-
-    /// Subtract mask b from a (AND NOT operation)
-    fn subtract_masks(&self, a: &[u8], b: &[u8]) -> Vec<u8> {
-        a.iter().zip(b.iter()).map(|(&va, &vb)| {
-            if vb > 127 { 0 } else { va }
-        }).collect()
-    }
-
-    /// Intersect two masks (AND operation)
-    fn intersect_masks(&self, a: &[u8], b: &[u8]) -> Vec<u8> {
-        a.iter().zip(b.iter()).map(|(&va, &vb)| va.min(vb)).collect()
+    /// Set all magic wand settings
+    pub fn set_wand_options(&mut self, options: WandOptions) {
+        self.wand = WandOptions {
+            tolerance: options.tolerance.clamp(0.0, 255.0),
+            close_gaps: options.close_gaps.max(0.0),
+            ..options
+        };
     }
 
     /// Create a rectangle selection
     pub fn select_rectangle(&mut self, x: f32, y: f32, width: f32, height: f32) {
-        let new_selection = Selection::rectangle(x, y, width, height);
+        let new_selection =
+            Selection::rectangle(x, y, width, height, self.canvas_width, self.canvas_height);
+        self.apply_selection(new_selection);
+    }
+
+    /// Create an ellipse selection inscribed in a rectangle
+    pub fn select_ellipse(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        let new_selection =
+            Selection::ellipse(x, y, width, height, self.canvas_width, self.canvas_height);
         self.apply_selection(new_selection);
     }
 
     /// Create a lasso selection
     pub fn select_lasso(&mut self, points: Vec<(f32, f32)>) {
-        let new_selection = Selection::lasso(points);
+        let new_selection = Selection::lasso(points, self.canvas_width, self.canvas_height);
         self.apply_selection(new_selection);
     }
 
+    /// Combine a coverage mask into the selection using the current mode
+    pub fn select_mask(&mut self, mask: SelectionMask) {
+        let mask = mask.resized(self.canvas_width, self.canvas_height);
+        self.apply_selection(Selection::from_mask(mask));
+    }
+
+    /// Combine a selection using an explicit mode instead of the current one
+    pub fn select_with_mode(&mut self, mut selection: Selection, mode: SelectionMode) {
+        selection.set_canvas_size(self.canvas_width, self.canvas_height);
+        self.current.combine(&selection, mode);
+    }
+
     /// Select all
     pub fn select_all(&mut self, canvas_width: u32, canvas_height: u32) {
-        self.current = Selection::rectangle(0.0, 0.0, canvas_width as f32, canvas_height as f32);
+        self.current = Selection::rectangle(
+            0.0,
+            0.0,
+            canvas_width as f32,
+            canvas_height as f32,
+            canvas_width,
+            canvas_height,
+        );
     }
 
     /// Clear selection
@@ -515,12 +553,25 @@ impl SelectionManager {
         self.current.contract(pixels);
     }
 
-    /// Set feather
-    pub fn set_feather(&mut self, radius: f32) {
-        self.current.set_feather(radius);
+    /// Replace selection with a border of given width
+    pub fn border(&mut self, width: f32) {
+        self.current.border(width);
+    }
+
+    /// Smooth selection
+    pub fn smooth(&mut self, radius: f32) {
+        self.current.smooth(radius);
+    }
+
+    /// Feather selection
+    pub fn feather(&mut self, radius: f32) {
+        self.current.feather(radius);
     }
 
     /// Magic wand selection
+    ///
+    /// `pixels` are the sampled RGBA pixels, which may come from a reference
+    /// layer or the merged image rather than the layer being edited.
     pub fn select_magic_wand(
         &mut self,
         x: u32,
@@ -529,138 +580,282 @@ impl SelectionManager {
         width: u32,
         height: u32,
     ) -> EngineResult<()> {
-        if x >= width || y >= height {
-            return Err(EngineError::InvalidOperation("Point outside canvas".to_string()));
+        let mask = wand::magic_wand(pixels, width, height, x, y, &self.wand)?;
+        self.select_mask(mask);
+        Ok(())
+    }
+
+    /// Select from a layer's transparency, combined with the given mode
+    pub fn select_layer_alpha(
+        &mut self,
+        pixels: &[u8],
+        width: u32,
+        height: u32,
+        mode: SelectionMode,
+    ) -> EngineResult<()> {
+        let mask = SelectionMask::from_alpha(width, height, pixels)?
+            .resized(self.canvas_width, self.canvas_height);
+        self.current.combine(&Selection::from_mask(mask), mode);
+        Ok(())
+    }
+
+    /// Select pixels matching a color range, combined using the current mode
+    pub fn select_color_range(
+        &mut self,
+        range: &ColorRange,
+        pixels: &[u8],
+        width: u32,
+        height: u32,
+    ) -> EngineResult<()> {
+        let mask = range.select(pixels, width, height)?;
+        self.select_mask(mask);
+        Ok(())
+    }
+
+    /// Add every pixel with a color similar to the selected ones
+    ///
+    /// Uses the magic wand tolerance. `pixels` must match the canvas size.
+    pub fn select_similar(&mut self, pixels: &[u8], width: u32, height: u32) -> EngineResult<()> {
+        let tolerance = self.wand.tolerance;
+        self.modify_by_color(width, height, |mask| {
+            color_range::select_similar(mask, pixels, tolerance)
+        })
+    }
+
+    /// Add similarly colored pixels adjacent to the selection
+    ///
+    /// Uses the magic wand tolerance. `pixels` must match the canvas size.
+    pub fn grow(&mut self, pixels: &[u8], width: u32, height: u32) -> EngineResult<()> {
+        let tolerance = self.wand.tolerance;
+        self.modify_by_color(width, height, |mask| color_range::grow(mask, pixels, tolerance))
+    }
+
+    fn modify_by_color(
+        &mut self,
+        width: u32,
+        height: u32,
+        operation: impl FnOnce(&mut SelectionMask) -> EngineResult<()>,
+    ) -> EngineResult<()> {
+        if !self.current.is_active {
+            return Err(EngineError::InvalidOperation("No active selection".to_string()));
+        }
+        if (width, height) != (self.canvas_width, self.canvas_height) {
+            return Err(EngineError::InvalidOperation(
+                "Pixel data does not match canvas size".to_string(),
+            ));
         }
 
-        let idx = ((y * width + x) * 4) as usize;
-        if idx + 3 >= pixels.len() {
-            return Err(EngineError::InvalidOperation("Invalid pixel data".to_string()));
+        let mut mask = self.current.mask.resized(width, height);
+        operation(&mut mask)?;
+        let feather = self.current.feather;
+        self.current = Selection::from_mask(mask);
+        self.current.feather = feather;
+        Ok(())
+    }
+
+    /// Enter quick mask mode, moving the selection onto an editing surface
+    ///
+    /// The selection is cleared while the mode is on so edits to the surface
+    /// are not clipped by it.
+    pub fn enter_quick_mask(&mut self) {
+        if self.quick_mask.is_none() {
+            let quick_mask =
+                QuickMask::from_selection(&self.current, self.canvas_width, self.canvas_height);
+            self.quick_mask = Some(quick_mask);
+            self.current.clear();
         }
+    }
 
-        let target_r = pixels[idx] as i32;
-        let target_g = pixels[idx + 1] as i32;
-        let target_b = pixels[idx + 2] as i32;
-        let tolerance = self.tolerance as i32;
+    /// Leave quick mask mode, turning the edited surface back into the selection
+    pub fn exit_quick_mask(&mut self) {
+        if let Some(quick_mask) = self.quick_mask.take() {
+            self.current = quick_mask.to_selection();
+        }
+    }
 
-        let mut mask = vec![0u8; (width * height) as usize];
+    /// Check whether quick mask mode is on
+    pub fn is_quick_mask(&self) -> bool {
+        self.quick_mask.is_some()
+    }
 
-        if self.contiguous {
-            // Flood fill algorithm for contiguous selection
-            let mut stack = vec![(x, y)];
-            let mut visited = vec![false; (width * height) as usize];
+    /// Get the quick mask while the mode is on
+    pub fn quick_mask(&self) -> Option<&QuickMask> {
+        self.quick_mask.as_ref()
+    }
 
-            while let Some((px, py)) = stack.pop() {
-                let pidx = (py * width + px) as usize;
-                if visited[pidx] {
-                    continue;
-                }
-                visited[pidx] = true;
+    /// Get the quick mask mutably while the mode is on
+    pub fn quick_mask_mut(&mut self) -> Option<&mut QuickMask> {
+        self.quick_mask.as_mut()
+    }
 
-                let pixel_idx = pidx * 4;
-                if pixel_idx + 3 >= pixels.len() {
-                    continue;
-                }
+    /// Start tracing a magnetic lasso over RGBA pixels
+    ///
+    /// Any outline already in progress is discarded.
+    pub fn begin_magnetic_lasso(
+        &mut self,
+        pixels: &[u8],
+        width: u32,
+        height: u32,
+    ) -> EngineResult<()> {
+        self.magnetic_lasso = Some(MagneticLasso::new(pixels, width, height)?);
+        Ok(())
+    }
 
-                let r = pixels[pixel_idx] as i32;
-                let g = pixels[pixel_idx + 1] as i32;
-                let b = pixels[pixel_idx + 2] as i32;
-
-                let diff = (r - target_r).abs() + (g - target_g).abs() + (b - target_b).abs();
-                if diff <= tolerance * 3 {
-                    mask[pidx] = 255;
-
-                    // Add neighbors
-                    if px > 0 {
-                        stack.push((px - 1, py));
-                    }
-                    if px < width - 1 {
-                        stack.push((px + 1, py));
-                    }
-                    if py > 0 {
-                        stack.push((px, py - 1));
-                    }
-                    if py < height - 1 {
-                        stack.push((px, py + 1));
-                    }
-                }
-            }
-        } else {
-            // Non-contiguous: select all matching pixels
-            for py in 0..height {
-                for px in 0..width {
-                    let pidx = ((py * width + px) * 4) as usize;
-                    if pidx + 3 >= pixels.len() {
-                        continue;
-                    }
-
-                    let r = pixels[pidx] as i32;
-                    let g = pixels[pidx + 1] as i32;
-                    let b = pixels[pidx + 2] as i32;
-
-                    let diff = (r - target_r).abs() + (g - target_g).abs() + (b - target_b).abs();
-                    if diff <= tolerance * 3 {
-                        mask[(py * width + px) as usize] = 255;
-                    }
-                }
-            }
+    /// Get the magnetic lasso while an outline is being traced
+    pub fn magnetic_lasso(&self) -> Option<&MagneticLasso> {
+        self.magnetic_lasso.as_ref()
+    }
+
+    /// Get the magnetic lasso mutably while an outline is being traced
+    pub fn magnetic_lasso_mut(&mut self) -> Option<&mut MagneticLasso> {
+        self.magnetic_lasso.as_mut()
+    }
+
+    /// Close the magnetic lasso outline and apply it as a lasso selection
+    pub fn finish_magnetic_lasso(&mut self) -> EngineResult<()> {
+        let lasso = self
+            .magnetic_lasso
+            .as_ref()
+            .ok_or_else(|| {
+                EngineError::InvalidOperation("No magnetic lasso in progress".to_string())
+            })?;
+        let points = lasso.close()?;
+        self.magnetic_lasso = None;
+        self.select_lasso(points);
+        Ok(())
+    }
+
+    /// Abandon the magnetic lasso outline without changing the selection
+    pub fn cancel_magnetic_lasso(&mut self) {
+        self.magnetic_lasso = None;
+    }
+
+    /// Transform the selection shape without touching any pixels
+    pub fn transform_selection(
+        &mut self,
+        transform: &Transform,
+        interpolation: Interpolation,
+    ) -> EngineResult<()> {
+        self.current.transform(transform, interpolation)
+    }
+
+    /// Start transforming lifted pixels as a floating selection
+    ///
+    /// Fails if a floating selection is already pending.
+    pub fn set_floating(&mut self, floating: FloatingSelection) -> EngineResult<()> {
+        if self.floating.is_some() {
+            return Err(EngineError::InvalidOperation(
+                "A floating selection is already pending".to_string(),
+            ));
         }
+        self.floating = Some(floating);
+        Ok(())
+    }
 
-        let new_selection = Selection {
-            shape: SelectionShape::Mask {
-                x: 0,
-                y: 0,
-                width,
-                height,
-                data: mask,
-            },
-            feather: 0.0,
-            is_active: true,
-        };
+    /// Get the pending floating selection
+    pub fn floating(&self) -> Option<&FloatingSelection> {
+        self.floating.as_ref()
+    }
 
-        self.apply_selection(new_selection);
+    /// Get the pending floating selection mutably
+    pub fn floating_mut(&mut self) -> Option<&mut FloatingSelection> {
+        self.floating.as_mut()
+    }
+
+    /// Remove the pending floating selection, leaving the selection as it is
+    pub fn take_floating(&mut self) -> Option<FloatingSelection> {
+        self.floating.take()
+    }
+
+    /// Check whether a floating selection is pending
+    pub fn is_floating(&self) -> bool {
+        self.floating.is_some()
+    }
+
+    /// Save the current selection as a new channel
+    pub fn save_channel(&mut self, name: impl Into<String>) -> EngineResult<Uuid> {
+        if !self.current.is_active {
+            return Err(EngineError::InvalidOperation("No active selection".to_string()));
+        }
+        let mask = self.current.mask.resized(self.canvas_width, self.canvas_height);
+        Ok(self.add_channel(SelectionChannel::new(name, mask)))
+    }
+
+    /// Add a channel, fitting it to the canvas size
+    pub fn add_channel(&mut self, mut channel: SelectionChannel) -> Uuid {
+        channel.mask = channel.mask.resized(self.canvas_width, self.canvas_height);
+        let id = channel.id;
+        self.channels.push(channel);
+        id
+    }
+
+    /// Combine a saved channel into the selection with the given mode
+    pub fn load_channel(&mut self, id: Uuid, mode: SelectionMode) -> EngineResult<()> {
+        let channel = self.channel(id).ok_or_else(|| channel_not_found(id))?;
+        let loaded = Selection::from_mask(channel.mask.clone());
+        self.current.combine(&loaded, mode);
         Ok(())
     }
 
+    /// Remove a saved channel
+    pub fn remove_channel(&mut self, id: Uuid) -> EngineResult<SelectionChannel> {
+        let index = self
+            .channels
+            .iter()
+            .position(|c| c.id == id)
+            .ok_or_else(|| channel_not_found(id))?;
+        Ok(self.channels.remove(index))
+    }
+
+    /// Rename a saved channel
+    pub fn rename_channel(&mut self, id: Uuid, name: impl Into<String>) -> EngineResult<()> {
+        let channel = self
+            .channels
+            .iter_mut()
+            .find(|c| c.id == id)
+            .ok_or_else(|| channel_not_found(id))?;
+        channel.name = name.into();
+        Ok(())
+    }
+
+    /// Get a saved channel
+    pub fn channel(&self, id: Uuid) -> Option<&SelectionChannel> {
+        self.channels.iter().find(|c| c.id == id)
+    }
+
+    /// Get all saved channels in creation order
+    pub fn channels(&self) -> &[SelectionChannel] {
+        &self.channels
+    }
+
+    /// Replace all saved channels, e.g. when opening a document
+    pub fn set_channels(&mut self, channels: Vec<SelectionChannel>) {
+        self.channels.clear();
+        for channel in channels {
+            self.add_channel(channel);
+        }
+    }
+
     /// Apply a new selection based on current mode
     fn apply_selection(&mut self, new_selection: Selection) {
-        match self.mode {
-            SelectionMode::Replace => {
-                self.current = new_selection;
-            }
-            SelectionMode::Add => {
-                if !self.current.is_active {
-                    self.current = new_selection;
-                } else {
-                    // Union: convert both to masks and combine
-                    let mask_a = self.selection_to_mask(&self.current, self.canvas_width, self.canvas_height);
-                    let mask_b = self.selection_to_mask(&new_selection, self.canvas_width, self.canvas_height);
-                    let combined = self.union_masks(&mask_a, &mask_b);
-                    self.current = self.mask_to_selection(combined, self.canvas_width, self.canvas_height);
-                }
-            }
-            SelectionMode::Subtract => {
-                if self.current.is_active {
-                    // Subtract: remove new selection from current
-                    let mask_a = self.selection_to_mask(&self.current, self.canvas_width, self.canvas_height);
-                    let mask_b = self.selection_to_mask(&new_selection, self.canvas_width, self.canvas_height);
-                    let combined = self.subtract_masks(&mask_a, &mask_b);
-                    self.current = self.mask_to_selection(combined, self.canvas_width, self.canvas_height);
-                }
-            }
-            SelectionMode::Intersect => {
-                if !self.current.is_active {
-                    // No current selection, nothing to intersect
-                    self.current = Selection::new();
-                } else {
-                    // Intersect: keep only overlapping area
-                    let mask_a = self.selection_to_mask(&self.current, self.canvas_width, self.canvas_height);
-                    let mask_b = self.selection_to_mask(&new_selection, self.canvas_width, self.canvas_height);
-                    let combined = self.intersect_masks(&mask_a, &mask_b);
-                    self.current = self.mask_to_selection(combined, self.canvas_width, self.canvas_height);
-                }
-            }
+        self.current.combine(&new_selection, self.mode);
+    }
+
+    /// Trace the selection boundary for marching ants at a display zoom
+    ///
+    /// Follows a pending floating selection. Empty when nothing is selected
+    /// or quick mask mode is on.
+    pub fn outline(&self, zoom: f32) -> SelectionOutline {
+        if let Some(floating) = &self.floating {
+            return floating
+                .transformed_mask(PREVIEW_INTERPOLATION)
+                .map(|mask| mask.outline(zoom))
+                .unwrap_or_default();
+        }
+        if !self.current.is_active || self.quick_mask.is_some() {
+            return SelectionOutline::default();
         }
+        self.current.mask.outline(zoom)
     }
 
     /// Get selection info for serialization
@@ -668,8 +863,9 @@ impl SelectionManager {
         let (shape_type, points) = match &self.current.shape {
             SelectionShape::None => ("none".to_string(), None),
             SelectionShape::Rectangle { .. } => ("rectangle".to_string(), None),
+            SelectionShape::Ellipse { .. } => ("ellipse".to_string(), None),
             SelectionShape::Lasso { points } => ("lasso".to_string(), Some(points.clone())),
-            SelectionShape::Mask { .. } => ("mask".to_string(), None),
+            SelectionShape::Mask => ("mask".to_string(), None),
         };
 
         SelectionInfo {
@@ -679,6 +875,8 @@ impl SelectionManager {
             feather: self.current.feather,
             shape_type,
             points,
+            quick_mask: self.quick_mask.is_some(),
+            floating: self.floating.is_some(),
         }
     }
 }
@@ -698,4 +896,8 @@ pub struct SelectionInfo {
     pub shape_type: String,
     /// Points for lasso visualization
     pub points: Option<Vec<(f32, f32)>>,
+    /// Whether quick mask mode is on
+    pub quick_mask: bool,
+    /// Whether a floating selection is pending
+    pub floating: bool,
 }
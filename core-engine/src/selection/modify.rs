@@ -0,0 +1,271 @@
+//! Selection modifiers
+//!
+//! Grow, shrink, border and smooth work on a signed distance field derived
+//! from the coverage mask, so fractional radii are honored and anti-aliased
+//! edges stay anti-aliased. Feathering is a Gaussian blur of the coverage.
+//! All operations treat the area beyond the canvas as a continuation of the
+//! edge pixels, so a selection touching the canvas border keeps touching it.
+
+use super::mask::{to_coverage, SelectionMask};
+use crate::filters::GaussianBlur;
+
+/// Squared distance standing in for "no such pixel on the canvas"
+const FAR: f64 = 1.0e20;
+
+impl SelectionMask {
+    /// Grow the selected area outward by `radius` pixels
+    pub fn grow(&mut self, radius: f32) {
+        if radius > 0.0 {
+            let field = self.signed_distance();
+            self.apply_field(&field, |d| d + radius);
+        }
+    }
+
+    /// Shrink the selected area inward by `radius` pixels
+    pub fn shrink(&mut self, radius: f32) {
+        if radius > 0.0 {
+            let field = self.signed_distance();
+            self.apply_field(&field, |d| d - radius);
+        }
+    }
+
+    /// Replace the selection with a band `width` pixels wide centered on its edge
+    pub fn border(&mut self, width: f32) {
+        if width > 0.0 {
+            let field = self.signed_distance();
+            let half = width / 2.0;
+            self.apply_field(&field, |d| half - d.abs());
+        }
+    }
+
+    /// Round off corners and drop specks and notches narrower than `radius`
+    ///
+    /// A morphological opening removes protrusions, then a closing fills
+    /// gaps of the same size.
+    pub fn smooth(&mut self, radius: f32) {
+        if radius > 0.0 {
+            self.shrink(radius);
+            self.grow(radius);
+            self.grow(radius);
+            self.shrink(radius);
+        }
+    }
+
+    /// Soften the edge with a Gaussian blur
+    ///
+    /// `radius` follows the Gaussian Blur filter: coverage fades over
+    /// roughly that many pixels on each side of the edge.
+    pub fn feather(&mut self, radius: f32) {
+        if radius < 0.5 || self.data().is_empty() {
+            return;
+        }
+
+        let (width, height) = (self.width() as usize, self.height() as usize);
+        let mut values: Vec<f32> = self.data().iter().map(|&v| v as f32 / 255.0).collect();
+        let mut line = Vec::new();
+        let mut blurred = Vec::new();
+
+        for size in GaussianBlur::boxes_for_gauss(radius / 2.0, 3) {
+            let r = ((size - 1) / 2) as usize;
+            for row in values.chunks_exact_mut(width) {
+                line.clear();
+                line.extend_from_slice(row);
+                box_blur_line(&line, &mut blurred, r);
+                row.copy_from_slice(&blurred);
+            }
+            for x in 0..width {
+                line.clear();
+                line.extend((0..height).map(|y| values[y * width + x]));
+                box_blur_line(&line, &mut blurred, r);
+                for (y, &v) in blurred.iter().enumerate() {
+                    values[y * width + x] = v;
+                }
+            }
+        }
+
+        for (out, v) in self.data_mut().iter_mut().zip(values) {
+            *out = to_coverage(v);
+        }
+    }
+
+    /// Signed distance in pixels from each pixel center to the selection edge
+    ///
+    /// Positive inside, negative outside. Partially covered pixels sit on the
+    /// edge, so their coverage gives the sub-pixel offset directly.
+    fn signed_distance(&self) -> Vec<f32> {
+        let data = self.data();
+        let to_unselected = distance_transform(self.width(), self.height(), |i| data[i] < 255);
+        let to_selected = distance_transform(self.width(), self.height(), |i| data[i] > 0);
+
+        data.iter()
+            .enumerate()
+            .map(|(i, &v)| match v {
+                0 => 0.5 - to_selected[i],
+                255 => to_unselected[i] - 0.5,
+                v => v as f32 / 255.0 - 0.5,
+            })
+            .collect()
+    }
+
+    /// Rebuild coverage from a distance field mapped through `offset`
+    fn apply_field(&mut self, field: &[f32], offset: impl Fn(f32) -> f32) {
+        for (out, &d) in self.data_mut().iter_mut().zip(field) {
+            *out = to_coverage(offset(d) + 0.5);
+        }
+    }
+}
+
+/// Euclidean distance from every pixel to the nearest pixel matching `seed`
+///
+/// Two-pass exact transform (Felzenszwalb & Huttenlocher), linear in the
+/// pixel count.
+fn distance_transform(width: u32, height: u32, seed: impl Fn(usize) -> bool) -> Vec<f32> {
+    let (width, height) = (width as usize, height as usize);
+    let mut grid: Vec<f64> = (0..width * height)
+        .map(|i| if seed(i) { 0.0 } else { FAR })
+        .collect();
+
+    let mut line = Vec::new();
+    let mut out = vec![0.0; width.max(height)];
+
+    for x in 0..width {
+        line.clear();
+        line.extend((0..height).map(|y| grid[y * width + x]));
+        squared_distance_1d(&line, &mut out[..height]);
+        for y in 0..height {
+            grid[y * width + x] = out[y];
+        }
+    }
+    for row in grid.chunks_exact_mut(width.max(1)) {
+        line.clear();
+        line.extend_from_slice(row);
+        squared_distance_1d(&line, &mut out[..width]);
+        row.copy_from_slice(&out[..width]);
+    }
+
+    grid.into_iter().map(|d| (d.min(FAR) as f32).sqrt()).collect()
+}
+
+/// 1D squared distance transform: lower envelope of parabolas rooted at `f`
+fn squared_distance_1d(f: &[f64], out: &mut [f64]) {
+    let n = f.len();
+    if n == 0 {
+        return;
+    }
+
+    let mut roots = vec![0usize; n];
+    let mut bounds = vec![0.0f64; n + 1];
+    let mut k = 0;
+    bounds[0] = f64::NEG_INFINITY;
+    bounds[1] = f64::INFINITY;
+
+    for q in 1..n {
+        let mut s = parabola_intersection(f, q, roots[k]);
+        while s <= bounds[k] {
+            k -= 1;
+            s = parabola_intersection(f, q, roots[k]);
+        }
+        k += 1;
+        roots[k] = q;
+        bounds[k] = s;
+        bounds[k + 1] = f64::INFINITY;
+    }
+
+    k = 0;
+    for (q, value) in out.iter_mut().enumerate() {
+        while bounds[k + 1] < q as f64 {
+            k += 1;
+        }
+        let offset = q as f64 - roots[k] as f64;
+        *value = offset * offset + f[roots[k]];
+    }
+}
+
+/// Position where the parabolas rooted at `q` and `p` meet
+fn parabola_intersection(f: &[f64], q: usize, p: usize) -> f64 {
+    let (qf, pf) = (q as f64, p as f64);
+    ((f[q] + qf * qf) - (f[p] + pf * pf)) / (2.0 * (qf - pf))
+}
+
+/// Box blur of one line, averaging only samples that fall inside it
+fn box_blur_line(source: &[f32], target: &mut Vec<f32>, radius: usize) {
+    let n = source.len();
+    let mut prefix = Vec::with_capacity(n + 1);
+    prefix.push(0.0f64);
+    for &v in source {
+        prefix.push(prefix[prefix.len() - 1] + v as f64);
+    }
+
+    target.clear();
+    target.extend((0..n).map(|i| {
+        let lo = i.saturating_sub(radius);
+        let hi = (i + radius + 1).min(n);
+        ((prefix[hi] - prefix[lo]) / (hi - lo) as f64) as f32
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selected_count(mask: &SelectionMask) -> usize {
+        mask.data().iter().filter(|&&v| v > 127).count()
+    }
+
+    #[test]
+    fn test_grow_and_shrink_lasso() {
+        let triangle = [(10.0, 10.0), (30.0, 10.0), (10.0, 30.0)];
+        let original = SelectionMask::polygon(40, 40, &triangle);
+
+        let mut grown = original.clone();
+        grown.grow(3.0);
+        assert_eq!(grown.get(8, 15), 255);
+        assert_eq!(grown.get(5, 15), 0);
+        assert!(selected_count(&grown) > selected_count(&original));
+
+        let mut shrunk = original.clone();
+        shrunk.shrink(3.0);
+        assert_eq!(shrunk.get(11, 15), 0);
+        assert_eq!(shrunk.get(15, 15), 255);
+        assert!(selected_count(&shrunk) < selected_count(&original));
+    }
+
+    #[test]
+    fn test_grow_keeps_fractional_edges() {
+        let mut mask = SelectionMask::rectangle(20, 20, 5.0, 5.0, 10.0, 10.0);
+        mask.grow(1.5);
+        assert_eq!(mask.get(4, 10), 255);
+        assert_eq!(mask.get(3, 10), 128);
+        assert_eq!(mask.get(2, 10), 0);
+    }
+
+    #[test]
+    fn test_border_selects_band_around_edge() {
+        let mut mask = SelectionMask::rectangle(30, 30, 10.0, 10.0, 10.0, 10.0);
+        mask.border(4.0);
+        assert_eq!(mask.get(15, 15), 0);
+        assert_eq!(mask.get(5, 15), 0);
+        assert_eq!(mask.get(9, 15), 255);
+        assert_eq!(mask.get(10, 15), 255);
+    }
+
+    #[test]
+    fn test_smooth_removes_specks() {
+        let mut mask = SelectionMask::rectangle(30, 30, 5.0, 5.0, 20.0, 20.0);
+        mask.set(1, 1, 255);
+        mask.set(15, 15, 0);
+        mask.smooth(2.0);
+        assert_eq!(mask.get(1, 1), 0);
+        assert_eq!(mask.get(15, 15), 255);
+    }
+
+    #[test]
+    fn test_feather_softens_edge_only() {
+        let mut mask = SelectionMask::rectangle(40, 40, 10.0, 0.0, 30.0, 40.0);
+        mask.feather(6.0);
+        assert_eq!(mask.get(39, 20), 255);
+        assert_eq!(mask.get(0, 20), 0);
+        assert!((100..156).contains(&mask.get(10, 20)));
+        assert!(mask.get(8, 20) > 0 && mask.get(12, 20) < 255);
+    }
+}
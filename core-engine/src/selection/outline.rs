@@ -0,0 +1,335 @@
+//! Selection outlines
+//!
+//! Traces the boundary of a coverage mask into closed polylines with
+//! marching squares, so frontends can draw marching ants around any
+//! selection, including wand and painted masks. Crossings are interpolated
+//! at half coverage, so anti-aliased edges give smooth sub-pixel outlines.
+//!
+//! Outlines are traced for a display zoom: when zoomed out the mask is
+//! sampled in blocks of screen-pixel size, and at any zoom the polylines are
+//! simplified to within a quarter of a screen pixel.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::SelectionMask;
+
+/// Coverage at which the boundary is drawn
+const LEVEL: f32 = 127.5;
+
+/// Closed polylines around the selected areas, in canvas coordinates
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SelectionOutline {
+    /// Each contour is closed: its last point connects back to its first
+    pub contours: Vec<Vec<(f32, f32)>>,
+}
+
+impl SelectionOutline {
+    /// Check whether there is nothing to draw
+    pub fn is_empty(&self) -> bool {
+        self.contours.is_empty()
+    }
+
+    /// Format the contours as SVG path data
+    pub fn to_svg_path(&self) -> String {
+        let mut path = String::new();
+        for contour in &self.contours {
+            for (i, (x, y)) in contour.iter().enumerate() {
+                let command = if i == 0 { 'M' } else { 'L' };
+                path.push_str(&format!("{}{} {} ", command, x, y));
+            }
+            path.push('Z');
+        }
+        path
+    }
+}
+
+/// Grid cell edge a contour crosses: column, row and whether it is vertical
+type EdgeKey = (u32, u32, bool);
+
+/// Cell sides, named by the edge of the unit square they lie on
+#[derive(Debug, Clone, Copy)]
+enum Side {
+    Top,
+    Right,
+    Bottom,
+    Left,
+}
+
+impl SelectionMask {
+    /// Trace the selection boundary for display at `zoom` (screen pixels per
+    /// canvas pixel)
+    pub fn outline(&self, zoom: f32) -> SelectionOutline {
+        let Some((bx, by, bw, bh)) = self.bounds() else {
+            return SelectionOutline::default();
+        };
+        let zoom = if zoom > 0.0 { zoom } else { 1.0 };
+        let step = (1.0 / zoom).floor().max(1.0) as u32;
+
+        // Block averages over the selected bounds, ringed with unselected
+        // samples so every contour closes
+        let columns = bw.div_ceil(step) + 2;
+        let rows = bh.div_ceil(step) + 2;
+        let mut samples = vec![0.0f32; (columns * rows) as usize];
+        for j in 1..rows - 1 {
+            for i in 1..columns - 1 {
+                let x0 = bx + (i - 1) * step;
+                let y0 = by + (j - 1) * step;
+                let x1 = (x0 + step).min(bx + bw);
+                let y1 = (y0 + step).min(by + bh);
+                let mut sum = 0u32;
+                for y in y0..y1 {
+                    for x in x0..x1 {
+                        sum += self.get(x, y) as u32;
+                    }
+                }
+                samples[(j * columns + i) as usize] = sum as f32 / ((x1 - x0) * (y1 - y0)) as f32;
+            }
+        }
+
+        let sample = |i: u32, j: u32| samples[(j * columns + i) as usize];
+        let position = |i: f32, j: f32| {
+            (
+                bx as f32 + (i - 0.5) * step as f32,
+                by as f32 + (j - 0.5) * step as f32,
+            )
+        };
+
+        // Oriented segments keyed by the edge they start on
+        let mut next: HashMap<EdgeKey, EdgeKey> = HashMap::new();
+        let mut points: HashMap<EdgeKey, (f32, f32)> = HashMap::new();
+        for j in 0..rows - 1 {
+            for i in 0..columns - 1 {
+                let corners = [
+                    sample(i, j),
+                    sample(i + 1, j),
+                    sample(i + 1, j + 1),
+                    sample(i, j + 1),
+                ];
+                let inside = corners.map(|v| v > LEVEL);
+                let center_inside = corners.iter().sum::<f32>() / 4.0 > LEVEL;
+
+                for (from, to) in cell_segments(inside, center_inside) {
+                    let (start, end) = (edge_key(i, j, from), edge_key(i, j, to));
+                    for (key, side) in [(start, from), (end, to)] {
+                        points.entry(key).or_insert_with(|| {
+                            let (u, v) = crossing(side, &corners);
+                            position(i as f32 + u, j as f32 + v)
+                        });
+                    }
+                    next.insert(start, end);
+                }
+            }
+        }
+
+        let tolerance = 0.25 / zoom;
+        let mut contours = Vec::new();
+        while let Some(&first) = next.keys().next() {
+            let mut contour = Vec::new();
+            let mut key = first;
+            while let Some(following) = next.remove(&key) {
+                contour.push(points[&key]);
+                key = following;
+            }
+            let contour = simplify_closed(&contour, tolerance);
+            if contour.len() >= 3 {
+                contours.push(contour);
+            }
+        }
+
+        SelectionOutline { contours }
+    }
+}
+
+/// Boundary segments through a cell whose corners (top-left, top-right,
+/// bottom-right, bottom-left) are inside or outside
+///
+/// Segments run with the selected side on their right in canvas coordinates
+/// (y down), so each edge crossing starts exactly one segment. Saddle cells
+/// join their selected corners when the cell center is selected.
+fn cell_segments(inside: [bool; 4], center_inside: bool) -> Vec<(Side, Side)> {
+    use Side::*;
+
+    let [tl, tr, br, bl] = inside;
+    let case = (tl as u8) << 3 | (tr as u8) << 2 | (br as u8) << 1 | bl as u8;
+    let unoriented: &[(Side, Side)] = match case {
+        0b0001 | 0b1110 => &[(Left, Bottom)],
+        0b0010 | 0b1101 => &[(Bottom, Right)],
+        0b0011 | 0b1100 => &[(Left, Right)],
+        0b0100 | 0b1011 => &[(Top, Right)],
+        0b0110 | 0b1001 => &[(Top, Bottom)],
+        0b0111 | 0b1000 => &[(Left, Top)],
+        0b0101 if center_inside => &[(Left, Top), (Bottom, Right)],
+        0b0101 => &[(Top, Right), (Left, Bottom)],
+        0b1010 if center_inside => &[(Top, Right), (Left, Bottom)],
+        0b1010 => &[(Left, Top), (Bottom, Right)],
+        _ => &[],
+    };
+
+    unoriented
+        .iter()
+        .map(|&(a, b)| if selected_on_right(a, b, inside) { (a, b) } else { (b, a) })
+        .collect()
+}
+
+/// Check whether the selected side of a segment from `a` to `b` is on its right
+fn selected_on_right(a: Side, b: Side, inside: [bool; 4]) -> bool {
+    let (ax, ay) = midpoint(a);
+    let (bx, by) = midpoint(b);
+    // Judge by the corner the segment cuts off, or the top-left corner when
+    // it runs across the cell
+    let (corner, selected) = match (a, b) {
+        (Side::Top, Side::Right) | (Side::Right, Side::Top) => ((1.0, 0.0), inside[1]),
+        (Side::Bottom, Side::Right) | (Side::Right, Side::Bottom) => ((1.0, 1.0), inside[2]),
+        (Side::Left, Side::Bottom) | (Side::Bottom, Side::Left) => ((0.0, 1.0), inside[3]),
+        _ => ((0.0, 0.0), inside[0]),
+    };
+    let cross = (bx - ax) * (corner.1 - ay) - (by - ay) * (corner.0 - ax);
+    // With y pointing down, a positive cross product means right of travel
+    (cross > 0.0) == selected
+}
+
+fn midpoint(side: Side) -> (f32, f32) {
+    match side {
+        Side::Top => (0.5, 0.0),
+        Side::Right => (1.0, 0.5),
+        Side::Bottom => (0.5, 1.0),
+        Side::Left => (0.0, 0.5),
+    }
+}
+
+/// Where the boundary crosses a cell side, in cell units
+fn crossing(side: Side, corners: &[f32; 4]) -> (f32, f32) {
+    let [tl, tr, br, bl] = *corners;
+    let t = |a: f32, b: f32| if a == b { 0.5 } else { ((LEVEL - a) / (b - a)).clamp(0.0, 1.0) };
+    match side {
+        Side::Top => (t(tl, tr), 0.0),
+        Side::Right => (1.0, t(tr, br)),
+        Side::Bottom => (t(bl, br), 1.0),
+        Side::Left => (0.0, t(tl, bl)),
+    }
+}
+
+/// Key for a cell side shared with the neighboring cell
+fn edge_key(i: u32, j: u32, side: Side) -> EdgeKey {
+    match side {
+        Side::Top => (i, j, false),
+        Side::Bottom => (i, j + 1, false),
+        Side::Left => (i, j, true),
+        Side::Right => (i + 1, j, true),
+    }
+}
+
+/// Douglas-Peucker simplification of a closed polyline
+fn simplify_closed(points: &[(f32, f32)], tolerance: f32) -> Vec<(f32, f32)> {
+    if points.len() < 4 {
+        return points.to_vec();
+    }
+
+    // Split the loop at the point farthest from the first
+    let distance_sq = |p: (f32, f32)| (p.0 - points[0].0).powi(2) + (p.1 - points[0].1).powi(2);
+    let far = (1..points.len())
+        .max_by(|&a, &b| distance_sq(points[a]).total_cmp(&distance_sq(points[b])))
+        .unwrap_or(0);
+
+    let mut closed = points.to_vec();
+    closed.push(points[0]);
+    let mut keep = vec![false; closed.len()];
+    keep[0] = true;
+    keep[far] = true;
+    mark_kept(&closed, 0, far, tolerance, &mut keep);
+    mark_kept(&closed, far, closed.len() - 1, tolerance, &mut keep);
+
+    points.iter().zip(&keep).filter(|(_, &k)| k).map(|(&p, _)| p).collect()
+}
+
+fn mark_kept(points: &[(f32, f32)], first: usize, last: usize, tolerance: f32, keep: &mut [bool]) {
+    if last <= first + 1 {
+        return;
+    }
+
+    let (ax, ay) = points[first];
+    let (bx, by) = points[last];
+    let length = ((bx - ax).powi(2) + (by - ay).powi(2)).sqrt();
+    let distance = |(px, py): (f32, f32)| {
+        if length == 0.0 {
+            ((px - ax).powi(2) + (py - ay).powi(2)).sqrt()
+        } else {
+            ((bx - ax) * (ay - py) - (ax - px) * (by - ay)).abs() / length
+        }
+    };
+
+    let (index, max) = (first + 1..last)
+        .map(|i| (i, distance(points[i])))
+        .fold((first, 0.0f32), |best, d| if d.1 > best.1 { d } else { best });
+    if max > tolerance {
+        keep[index] = true;
+        mark_kept(points, first, index, tolerance, keep);
+        mark_kept(points, index, last, tolerance, keep);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::selection::SelectionMode;
+
+    /// Signed area; positive when clockwise on screen (y down)
+    fn area(contour: &[(f32, f32)]) -> f32 {
+        let n = contour.len();
+        (0..n)
+            .map(|i| {
+                let (a, b) = (contour[i], contour[(i + 1) % n]);
+                a.0 * b.1 - b.0 * a.1
+            })
+            .sum::<f32>()
+            / 2.0
+    }
+
+    #[test]
+    fn test_rectangle_outline() {
+        let mask = SelectionMask::rectangle(32, 32, 4.0, 6.0, 10.0, 8.0);
+        let outline = mask.outline(1.0);
+        assert_eq!(outline.contours.len(), 1);
+
+        let contour = &outline.contours[0];
+        // Marching squares chamfers each corner by half a pixel
+        assert!((area(contour).abs() - 79.5).abs() < 0.01, "{}", area(contour));
+        for &(x, y) in contour {
+            assert!((4.0..=14.0).contains(&x) && (6.0..=14.0).contains(&y));
+        }
+    }
+
+    #[test]
+    fn test_hole_and_islands() {
+        let mut mask = SelectionMask::rectangle(40, 40, 5.0, 5.0, 20.0, 20.0);
+        let hole = SelectionMask::rectangle(40, 40, 10.0, 10.0, 5.0, 5.0);
+        let island = SelectionMask::rectangle(40, 40, 30.0, 30.0, 4.0, 4.0);
+        mask.combine(&hole, SelectionMode::Subtract);
+        mask.combine(&island, SelectionMode::Add);
+
+        let outline = mask.outline(1.0);
+        assert_eq!(outline.contours.len(), 3);
+        // Holes wind the opposite way to the areas around them
+        let signs: Vec<bool> = outline.contours.iter().map(|c| area(c) > 0.0).collect();
+        assert_eq!(signs.iter().filter(|&&s| s).count(), 2);
+        assert_eq!(outline.to_svg_path().matches('Z').count(), 3);
+    }
+
+    #[test]
+    fn test_zoomed_out_outline_is_coarser() {
+        let mask = SelectionMask::ellipse(200, 200, 10.0, 10.0, 180.0, 180.0);
+        let close = mask.outline(4.0);
+        let far = mask.outline(0.25);
+        assert_eq!(far.contours.len(), 1);
+        assert!(far.contours[0].len() < close.contours[0].len());
+
+        // Smooth edges are traced to within a fraction of a pixel
+        for &(x, y) in &close.contours[0] {
+            let radius = ((x - 100.0).powi(2) + (y - 100.0).powi(2)).sqrt();
+            assert!((radius - 90.0).abs() < 0.5, "{}", radius);
+        }
+    }
+
+}
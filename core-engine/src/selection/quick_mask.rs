@@ -0,0 +1,125 @@
+//! Quick mask mode
+//!
+//! In quick mask mode the selection becomes a grayscale surface that every
+//! brush, fill, filter and adjustment edits in place of the active layer:
+//! white is selected, black unselected and grays partially selected. The
+//! surface is an ordinary [`Layer`] so editing code needs no special cases;
+//! it converts to and from [`LayerMask`] on the way in and out and is shown
+//! as a tinted overlay over unselected areas.
+
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use super::{Selection, SelectionMask};
+use crate::color::Color;
+use crate::layer::{Layer, LayerMask};
+
+/// Grayscale editing surface for a selection
+#[derive(Debug)]
+pub struct QuickMask {
+    /// Layer that editing operations draw into
+    surface: Arc<RwLock<Layer>>,
+    /// Overlay color shown over unselected areas; alpha sets its strength
+    pub tint: Color,
+}
+
+impl QuickMask {
+    /// Create an editing surface from a mask
+    pub fn new(mask: &LayerMask) -> Self {
+        let mut surface = Layer::new("Quick Mask", mask.width, mask.height);
+        for y in 0..mask.height {
+            for x in 0..mask.width {
+                let value = mask.get(x, y);
+                surface.set_pixel(x, y, Color::from_rgb(value, value, value));
+            }
+        }
+
+        Self {
+            surface: Arc::new(RwLock::new(surface)),
+            tint: Color::from_rgba(1.0, 0.0, 0.0, 0.5),
+        }
+    }
+
+    /// Create an editing surface from a selection
+    ///
+    /// Without an active selection the whole canvas starts out selected, so
+    /// painting black carves the selection out of everything.
+    pub fn from_selection(selection: &Selection, width: u32, height: u32) -> Self {
+        let mask = if selection.is_active {
+            selection.mask.resized(width, height)
+        } else {
+            SelectionMask::full(width, height)
+        };
+        Self::new(&mask.to_layer_mask())
+    }
+
+    /// The layer editing operations should draw into
+    pub fn surface(&self) -> Arc<RwLock<Layer>> {
+        Arc::clone(&self.surface)
+    }
+
+    /// Read the surface back as a layer mask
+    ///
+    /// Brightness gives the selection strength; erased (transparent) areas
+    /// count as unselected.
+    pub fn to_layer_mask(&self) -> LayerMask {
+        let surface = self.surface.read();
+        let data = surface
+            .pixels
+            .chunks_exact(4)
+            .map(|p| {
+                let luminance = Color::from_rgba8(p[0], p[1], p[2], p[3]).luminance();
+                (luminance * p[3] as f32 / 255.0).clamp(0.0, 1.0)
+            })
+            .collect();
+        LayerMask::from_grayscale(surface.width(), surface.height(), data)
+    }
+
+    /// Convert the surface into a selection
+    pub fn to_selection(&self) -> Selection {
+        Selection::from_mask(SelectionMask::from_layer_mask(&self.to_layer_mask()))
+    }
+
+    /// Fit the surface to a new canvas size, anchored at the top-left corner
+    pub fn set_canvas_size(&self, width: u32, height: u32) {
+        let mut surface = self.surface.write();
+        if (surface.width(), surface.height()) != (width, height) {
+            surface.resize(width, height);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_keeps_soft_edges() {
+        let selection = Selection::ellipse(2.0, 2.0, 12.0, 12.0, 16, 16);
+        let quick_mask = QuickMask::from_selection(&selection, 16, 16);
+        let restored = quick_mask.to_selection();
+
+        for (a, b) in selection.mask.data().iter().zip(restored.mask.data()) {
+            assert!((*a as i32 - *b as i32).abs() <= 1);
+        }
+    }
+
+    #[test]
+    fn test_painting_the_surface_edits_selection() {
+        let quick_mask = QuickMask::from_selection(&Selection::new(), 8, 8);
+        {
+            let surface = quick_mask.surface();
+            let mut layer = surface.write();
+            layer.set_pixel(3, 3, Color::black());
+            layer.set_pixel(4, 3, Color::gray(0.5));
+            layer.set_pixel(5, 3, Color::transparent());
+        }
+
+        let selection = quick_mask.to_selection();
+        assert!(selection.contains(0.0, 0.0));
+        assert_eq!(selection.mask.get(3, 3), 0);
+        assert!((120..136).contains(&selection.mask.get(4, 3)));
+        assert_eq!(selection.mask.get(5, 3), 0);
+    }
+}
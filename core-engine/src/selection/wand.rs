@@ -0,0 +1,302 @@
+//! Magic wand selection
+//!
+//! The wand selects pixels whose color is within tolerance of the clicked
+//! pixel. The pixels it samples need not belong to the layer being edited:
+//! colorists pick up regions enclosed by line art on a reference layer or in
+//! the merged image, then fill those regions on their flats layer.
+//!
+//! For line art the wand can close gaps: non-matching pixels (the lines) are
+//! thickened before flooding so leaks through small breaks are sealed, then
+//! the region is grown back to the lines. The result can also be expanded
+//! under the lines so fills meet the ink without a halo, or contracted to
+//! leave a margin.
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::SelectionMask;
+use crate::error::{EngineError, EngineResult};
+
+/// Pixels the magic wand samples
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SampleSource {
+    /// The active layer
+    #[default]
+    ActiveLayer,
+    /// A designated reference layer, such as line art
+    Layer(Uuid),
+    /// All visible layers composited
+    Merged,
+}
+
+/// Magic wand settings
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WandOptions {
+    /// Per-channel color tolerance (0-255)
+    pub tolerance: f32,
+    /// Only select pixels connected to the clicked pixel
+    pub contiguous: bool,
+    /// Widest gap in the surrounding lines to seal, in pixels
+    pub close_gaps: f32,
+    /// Pixels to grow the result under the surrounding lines; negative
+    /// values contract it away from them
+    pub expand: f32,
+}
+
+impl Default for WandOptions {
+    fn default() -> Self {
+        Self {
+            tolerance: 32.0,
+            contiguous: true,
+            close_gaps: 0.0,
+            expand: 0.0,
+        }
+    }
+}
+
+const NEIGHBORS_8: [(i64, i64); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+/// Select the region around (x, y) in RGBA pixels
+pub(crate) fn magic_wand(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    x: u32,
+    y: u32,
+    options: &WandOptions,
+) -> EngineResult<SelectionMask> {
+    if x >= width || y >= height {
+        return Err(EngineError::InvalidOperation("Point outside canvas".to_string()));
+    }
+    if pixels.len() < (width * height * 4) as usize {
+        return Err(EngineError::InvalidOperation("Invalid pixel data".to_string()));
+    }
+
+    let seed_index = (y * width + x) as usize;
+    let seed = &pixels[seed_index * 4..seed_index * 4 + 4];
+    let limit = options.tolerance as i32 * 3;
+    let matching: Vec<bool> = pixels
+        .chunks_exact(4)
+        .take((width * height) as usize)
+        .map(|p| color_difference(p, seed) <= limit)
+        .collect();
+
+    let mut mask = if options.contiguous {
+        contiguous_region(&matching, width, height, seed_index, options.close_gaps)?
+    } else {
+        to_mask(&matching, width, height)?
+    };
+
+    if options.expand >= 0.0 {
+        mask.grow(options.expand);
+    } else {
+        mask.shrink(-options.expand);
+    }
+    Ok(mask)
+}
+
+/// Flood from the seed through matching pixels, sealing gaps in the barrier
+fn contiguous_region(
+    matching: &[bool],
+    width: u32,
+    height: u32,
+    seed: usize,
+    close_gaps: f32,
+) -> EngineResult<SelectionMask> {
+    if close_gaps > 0.0 {
+        let reach = close_gaps / 2.0;
+        let lines: Vec<bool> = matching.iter().map(|m| !m).collect();
+        let mut barrier = to_mask(&lines, width, height)?;
+        barrier.grow(reach);
+
+        let open: Vec<bool> = matching
+            .iter()
+            .zip(barrier.data())
+            .map(|(&m, &b)| m && b < 128)
+            .collect();
+
+        // Clicking inside an area narrower than the gap size leaves nothing
+        // to flood; fall back to the unsealed region
+        if open[seed] {
+            let region = flood(&open, width, height, seed);
+            let steps = reach.ceil() as u32;
+            return to_mask(&grow_through(region, matching, width, height, steps), width, height);
+        }
+    }
+
+    to_mask(&flood(matching, width, height, seed), width, height)
+}
+
+/// 4-connected scanline flood fill over `open` pixels
+///
+/// Each step fills a whole horizontal run, then queues one seed per open run
+/// in the rows above and below, so the stack stays small even for large
+/// regions.
+fn flood(open: &[bool], width: u32, height: u32, seed: usize) -> Vec<bool> {
+    let mut filled = vec![false; open.len()];
+    let width = width as usize;
+    let height = height as usize;
+    let mut stack = vec![seed];
+
+    while let Some(i) = stack.pop() {
+        if filled[i] || !open[i] {
+            continue;
+        }
+        let (x, y) = (i % width, i / width);
+        let row = y * width;
+        let mut left = x;
+        while left > 0 && open[row + left - 1] && !filled[row + left - 1] {
+            left -= 1;
+        }
+        let mut right = x;
+        while right + 1 < width && open[row + right + 1] && !filled[row + right + 1] {
+            right += 1;
+        }
+        filled[row + left..=row + right].fill(true);
+
+        let neighbors = [y.checked_sub(1), (y + 1 < height).then_some(y + 1)];
+        for ny in neighbors.into_iter().flatten() {
+            let row = ny * width;
+            let mut in_run = false;
+            for nx in left..=right {
+                let n = row + nx;
+                let fillable = open[n] && !filled[n];
+                if fillable && !in_run {
+                    stack.push(n);
+                }
+                in_run = fillable;
+            }
+        }
+    }
+    filled
+}
+
+/// Grow a region back toward the lines through matching pixels
+///
+/// Growth is limited to `steps` 8-connected moves, enough to refill the band
+/// the sealed barrier took away (square corners included) but not to escape
+/// through a sealed gap.
+fn grow_through(
+    mut region: Vec<bool>,
+    matching: &[bool],
+    width: u32,
+    height: u32,
+    steps: u32,
+) -> Vec<bool> {
+    let (width, height) = (width as i64, height as i64);
+    let mut frontier: Vec<usize> = (0..region.len()).filter(|&i| region[i]).collect();
+
+    for _ in 0..steps {
+        let mut next = Vec::new();
+        for i in frontier {
+            let (x, y) = (i as i64 % width, i as i64 / width);
+            for (dx, dy) in NEIGHBORS_8 {
+                let (nx, ny) = (x + dx, y + dy);
+                if nx < 0 || ny < 0 || nx >= width || ny >= height {
+                    continue;
+                }
+                let n = (ny * width + nx) as usize;
+                if matching[n] && !region[n] {
+                    region[n] = true;
+                    next.push(n);
+                }
+            }
+        }
+        frontier = next;
+    }
+    region
+}
+
+/// Sum of channel differences between premultiplied colors
+///
+/// Premultiplying makes every fully transparent pixel the same color, so
+/// black line art on a transparent layer is told apart from the empty areas
+/// between the lines.
+fn color_difference(a: &[u8], b: &[u8]) -> i32 {
+    let premultiply = |p: &[u8], c: usize| p[c] as i32 * p[3] as i32 / 255;
+    let rgb: i32 = (0..3).map(|c| (premultiply(a, c) - premultiply(b, c)).abs()).sum();
+    rgb + (a[3] as i32 - b[3] as i32).abs()
+}
+
+fn to_mask(selected: &[bool], width: u32, height: u32) -> EngineResult<SelectionMask> {
+    let data = selected.iter().map(|&s| if s { 255 } else { 0 }).collect();
+    SelectionMask::from_data(width, height, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Transparent 20x20 layer with a black square outline from 4 to 15 that
+    /// has a 2 pixel break in its top edge
+    fn line_art() -> Vec<u8> {
+        let mut pixels = vec![0u8; 20 * 20 * 4];
+        for y in 4..16u32 {
+            for x in 4..16u32 {
+                let edge = x == 4 || x == 15 || y == 4 || y == 15;
+                let gap = y == 4 && (9..11).contains(&x);
+                if edge && !gap {
+                    let i = ((y * 20 + x) * 4) as usize;
+                    pixels[i..i + 4].copy_from_slice(&[0, 0, 0, 255]);
+                }
+            }
+        }
+        pixels
+    }
+
+    #[test]
+    fn test_wand_leaks_through_gap_without_closing() {
+        let mask = magic_wand(&line_art(), 20, 20, 10, 10, &WandOptions::default()).unwrap();
+        assert_eq!(mask.get(10, 10), 255);
+        assert_eq!(mask.get(1, 1), 255);
+        assert_eq!(mask.get(4, 10), 0);
+    }
+
+    #[test]
+    fn test_wand_closes_gaps() {
+        let options = WandOptions {
+            close_gaps: 4.0,
+            ..WandOptions::default()
+        };
+        let mask = magic_wand(&line_art(), 20, 20, 10, 10, &options).unwrap();
+        assert_eq!(mask.get(10, 10), 255);
+        assert_eq!(mask.get(5, 5), 255);
+        assert_eq!(mask.get(14, 14), 255);
+        assert_eq!(mask.get(1, 1), 0);
+        assert_eq!(mask.get(10, 1), 0);
+    }
+
+    #[test]
+    fn test_wand_expands_under_lines() {
+        let options = WandOptions {
+            close_gaps: 4.0,
+            expand: 1.0,
+            ..WandOptions::default()
+        };
+        let mask = magic_wand(&line_art(), 20, 20, 10, 10, &options).unwrap();
+        assert_eq!(mask.get(4, 10), 255);
+        assert_eq!(mask.get(3, 10), 0);
+    }
+
+    #[test]
+    fn test_wand_contracts_away_from_lines() {
+        let options = WandOptions {
+            close_gaps: 4.0,
+            expand: -2.0,
+            ..WandOptions::default()
+        };
+        let mask = magic_wand(&line_art(), 20, 20, 10, 10, &options).unwrap();
+        assert_eq!(mask.get(10, 10), 255);
+        assert_eq!(mask.get(6, 10), 0);
+        assert_eq!(mask.get(8, 10), 255);
+    }
+}
@@ -0,0 +1,492 @@
+//! Vector Shapes Module
+//!
+//! Geometric shapes with a fill and a centered stroke, rasterized with
+//! anti-aliasing. Every shape is described by two points: box shapes fit
+//! inside the rectangle they span and lines run from one to the other.
+//! Shapes drawn into vector layers are kept on the layer so they can be
+//! edited and rendered again.
+
+use crate::color::Color;
+use crate::layer::Layer;
+use crate::selection::SelectionMask;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+/// Arrowhead width and length as a multiple of the line weight
+const ARROW_SCALE: f32 = 4.0;
+
+/// Smallest arrowhead width and length in pixels
+const MIN_ARROW_SIZE: f32 = 8.0;
+
+/// Kind of shape and its kind-specific settings
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ShapeKind {
+    /// Rectangle
+    Rectangle,
+    /// Rectangle with circular corners
+    RoundedRectangle {
+        /// Corner radius in pixels
+        radius: f32,
+    },
+    /// Ellipse
+    Ellipse,
+    /// Regular polygon, first corner at the top
+    Polygon {
+        /// Number of sides (at least 3)
+        sides: u32,
+    },
+    /// Star, first point at the top
+    Star {
+        /// Number of points (at least 3)
+        points: u32,
+        /// Inner radius as a fraction of the outer radius (0.0 - 1.0)
+        inner_ratio: f32,
+    },
+    /// Straight line with optional arrowheads
+    Line {
+        /// Line thickness in pixels
+        weight: f32,
+        /// Arrowhead at the start point
+        arrow_start: bool,
+        /// Arrowhead at the end point
+        arrow_end: bool,
+    },
+}
+
+impl ShapeKind {
+    /// Display name
+    pub fn name(&self) -> &'static str {
+        match self {
+            ShapeKind::Rectangle => "Rectangle",
+            ShapeKind::RoundedRectangle { .. } => "Rounded Rectangle",
+            ShapeKind::Ellipse => "Ellipse",
+            ShapeKind::Polygon { .. } => "Polygon",
+            ShapeKind::Star { .. } => "Star",
+            ShapeKind::Line { .. } => "Line",
+        }
+    }
+}
+
+/// Fill and stroke of a shape
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ShapeStyle {
+    /// Fill color, if filled
+    pub fill: Option<Color>,
+    /// Stroke color, if stroked
+    pub stroke: Option<Color>,
+    /// Stroke width in pixels, centered on the outline
+    pub stroke_width: f32,
+    /// Anti-aliased edges
+    pub anti_aliasing: bool,
+}
+
+impl Default for ShapeStyle {
+    fn default() -> Self {
+        Self {
+            fill: Some(Color::black()),
+            stroke: None,
+            stroke_width: 1.0,
+            anti_aliasing: true,
+        }
+    }
+}
+
+/// A shape with its geometry and style
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Shape {
+    /// Kind of shape
+    pub kind: ShapeKind,
+    /// First corner of the box, or the line start
+    pub start: (f32, f32),
+    /// Opposite corner of the box, or the line end
+    pub end: (f32, f32),
+    /// Fill and stroke
+    pub style: ShapeStyle,
+}
+
+impl Shape {
+    /// Create a shape
+    pub fn new(kind: ShapeKind, start: (f32, f32), end: (f32, f32), style: ShapeStyle) -> Self {
+        Self { kind, start, end, style }
+    }
+
+    /// Box spanned by the two points as (x, y, width, height)
+    pub fn rect(&self) -> (f32, f32, f32, f32) {
+        let x = self.start.0.min(self.end.0);
+        let y = self.start.1.min(self.end.1);
+        (x, y, (self.end.0 - self.start.0).abs(), (self.end.1 - self.start.1).abs())
+    }
+
+    /// Closed outline of the shape
+    ///
+    /// Empty when the shape has no area.
+    pub fn outline(&self) -> Vec<(f32, f32)> {
+        let (x, y, width, height) = self.rect();
+        if !matches!(self.kind, ShapeKind::Line { .. }) && (width < f32::EPSILON || height < f32::EPSILON) {
+            return Vec::new();
+        }
+        let center = (x + width / 2.0, y + height / 2.0);
+        let radii = (width / 2.0, height / 2.0);
+
+        match self.kind {
+            ShapeKind::Rectangle => {
+                vec![(x, y), (x + width, y), (x + width, y + height), (x, y + height)]
+            }
+            ShapeKind::RoundedRectangle { radius } => {
+                let r = radius.min(width / 2.0).min(height / 2.0);
+                if r <= 0.0 {
+                    return vec![(x, y), (x + width, y), (x + width, y + height), (x, y + height)];
+                }
+                let steps = arc_steps(r, FRAC_PI_2);
+                let corners = [
+                    (x + width - r, y + r, -FRAC_PI_2),
+                    (x + width - r, y + height - r, 0.0),
+                    (x + r, y + height - r, FRAC_PI_2),
+                    (x + r, y + r, PI),
+                ];
+                corners
+                    .iter()
+                    .flat_map(|&(cx, cy, from)| {
+                        (0..=steps).map(move |i| {
+                            let angle = from + FRAC_PI_2 * i as f32 / steps as f32;
+                            (cx + r * angle.cos(), cy + r * angle.sin())
+                        })
+                    })
+                    .collect()
+            }
+            ShapeKind::Ellipse => {
+                let steps = arc_steps(radii.0.max(radii.1), TAU);
+                (0..steps)
+                    .map(|i| {
+                        let angle = TAU * i as f32 / steps as f32;
+                        (center.0 + radii.0 * angle.cos(), center.1 + radii.1 * angle.sin())
+                    })
+                    .collect()
+            }
+            ShapeKind::Polygon { sides } => {
+                let sides = sides.max(3);
+                (0..sides)
+                    .map(|i| {
+                        let angle = -FRAC_PI_2 + TAU * i as f32 / sides as f32;
+                        (center.0 + radii.0 * angle.cos(), center.1 + radii.1 * angle.sin())
+                    })
+                    .collect()
+            }
+            ShapeKind::Star { points, inner_ratio } => {
+                let corners = points.max(3) * 2;
+                let inner = inner_ratio.clamp(0.0, 1.0);
+                (0..corners)
+                    .map(|i| {
+                        let angle = -FRAC_PI_2 + TAU * i as f32 / corners as f32;
+                        let scale = if i % 2 == 0 { 1.0 } else { inner };
+                        (
+                            center.0 + radii.0 * scale * angle.cos(),
+                            center.1 + radii.1 * scale * angle.sin(),
+                        )
+                    })
+                    .collect()
+            }
+            ShapeKind::Line { weight, arrow_start, arrow_end } => {
+                self.line_outline(weight, arrow_start, arrow_end)
+            }
+        }
+    }
+
+    /// Line body with arrowheads merged into one polygon
+    fn line_outline(&self, weight: f32, arrow_start: bool, arrow_end: bool) -> Vec<(f32, f32)> {
+        let (a, b) = (self.start, self.end);
+        let length = (b.0 - a.0).hypot(b.1 - a.1);
+        if length < f32::EPSILON {
+            return Vec::new();
+        }
+        let direction = ((b.0 - a.0) / length, (b.1 - a.1) / length);
+        let normal = (-direction.1, direction.0);
+        let half = weight.max(1.0) / 2.0;
+
+        // Arrowheads share the length when the line is too short for both
+        let head = (weight * ARROW_SCALE).max(MIN_ARROW_SIZE);
+        let heads = arrow_start as u32 + arrow_end as u32;
+        let head_length = if heads > 0 { head.min(length / heads as f32) } else { 0.0 };
+        let head_half = (head / 2.0).max(half);
+
+        let along = |p: (f32, f32), distance: f32| (p.0 + direction.0 * distance, p.1 + direction.1 * distance);
+        let side = |p: (f32, f32), offset: f32| (p.0 + normal.0 * offset, p.1 + normal.1 * offset);
+
+        let mut points = Vec::with_capacity(10);
+        let body_start = if arrow_start { along(a, head_length) } else { a };
+        let body_end = if arrow_end { along(b, -head_length) } else { b };
+
+        if arrow_start {
+            points.push(a);
+            points.push(side(body_start, head_half));
+        }
+        points.push(side(body_start, half));
+        points.push(side(body_end, half));
+        if arrow_end {
+            points.push(side(body_end, head_half));
+            points.push(b);
+            points.push(side(body_end, -head_half));
+        }
+        points.push(side(body_end, -half));
+        points.push(side(body_start, -half));
+        if arrow_start {
+            points.push(side(body_start, -head_half));
+        }
+        points
+    }
+
+    /// Fill and stroke coverage on a layer of the given size
+    pub fn coverage(&self, width: u32, height: u32) -> (Option<SelectionMask>, Option<SelectionMask>) {
+        let outline = self.outline();
+        if outline.len() < 3 {
+            return (None, None);
+        }
+        let fill = self
+            .style
+            .fill
+            .map(|_| SelectionMask::polygon(width, height, &outline));
+        let stroke = self
+            .style
+            .stroke
+            .filter(|_| self.style.stroke_width > 0.0)
+            .map(|_| stroke_mask(width, height, &outline, self.style.stroke_width));
+        (fill, stroke)
+    }
+
+    /// Pixel bounds of everything the shape touches as (x, y, width, height)
+    pub fn pixel_bounds(&self, width: u32, height: u32) -> Option<(u32, u32, u32, u32)> {
+        let outline = self.outline();
+        if outline.len() < 3 {
+            return None;
+        }
+        let pad = if self.style.stroke.is_some() { self.style.stroke_width / 2.0 } else { 0.0 } + 1.0;
+        let min_x = outline.iter().map(|p| p.0).fold(f32::MAX, f32::min) - pad;
+        let min_y = outline.iter().map(|p| p.1).fold(f32::MAX, f32::min) - pad;
+        let max_x = outline.iter().map(|p| p.0).fold(f32::MIN, f32::max) + pad;
+        let max_y = outline.iter().map(|p| p.1).fold(f32::MIN, f32::max) + pad;
+
+        let x0 = min_x.floor().max(0.0) as u32;
+        let y0 = min_y.floor().max(0.0) as u32;
+        let x1 = (max_x.ceil().max(0.0) as u32).min(width);
+        let y1 = (max_y.ceil().max(0.0) as u32).min(height);
+        (x1 > x0 && y1 > y0).then(|| (x0, y0, x1 - x0, y1 - y0))
+    }
+
+    /// Draw the shape over a layer's pixels, with the stroke over the fill
+    pub fn render(&self, layer: &mut Layer, opacity: f32) {
+        let (width, height) = (layer.width(), layer.height());
+        let Some((x0, y0, w, h)) = self.pixel_bounds(width, height) else {
+            return;
+        };
+        let (fill_mask, stroke_mask) = self.coverage(width, height);
+        let anti_aliasing = self.style.anti_aliasing;
+        let amount = |mask: &Option<SelectionMask>, x: u32, y: u32| {
+            let coverage = mask.as_ref().map_or(0.0, |mask| mask.coverage(x, y));
+            if anti_aliasing {
+                coverage
+            } else if coverage >= 0.5 {
+                1.0
+            } else {
+                0.0
+            }
+        };
+
+        for y in y0..y0 + h {
+            for x in x0..x0 + w {
+                let mut color = Color::transparent();
+                if let Some(fill) = self.style.fill {
+                    color = fill.with_alpha(fill.a * amount(&fill_mask, x, y));
+                }
+                if let Some(stroke) = self.style.stroke {
+                    color = color.blend_over(stroke.with_alpha(stroke.a * amount(&stroke_mask, x, y)));
+                }
+                if color.a <= 0.0 {
+                    continue;
+                }
+                if let Some(existing) = layer.get_pixel(x, y) {
+                    layer.set_pixel(x, y, existing.blend_over(color.with_alpha(color.a * opacity)));
+                }
+            }
+        }
+    }
+}
+
+/// Segments for an arc of `sweep` radians so each is about two pixels long
+fn arc_steps(radius: f32, sweep: f32) -> usize {
+    let min = (sweep / FRAC_PI_2 * 4.0) as usize;
+    ((radius * sweep / 2.0).ceil() as usize).clamp(min, 1024)
+}
+
+/// Coverage of a stroke of `stroke_width` centered on a closed outline
+///
+/// Each segment only visits the pixels near it, keeping the strongest
+/// coverage where segments overlap.
+fn stroke_mask(width: u32, height: u32, outline: &[(f32, f32)], stroke_width: f32) -> SelectionMask {
+    let mut mask = SelectionMask::new(width, height);
+    let half = stroke_width / 2.0;
+    let reach = half + 1.0;
+
+    for (i, &a) in outline.iter().enumerate() {
+        let b = outline[(i + 1) % outline.len()];
+        let x0 = (a.0.min(b.0) - reach).floor().max(0.0) as u32;
+        let y0 = (a.1.min(b.1) - reach).floor().max(0.0) as u32;
+        let x1 = ((a.0.max(b.0) + reach).ceil().max(0.0) as u32).min(width);
+        let y1 = ((a.1.max(b.1) + reach).ceil().max(0.0) as u32).min(height);
+
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let distance = segment_distance((x as f32 + 0.5, y as f32 + 0.5), a, b);
+                let coverage = (half - distance + 0.5).clamp(0.0, 1.0);
+                let value = (coverage * 255.0).round() as u8;
+                if value > mask.get(x, y) {
+                    mask.set(x, y, value);
+                }
+            }
+        }
+    }
+    mask
+}
+
+/// Distance from `p` to the segment from `a` to `b`
+fn segment_distance(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length_squared = dx * dx + dy * dy;
+    let t = if length_squared > 0.0 {
+        (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length_squared).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (p.0 - a.0 - t * dx).hypot(p.1 - a.1 - t * dy)
+}
+
+/// Where shape tools put their output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ShapeOutput {
+    /// Paint into the active layer's pixels
+    #[default]
+    Raster,
+    /// Add a vector layer holding the shape
+    Vector,
+}
+
+/// Shape tool settings
+///
+/// The primary color fills and the secondary color strokes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShapeOptions {
+    /// Fill shapes
+    pub fill: bool,
+    /// Stroke shapes
+    pub stroke: bool,
+    /// Stroke width in pixels
+    pub stroke_width: f32,
+    /// Rectangle corner radius; rounded when above zero
+    pub corner_radius: f32,
+    /// Polygon sides, or star points
+    pub sides: u32,
+    /// Draw stars with the polygon tool
+    pub star: bool,
+    /// Star inner radius as a fraction of the outer radius
+    pub star_inner_ratio: f32,
+    /// Line thickness in pixels
+    pub line_weight: f32,
+    /// Arrowhead at the line start
+    pub arrow_start: bool,
+    /// Arrowhead at the line end
+    pub arrow_end: bool,
+    /// Raster pixels or a new vector layer
+    pub output: ShapeOutput,
+}
+
+impl Default for ShapeOptions {
+    fn default() -> Self {
+        Self {
+            fill: true,
+            stroke: false,
+            stroke_width: 3.0,
+            corner_radius: 0.0,
+            sides: 5,
+            star: false,
+            star_inner_ratio: 0.5,
+            line_weight: 4.0,
+            arrow_start: false,
+            arrow_end: false,
+            output: ShapeOutput::Raster,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filled(kind: ShapeKind, start: (f32, f32), end: (f32, f32)) -> Shape {
+        Shape::new(kind, start, end, ShapeStyle::default())
+    }
+
+    #[test]
+    fn test_outlines() {
+        let polygon = filled(ShapeKind::Polygon { sides: 6 }, (0.0, 0.0), (20.0, 20.0)).outline();
+        assert_eq!(polygon.len(), 6);
+        assert!((polygon[0].0 - 10.0).abs() < 1e-4 && polygon[0].1.abs() < 1e-4);
+
+        let star = filled(ShapeKind::Star { points: 5, inner_ratio: 0.5 }, (0.0, 0.0), (20.0, 20.0)).outline();
+        assert_eq!(star.len(), 10);
+        let inner = (star[1].0 - 10.0).hypot(star[1].1 - 10.0);
+        assert!((inner - 5.0).abs() < 1e-4);
+
+        let rounded = filled(ShapeKind::RoundedRectangle { radius: 4.0 }, (0.0, 0.0), (20.0, 10.0)).outline();
+        assert!(rounded.iter().all(|p| p.0 >= -1e-4 && p.0 <= 20.0 + 1e-4 && p.1 >= -1e-4 && p.1 <= 10.0 + 1e-4));
+        assert!(!rounded.contains(&(0.0, 0.0)));
+
+        assert!(filled(ShapeKind::Ellipse, (5.0, 5.0), (5.0, 9.0)).outline().is_empty());
+
+        let arrow = ShapeKind::Line { weight: 2.0, arrow_start: false, arrow_end: true };
+        let line = filled(arrow, (0.0, 10.0), (40.0, 10.0)).outline();
+        assert!(line.contains(&(40.0, 10.0)));
+        let widest = line.iter().map(|p| (p.1 - 10.0).abs()).fold(0.0, f32::max);
+        assert_eq!(widest, MIN_ARROW_SIZE / 2.0);
+    }
+
+    #[test]
+    fn test_render_fill_and_stroke() {
+        let mut layer = Layer::new("Test", 40, 40);
+        let style = ShapeStyle {
+            fill: Some(Color::red()),
+            stroke: Some(Color::from_rgb(0.0, 0.0, 1.0)),
+            stroke_width: 4.0,
+            anti_aliasing: true,
+        };
+        Shape::new(ShapeKind::Rectangle, (10.0, 10.0), (30.0, 30.0), style).render(&mut layer, 1.0);
+
+        assert_eq!(layer.get_pixel(20, 20).unwrap().to_rgba8(), (255, 0, 0, 255));
+        // The stroke straddles the outline
+        assert_eq!(layer.get_pixel(20, 9).unwrap().to_rgba8(), (0, 0, 255, 255));
+        assert_eq!(layer.get_pixel(20, 11).unwrap().to_rgba8(), (0, 0, 255, 255));
+        assert_eq!(layer.get_pixel(20, 13).unwrap().to_rgba8(), (255, 0, 0, 255));
+        assert_eq!(layer.get_pixel(20, 5).unwrap().a, 0.0);
+    }
+
+    #[test]
+    fn test_anti_aliasing_toggle() {
+        let mut smooth = Layer::new("Smooth", 32, 32);
+        let mut hard = Layer::new("Hard", 32, 32);
+        let mut shape = filled(ShapeKind::Ellipse, (2.0, 2.0), (29.0, 29.0));
+        shape.render(&mut smooth, 1.0);
+        shape.style.anti_aliasing = false;
+        shape.render(&mut hard, 1.0);
+
+        let partial = |layer: &Layer| {
+            (0..32)
+                .flat_map(|y| (0..32).map(move |x| (x, y)))
+                .filter(|&(x, y)| {
+                    let a = layer.get_pixel(x, y).unwrap().a;
+                    a > 0.0 && a < 1.0
+                })
+                .count()
+        };
+        assert!(partial(&smooth) > 0);
+        assert_eq!(partial(&hard), 0);
+    }
+}
@@ -0,0 +1,147 @@
+//! Undoable pixel edits for tools that write to layers directly
+
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::error::{EngineError, EngineResult};
+use crate::history::{DirtyRect, HistoryState, LayerSnapshot};
+use crate::layer::Layer;
+use crate::DrawEngine;
+
+/// An edit of the engine's edit target that becomes one undo step
+///
+/// Each applied region is clipped by the selection coverage. Edits read and
+/// write an unclipped copy of the layer, so overlapping dabs build up at full
+/// strength and each pixel is blended toward `before` only once. Finishing
+/// pushes the union of all regions to history; aborting restores the layer.
+pub(crate) struct PixelEdit {
+    layer: Arc<RwLock<Layer>>,
+    before: Vec<u8>,
+    unclipped: Vec<u8>,
+    dirty: Option<DirtyRect>,
+    description: &'static str,
+}
+
+impl PixelEdit {
+    /// Start editing the active layer or quick mask surface
+    pub(crate) fn begin(engine: &DrawEngine, description: &'static str) -> EngineResult<Self> {
+        let layer = engine
+            .edit_target()
+            .ok_or_else(|| EngineError::InvalidOperation("No active layer".to_string()))?;
+        let before = {
+            let layer = layer.read();
+            if !layer.lock.can_draw() {
+                return Err(EngineError::InvalidOperation("Layer is locked".to_string()));
+            }
+            layer.pixels.clone()
+        };
+        Ok(Self {
+            layer,
+            unclipped: before.clone(),
+            before,
+            dirty: None,
+            description,
+        })
+    }
+
+    /// Layer size as (width, height)
+    pub(crate) fn size(&self) -> (u32, u32) {
+        let layer = self.layer.read();
+        (layer.width(), layer.height())
+    }
+
+    /// Layer pixels as they were when the edit began
+    pub(crate) fn before(&self) -> &[u8] {
+        &self.before
+    }
+
+    /// Edit pixels inside `area`, then clip them by the selection
+    ///
+    /// Does nothing once the layer no longer matches its size at the start
+    /// of the edit, such as after an undo of a resize.
+    pub(crate) fn apply(
+        &mut self,
+        engine: &DrawEngine,
+        mut area: DirtyRect,
+        edit: impl FnOnce(&mut Layer, &[u8]),
+    ) {
+        let mut layer = self.layer.write();
+        area.clamp(layer.width(), layer.height());
+        if area.is_empty() || layer.pixels.len() != self.before.len() {
+            return;
+        }
+
+        std::mem::swap(&mut layer.pixels, &mut self.unclipped);
+        edit(&mut layer, &self.before);
+        std::mem::swap(&mut layer.pixels, &mut self.unclipped);
+
+        let width = layer.width();
+        copy_area(&self.unclipped, &mut layer.pixels, width, area);
+        engine.clip_to_selection(&mut layer, &self.before, area);
+        self.dirty.get_or_insert(area).union(&area);
+    }
+
+    /// Record the edit as one undo step
+    pub(crate) fn finish(self, engine: &DrawEngine) {
+        let Some(dirty) = self.dirty else {
+            return;
+        };
+        let layer = self.layer.read();
+        let mut state = HistoryState::new(self.description);
+        state.add_snapshot(LayerSnapshot::incremental_compressed(
+            layer.id,
+            &self.before,
+            layer.width(),
+            layer.height(),
+            dirty,
+        ));
+        engine.history_manager().write().push_state(state);
+    }
+
+    /// Put the layer back as it was
+    pub(crate) fn abort(self) {
+        let mut layer = self.layer.write();
+        if self.dirty.is_some() && layer.pixels.len() == self.before.len() {
+            layer.pixels = self.before;
+        }
+    }
+}
+
+/// Copy the pixels inside `area` between two buffers of the same layer size
+pub(crate) fn copy_area(source: &[u8], target: &mut [u8], width: u32, area: DirtyRect) {
+    let width = width as usize;
+    for y in area.y as usize..(area.y + area.height) as usize {
+        let start = (y * width + area.x as usize) * 4;
+        let end = start + area.width as usize * 4;
+        if let (Some(from), Some(to)) = (source.get(start..end), target.get_mut(start..end)) {
+            to.copy_from_slice(from);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_skips_layer_resized_underneath() {
+        let engine = DrawEngine::new().unwrap();
+        let layer_id = {
+            let layer_manager_arc = engine.layer_manager();
+            let mut layer_manager = layer_manager_arc.write();
+            layer_manager.set_canvas_size(8, 8);
+            layer_manager.add_layer("Layer")
+        };
+        let layer_arc = engine.layer_manager().read().get_layer(layer_id).unwrap();
+
+        let mut edit = PixelEdit::begin(&engine, "Edit").unwrap();
+        layer_arc.write().resize(4, 4);
+        edit.apply(&engine, DirtyRect::new(0, 0, 4, 4), |layer, _| layer.pixels.fill(255));
+        assert!(layer_arc.read().pixels.iter().all(|&v| v == 0));
+
+        // Aborting must not put back pixels of the old size
+        edit.abort();
+        assert_eq!(layer_arc.read().pixels.len(), 4 * 4 * 4);
+    }
+}
@@ -0,0 +1,93 @@
+//! Paint bucket
+
+use super::{Tool, ToolContext, ToolEvent, ToolType};
+use crate::error::EngineResult;
+use crate::fill::{BucketContent, FillContent, FillOptions};
+use crate::selection::SampleSource;
+
+/// Fills the region around the clicked pixel
+///
+/// Fills with the primary color, or the secondary color with Alt, or with
+/// the pattern or gradient chosen in the bucket options. The region is found
+/// in the bucket's reference layer if one is set, otherwise in the merged
+/// image when "sample all layers" is on, otherwise in the layer itself.
+pub struct FillTool;
+
+impl FillTool {
+    /// Create a paint bucket tool
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for FillTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tool for FillTool {
+    fn tool_type(&self) -> ToolType {
+        ToolType::Fill
+    }
+
+    fn name(&self) -> &str {
+        "Paint Bucket"
+    }
+
+    fn cursor(&self) -> &str {
+        "bucket"
+    }
+
+    fn on_press(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        if event.x < 0.0 || event.y < 0.0 {
+            return Ok(());
+        }
+        let color = if event.modifiers.alt {
+            *ctx.secondary_color
+        } else {
+            *ctx.primary_color
+        };
+        let bucket = &ctx.options.bucket;
+        let content = match (bucket.content, &bucket.pattern) {
+            (BucketContent::Pattern, Some(pattern)) => FillContent::Pattern(pattern.clone()),
+            (BucketContent::Gradient, _) => FillContent::Gradient {
+                options: ctx.options.gradient.clone(),
+                angle: bucket.gradient_angle,
+                foreground: *ctx.primary_color,
+                background: *ctx.secondary_color,
+            },
+            _ => FillContent::Color(color),
+        };
+
+        let source = match bucket.reference_layer {
+            Some(id) => SampleSource::Layer(id),
+            None if ctx.options.sample_all_layers => SampleSource::Merged,
+            None => SampleSource::ActiveLayer,
+        };
+        let options = FillOptions {
+            source,
+            tolerance: ctx.options.tolerance,
+            contiguous: ctx.options.contiguous,
+            close_gaps: bucket.close_gaps,
+            expand: bucket.expand,
+            anti_alias: ctx.options.anti_aliasing,
+            opacity: ctx.options.opacity,
+        };
+        ctx.engine.bucket_fill(event.x as u32, event.y as u32, &content, &options)
+    }
+
+    fn on_move(&mut self, _ctx: &mut ToolContext, _event: &ToolEvent) -> EngineResult<()> {
+        Ok(())
+    }
+
+    fn on_release(&mut self, _ctx: &mut ToolContext, _event: &ToolEvent) -> EngineResult<()> {
+        Ok(())
+    }
+
+    fn cancel(&mut self, _ctx: &mut ToolContext) -> EngineResult<()> {
+        Ok(())
+    }
+
+    fn reset(&mut self) {}
+}
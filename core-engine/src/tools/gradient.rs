@@ -0,0 +1,124 @@
+//! Gradient tool
+//!
+//! Dragging sets the gradient direction, shown as a line. On release the
+//! gradient from the tool options is drawn over the edit target, inside the
+//! selection, as one undo step. Foreground and background stops take the
+//! primary and secondary colors. Shift snaps the direction to 45 degree
+//! steps.
+
+use super::{snap_angle, PixelEdit, Tool, ToolContext, ToolEvent, ToolPreview, ToolType};
+use crate::error::EngineResult;
+use crate::history::DirtyRect;
+
+/// Draws multi-stop gradients
+pub struct GradientTool {
+    start: Option<(f32, f32)>,
+    end: (f32, f32),
+    constrain: bool,
+}
+
+impl GradientTool {
+    /// Create a gradient tool
+    pub fn new() -> Self {
+        Self {
+            start: None,
+            end: (0.0, 0.0),
+            constrain: false,
+        }
+    }
+
+    fn track(&mut self, event: &ToolEvent) {
+        self.end = event.position();
+        self.constrain = event.modifiers.shift;
+    }
+
+    fn line(&self) -> Option<((f32, f32), (f32, f32))> {
+        let start = self.start?;
+        let end = if self.constrain {
+            snap_angle(start, self.end)
+        } else {
+            self.end
+        };
+        Some((start, end))
+    }
+}
+
+impl Default for GradientTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tool for GradientTool {
+    fn tool_type(&self) -> ToolType {
+        ToolType::Gradient
+    }
+
+    fn name(&self) -> &str {
+        "Gradient"
+    }
+
+    fn cursor(&self) -> &str {
+        "crosshair"
+    }
+
+    fn on_press(&mut self, _ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        self.start = Some(event.position());
+        self.track(event);
+        Ok(())
+    }
+
+    fn on_move(&mut self, _ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        if self.start.is_some() {
+            self.track(event);
+        }
+        Ok(())
+    }
+
+    fn on_release(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        self.track(event);
+        let Some((start, end)) = self.line() else {
+            return Ok(());
+        };
+        self.start = None;
+
+        let options = &ctx.options.gradient;
+        if options.position(start, end, start).is_none() {
+            return Ok(());
+        }
+
+        let mut edit = PixelEdit::begin(ctx.engine, "Gradient")?;
+        let (width, height) = edit.size();
+        let (foreground, background) = (*ctx.primary_color, *ctx.secondary_color);
+        let opacity = ctx.options.opacity;
+        edit.apply(ctx.engine, DirtyRect::full(width, height), |layer, _| {
+            options.render(
+                layer,
+                (0, 0, width, height),
+                start,
+                end,
+                foreground,
+                background,
+                opacity,
+            );
+        });
+        edit.finish(ctx.engine);
+        Ok(())
+    }
+
+    fn cancel(&mut self, _ctx: &mut ToolContext) -> EngineResult<()> {
+        self.reset();
+        Ok(())
+    }
+
+    fn preview(&self) -> ToolPreview {
+        match self.line() {
+            Some(((x0, y0), (x1, y1))) => ToolPreview::Line { x0, y0, x1, y1 },
+            None => ToolPreview::None,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.start = None;
+    }
+}
@@ -0,0 +1,137 @@
+//! Liquify brush
+//!
+//! The first press on a layer starts a liquify session; every drag after
+//! that warps the same session, so strokes can be reconstructed or refined
+//! until the session is committed as one undo step or cancelled. Alt
+//! switches to the opposite twirl, pinch or bloat, or thaws instead of
+//! freezing.
+
+use super::retouch::Dabber;
+use super::{Tool, ToolContext, ToolEvent, ToolPreview, ToolType};
+use crate::error::EngineResult;
+use crate::liquify::LiquifyMode;
+
+/// Dab spacing as a share of the brush radius
+const DAB_SPACING: f32 = 0.2;
+
+/// Warps the active layer with push, twirl, pinch, bloat and reconstruct
+/// dabs and paints the freeze mask
+pub struct LiquifyTool {
+    dabber: Dabber,
+    /// Mode for the current drag, if the pointer is down
+    mode: Option<LiquifyMode>,
+    /// Center of the previous dab, for push motion
+    last_dab: Option<(f32, f32)>,
+    cursor: Option<(f32, f32)>,
+    radius: f32,
+}
+
+impl LiquifyTool {
+    /// Create a liquify tool
+    pub fn new() -> Self {
+        Self {
+            dabber: Dabber::default(),
+            mode: None,
+            last_dab: None,
+            cursor: None,
+            radius: 0.0,
+        }
+    }
+
+    fn stamp(&mut self, ctx: &ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        let Some(mode) = self.mode else {
+            return Ok(());
+        };
+        let strength = ctx.options.liquify.strength * event.pressure;
+        for center in self.dabber.advance(event.position(), self.radius * DAB_SPACING) {
+            let motion = match self.last_dab.replace(center) {
+                Some(last) => (center.0 - last.0, center.1 - last.1),
+                None => (0.0, 0.0),
+            };
+            ctx.engine.liquify_dab(mode, center, self.radius, strength, motion)?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for LiquifyTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tool for LiquifyTool {
+    fn tool_type(&self) -> ToolType {
+        ToolType::Liquify
+    }
+
+    fn name(&self) -> &str {
+        "Liquify"
+    }
+
+    fn cursor(&self) -> &str {
+        "crosshair"
+    }
+
+    fn on_press(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        if !ctx.engine.is_liquifying() {
+            ctx.engine.begin_liquify()?;
+        }
+        let options = &ctx.options.liquify;
+        self.mode = Some(if event.modifiers.alt {
+            options.mode.alternate()
+        } else {
+            options.mode
+        });
+        self.dabber = Dabber::default();
+        self.last_dab = None;
+        self.radius = (options.size / 2.0).max(0.5);
+        self.cursor = Some(event.position());
+        self.stamp(ctx, event)
+    }
+
+    fn on_move(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        self.cursor = Some(event.position());
+        if self.mode.is_none() {
+            self.radius = ctx.options.liquify.size / 2.0;
+            return Ok(());
+        }
+        self.stamp(ctx, event)
+    }
+
+    fn on_release(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        let result = self.stamp(ctx, event);
+        self.mode = None;
+        result
+    }
+
+    fn commit(&mut self, ctx: &mut ToolContext) -> EngineResult<()> {
+        self.mode = None;
+        if ctx.engine.is_liquifying() {
+            ctx.engine.commit_liquify()?;
+        }
+        Ok(())
+    }
+
+    fn cancel(&mut self, ctx: &mut ToolContext) -> EngineResult<()> {
+        self.mode = None;
+        if ctx.engine.is_liquifying() {
+            ctx.engine.cancel_liquify()?;
+        }
+        Ok(())
+    }
+
+    fn preview(&self) -> ToolPreview {
+        match self.cursor {
+            Some((x, y)) => ToolPreview::Brush { x, y, radius: self.radius },
+            None => ToolPreview::None,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.dabber = Dabber::default();
+        self.mode = None;
+        self.last_dab = None;
+        self.cursor = None;
+    }
+}
@@ -0,0 +1,143 @@
+//! Ruler (measure tool)
+//!
+//! Dragging draws a ruler line that is kept in the engine so frontends can
+//! redraw it and read it out in any unit. Pressing near an end of a kept
+//! line drags that end instead. Shift constrains the line to multiples of
+//! 45 degrees; lines too short to measure are dropped on release.
+
+use super::{snap_angle, Tool, ToolContext, ToolEvent, ToolPreview, ToolType};
+use crate::error::EngineResult;
+use uuid::Uuid;
+
+/// Distance in pixels within which a press grabs a line end
+const HANDLE_RADIUS: f32 = 8.0;
+
+/// The line being drawn or adjusted
+struct RulerDrag {
+    id: Uuid,
+    /// End that stays put
+    anchor: (f32, f32),
+    /// Whether the moving end is the line's start
+    moving_start: bool,
+    /// Endpoints before the drag, if the line already existed
+    original: Option<((f32, f32), (f32, f32))>,
+}
+
+/// Measures distance and angle between two points
+pub struct MeasureTool {
+    drag: Option<RulerDrag>,
+    /// Line under the pointer while dragging, as (start, end)
+    line: Option<((f32, f32), (f32, f32))>,
+}
+
+impl MeasureTool {
+    /// Create a measure tool
+    pub fn new() -> Self {
+        Self { drag: None, line: None }
+    }
+
+    fn update(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        let Some(drag) = &self.drag else {
+            return Ok(());
+        };
+        let mut end = event.position();
+        if event.modifiers.shift {
+            end = snap_angle(drag.anchor, end);
+        }
+        let (start, end) = if drag.moving_start { (end, drag.anchor) } else { (drag.anchor, end) };
+        ctx.engine.update_measurement(drag.id, start, end)?;
+        self.line = Some((start, end));
+        Ok(())
+    }
+}
+
+impl Default for MeasureTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tool for MeasureTool {
+    fn tool_type(&self) -> ToolType {
+        ToolType::Measure
+    }
+
+    fn name(&self) -> &str {
+        "Ruler"
+    }
+
+    fn cursor(&self) -> &str {
+        "crosshair"
+    }
+
+    fn on_press(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        let position = event.position();
+        let near = |point: (f32, f32)| {
+            (point.0 - position.0).hypot(point.1 - position.1) <= HANDLE_RADIUS
+        };
+
+        // Newest lines are drawn on top, so they win
+        let grabbed = ctx.engine.measurements().into_iter().rev().find_map(|m| {
+            let moving_start = if near(m.end) {
+                false
+            } else if near(m.start) {
+                true
+            } else {
+                return None;
+            };
+            Some(RulerDrag {
+                id: m.id,
+                anchor: if moving_start { m.end } else { m.start },
+                moving_start,
+                original: Some((m.start, m.end)),
+            })
+        });
+        self.drag = Some(grabbed.unwrap_or_else(|| RulerDrag {
+            id: ctx.engine.add_measurement(position, position),
+            anchor: position,
+            moving_start: false,
+            original: None,
+        }));
+        self.update(ctx, event)
+    }
+
+    fn on_move(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        self.update(ctx, event)
+    }
+
+    fn on_release(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        let result = self.update(ctx, event);
+        if let (Some(drag), Some((start, end))) = (self.drag.take(), self.line.take()) {
+            if (end.0 - start.0).hypot(end.1 - start.1) < 1.0 {
+                ctx.engine.remove_measurement(drag.id);
+            }
+        }
+        result
+    }
+
+    fn cancel(&mut self, ctx: &mut ToolContext) -> EngineResult<()> {
+        self.line = None;
+        match self.drag.take() {
+            Some(RulerDrag { id, original: Some((start, end)), .. }) => {
+                ctx.engine.update_measurement(id, start, end)
+            }
+            Some(drag) => {
+                ctx.engine.remove_measurement(drag.id);
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn preview(&self) -> ToolPreview {
+        match self.line {
+            Some(((x0, y0), (x1, y1))) => ToolPreview::Line { x0, y0, x1, y1 },
+            None => ToolPreview::None,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.drag = None;
+        self.line = None;
+    }
+}
@@ -1,11 +1,62 @@
 //! Drawing tools module
-
-use crate::color::Color;
+//!
+//! Every [`ToolType`] has a concrete [`Tool`] that turns pointer events into
+//! engine operations, so all frontends share the same tool behavior. The
+//! [`ToolManager`] owns one instance per tool type and dispatches events to
+//! the current one together with a [`ToolContext`] giving access to the
+//! engine, the shared [`ToolOptions`] and the primary and secondary colors.
+//!
+//! Tools either edit the document as the pointer moves (painting, retouching,
+//! moving) or show a [`ToolPreview`] overlay while dragging and apply the
+//! result on release (shapes, gradients, selections). Tools that stay open
+//! across several clicks, such as the pen or free transform, apply their work
+//! on [`ToolManager::commit`] and discard it on [`ToolManager::cancel`].
+
+mod edit;
+mod fill;
+mod gradient;
+mod liquify;
+mod measure;
+mod navigate;
+mod paint;
+mod pen;
+mod picker;
+mod retouch;
+mod select;
+mod shape;
+mod text;
+mod transform;
+
+pub use fill::FillTool;
+pub use gradient::GradientTool;
+pub use liquify::LiquifyTool;
+pub use measure::MeasureTool;
+pub use navigate::{HandTool, ZoomTool};
+pub use paint::PaintTool;
+pub use pen::PenTool;
+pub use picker::ColorPickerTool;
+pub use retouch::{CloneTool, RetouchTool};
+pub use select::{LassoSelectTool, MagicWandTool, MarqueeTool};
+pub use shape::ShapeTool;
+pub use text::TextTool;
+pub use transform::{MoveTool, TransformTool};
+pub(crate) use edit::{copy_area, PixelEdit};
+
+use crate::color::{Color, PickerOptions};
+use crate::error::EngineResult;
+use crate::fill::BucketOptions;
+use crate::gradient::GradientOptions;
+use crate::liquify::LiquifyOptions;
+use crate::measure::MeasureOptions;
+use crate::selection::{FreeTransformOptions, SelectionMode};
+use crate::shape::ShapeOptions;
+use crate::stroke::StrokePoint;
+use crate::DrawEngine;
 use serde::{Deserialize, Serialize};
-use uuid::Uuid;
+use std::collections::HashMap;
 
 /// Tool type enumeration
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ToolType {
     /// Brush tool
     Brush,
@@ -21,6 +72,8 @@ pub enum ToolType {
     Rectangle,
     /// Ellipse tool
     Ellipse,
+    /// Polygon and star tool
+    Polygon,
     /// Selection - rectangular
     SelectRect,
     /// Selection - lasso
@@ -47,6 +100,12 @@ pub enum ToolType {
     Sharpen,
     /// Clone stamp
     Clone,
+    /// Healing brush
+    Heal,
+    /// Liquify brush
+    Liquify,
+    /// Ruler (measure distance and angle)
+    Measure,
     /// Hand tool (pan)
     Hand,
     /// Zoom tool
@@ -70,8 +129,213 @@ pub enum ToolState {
     Hover,
 }
 
+/// Modifier keys held during a pointer event
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Modifiers {
+    /// Shift: constrain proportions or angles, add to selections
+    pub shift: bool,
+    /// Alt/Option: draw from center, sample, subtract from selections
+    pub alt: bool,
+    /// Ctrl/Cmd: tool-specific alternate mode
+    pub ctrl: bool,
+}
+
+impl Modifiers {
+    /// Selection mode implied by the modifiers, falling back to `default`
+    ///
+    /// Shift adds, Alt subtracts and both together intersect.
+    pub fn selection_mode(&self, default: SelectionMode) -> SelectionMode {
+        match (self.shift, self.alt) {
+            (true, true) => SelectionMode::Intersect,
+            (true, false) => SelectionMode::Add,
+            (false, true) => SelectionMode::Subtract,
+            (false, false) => default,
+        }
+    }
+}
+
+/// A pointer event in canvas coordinates
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ToolEvent {
+    /// Canvas X position
+    pub x: f32,
+    /// Canvas Y position
+    pub y: f32,
+    /// Pressure (0.0 - 1.0), 1.0 for mice
+    #[serde(default = "default_pressure")]
+    pub pressure: f32,
+    /// Tilt X (-1.0 to 1.0)
+    #[serde(default)]
+    pub tilt_x: f32,
+    /// Tilt Y (-1.0 to 1.0)
+    #[serde(default)]
+    pub tilt_y: f32,
+    /// Timestamp in milliseconds
+    #[serde(default)]
+    pub timestamp: u64,
+    /// Modifier keys
+    #[serde(default)]
+    pub modifiers: Modifiers,
+}
+
+fn default_pressure() -> f32 {
+    1.0
+}
+
+impl ToolEvent {
+    /// Create an event at a position with the given pressure
+    pub fn new(x: f32, y: f32, pressure: f32) -> Self {
+        Self {
+            x,
+            y,
+            pressure,
+            tilt_x: 0.0,
+            tilt_y: 0.0,
+            timestamp: 0,
+            modifiers: Modifiers::default(),
+        }
+    }
+
+    /// Set the modifier keys
+    pub fn with_modifiers(mut self, modifiers: Modifiers) -> Self {
+        self.modifiers = modifiers;
+        self
+    }
+
+    /// Position as a tuple
+    pub fn position(&self) -> (f32, f32) {
+        (self.x, self.y)
+    }
+
+    /// Convert to a stroke point for the brush engine
+    pub fn to_stroke_point(&self) -> StrokePoint {
+        StrokePoint::full(
+            self.x,
+            self.y,
+            self.pressure,
+            self.tilt_x,
+            self.tilt_y,
+            0.0,
+            self.timestamp,
+        )
+    }
+}
+
+/// Overlay a frontend should draw for the current tool
+///
+/// All coordinates are in canvas pixels.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ToolPreview {
+    /// Nothing to draw
+    None,
+    /// Brush outline around the pointer
+    Brush {
+        /// Center X
+        x: f32,
+        /// Center Y
+        y: f32,
+        /// Brush radius
+        radius: f32,
+    },
+    /// Brush outline plus the point being cloned from
+    CloneSource {
+        /// Center X
+        x: f32,
+        /// Center Y
+        y: f32,
+        /// Brush radius
+        radius: f32,
+        /// Source X
+        source_x: f32,
+        /// Source Y
+        source_y: f32,
+    },
+    /// Axis-aligned rectangle outline
+    Rect {
+        /// Left edge
+        x: f32,
+        /// Top edge
+        y: f32,
+        /// Width
+        width: f32,
+        /// Height
+        height: f32,
+    },
+    /// Ellipse inscribed in a rectangle
+    Ellipse {
+        /// Left edge
+        x: f32,
+        /// Top edge
+        y: f32,
+        /// Width
+        width: f32,
+        /// Height
+        height: f32,
+    },
+    /// Straight line, e.g. a line shape or gradient direction
+    Line {
+        /// Start X
+        x0: f32,
+        /// Start Y
+        y0: f32,
+        /// End X
+        x1: f32,
+        /// End Y
+        y1: f32,
+    },
+    /// Polyline such as a lasso or flattened pen path
+    Path {
+        /// Points along the path
+        points: Vec<(f32, f32)>,
+        /// Whether the path is closed
+        closed: bool,
+        /// Anchor points to draw handles for
+        anchors: Vec<(f32, f32)>,
+    },
+    /// Transform handles around floating content
+    Handles {
+        /// Corners in order top-left, top-right, bottom-right, bottom-left
+        corners: [(f32, f32); 4],
+    },
+    /// Control points of a mesh warp
+    Mesh {
+        /// Cells across
+        columns: u32,
+        /// Cells down
+        rows: u32,
+        /// `(columns + 1) * (rows + 1)` points, row by row
+        points: Vec<(f32, f32)>,
+    },
+    /// Text insertion point
+    Caret {
+        /// Baseline X
+        x: f32,
+        /// Baseline Y
+        y: f32,
+        /// Caret height
+        height: f32,
+    },
+}
+
+/// What a tool can reach while handling an event
+pub struct ToolContext<'a> {
+    /// Engine to edit
+    pub engine: &'a DrawEngine,
+    /// Shared tool options
+    pub options: &'a ToolOptions,
+    /// Primary (foreground) color
+    pub primary_color: &'a mut Color,
+    /// Secondary (background) color
+    pub secondary_color: &'a mut Color,
+}
+
 /// Base trait for all tools
-pub trait Tool {
+///
+/// Events arrive in order press, any number of moves, release. Moves also
+/// arrive while no button is pressed so tools can update hover previews.
+pub trait Tool: Send + Sync {
     /// Get tool type
     fn tool_type(&self) -> ToolType;
 
@@ -84,21 +348,59 @@ pub trait Tool {
     }
 
     /// Handle mouse/pen down
-    fn on_press(&mut self, x: f32, y: f32, pressure: f32);
+    fn on_press(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()>;
 
-    /// Handle mouse/pen move
-    fn on_move(&mut self, x: f32, y: f32, pressure: f32);
+    /// Handle mouse/pen move, pressed or hovering
+    fn on_move(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()>;
 
     /// Handle mouse/pen up
-    fn on_release(&mut self, x: f32, y: f32);
+    fn on_release(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()>;
+
+    /// Apply work that stays open across clicks, e.g. on Enter
+    fn commit(&mut self, _ctx: &mut ToolContext) -> EngineResult<()> {
+        Ok(())
+    }
+
+    /// Cancel current operation, undoing any edits it made so far
+    fn cancel(&mut self, ctx: &mut ToolContext) -> EngineResult<()>;
 
-    /// Cancel current operation
-    fn cancel(&mut self);
+    /// Overlay to draw for the current state
+    fn preview(&self) -> ToolPreview {
+        ToolPreview::None
+    }
 
-    /// Reset tool state
+    /// Reset tool state without touching the document
     fn reset(&mut self);
 }
 
+/// Create the tool implementing a tool type
+pub fn create_tool(tool_type: ToolType) -> Box<dyn Tool> {
+    match tool_type {
+        ToolType::Brush | ToolType::Pencil | ToolType::Eraser => Box::new(PaintTool::new(tool_type)),
+        ToolType::Smudge | ToolType::Blur | ToolType::Sharpen => {
+            Box::new(RetouchTool::new(tool_type))
+        }
+        ToolType::Clone | ToolType::Heal => Box::new(CloneTool::new(tool_type)),
+        ToolType::Liquify => Box::new(LiquifyTool::new()),
+        ToolType::Line | ToolType::Rectangle | ToolType::Ellipse | ToolType::Polygon => {
+            Box::new(ShapeTool::new(tool_type))
+        }
+        ToolType::SelectRect => Box::new(MarqueeTool::new()),
+        ToolType::SelectLasso => Box::new(LassoSelectTool::new()),
+        ToolType::SelectMagic => Box::new(MagicWandTool::new()),
+        ToolType::Move => Box::new(MoveTool::new()),
+        ToolType::Transform => Box::new(TransformTool::new()),
+        ToolType::ColorPicker => Box::new(ColorPickerTool::new()),
+        ToolType::Fill => Box::new(FillTool::new()),
+        ToolType::Gradient => Box::new(GradientTool::new()),
+        ToolType::Pen => Box::new(PenTool::new()),
+        ToolType::Text => Box::new(TextTool::new()),
+        ToolType::Measure => Box::new(MeasureTool::new()),
+        ToolType::Hand => Box::new(HandTool::new()),
+        ToolType::Zoom => Box::new(ZoomTool::new()),
+    }
+}
+
 /// Tool manager
 pub struct ToolManager {
     /// Current tool type
@@ -109,10 +411,15 @@ pub struct ToolManager {
     secondary_color: Color,
     /// Tool options
     options: ToolOptions,
+    /// Tool instances, created on first use
+    tools: HashMap<ToolType, Box<dyn Tool>>,
+    /// Whether the pointer is down
+    state: ToolState,
 }
 
 /// Tool options
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ToolOptions {
     /// Brush size
     pub size: f32,
@@ -126,14 +433,30 @@ pub struct ToolOptions {
     pub smoothing: f32,
     /// Anti-aliasing
     pub anti_aliasing: bool,
-    /// Sample all layers (for picker/clone)
+    /// Sample all layers (for clone/fill/select)
     pub sample_all_layers: bool,
+    /// Keep the clone source offset between strokes (for clone/heal)
+    pub aligned: bool,
     /// Contiguous (for fill/select)
     pub contiguous: bool,
     /// Tolerance (for magic wand/fill)
     pub tolerance: f32,
     /// Feather radius
     pub feather: f32,
+    /// Gradient and how the gradient tool draws it
+    pub gradient: GradientOptions,
+    /// Shape tool settings
+    pub shape: ShapeOptions,
+    /// Paint bucket settings
+    pub bucket: BucketOptions,
+    /// Liquify brush settings
+    pub liquify: LiquifyOptions,
+    /// Free transform settings
+    pub transform: FreeTransformOptions,
+    /// Eyedropper settings
+    pub picker: PickerOptions,
+    /// Ruler settings
+    pub measure: MeasureOptions,
 }
 
 impl Default for ToolOptions {
@@ -146,9 +469,17 @@ impl Default for ToolOptions {
             smoothing: 0.5,
             anti_aliasing: true,
             sample_all_layers: false,
+            aligned: true,
             contiguous: true,
             tolerance: 32.0,
             feather: 0.0,
+            gradient: GradientOptions::default(),
+            shape: ShapeOptions::default(),
+            bucket: BucketOptions::default(),
+            liquify: LiquifyOptions::default(),
+            transform: FreeTransformOptions::default(),
+            picker: PickerOptions::default(),
+            measure: MeasureOptions::default(),
         }
     }
 }
@@ -161,6 +492,8 @@ impl ToolManager {
             primary_color: Color::black(),
             secondary_color: Color::white(),
             options: ToolOptions::default(),
+            tools: HashMap::new(),
+            state: ToolState::Idle,
         }
     }
 
@@ -170,8 +503,20 @@ impl ToolManager {
     }
 
     /// Set current tool
+    ///
+    /// The previous tool's transient state is dropped without touching the
+    /// document; use [`DrawEngine::set_tool`] to commit pending work first.
     pub fn set_tool(&mut self, tool: ToolType) {
+        if let Some(previous) = self.tools.get_mut(&self.current_tool) {
+            previous.reset();
+        }
         self.current_tool = tool;
+        self.state = ToolState::Idle;
+    }
+
+    /// Get the current pointer state
+    pub fn state(&self) -> ToolState {
+        self.state
     }
 
     /// Get primary color
@@ -225,6 +570,89 @@ impl ToolManager {
         self.options.opacity = opacity.clamp(0.0, 1.0);
     }
 
+    /// Dispatch a pointer press to the current tool
+    pub fn pointer_down(&mut self, engine: &DrawEngine, event: &ToolEvent) -> EngineResult<()> {
+        self.state = ToolState::Active;
+        let result = self.dispatch(engine, |tool, ctx| tool.on_press(ctx, event));
+        if result.is_err() {
+            self.state = ToolState::Idle;
+            self.current_mut().reset();
+        }
+        result
+    }
+
+    /// Dispatch a pointer move, pressed or hovering, to the current tool
+    pub fn pointer_move(&mut self, engine: &DrawEngine, event: &ToolEvent) -> EngineResult<()> {
+        if self.state == ToolState::Idle {
+            self.state = ToolState::Hover;
+        }
+        self.dispatch(engine, |tool, ctx| tool.on_move(ctx, event))
+    }
+
+    /// Dispatch a pointer release to the current tool
+    pub fn pointer_up(&mut self, engine: &DrawEngine, event: &ToolEvent) -> EngineResult<()> {
+        if self.state != ToolState::Active {
+            return Ok(());
+        }
+        self.state = ToolState::Hover;
+        let result = self.dispatch(engine, |tool, ctx| tool.on_release(ctx, event));
+        if result.is_err() {
+            self.current_mut().reset();
+        }
+        result
+    }
+
+    /// Apply the current tool's pending work
+    pub fn commit(&mut self, engine: &DrawEngine) -> EngineResult<()> {
+        self.dispatch(engine, |tool, ctx| tool.commit(ctx))
+    }
+
+    /// Cancel the current tool's operation
+    pub fn cancel(&mut self, engine: &DrawEngine) -> EngineResult<()> {
+        self.state = ToolState::Idle;
+        self.dispatch(engine, |tool, ctx| tool.cancel(ctx))
+    }
+
+    /// Overlay to draw for the current tool
+    pub fn preview(&self) -> ToolPreview {
+        self.tools
+            .get(&self.current_tool)
+            .map(|tool| tool.preview())
+            .unwrap_or(ToolPreview::None)
+    }
+
+    /// Drop the transient state of every tool, e.g. when a document is replaced
+    pub fn reset(&mut self) {
+        for tool in self.tools.values_mut() {
+            tool.reset();
+        }
+        self.state = ToolState::Idle;
+    }
+
+    fn current_mut(&mut self) -> &mut Box<dyn Tool> {
+        self.tools
+            .entry(self.current_tool)
+            .or_insert_with(|| create_tool(self.current_tool))
+    }
+
+    fn dispatch(
+        &mut self,
+        engine: &DrawEngine,
+        handler: impl FnOnce(&mut dyn Tool, &mut ToolContext) -> EngineResult<()>,
+    ) -> EngineResult<()> {
+        let tool = self
+            .tools
+            .entry(self.current_tool)
+            .or_insert_with(|| create_tool(self.current_tool));
+        let mut ctx = ToolContext {
+            engine,
+            options: &self.options,
+            primary_color: &mut self.primary_color,
+            secondary_color: &mut self.secondary_color,
+        };
+        handler(tool.as_mut(), &mut ctx)
+    }
+
     /// Get cursor for current tool
     pub fn cursor(&self) -> &str {
         match self.current_tool {
@@ -250,6 +678,8 @@ impl ToolManager {
                 | ToolType::Blur
                 | ToolType::Sharpen
                 | ToolType::Clone
+                | ToolType::Heal
+                | ToolType::Liquify
         )
     }
 
@@ -265,7 +695,7 @@ impl ToolManager {
     pub fn is_shape_tool(&self) -> bool {
         matches!(
             self.current_tool,
-            ToolType::Line | ToolType::Rectangle | ToolType::Ellipse
+            ToolType::Line | ToolType::Rectangle | ToolType::Ellipse | ToolType::Polygon
         )
     }
 }
@@ -276,6 +706,38 @@ impl Default for ToolManager {
     }
 }
 
+/// Constrain the vector from `start` to `end` to multiples of 45 degrees
+pub(crate) fn snap_angle(start: (f32, f32), end: (f32, f32)) -> (f32, f32) {
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    let length = (dx * dx + dy * dy).sqrt();
+    let step = std::f32::consts::FRAC_PI_4;
+    let angle = (dy.atan2(dx) / step).round() * step;
+    (start.0 + length * angle.cos(), start.1 + length * angle.sin())
+}
+
+/// Rectangle spanned by a drag as (x, y, width, height)
+///
+/// `square` makes both sides the longer one; `from_center` treats `start` as
+/// the center rather than a corner.
+pub(crate) fn drag_rect(
+    start: (f32, f32),
+    end: (f32, f32),
+    square: bool,
+    from_center: bool,
+) -> (f32, f32, f32, f32) {
+    let (mut dx, mut dy) = (end.0 - start.0, end.1 - start.1);
+    if square {
+        let side = dx.abs().max(dy.abs());
+        dx = side.copysign(dx);
+        dy = side.copysign(dy);
+    }
+    if from_center {
+        (start.0 - dx.abs(), start.1 - dy.abs(), dx.abs() * 2.0, dy.abs() * 2.0)
+    } else {
+        (start.0.min(start.0 + dx), start.1.min(start.1 + dy), dx.abs(), dy.abs())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -310,4 +772,35 @@ mod tests {
         manager.set_tool(ToolType::SelectRect);
         assert!(manager.is_selection_tool());
     }
+
+    #[test]
+    fn test_every_tool_type_has_a_tool() {
+        use ToolType::*;
+        for tool_type in [
+            Brush, Eraser, Pencil, Pen, Line, Rectangle, Ellipse, Polygon, SelectRect,
+            SelectLasso, SelectMagic, Move, Transform, ColorPicker, Fill, Gradient, Text, Smudge,
+            Blur, Sharpen, Clone, Heal, Liquify, Measure, Hand, Zoom,
+        ] {
+            assert_eq!(create_tool(tool_type).tool_type(), tool_type);
+        }
+    }
+
+    #[test]
+    fn test_modifier_selection_modes() {
+        let shift = Modifiers { shift: true, ..Modifiers::default() };
+        let both = Modifiers { shift: true, alt: true, ..Modifiers::default() };
+        assert_eq!(shift.selection_mode(SelectionMode::Replace), SelectionMode::Add);
+        assert_eq!(both.selection_mode(SelectionMode::Replace), SelectionMode::Intersect);
+        assert_eq!(Modifiers::default().selection_mode(SelectionMode::Subtract), SelectionMode::Subtract);
+    }
+
+    #[test]
+    fn test_drag_constraints() {
+        assert_eq!(drag_rect((10.0, 10.0), (4.0, 14.0), false, false), (4.0, 10.0, 6.0, 4.0));
+        assert_eq!(drag_rect((10.0, 10.0), (4.0, 14.0), true, false), (4.0, 10.0, 6.0, 6.0));
+        assert_eq!(drag_rect((10.0, 10.0), (12.0, 13.0), false, true), (8.0, 7.0, 4.0, 6.0));
+
+        let (x, y) = snap_angle((0.0, 0.0), (10.0, 1.0));
+        assert!((x - 10.05).abs() < 0.01 && y.abs() < 1e-4);
+    }
 }
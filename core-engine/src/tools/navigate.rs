@@ -0,0 +1,139 @@
+//! Hand and zoom
+//!
+//! Both tools change the render pipeline's view, where a canvas point `p`
+//! appears on screen at `p * zoom + pan`.
+
+use super::{Tool, ToolContext, ToolEvent, ToolType};
+use crate::error::EngineResult;
+
+/// Smallest zoom level
+const MIN_ZOOM: f32 = 0.01;
+/// Largest zoom level
+const MAX_ZOOM: f32 = 64.0;
+
+/// Pans the view so the grabbed canvas point follows the pointer
+///
+/// Events are in canvas coordinates, which the frontend recomputes from the
+/// updated view, so the grabbed point stays fixed in canvas space.
+pub struct HandTool {
+    grab: Option<(f32, f32)>,
+}
+
+impl HandTool {
+    /// Create a hand tool
+    pub fn new() -> Self {
+        Self { grab: None }
+    }
+}
+
+impl Default for HandTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tool for HandTool {
+    fn tool_type(&self) -> ToolType {
+        ToolType::Hand
+    }
+
+    fn name(&self) -> &str {
+        "Hand"
+    }
+
+    fn cursor(&self) -> &str {
+        "grab"
+    }
+
+    fn on_press(&mut self, _ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        self.grab = Some(event.position());
+        Ok(())
+    }
+
+    fn on_move(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        let Some(grab) = self.grab else {
+            return Ok(());
+        };
+        let render_pipeline = ctx.engine.render_pipeline();
+        let mut render_pipeline = render_pipeline.write();
+        let context = render_pipeline.context();
+        let zoom = context.zoom;
+        let pan_x = context.pan_x + (event.x - grab.0) * zoom;
+        let pan_y = context.pan_y + (event.y - grab.1) * zoom;
+        render_pipeline.set_view(zoom, pan_x, pan_y);
+        Ok(())
+    }
+
+    fn on_release(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        self.on_move(ctx, event)?;
+        self.grab = None;
+        Ok(())
+    }
+
+    fn cancel(&mut self, _ctx: &mut ToolContext) -> EngineResult<()> {
+        self.grab = None;
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.grab = None;
+    }
+}
+
+/// Zooms in by a factor of two around the clicked point, or out with Alt
+pub struct ZoomTool;
+
+impl ZoomTool {
+    /// Create a zoom tool
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for ZoomTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tool for ZoomTool {
+    fn tool_type(&self) -> ToolType {
+        ToolType::Zoom
+    }
+
+    fn name(&self) -> &str {
+        "Zoom"
+    }
+
+    fn cursor(&self) -> &str {
+        "zoom-in"
+    }
+
+    fn on_press(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        let render_pipeline = ctx.engine.render_pipeline();
+        let mut render_pipeline = render_pipeline.write();
+        let context = render_pipeline.context();
+        let factor = if event.modifiers.alt { 0.5 } else { 2.0 };
+        let zoom = (context.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+
+        // Keep the clicked point under the pointer
+        let screen_x = event.x * context.zoom + context.pan_x;
+        let screen_y = event.y * context.zoom + context.pan_y;
+        render_pipeline.set_view(zoom, screen_x - event.x * zoom, screen_y - event.y * zoom);
+        Ok(())
+    }
+
+    fn on_move(&mut self, _ctx: &mut ToolContext, _event: &ToolEvent) -> EngineResult<()> {
+        Ok(())
+    }
+
+    fn on_release(&mut self, _ctx: &mut ToolContext, _event: &ToolEvent) -> EngineResult<()> {
+        Ok(())
+    }
+
+    fn cancel(&mut self, _ctx: &mut ToolContext) -> EngineResult<()> {
+        Ok(())
+    }
+
+    fn reset(&mut self) {}
+}
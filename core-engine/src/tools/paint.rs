@@ -0,0 +1,157 @@
+//! Brush, pencil and eraser
+
+use super::{Tool, ToolContext, ToolEvent, ToolPreview, ToolType};
+use crate::brush::{BrushMode, BrushSettings};
+use crate::color::Color;
+use crate::error::EngineResult;
+
+/// Paints engine strokes with the current brush
+///
+/// The brush tip comes from the brush engine while size, opacity, hardness,
+/// flow and smoothing come from the tool options. The pencil always paints
+/// hard, aliased edges and the eraser paints in eraser mode. The brush
+/// engine's own settings are restored after every stroke.
+pub struct PaintTool {
+    tool_type: ToolType,
+    /// Brush settings, mode and color to restore after the stroke
+    saved: Option<(BrushSettings, BrushMode, Color)>,
+    /// Points sent to the engine in the current stroke
+    points: usize,
+    last: Option<(f32, f32)>,
+    radius: f32,
+}
+
+impl PaintTool {
+    /// Create a paint tool for [`ToolType::Brush`], [`ToolType::Pencil`] or
+    /// [`ToolType::Eraser`]
+    pub fn new(tool_type: ToolType) -> Self {
+        Self {
+            tool_type,
+            saved: None,
+            points: 0,
+            last: None,
+            radius: 0.0,
+        }
+    }
+
+    fn configure_brush(&mut self, ctx: &ToolContext) {
+        let brush_engine = ctx.engine.brush_engine();
+        let mut brush_engine = brush_engine.write();
+        self.saved = Some((
+            brush_engine.current_brush().settings.clone(),
+            brush_engine.current_mode(),
+            *brush_engine.current_color(),
+        ));
+
+        let options = ctx.options;
+        let settings = &mut brush_engine.current_brush_mut().settings;
+        settings.size = options.size;
+        settings.opacity = options.opacity;
+        settings.hardness = options.hardness;
+        settings.flow = options.flow;
+        settings.smoothing = options.smoothing;
+        settings.anti_aliasing = options.anti_aliasing;
+        if self.tool_type == ToolType::Pencil {
+            settings.hardness = 1.0;
+            settings.anti_aliasing = false;
+        }
+
+        brush_engine.set_color(*ctx.primary_color);
+        brush_engine.set_mode(if self.tool_type == ToolType::Eraser {
+            BrushMode::Eraser
+        } else {
+            BrushMode::Normal
+        });
+    }
+
+    fn restore_brush(&mut self, ctx: &ToolContext) {
+        if let Some((settings, mode, color)) = self.saved.take() {
+            let brush_engine = ctx.engine.brush_engine();
+            let mut brush_engine = brush_engine.write();
+            brush_engine.current_brush_mut().settings = settings;
+            brush_engine.set_mode(mode);
+            brush_engine.set_color(color);
+        }
+    }
+
+    fn add_point(&mut self, ctx: &ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        ctx.engine.add_stroke_point(event.to_stroke_point())?;
+        self.points += 1;
+        Ok(())
+    }
+}
+
+impl Tool for PaintTool {
+    fn tool_type(&self) -> ToolType {
+        self.tool_type
+    }
+
+    fn name(&self) -> &str {
+        match self.tool_type {
+            ToolType::Pencil => "Pencil",
+            ToolType::Eraser => "Eraser",
+            _ => "Brush",
+        }
+    }
+
+    fn cursor(&self) -> &str {
+        "crosshair"
+    }
+
+    fn on_press(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        self.configure_brush(ctx);
+        self.points = 0;
+        self.radius = ctx.options.size / 2.0;
+        self.last = Some(event.position());
+        if let Err(e) = ctx.engine.begin_stroke() {
+            self.restore_brush(ctx);
+            return Err(e);
+        }
+        self.add_point(ctx, event)
+    }
+
+    fn on_move(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        self.last = Some(event.position());
+        self.radius = ctx.options.size / 2.0;
+        if self.saved.is_none() {
+            return Ok(());
+        }
+        self.add_point(ctx, event)
+    }
+
+    fn on_release(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        if self.saved.is_none() {
+            return Ok(());
+        }
+        // A single click still leaves a dab
+        let result = if self.points < 2 || self.last != Some(event.position()) {
+            self.add_point(ctx, event)
+        } else {
+            Ok(())
+        };
+        let ended = ctx.engine.end_stroke();
+        self.restore_brush(ctx);
+        result.and(ended)
+    }
+
+    fn cancel(&mut self, ctx: &mut ToolContext) -> EngineResult<()> {
+        if self.saved.is_some() {
+            ctx.engine.cancel_stroke();
+            self.restore_brush(ctx);
+        }
+        Ok(())
+    }
+
+    fn preview(&self) -> ToolPreview {
+        match self.last {
+            Some((x, y)) => ToolPreview::Brush { x, y, radius: self.radius },
+            None => ToolPreview::None,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.saved = None;
+        self.points = 0;
+        self.last = None;
+    }
+}
@@ -0,0 +1,209 @@
+//! Pen tool
+//!
+//! Clicks place anchor points and dragging from a new anchor pulls out
+//! symmetric Bezier handles. Clicking the first anchor again, or committing,
+//! closes the path and turns it into a selection, combined according to the
+//! modifiers held on the first click.
+
+use super::select::{apply_selection, canvas_size};
+use super::{Modifiers, Tool, ToolContext, ToolEvent, ToolPreview, ToolType};
+use crate::error::EngineResult;
+use crate::selection::Selection;
+
+/// Distance in pixels within which a click on the first anchor closes the path
+const CLOSE_DISTANCE: f32 = 6.0;
+
+/// Line segments per Bezier curve when flattening
+const CURVE_STEPS: usize = 16;
+
+/// A path anchor with an optional outgoing handle
+///
+/// The incoming handle mirrors the outgoing one.
+#[derive(Debug, Clone, Copy)]
+struct Anchor {
+    point: (f32, f32),
+    handle: Option<(f32, f32)>,
+}
+
+impl Anchor {
+    fn handle_out(&self) -> (f32, f32) {
+        self.handle.unwrap_or(self.point)
+    }
+
+    fn handle_in(&self) -> (f32, f32) {
+        match self.handle {
+            Some((hx, hy)) => (2.0 * self.point.0 - hx, 2.0 * self.point.1 - hy),
+            None => self.point,
+        }
+    }
+}
+
+/// Draws Bezier paths that become selections
+pub struct PenTool {
+    anchors: Vec<Anchor>,
+    dragging: bool,
+    hover: Option<(f32, f32)>,
+    modifiers: Modifiers,
+}
+
+impl PenTool {
+    /// Create a pen tool
+    pub fn new() -> Self {
+        Self {
+            anchors: Vec::new(),
+            dragging: false,
+            hover: None,
+            modifiers: Modifiers::default(),
+        }
+    }
+
+    /// Anchor positions of the path being drawn
+    pub fn anchors(&self) -> Vec<(f32, f32)> {
+        self.anchors.iter().map(|anchor| anchor.point).collect()
+    }
+
+    /// The path as a polyline, optionally closed back to the first anchor
+    pub fn flatten(&self, closed: bool) -> Vec<(f32, f32)> {
+        let Some(first) = self.anchors.first() else {
+            return Vec::new();
+        };
+        let mut points = vec![first.point];
+        let mut segments: Vec<_> = self.anchors.windows(2).map(|pair| (pair[0], pair[1])).collect();
+        if closed && self.anchors.len() > 2 {
+            segments.push((*self.anchors.last().expect("not empty"), *first));
+        }
+
+        for (from, to) in segments {
+            let (p0, p1, p2, p3) = (from.point, from.handle_out(), to.handle_in(), to.point);
+            for step in 1..=CURVE_STEPS {
+                let t = step as f32 / CURVE_STEPS as f32;
+                let u = 1.0 - t;
+                let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+                points.push((
+                    a * p0.0 + b * p1.0 + c * p2.0 + d * p3.0,
+                    a * p0.1 + b * p1.1 + c * p2.1 + d * p3.1,
+                ));
+            }
+        }
+        if closed && points.len() > 1 && points.first() == points.last() {
+            points.pop();
+        }
+        points
+    }
+
+    fn close(&mut self, ctx: &mut ToolContext) {
+        let points = self.flatten(true);
+        self.reset();
+        if points.len() < 3 {
+            return;
+        }
+        let (width, height) = canvas_size(ctx.engine);
+        let selection = Selection::lasso(points, width, height);
+        apply_selection(ctx.engine, ctx.options, self.modifiers, selection);
+    }
+}
+
+impl Default for PenTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tool for PenTool {
+    fn tool_type(&self) -> ToolType {
+        ToolType::Pen
+    }
+
+    fn name(&self) -> &str {
+        "Pen"
+    }
+
+    fn cursor(&self) -> &str {
+        "crosshair"
+    }
+
+    fn on_press(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        if let Some(first) = self.anchors.first() {
+            let distance = (event.x - first.point.0).hypot(event.y - first.point.1);
+            if self.anchors.len() > 2 && distance <= CLOSE_DISTANCE {
+                self.close(ctx);
+                return Ok(());
+            }
+        } else {
+            self.modifiers = event.modifiers;
+        }
+        self.anchors.push(Anchor { point: event.position(), handle: None });
+        self.dragging = true;
+        Ok(())
+    }
+
+    fn on_move(&mut self, _ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        self.hover = Some(event.position());
+        if !self.dragging {
+            return Ok(());
+        }
+        if let Some(anchor) = self.anchors.last_mut() {
+            let pulled = (event.x - anchor.point.0).hypot(event.y - anchor.point.1) >= 1.0;
+            anchor.handle = pulled.then_some(event.position());
+        }
+        Ok(())
+    }
+
+    fn on_release(&mut self, _ctx: &mut ToolContext, _event: &ToolEvent) -> EngineResult<()> {
+        self.dragging = false;
+        Ok(())
+    }
+
+    fn commit(&mut self, ctx: &mut ToolContext) -> EngineResult<()> {
+        self.close(ctx);
+        Ok(())
+    }
+
+    fn cancel(&mut self, _ctx: &mut ToolContext) -> EngineResult<()> {
+        self.reset();
+        Ok(())
+    }
+
+    fn preview(&self) -> ToolPreview {
+        if self.anchors.is_empty() {
+            return ToolPreview::None;
+        }
+        let mut points = self.flatten(false);
+        if let (false, Some(hover)) = (self.dragging, self.hover) {
+            points.push(hover);
+        }
+        ToolPreview::Path {
+            points,
+            closed: false,
+            anchors: self.anchors(),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.anchors.clear();
+        self.dragging = false;
+        self.hover = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flatten_straight_and_curved_segments() {
+        let mut pen = PenTool::new();
+        pen.anchors.push(Anchor { point: (0.0, 0.0), handle: None });
+        pen.anchors.push(Anchor { point: (10.0, 0.0), handle: None });
+        let line = pen.flatten(false);
+        assert_eq!(line.len(), CURVE_STEPS + 1);
+        assert!(line.iter().all(|p| p.1.abs() < 1e-5));
+
+        pen.anchors[0].handle = Some((0.0, 10.0));
+        pen.anchors.push(Anchor { point: (10.0, 10.0), handle: None });
+        let closed = pen.flatten(true);
+        assert_eq!(closed.len(), CURVE_STEPS * 3);
+        // The handle pulls the first segment below the straight line
+        assert!(closed[CURVE_STEPS / 2].1 > 1.0);
+    }
+}
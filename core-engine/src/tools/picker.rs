@@ -0,0 +1,108 @@
+//! Color picker (eyedropper)
+
+use super::{Tool, ToolContext, ToolEvent, ToolPreview, ToolType};
+use crate::color::Color;
+use crate::error::EngineResult;
+
+/// Picks the primary color, or the secondary color with Alt, while dragging
+///
+/// Samples the area and layers set in the picker options. The color under
+/// the pointer when it is released goes into the color history.
+pub struct ColorPickerTool {
+    pressed: bool,
+    /// Last color picked during the current drag
+    picked: Option<Color>,
+    /// Sampled area around the pointer, as (x, y, size)
+    area: Option<(f32, f32, f32)>,
+}
+
+impl ColorPickerTool {
+    /// Create a color picker tool
+    pub fn new() -> Self {
+        Self {
+            pressed: false,
+            picked: None,
+            area: None,
+        }
+    }
+
+    fn sample(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        if event.x < 0.0 || event.y < 0.0 {
+            return Ok(());
+        }
+        let (x, y) = (event.x as u32, event.y as u32);
+        let options = ctx.options.picker;
+        let Some(color) = ctx.engine.sample_color(x, y, &options)? else {
+            return Ok(());
+        };
+
+        let radius = options.sample_size.radius() as f32;
+        self.area = Some((x as f32 - radius, y as f32 - radius, radius * 2.0 + 1.0));
+        self.picked = Some(color);
+        if event.modifiers.alt {
+            *ctx.secondary_color = color;
+        } else {
+            *ctx.primary_color = color;
+        }
+        Ok(())
+    }
+}
+
+impl Default for ColorPickerTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tool for ColorPickerTool {
+    fn tool_type(&self) -> ToolType {
+        ToolType::ColorPicker
+    }
+
+    fn name(&self) -> &str {
+        "Eyedropper"
+    }
+
+    fn cursor(&self) -> &str {
+        "eyedropper"
+    }
+
+    fn on_press(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        self.pressed = true;
+        self.sample(ctx, event)
+    }
+
+    fn on_move(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        if self.pressed {
+            self.sample(ctx, event)?;
+        }
+        Ok(())
+    }
+
+    fn on_release(&mut self, ctx: &mut ToolContext, _event: &ToolEvent) -> EngineResult<()> {
+        self.pressed = false;
+        self.area = None;
+        if let Some(color) = self.picked.take().filter(|color| color.a > 0.0) {
+            ctx.engine.color_manager().write().add_to_history(color);
+        }
+        Ok(())
+    }
+
+    fn cancel(&mut self, _ctx: &mut ToolContext) -> EngineResult<()> {
+        self.reset();
+        Ok(())
+    }
+
+    fn preview(&self) -> ToolPreview {
+        match self.area {
+            Some((x, y, size)) if size > 1.0 => ToolPreview::Rect { x, y, width: size, height: size },
+            _ => ToolPreview::None,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.pressed = false;
+        self.picked = None;
+        self.area = None;
+    }
+}
@@ -0,0 +1,685 @@
+//! Smudge, blur, sharpen, clone stamp and healing brush
+//!
+//! Retouching tools stamp dabs along the pointer path and edit the layer
+//! pixels under each dab directly. Smudge, blur and sharpen use circular dabs
+//! whose falloff follows the hardness and whose strength is opacity times
+//! flow; the clone tools stamp the current brush's tip. A whole drag is one
+//! undo step.
+
+use super::{PixelEdit, Tool, ToolContext, ToolEvent, ToolOptions, ToolPreview, ToolType};
+use crate::brush::{Brush, BrushStamp};
+use crate::error::{EngineError, EngineResult};
+use crate::heal;
+use crate::history::DirtyRect;
+use crate::layer::Layer;
+
+/// Dab opacity at `distance` from the center of a dab
+pub(crate) fn dab_falloff(distance: f32, radius: f32, hardness: f32) -> f32 {
+    if distance >= radius {
+        return 0.0;
+    }
+    let inner = radius * hardness.clamp(0.0, 1.0);
+    if distance <= inner {
+        return 1.0;
+    }
+    let t = (distance - inner) / (radius - inner);
+    1.0 - t * t * (3.0 - 2.0 * t)
+}
+
+/// Places evenly spaced dabs along the pointer path
+#[derive(Debug, Default)]
+pub(super) struct Dabber {
+    last: Option<(f32, f32)>,
+}
+
+impl Dabber {
+    /// Dab centers up to `to`, starting with a dab at the first position
+    pub(super) fn advance(&mut self, to: (f32, f32), spacing: f32) -> Vec<(f32, f32)> {
+        let Some(from) = self.last else {
+            self.last = Some(to);
+            return vec![to];
+        };
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        let distance = (dx * dx + dy * dy).sqrt();
+        let spacing = spacing.max(1.0);
+        let count = (distance / spacing).floor() as usize;
+        let dabs: Vec<_> = (1..=count)
+            .map(|i| {
+                let t = i as f32 * spacing / distance;
+                (from.0 + dx * t, from.1 + dy * t)
+            })
+            .collect();
+        if let Some(&last) = dabs.last() {
+            self.last = Some(last);
+        }
+        dabs
+    }
+}
+
+/// Pixel range covered by a dab, clamped to the layer
+fn dab_rect(center: (f32, f32), radius: f32, width: u32, height: u32) -> DirtyRect {
+    let x0 = (center.0 - radius).floor().max(0.0) as u32;
+    let y0 = (center.1 - radius).floor().max(0.0) as u32;
+    let x1 = ((center.0 + radius).ceil().max(0.0) as u32 + 1).min(width);
+    let y1 = ((center.1 + radius).ceil().max(0.0) as u32 + 1).min(height);
+    DirtyRect::from_bounds(x0, y0, x1.max(x0), y1.max(y0))
+}
+
+/// Premultiplied RGBA at a byte offset
+fn load(pixels: &[u8], idx: usize) -> [f32; 4] {
+    let alpha = pixels[idx + 3] as f32 / 255.0;
+    [
+        pixels[idx] as f32 * alpha,
+        pixels[idx + 1] as f32 * alpha,
+        pixels[idx + 2] as f32 * alpha,
+        pixels[idx + 3] as f32,
+    ]
+}
+
+/// Store premultiplied RGBA at a byte offset
+fn store(pixels: &mut [u8], idx: usize, color: [f32; 4]) {
+    let alpha = color[3].clamp(0.0, 255.0);
+    let scale = if alpha > 0.0 { 255.0 / alpha } else { 0.0 };
+    for channel in 0..3 {
+        pixels[idx + channel] = (color[channel] * scale).round().clamp(0.0, 255.0) as u8;
+    }
+    pixels[idx + 3] = alpha.round() as u8;
+}
+
+fn lerp(a: [f32; 4], b: [f32; 4], t: f32) -> [f32; 4] {
+    [0, 1, 2, 3].map(|i| a[i] + (b[i] - a[i]) * t)
+}
+
+/// Dab spacing for a brush radius
+fn spacing(radius: f32) -> f32 {
+    radius * 0.25
+}
+
+/// Smudges, blurs or sharpens under the brush
+pub struct RetouchTool {
+    tool_type: ToolType,
+    edit: Option<PixelEdit>,
+    dabber: Dabber,
+    /// Colors picked up by the smudge tool, one per pixel of the dab square
+    carried: Vec<Option<[f32; 4]>>,
+    cursor: Option<(f32, f32)>,
+    radius: f32,
+}
+
+impl RetouchTool {
+    /// Create a retouch tool for [`ToolType::Smudge`], [`ToolType::Blur`] or
+    /// [`ToolType::Sharpen`]
+    pub fn new(tool_type: ToolType) -> Self {
+        Self {
+            tool_type,
+            edit: None,
+            dabber: Dabber::default(),
+            carried: Vec::new(),
+            cursor: None,
+            radius: 0.0,
+        }
+    }
+
+    fn stamp(&mut self, ctx: &ToolContext, to: (f32, f32)) {
+        let Some(edit) = self.edit.as_mut() else {
+            return;
+        };
+        let options = ctx.options;
+        let radius = self.radius;
+        let strength = (options.opacity * options.flow).clamp(0.0, 1.0);
+        let (width, height) = edit.size();
+
+        for center in self.dabber.advance(to, spacing(radius)) {
+            let area = dab_rect(center, radius, width, height);
+            let tool_type = self.tool_type;
+            let carried = &mut self.carried;
+            edit.apply(ctx.engine, area, |layer, _| match tool_type {
+                ToolType::Smudge => smudge_dab(layer, center, radius, strength, options, carried),
+                _ => filter_dab(layer, area, center, radius, strength, options, tool_type),
+            });
+        }
+    }
+}
+
+/// Blur or sharpen the pixels under a dab with a 3x3 box kernel
+fn filter_dab(
+    layer: &mut Layer,
+    area: DirtyRect,
+    center: (f32, f32),
+    radius: f32,
+    strength: f32,
+    options: &ToolOptions,
+    tool_type: ToolType,
+) {
+    let (width, height) = (layer.width(), layer.height());
+    let source = layer.pixels.clone();
+    for y in area.y..area.y + area.height {
+        for x in area.x..area.x + area.width {
+            let distance = (x as f32 + 0.5 - center.0).hypot(y as f32 + 0.5 - center.1);
+            let weight = dab_falloff(distance, radius, options.hardness) * strength;
+            if weight <= 0.0 {
+                continue;
+            }
+
+            let mut average = [0.0f32; 4];
+            let mut count = 0.0;
+            for ny in y.saturating_sub(1)..=(y + 1).min(height - 1) {
+                for nx in x.saturating_sub(1)..=(x + 1).min(width - 1) {
+                    let color = load(&source, ((ny * width + nx) * 4) as usize);
+                    for (total, value) in average.iter_mut().zip(color) {
+                        *total += value;
+                    }
+                    count += 1.0;
+                }
+            }
+            let average = average.map(|value| value / count);
+
+            let idx = ((y * width + x) * 4) as usize;
+            let current = load(&source, idx);
+            let result = if tool_type == ToolType::Sharpen {
+                // Push away from the local average, keeping alpha
+                let sharpened = lerp(current, average, -weight);
+                let alpha = current[3];
+                [0, 1, 2, 3].map(|i| if i == 3 { alpha } else { sharpened[i].clamp(0.0, alpha) })
+            } else {
+                lerp(current, average, weight)
+            };
+            store(&mut layer.pixels, idx, result);
+        }
+    }
+}
+
+/// Smear carried colors into the pixels under a dab and pick up new ones
+fn smudge_dab(
+    layer: &mut Layer,
+    center: (f32, f32),
+    radius: f32,
+    strength: f32,
+    options: &ToolOptions,
+    carried: &mut Vec<Option<[f32; 4]>>,
+) {
+    let (width, height) = (layer.width() as i32, layer.height() as i32);
+    let reach = radius.ceil() as i32;
+    let side = (reach * 2 + 1) as usize;
+    let first = carried.is_empty();
+    if first {
+        carried.resize(side * side, None);
+    }
+    let (cx, cy) = (center.0.floor() as i32, center.1.floor() as i32);
+
+    for dy in -reach..=reach {
+        for dx in -reach..=reach {
+            let (x, y) = (cx + dx, cy + dy);
+            if x < 0 || y < 0 || x >= width || y >= height {
+                continue;
+            }
+            let slot = ((dy + reach) as usize) * side + (dx + reach) as usize;
+            let idx = ((y * width + x) * 4) as usize;
+            let current = load(&layer.pixels, idx);
+            let distance = (x as f32 + 0.5 - center.0).hypot(y as f32 + 0.5 - center.1);
+            let weight = dab_falloff(distance, radius, options.hardness) * strength;
+
+            match carried[slot] {
+                Some(color) if !first && weight > 0.0 => {
+                    let smeared = lerp(current, color, weight);
+                    store(&mut layer.pixels, idx, smeared);
+                    carried[slot] = Some(lerp(color, current, 1.0 - strength));
+                }
+                _ => carried[slot] = Some(current),
+            }
+        }
+    }
+}
+
+impl Tool for RetouchTool {
+    fn tool_type(&self) -> ToolType {
+        self.tool_type
+    }
+
+    fn name(&self) -> &str {
+        match self.tool_type {
+            ToolType::Smudge => "Smudge",
+            ToolType::Sharpen => "Sharpen",
+            _ => "Blur",
+        }
+    }
+
+    fn cursor(&self) -> &str {
+        "crosshair"
+    }
+
+    fn on_press(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        let description = match self.tool_type {
+            ToolType::Smudge => "Smudge",
+            ToolType::Sharpen => "Sharpen",
+            _ => "Blur",
+        };
+        self.edit = Some(PixelEdit::begin(ctx.engine, description)?);
+        self.dabber = Dabber::default();
+        self.carried.clear();
+        self.radius = (ctx.options.size / 2.0).max(0.5);
+        self.cursor = Some(event.position());
+        self.stamp(ctx, event.position());
+        Ok(())
+    }
+
+    fn on_move(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        self.cursor = Some(event.position());
+        if self.edit.is_none() {
+            self.radius = ctx.options.size / 2.0;
+            return Ok(());
+        }
+        self.stamp(ctx, event.position());
+        Ok(())
+    }
+
+    fn on_release(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        self.stamp(ctx, event.position());
+        if let Some(edit) = self.edit.take() {
+            edit.finish(ctx.engine);
+        }
+        Ok(())
+    }
+
+    fn cancel(&mut self, _ctx: &mut ToolContext) -> EngineResult<()> {
+        if let Some(edit) = self.edit.take() {
+            edit.abort();
+        }
+        Ok(())
+    }
+
+    fn preview(&self) -> ToolPreview {
+        match self.cursor {
+            Some((x, y)) => ToolPreview::Brush { x, y, radius: self.radius },
+            None => ToolPreview::None,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.edit = None;
+        self.dabber = Dabber::default();
+        self.carried.clear();
+        self.cursor = None;
+    }
+}
+
+/// Paints pixels copied from a source point, or heals with them
+///
+/// Alt-click sets the source. Aligned strokes keep the offset between the
+/// source and the first stroke until a new source is set; otherwise every
+/// stroke starts cloning from the source point again. With "sample all
+/// layers" the merged image is cloned. Dabs use the current brush's tip,
+/// spacing and pressure curves with the tool's size, opacity, hardness and
+/// flow.
+///
+/// The healing brush clones the same way while dragging, then on release
+/// blends the stroke in the gradient domain so the cloned texture takes on
+/// the color and luminance around it.
+pub struct CloneTool {
+    tool_type: ToolType,
+    source: Option<(f32, f32)>,
+    /// Offset kept between aligned strokes
+    offset: Option<(f32, f32)>,
+    /// Offset of the current stroke
+    stroke_offset: Option<(f32, f32)>,
+    edit: Option<PixelEdit>,
+    /// Pixels to clone from, captured when the stroke starts
+    sample: Vec<u8>,
+    /// Current brush with the tool options applied
+    brush: Option<Brush>,
+    dabber: Dabber,
+    /// Position and pressure of the previous event in the stroke
+    last: Option<((f32, f32), f32)>,
+    /// Combined dab coverage of the stroke, one value per pixel, for healing
+    coverage: Vec<f32>,
+    /// Pixels the stroke has touched
+    area: Option<DirtyRect>,
+    cursor: Option<(f32, f32)>,
+    radius: f32,
+}
+
+impl CloneTool {
+    /// Create a clone tool for [`ToolType::Clone`] or [`ToolType::Heal`]
+    pub fn new(tool_type: ToolType) -> Self {
+        Self {
+            tool_type,
+            source: None,
+            offset: None,
+            stroke_offset: None,
+            edit: None,
+            sample: Vec::new(),
+            brush: None,
+            dabber: Dabber::default(),
+            last: None,
+            coverage: Vec::new(),
+            area: None,
+            cursor: None,
+            radius: 0.0,
+        }
+    }
+
+    /// The point clones are currently taken from
+    pub fn source(&self) -> Option<(f32, f32)> {
+        self.source
+    }
+
+    fn stamp(&mut self, ctx: &ToolContext, to: (f32, f32), pressure: f32) {
+        let (Some(edit), Some(offset), Some(brush)) =
+            (self.edit.as_mut(), self.stroke_offset, self.brush.as_ref())
+        else {
+            return;
+        };
+        let (from, from_pressure) = self.last.unwrap_or((to, pressure));
+        self.last = Some((to, pressure));
+        let length = (to.0 - from.0).hypot(to.1 - from.1);
+        let (width, height) = edit.size();
+        let spacing = brush.settings.spacing * brush.size_at_pressure(pressure);
+
+        for center in self.dabber.advance(to, spacing) {
+            let t = if length > 0.0 {
+                ((center.0 - from.0).hypot(center.1 - from.1) / length).min(1.0)
+            } else {
+                1.0
+            };
+            let pressure = from_pressure + (pressure - from_pressure) * t;
+            let stamp = brush.generate_stamp(
+                brush.size_at_pressure(pressure),
+                brush.hardness_at_pressure(pressure),
+                0.0,
+            );
+            let strength = (brush.opacity_at_pressure(pressure) * brush.settings.flow).clamp(0.0, 1.0);
+            let half = stamp.size as f32 / 2.0;
+            let origin = ((center.0 - half).floor() as i64, (center.1 - half).floor() as i64);
+            let clamp = |v: i64, max: u32| v.clamp(0, max as i64) as u32;
+            let area = DirtyRect::from_bounds(
+                clamp(origin.0, width),
+                clamp(origin.1, height),
+                clamp(origin.0 + stamp.size as i64, width),
+                clamp(origin.1 + stamp.size as i64, height),
+            );
+            if area.is_empty() {
+                continue;
+            }
+
+            let sample = &self.sample;
+            let coverage = &mut self.coverage;
+            edit.apply(ctx.engine, area, |layer, _| {
+                clone_dab(layer, sample, coverage, &stamp, origin, offset, strength);
+            });
+            self.area.get_or_insert(area).union(&area);
+        }
+    }
+
+    /// Redo the stroke as a gradient-domain blend of the clone into the layer
+    fn heal_stroke(&mut self, ctx: &ToolContext) {
+        let (Some(edit), Some(offset), Some(mut area)) = (self.edit.as_mut(), self.stroke_offset, self.area)
+        else {
+            return;
+        };
+        let (width, height) = edit.size();
+        // Leave a ring of untouched pixels for the blend to meet
+        area.pad(1, width, height);
+        let (sample, coverage) = (&self.sample, &self.coverage);
+
+        edit.apply(ctx.engine, area, |layer, before| {
+            let pixels = (area.width * area.height) as usize;
+            let mut target = Vec::with_capacity(pixels * 4);
+            let mut source = Vec::with_capacity(pixels * 4);
+            let mut weights = Vec::with_capacity(pixels);
+            for y in area.y..area.y + area.height {
+                for x in area.x..area.x + area.width {
+                    let idx = ((y * width + x) * 4) as usize;
+                    target.extend_from_slice(&before[idx..idx + 4]);
+                    let from = match source_index(x, y, offset, width, height) {
+                        Some(src) => &sample[src..src + 4],
+                        None => &before[idx..idx + 4],
+                    };
+                    source.extend_from_slice(from);
+                    weights.push(coverage.get((y * width + x) as usize).copied().unwrap_or(0.0));
+                }
+            }
+
+            let healed = heal::heal(
+                &target,
+                &source,
+                &weights,
+                area.width as usize,
+                area.height as usize,
+            );
+            let row = (area.width * 4) as usize;
+            for (i, y) in (area.y..area.y + area.height).enumerate() {
+                let idx = ((y * width + area.x) * 4) as usize;
+                layer.pixels[idx..idx + row].copy_from_slice(&healed[i * row..(i + 1) * row]);
+            }
+        });
+    }
+
+    fn end_stroke(&mut self) {
+        self.edit = None;
+        self.sample = Vec::new();
+        self.brush = None;
+        self.coverage = Vec::new();
+        self.stroke_offset = None;
+        self.area = None;
+        self.last = None;
+    }
+}
+
+/// The current brush with the tool's size, opacity, hardness and flow
+fn tool_brush(ctx: &ToolContext) -> Brush {
+    let mut brush = ctx.engine.brush_engine().read().current_brush().clone();
+    let options = ctx.options;
+    brush.settings.size = options.size;
+    brush.settings.opacity = options.opacity;
+    brush.settings.hardness = options.hardness;
+    brush.settings.flow = options.flow;
+    brush
+}
+
+/// Byte offset of the pixel cloned onto (x, y), if it is on the canvas
+fn source_index(x: u32, y: u32, offset: (f32, f32), width: u32, height: u32) -> Option<usize> {
+    let sx = (x as f32 + offset.0).round();
+    let sy = (y as f32 + offset.1).round();
+    if sx < 0.0 || sy < 0.0 || sx >= width as f32 || sy >= height as f32 {
+        return None;
+    }
+    Some(((sy as u32 * width + sx as u32) * 4) as usize)
+}
+
+/// Blend cloned pixels into the layer under one brush stamp
+///
+/// `coverage`, when not empty, collects the combined strength of all dabs.
+fn clone_dab(
+    layer: &mut Layer,
+    sample: &[u8],
+    coverage: &mut [f32],
+    stamp: &BrushStamp,
+    origin: (i64, i64),
+    offset: (f32, f32),
+    strength: f32,
+) {
+    let (width, height) = (layer.width(), layer.height());
+    let size = stamp.size as i64;
+    for sy in 0..size {
+        for sx in 0..size {
+            let (x, y) = (origin.0 + sx, origin.1 + sy);
+            if x < 0 || y < 0 || x >= width as i64 || y >= height as i64 {
+                continue;
+            }
+            let (x, y) = (x as u32, y as u32);
+            let weight = stamp.data[(sy * size + sx) as usize] * strength;
+            let Some(src) = source_index(x, y, offset, width, height) else {
+                continue;
+            };
+            if weight <= 0.0 {
+                continue;
+            }
+
+            let idx = ((y * width + x) * 4) as usize;
+            let blended = lerp(load(&layer.pixels, idx), load(sample, src), weight);
+            store(&mut layer.pixels, idx, blended);
+            if let Some(total) = coverage.get_mut((y * width + x) as usize) {
+                *total += weight * (1.0 - *total);
+            }
+        }
+    }
+}
+
+impl Default for CloneTool {
+    fn default() -> Self {
+        Self::new(ToolType::Clone)
+    }
+}
+
+impl Tool for CloneTool {
+    fn tool_type(&self) -> ToolType {
+        self.tool_type
+    }
+
+    fn name(&self) -> &str {
+        match self.tool_type {
+            ToolType::Heal => "Healing Brush",
+            _ => "Clone Stamp",
+        }
+    }
+
+    fn cursor(&self) -> &str {
+        "crosshair"
+    }
+
+    fn on_press(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        if event.modifiers.alt {
+            self.source = Some(event.position());
+            self.offset = None;
+            return Ok(());
+        }
+        let source = self.source.ok_or_else(|| {
+            EngineError::InvalidOperation("Alt-click to set a clone source first".to_string())
+        })?;
+
+        let description = match self.tool_type {
+            ToolType::Heal => "Healing Brush",
+            _ => "Clone Stamp",
+        };
+        let edit = PixelEdit::begin(ctx.engine, description)?;
+        self.sample = if ctx.options.sample_all_layers {
+            ctx.engine.layer_manager().read().flatten().pixels
+        } else {
+            edit.before().to_vec()
+        };
+        if self.sample.len() != edit.before().len() {
+            return Err(EngineError::InvalidOperation(
+                "Merged image does not match layer size".to_string(),
+            ));
+        }
+
+        let start_offset = (source.0 - event.x, source.1 - event.y);
+        self.stroke_offset = Some(if ctx.options.aligned {
+            *self.offset.get_or_insert(start_offset)
+        } else {
+            start_offset
+        });
+        if self.tool_type == ToolType::Heal {
+            let (width, height) = edit.size();
+            self.coverage = vec![0.0; (width * height) as usize];
+        }
+        self.edit = Some(edit);
+        self.brush = Some(tool_brush(ctx));
+        self.dabber = Dabber::default();
+        self.last = None;
+        self.area = None;
+        self.radius = (ctx.options.size / 2.0).max(0.5);
+        self.cursor = Some(event.position());
+        self.stamp(ctx, event.position(), event.pressure);
+        Ok(())
+    }
+
+    fn on_move(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        self.cursor = Some(event.position());
+        if self.edit.is_none() {
+            self.radius = ctx.options.size / 2.0;
+            return Ok(());
+        }
+        self.stamp(ctx, event.position(), event.pressure);
+        Ok(())
+    }
+
+    fn on_release(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        self.stamp(ctx, event.position(), event.pressure);
+        if self.tool_type == ToolType::Heal {
+            self.heal_stroke(ctx);
+        }
+        if let Some(edit) = self.edit.take() {
+            edit.finish(ctx.engine);
+        }
+        self.end_stroke();
+        Ok(())
+    }
+
+    fn cancel(&mut self, _ctx: &mut ToolContext) -> EngineResult<()> {
+        if let Some(edit) = self.edit.take() {
+            edit.abort();
+        }
+        self.end_stroke();
+        Ok(())
+    }
+
+    fn preview(&self) -> ToolPreview {
+        let Some((x, y)) = self.cursor else {
+            return ToolPreview::None;
+        };
+        match (self.stroke_offset.or(self.offset), self.source) {
+            (Some((dx, dy)), _) => ToolPreview::CloneSource {
+                x,
+                y,
+                radius: self.radius,
+                source_x: x + dx,
+                source_y: y + dy,
+            },
+            (None, Some((source_x, source_y))) => ToolPreview::CloneSource {
+                x,
+                y,
+                radius: self.radius,
+                source_x,
+                source_y,
+            },
+            (None, None) => ToolPreview::Brush { x, y, radius: self.radius },
+        }
+    }
+
+    fn reset(&mut self) {
+        self.end_stroke();
+        self.dabber = Dabber::default();
+        self.cursor = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dab_falloff() {
+        assert_eq!(dab_falloff(0.0, 10.0, 0.5), 1.0);
+        assert_eq!(dab_falloff(5.0, 10.0, 0.5), 1.0);
+        assert!((dab_falloff(7.5, 10.0, 0.5) - 0.5).abs() < 1e-5);
+        assert_eq!(dab_falloff(10.0, 10.0, 0.5), 0.0);
+    }
+
+    #[test]
+    fn test_dabs_are_evenly_spaced() {
+        let mut dabber = Dabber::default();
+        assert_eq!(dabber.advance((0.0, 0.0), 2.0), vec![(0.0, 0.0)]);
+        assert_eq!(dabber.advance((5.0, 0.0), 2.0), vec![(2.0, 0.0), (4.0, 0.0)]);
+        assert!(dabber.advance((5.0, 0.0), 2.0).is_empty());
+        assert_eq!(dabber.advance((6.5, 0.0), 2.0), vec![(6.0, 0.0)]);
+    }
+
+    #[test]
+    fn test_premultiplied_round_trip() {
+        let mut pixels = vec![200, 100, 50, 128];
+        let color = load(&pixels, 0);
+        store(&mut pixels, 0, color);
+        assert_eq!(pixels, vec![200, 100, 50, 128]);
+    }
+}
@@ -0,0 +1,306 @@
+//! Rectangle, lasso and magic wand selection
+//!
+//! Modifiers held on press pick how the new selection combines with the
+//! current one: Shift adds, Alt subtracts, both intersect. Otherwise the
+//! selection manager's mode applies. The tool's feather option softens each
+//! new selection before it is combined.
+
+use super::{
+    drag_rect, Modifiers, Tool, ToolContext, ToolEvent, ToolOptions, ToolPreview, ToolType,
+};
+use crate::error::EngineResult;
+use crate::selection::{SampleSource, Selection, SelectionMode, WandOptions};
+use crate::DrawEngine;
+
+/// Combine a new selection into the engine's selection
+pub(super) fn apply_selection(
+    engine: &DrawEngine,
+    options: &ToolOptions,
+    modifiers: Modifiers,
+    mut selection: Selection,
+) {
+    if options.feather > 0.0 {
+        selection.feather(options.feather);
+    }
+    let selection_manager = engine.selection_manager();
+    let mut selection_manager = selection_manager.write();
+    let mode = modifiers.selection_mode(selection_manager.mode());
+    selection_manager.select_with_mode(selection, mode);
+}
+
+/// Canvas size as (width, height)
+pub(super) fn canvas_size(engine: &DrawEngine) -> (u32, u32) {
+    let canvas = engine.canvas();
+    let canvas = canvas.read();
+    (canvas.width(), canvas.height())
+}
+
+/// Rectangular marquee
+///
+/// Holding Shift after the drag started makes a square and Alt grows it from
+/// the press point. A click without dragging deselects.
+pub struct MarqueeTool {
+    start: Option<(f32, f32)>,
+    end: (f32, f32),
+    press_modifiers: Modifiers,
+    square: bool,
+    from_center: bool,
+}
+
+impl MarqueeTool {
+    /// Create a rectangular marquee tool
+    pub fn new() -> Self {
+        Self {
+            start: None,
+            end: (0.0, 0.0),
+            press_modifiers: Modifiers::default(),
+            square: false,
+            from_center: false,
+        }
+    }
+
+    fn rect(&self) -> Option<(f32, f32, f32, f32)> {
+        self.start
+            .map(|start| drag_rect(start, self.end, self.square, self.from_center))
+    }
+
+    fn track(&mut self, event: &ToolEvent) {
+        self.end = event.position();
+        self.square = event.modifiers.shift && !self.press_modifiers.shift;
+        self.from_center = event.modifiers.alt && !self.press_modifiers.alt;
+    }
+}
+
+impl Default for MarqueeTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tool for MarqueeTool {
+    fn tool_type(&self) -> ToolType {
+        ToolType::SelectRect
+    }
+
+    fn name(&self) -> &str {
+        "Rectangular Marquee"
+    }
+
+    fn cursor(&self) -> &str {
+        "crosshair"
+    }
+
+    fn on_press(&mut self, _ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        self.start = Some(event.position());
+        self.press_modifiers = event.modifiers;
+        self.track(event);
+        Ok(())
+    }
+
+    fn on_move(&mut self, _ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        if self.start.is_some() {
+            self.track(event);
+        }
+        Ok(())
+    }
+
+    fn on_release(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        self.track(event);
+        let Some((x, y, width, height)) = self.rect() else {
+            return Ok(());
+        };
+        self.start = None;
+
+        let modifiers = self.press_modifiers;
+        if width < 1.0 || height < 1.0 {
+            if modifiers.selection_mode(SelectionMode::Replace) == SelectionMode::Replace {
+                ctx.engine.selection_manager().write().clear();
+            }
+            return Ok(());
+        }
+        let (canvas_width, canvas_height) = canvas_size(ctx.engine);
+        let selection = Selection::rectangle(x, y, width, height, canvas_width, canvas_height);
+        apply_selection(ctx.engine, ctx.options, modifiers, selection);
+        Ok(())
+    }
+
+    fn cancel(&mut self, _ctx: &mut ToolContext) -> EngineResult<()> {
+        self.reset();
+        Ok(())
+    }
+
+    fn preview(&self) -> ToolPreview {
+        match self.rect() {
+            Some((x, y, width, height)) => ToolPreview::Rect { x, y, width, height },
+            None => ToolPreview::None,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.start = None;
+    }
+}
+
+/// Freehand lasso, closed on release
+pub struct LassoSelectTool {
+    points: Vec<(f32, f32)>,
+    press_modifiers: Modifiers,
+}
+
+impl LassoSelectTool {
+    /// Create a lasso tool
+    pub fn new() -> Self {
+        Self {
+            points: Vec::new(),
+            press_modifiers: Modifiers::default(),
+        }
+    }
+
+    fn add_point(&mut self, point: (f32, f32)) {
+        let far_enough = self.points.last().is_none_or(|last| {
+            (point.0 - last.0).hypot(point.1 - last.1) >= 1.0
+        });
+        if far_enough {
+            self.points.push(point);
+        }
+    }
+}
+
+impl Default for LassoSelectTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tool for LassoSelectTool {
+    fn tool_type(&self) -> ToolType {
+        ToolType::SelectLasso
+    }
+
+    fn name(&self) -> &str {
+        "Lasso"
+    }
+
+    fn cursor(&self) -> &str {
+        "crosshair"
+    }
+
+    fn on_press(&mut self, _ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        self.points = vec![event.position()];
+        self.press_modifiers = event.modifiers;
+        Ok(())
+    }
+
+    fn on_move(&mut self, _ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        if !self.points.is_empty() {
+            self.add_point(event.position());
+        }
+        Ok(())
+    }
+
+    fn on_release(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        if self.points.is_empty() {
+            return Ok(());
+        }
+        self.add_point(event.position());
+        let points = std::mem::take(&mut self.points);
+        if points.len() < 3 {
+            return Ok(());
+        }
+        let (canvas_width, canvas_height) = canvas_size(ctx.engine);
+        let selection = Selection::lasso(points, canvas_width, canvas_height);
+        apply_selection(ctx.engine, ctx.options, self.press_modifiers, selection);
+        Ok(())
+    }
+
+    fn cancel(&mut self, _ctx: &mut ToolContext) -> EngineResult<()> {
+        self.reset();
+        Ok(())
+    }
+
+    fn preview(&self) -> ToolPreview {
+        if self.points.is_empty() {
+            return ToolPreview::None;
+        }
+        ToolPreview::Path {
+            points: self.points.clone(),
+            closed: false,
+            anchors: Vec::new(),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.points.clear();
+    }
+}
+
+/// Magic wand
+///
+/// Tolerance and contiguity come from the tool options; gap closing and
+/// expansion keep the selection manager's wand settings. Samples the merged
+/// image when "sample all layers" is on.
+pub struct MagicWandTool;
+
+impl MagicWandTool {
+    /// Create a magic wand tool
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for MagicWandTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tool for MagicWandTool {
+    fn tool_type(&self) -> ToolType {
+        ToolType::SelectMagic
+    }
+
+    fn name(&self) -> &str {
+        "Magic Wand"
+    }
+
+    fn cursor(&self) -> &str {
+        "crosshair"
+    }
+
+    fn on_press(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        let (width, height) = canvas_size(ctx.engine);
+        if event.x < 0.0 || event.y < 0.0 || event.x >= width as f32 || event.y >= height as f32 {
+            return Ok(());
+        }
+        let source = if ctx.options.sample_all_layers {
+            SampleSource::Merged
+        } else {
+            SampleSource::ActiveLayer
+        };
+        let options = WandOptions {
+            tolerance: ctx.options.tolerance,
+            contiguous: ctx.options.contiguous,
+            ..*ctx.engine.selection_manager().read().wand_options()
+        };
+        let mask = ctx
+            .engine
+            .wand_mask(event.x as u32, event.y as u32, source, &options)?
+            .resized(width, height);
+        apply_selection(ctx.engine, ctx.options, event.modifiers, Selection::from_mask(mask));
+        Ok(())
+    }
+
+    fn on_move(&mut self, _ctx: &mut ToolContext, _event: &ToolEvent) -> EngineResult<()> {
+        Ok(())
+    }
+
+    fn on_release(&mut self, _ctx: &mut ToolContext, _event: &ToolEvent) -> EngineResult<()> {
+        Ok(())
+    }
+
+    fn cancel(&mut self, _ctx: &mut ToolContext) -> EngineResult<()> {
+        Ok(())
+    }
+
+    fn reset(&mut self) {}
+}
@@ -0,0 +1,192 @@
+//! Line, rectangle, ellipse and polygon shapes
+//!
+//! Shapes are previewed as outlines while dragging and drawn on release,
+//! filled with the primary color and stroked with the secondary color as set
+//! in the shape options. Raster output paints into the edit target inside the
+//! selection as one undo step; vector output adds a vector layer above the
+//! active layer. Shift constrains lines to 45 degree steps and boxes to equal
+//! sides; Alt draws boxes from the center.
+
+use super::{drag_rect, snap_angle, PixelEdit, Tool, ToolContext, ToolEvent, ToolPreview, ToolType};
+use crate::error::EngineResult;
+use crate::history::DirtyRect;
+use crate::shape::{Shape, ShapeKind, ShapeOutput, ShapeStyle};
+
+/// Draws shapes for the line, rectangle, ellipse and polygon tools
+///
+/// The rectangle tool rounds its corners when the corner radius is set, and
+/// the polygon tool draws stars when the star option is on.
+pub struct ShapeTool {
+    tool_type: ToolType,
+    start: Option<(f32, f32)>,
+    end: (f32, f32),
+    constrain: bool,
+    from_center: bool,
+    /// Shape for the current drag, if it has any extent
+    shape: Option<Shape>,
+}
+
+impl ShapeTool {
+    /// Create a shape tool for [`ToolType::Line`], [`ToolType::Rectangle`],
+    /// [`ToolType::Ellipse`] or [`ToolType::Polygon`]
+    pub fn new(tool_type: ToolType) -> Self {
+        Self {
+            tool_type,
+            start: None,
+            end: (0.0, 0.0),
+            constrain: false,
+            from_center: false,
+            shape: None,
+        }
+    }
+
+    fn track(&mut self, ctx: &ToolContext, event: &ToolEvent) {
+        self.end = event.position();
+        self.constrain = event.modifiers.shift;
+        self.from_center = event.modifiers.alt;
+        self.shape = self.build(ctx);
+    }
+
+    /// Shape spanned by the drag with the current options
+    fn build(&self, ctx: &ToolContext) -> Option<Shape> {
+        let start = self.start?;
+        let options = &ctx.options.shape;
+        let style = ShapeStyle {
+            fill: options.fill.then_some(*ctx.primary_color),
+            stroke: options.stroke.then_some(*ctx.secondary_color),
+            stroke_width: options.stroke_width,
+            anti_aliasing: ctx.options.anti_aliasing,
+        };
+
+        if self.tool_type == ToolType::Line {
+            let end = if self.constrain {
+                snap_angle(start, self.end)
+            } else {
+                self.end
+            };
+            if (end.0 - start.0).hypot(end.1 - start.1) < 1.0 {
+                return None;
+            }
+            let kind = ShapeKind::Line {
+                weight: options.line_weight,
+                arrow_start: options.arrow_start,
+                arrow_end: options.arrow_end,
+            };
+            return Some(Shape::new(kind, start, end, style));
+        }
+
+        let (x, y, width, height) = drag_rect(start, self.end, self.constrain, self.from_center);
+        if width < 1.0 || height < 1.0 {
+            return None;
+        }
+        let kind = match self.tool_type {
+            ToolType::Ellipse => ShapeKind::Ellipse,
+            ToolType::Polygon if options.star => ShapeKind::Star {
+                points: options.sides,
+                inner_ratio: options.star_inner_ratio,
+            },
+            ToolType::Polygon => ShapeKind::Polygon { sides: options.sides },
+            _ if options.corner_radius > 0.0 => ShapeKind::RoundedRectangle {
+                radius: options.corner_radius,
+            },
+            _ => ShapeKind::Rectangle,
+        };
+        Some(Shape::new(kind, (x, y), (x + width, y + height), style))
+    }
+}
+
+impl Tool for ShapeTool {
+    fn tool_type(&self) -> ToolType {
+        self.tool_type
+    }
+
+    fn name(&self) -> &str {
+        match self.tool_type {
+            ToolType::Line => "Line",
+            ToolType::Ellipse => "Ellipse",
+            ToolType::Polygon => "Polygon",
+            _ => "Rectangle",
+        }
+    }
+
+    fn cursor(&self) -> &str {
+        "crosshair"
+    }
+
+    fn on_press(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        self.start = Some(event.position());
+        self.track(ctx, event);
+        Ok(())
+    }
+
+    fn on_move(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        if self.start.is_some() {
+            self.track(ctx, event);
+        }
+        Ok(())
+    }
+
+    fn on_release(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        if self.start.is_none() {
+            return Ok(());
+        }
+        self.track(ctx, event);
+        self.start = None;
+        let Some(shape) = self.shape.take() else {
+            return Ok(());
+        };
+        if shape.style.fill.is_none() && shape.style.stroke.is_none() {
+            return Ok(());
+        }
+
+        let opacity = ctx.options.opacity;
+        if ctx.options.shape.output == ShapeOutput::Vector {
+            ctx.engine.add_shape_layer(shape, opacity)?;
+            return Ok(());
+        }
+
+        let mut edit = PixelEdit::begin(ctx.engine, shape.kind.name())?;
+        let (width, height) = edit.size();
+        let Some((x, y, w, h)) = shape.pixel_bounds(width, height) else {
+            edit.abort();
+            return Ok(());
+        };
+        edit.apply(ctx.engine, DirtyRect::new(x, y, w, h), |layer, _| {
+            shape.render(layer, opacity);
+        });
+        edit.finish(ctx.engine);
+        Ok(())
+    }
+
+    fn cancel(&mut self, _ctx: &mut ToolContext) -> EngineResult<()> {
+        self.reset();
+        Ok(())
+    }
+
+    fn preview(&self) -> ToolPreview {
+        let Some(shape) = &self.shape else {
+            return ToolPreview::None;
+        };
+        let (x, y, width, height) = shape.rect();
+        match shape.kind {
+            ShapeKind::Rectangle => ToolPreview::Rect { x, y, width, height },
+            ShapeKind::Ellipse => ToolPreview::Ellipse { x, y, width, height },
+            ShapeKind::Line { arrow_start: false, arrow_end: false, .. } => ToolPreview::Line {
+                x0: shape.start.0,
+                y0: shape.start.1,
+                x1: shape.end.0,
+                y1: shape.end.1,
+            },
+            _ => ToolPreview::Path {
+                points: shape.outline(),
+                closed: true,
+                anchors: Vec::new(),
+            },
+        }
+    }
+
+    fn reset(&mut self) {
+        self.start = None;
+        self.shape = None;
+    }
+}
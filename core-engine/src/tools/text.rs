@@ -0,0 +1,84 @@
+//! Text tool
+
+use super::{Tool, ToolContext, ToolEvent, ToolPreview, ToolType};
+use crate::error::EngineResult;
+
+/// Places the text insertion point
+///
+/// The engine has no text rendering yet, so typing is handled by the
+/// frontend's text input. The tool keeps the insertion point and its caret
+/// size (from the tool size) so every frontend anchors text the same way.
+pub struct TextTool {
+    caret: Option<(f32, f32)>,
+    height: f32,
+}
+
+impl TextTool {
+    /// Create a text tool
+    pub fn new() -> Self {
+        Self {
+            caret: None,
+            height: 0.0,
+        }
+    }
+
+    /// Baseline position of the insertion point, if placed
+    pub fn caret(&self) -> Option<(f32, f32)> {
+        self.caret
+    }
+}
+
+impl Default for TextTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tool for TextTool {
+    fn tool_type(&self) -> ToolType {
+        ToolType::Text
+    }
+
+    fn name(&self) -> &str {
+        "Text"
+    }
+
+    fn cursor(&self) -> &str {
+        "text"
+    }
+
+    fn on_press(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        self.caret = Some(event.position());
+        self.height = ctx.options.size;
+        Ok(())
+    }
+
+    fn on_move(&mut self, _ctx: &mut ToolContext, _event: &ToolEvent) -> EngineResult<()> {
+        Ok(())
+    }
+
+    fn on_release(&mut self, _ctx: &mut ToolContext, _event: &ToolEvent) -> EngineResult<()> {
+        Ok(())
+    }
+
+    fn commit(&mut self, _ctx: &mut ToolContext) -> EngineResult<()> {
+        self.reset();
+        Ok(())
+    }
+
+    fn cancel(&mut self, _ctx: &mut ToolContext) -> EngineResult<()> {
+        self.reset();
+        Ok(())
+    }
+
+    fn preview(&self) -> ToolPreview {
+        match self.caret {
+            Some((x, y)) => ToolPreview::Caret { x, y, height: self.height },
+            None => ToolPreview::None,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.caret = None;
+    }
+}
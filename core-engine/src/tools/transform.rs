@@ -0,0 +1,443 @@
+//! Move and free transform
+//!
+//! Both tools work on a floating selection. Without a selection the whole
+//! active layer is lifted, and the temporary select-all is cleared again once
+//! the pixels are dropped. Free transform can also give the content
+//! perspective, distort it by its corners or bend it with a mesh.
+
+use glam::Vec2;
+
+use super::{Tool, ToolContext, ToolEvent, ToolPreview, ToolType};
+use crate::error::EngineResult;
+use crate::geometry::Transform;
+use crate::selection::{perspective_corners, FloatingSelection, FloatingWarp, FreeTransformMode};
+use crate::transform::Interpolation;
+use crate::DrawEngine;
+
+/// Distance in pixels within which a press grabs a corner or mesh point
+const HANDLE_RADIUS: f32 = 8.0;
+
+/// Float the selection, or the whole layer without one
+///
+/// Returns whether the layer was selected just for lifting.
+fn lift(engine: &DrawEngine) -> EngineResult<bool> {
+    let select_all = !engine.selection_manager().read().has_selection();
+    if select_all {
+        let (width, height) = {
+            let canvas = engine.canvas();
+            let canvas = canvas.read();
+            (canvas.width(), canvas.height())
+        };
+        engine.selection_manager().write().select_all(width, height);
+    }
+    if let Err(e) = engine.float_selection() {
+        if select_all {
+            engine.selection_manager().write().clear();
+        }
+        return Err(e);
+    }
+    Ok(select_all)
+}
+
+/// Moves the selected pixels, or the whole layer, by whole pixels
+///
+/// The pixels are dropped on release. Shift constrains the move to the
+/// dominant axis. A floating selection left pending by the transform tool is
+/// moved as is and stays pending.
+pub struct MoveTool {
+    start: Option<(f32, f32)>,
+    base: Transform,
+    /// Whether this tool lifted the pixels and should drop them
+    lifted: bool,
+    selected_all: bool,
+    corners: Option<[(f32, f32); 4]>,
+}
+
+impl MoveTool {
+    /// Create a move tool
+    pub fn new() -> Self {
+        Self {
+            start: None,
+            base: Transform::identity(),
+            lifted: false,
+            selected_all: false,
+            corners: None,
+        }
+    }
+
+    fn drag(&mut self, ctx: &ToolContext, event: &ToolEvent) {
+        let Some(start) = self.start else {
+            return;
+        };
+        let (mut dx, mut dy) = (event.x - start.0, event.y - start.1);
+        if event.modifiers.shift {
+            if dx.abs() > dy.abs() {
+                dy = 0.0;
+            } else {
+                dx = 0.0;
+            }
+        }
+        let moved = self.base.multiply(&Transform::translation(dx.round(), dy.round()));
+        let selection_manager = ctx.engine.selection_manager();
+        let mut selection_manager = selection_manager.write();
+        if let Some(floating) = selection_manager.floating_mut() {
+            floating.set_transform(moved);
+            self.corners = Some(floating.corners());
+        }
+    }
+}
+
+impl Default for MoveTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tool for MoveTool {
+    fn tool_type(&self) -> ToolType {
+        ToolType::Move
+    }
+
+    fn name(&self) -> &str {
+        "Move"
+    }
+
+    fn cursor(&self) -> &str {
+        "move"
+    }
+
+    fn on_press(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        self.lifted = !ctx.engine.selection_manager().read().is_floating();
+        self.selected_all = self.lifted && lift(ctx.engine)?;
+
+        let selection_manager = ctx.engine.selection_manager();
+        let selection_manager = selection_manager.read();
+        if let Some(floating) = selection_manager.floating() {
+            self.base = *floating.transform();
+            self.corners = Some(floating.corners());
+        }
+        self.start = Some(event.position());
+        Ok(())
+    }
+
+    fn on_move(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        self.drag(ctx, event);
+        Ok(())
+    }
+
+    fn on_release(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        if self.start.is_none() {
+            return Ok(());
+        }
+        self.drag(ctx, event);
+        self.start = None;
+        self.corners = None;
+        if !self.lifted {
+            return Ok(());
+        }
+
+        // Whole-pixel moves are exact with nearest neighbor
+        ctx.engine.commit_floating_selection(Interpolation::Nearest)?;
+        if self.selected_all {
+            ctx.engine.selection_manager().write().clear();
+        }
+        Ok(())
+    }
+
+    fn cancel(&mut self, ctx: &mut ToolContext) -> EngineResult<()> {
+        if self.start.take().is_some() {
+            if self.lifted {
+                ctx.engine.cancel_floating_selection()?;
+                if self.selected_all {
+                    ctx.engine.selection_manager().write().clear();
+                }
+            } else {
+                let selection_manager = ctx.engine.selection_manager();
+                let mut selection_manager = selection_manager.write();
+                if let Some(floating) = selection_manager.floating_mut() {
+                    floating.set_transform(self.base);
+                }
+            }
+        }
+        self.corners = None;
+        Ok(())
+    }
+
+    fn preview(&self) -> ToolPreview {
+        match self.corners {
+            Some(corners) => ToolPreview::Handles { corners },
+            None => ToolPreview::None,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.start = None;
+        self.corners = None;
+    }
+}
+
+/// How a transform drag changes the floating content
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TransformDrag {
+    Move,
+    Rotate,
+    Scale,
+    /// Drag a corner, mirroring it onto its neighbor for perspective
+    Corner { index: usize, perspective: bool },
+    /// Drag a mesh control point
+    MeshPoint { column: u32, row: u32 },
+}
+
+/// Handles to draw for floating content: its mesh when warping, otherwise
+/// its corners
+fn handles(floating: &FloatingSelection) -> ToolPreview {
+    match (floating.warp(), floating.mesh_points()) {
+        (Some(FloatingWarp::Mesh(mesh)), Some(points)) => ToolPreview::Mesh {
+            columns: mesh.columns(),
+            rows: mesh.rows(),
+            points,
+        },
+        _ => ToolPreview::Handles { corners: floating.corners() },
+    }
+}
+
+/// Index of the point nearest to `position` within [`HANDLE_RADIUS`]
+fn grab(points: &[(f32, f32)], position: (f32, f32)) -> Option<usize> {
+    points
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (i, (p.0 - position.0).hypot(p.1 - position.1)))
+        .filter(|&(_, distance)| distance <= HANDLE_RADIUS)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(i, _)| i)
+}
+
+/// Free transform of the selected pixels
+///
+/// In free mode dragging moves the content, Alt-dragging rotates it and
+/// Ctrl-dragging scales it around its center; Shift snaps rotation to 15
+/// degrees and moves to the dominant axis. Perspective and distort modes
+/// drag the corner under the pointer and warp mode a point of the mesh,
+/// falling back to moving elsewhere. The content stays floating across
+/// drags, so it is resampled only once when committed with the interpolation
+/// from the transform options. Cancel puts the pixels back.
+pub struct TransformTool {
+    drag: Option<(TransformDrag, (f32, f32))>,
+    base: Transform,
+    base_corners: [(f32, f32); 4],
+    base_point: (f32, f32),
+    center: Vec2,
+    selected_all: bool,
+    handles: ToolPreview,
+}
+
+impl TransformTool {
+    /// Create a free transform tool
+    pub fn new() -> Self {
+        Self {
+            drag: None,
+            base: Transform::identity(),
+            base_corners: [(0.0, 0.0); 4],
+            base_point: (0.0, 0.0),
+            center: Vec2::ZERO,
+            selected_all: false,
+            handles: ToolPreview::None,
+        }
+    }
+
+    /// Drag to start at `position`, grabbing a handle when the mode has them
+    fn pick_drag(&mut self, ctx: &ToolContext, floating: &mut FloatingSelection, event: &ToolEvent) -> TransformDrag {
+        let options = &ctx.options.transform;
+        let position = event.position();
+        match options.mode {
+            FreeTransformMode::Perspective | FreeTransformMode::Distort => {
+                if let Some(index) = grab(&self.base_corners, position) {
+                    let perspective = options.mode == FreeTransformMode::Perspective;
+                    return TransformDrag::Corner { index, perspective };
+                }
+            }
+            FreeTransformMode::Warp => {
+                let (columns, rows) = (options.warp_columns.max(1), options.warp_rows.max(1));
+                let matches = matches!(
+                    floating.warp(),
+                    Some(FloatingWarp::Mesh(mesh)) if mesh.columns() == columns && mesh.rows() == rows
+                );
+                if !matches {
+                    floating.begin_mesh(columns, rows);
+                    self.base = *floating.transform();
+                }
+                let points = floating.mesh_points().unwrap_or_default();
+                if let Some(i) = grab(&points, position) {
+                    self.base_point = points[i];
+                    let stride = columns + 1;
+                    return TransformDrag::MeshPoint {
+                        column: i as u32 % stride,
+                        row: i as u32 / stride,
+                    };
+                }
+            }
+            FreeTransformMode::Free => {}
+        }
+        if event.modifiers.alt {
+            TransformDrag::Rotate
+        } else if event.modifiers.ctrl {
+            TransformDrag::Scale
+        } else {
+            TransformDrag::Move
+        }
+    }
+
+    /// Follow the pointer; corner drags that would fold the content are
+    /// ignored, keeping the last valid shape
+    fn update(&mut self, ctx: &ToolContext, event: &ToolEvent) {
+        let Some((mode, start)) = self.drag else {
+            return;
+        };
+        let start = Vec2::new(start.0, start.1);
+        let current = Vec2::new(event.x, event.y);
+        let selection_manager = ctx.engine.selection_manager();
+        let mut selection_manager = selection_manager.write();
+        let Some(floating) = selection_manager.floating_mut() else {
+            return;
+        };
+
+        let delta = current - start;
+        let change = match mode {
+            TransformDrag::Corner { index, perspective } => {
+                let from = self.base_corners[index];
+                let to = (from.0 + delta.x, from.1 + delta.y);
+                let corners = if perspective {
+                    perspective_corners(self.base_corners, index, to)
+                } else {
+                    let mut corners = self.base_corners;
+                    corners[index] = to;
+                    corners
+                };
+                let _ = floating.distort(corners);
+                self.handles = handles(floating);
+                return;
+            }
+            TransformDrag::MeshPoint { column, row } => {
+                let to = (self.base_point.0 + delta.x, self.base_point.1 + delta.y);
+                let _ = floating.move_mesh_point(column, row, to);
+                self.handles = handles(floating);
+                return;
+            }
+            TransformDrag::Move => {
+                let mut delta = delta;
+                if event.modifiers.shift {
+                    if delta.x.abs() > delta.y.abs() {
+                        delta.y = 0.0;
+                    } else {
+                        delta.x = 0.0;
+                    }
+                }
+                Transform::translation(delta.x, delta.y)
+            }
+            TransformDrag::Rotate => {
+                let (from, to) = (start - self.center, current - self.center);
+                let mut angle = to.y.atan2(to.x) - from.y.atan2(from.x);
+                if event.modifiers.shift {
+                    let step = 15f32.to_radians();
+                    angle = (angle / step).round() * step;
+                }
+                Transform::rotation(angle).around(self.center)
+            }
+            TransformDrag::Scale => {
+                let from = (start - self.center).length();
+                if from < 1.0 {
+                    return;
+                }
+                let factor = ((current - self.center).length() / from).max(0.01);
+                Transform::scale(factor, factor).around(self.center)
+            }
+        };
+
+        floating.set_transform(self.base.multiply(&change));
+        self.handles = handles(floating);
+    }
+}
+
+impl Default for TransformTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tool for TransformTool {
+    fn tool_type(&self) -> ToolType {
+        ToolType::Transform
+    }
+
+    fn name(&self) -> &str {
+        "Free Transform"
+    }
+
+    fn cursor(&self) -> &str {
+        "move"
+    }
+
+    fn on_press(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        if !ctx.engine.selection_manager().read().is_floating() {
+            self.selected_all = lift(ctx.engine)?;
+        }
+
+        let selection_manager = ctx.engine.selection_manager();
+        let mut selection_manager = selection_manager.write();
+        if let Some(floating) = selection_manager.floating_mut() {
+            self.base = *floating.transform();
+            self.base_corners = floating.corners();
+            let mode = self.pick_drag(ctx, floating, event);
+            self.center = floating.center();
+            self.handles = handles(floating);
+            self.drag = Some((mode, event.position()));
+        }
+        Ok(())
+    }
+
+    fn on_move(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        self.update(ctx, event);
+        Ok(())
+    }
+
+    fn on_release(&mut self, ctx: &mut ToolContext, event: &ToolEvent) -> EngineResult<()> {
+        self.update(ctx, event);
+        self.drag = None;
+        Ok(())
+    }
+
+    fn commit(&mut self, ctx: &mut ToolContext) -> EngineResult<()> {
+        self.drag = None;
+        self.handles = ToolPreview::None;
+        if !ctx.engine.selection_manager().read().is_floating() {
+            return Ok(());
+        }
+        ctx.engine.commit_floating_selection(ctx.options.transform.interpolation)?;
+        if std::mem::take(&mut self.selected_all) {
+            ctx.engine.selection_manager().write().clear();
+        }
+        Ok(())
+    }
+
+    fn cancel(&mut self, ctx: &mut ToolContext) -> EngineResult<()> {
+        self.drag = None;
+        self.handles = ToolPreview::None;
+        if !ctx.engine.selection_manager().read().is_floating() {
+            return Ok(());
+        }
+        ctx.engine.cancel_floating_selection()?;
+        if std::mem::take(&mut self.selected_all) {
+            ctx.engine.selection_manager().write().clear();
+        }
+        Ok(())
+    }
+
+    fn preview(&self) -> ToolPreview {
+        self.handles.clone()
+    }
+
+    fn reset(&mut self) {
+        self.drag = None;
+        self.handles = ToolPreview::None;
+    }
+}
@@ -2,11 +2,13 @@
 //!
 //! Resize images using various interpolation algorithms.
 
+use serde::{Deserialize, Serialize};
+
 use super::{ImageData, TransformError, TransformResult};
 use crate::color::Color;
 
 /// Interpolation method for image resizing
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
 pub enum Interpolation {
     /// Nearest neighbor (fastest, pixelated)
     Nearest,
@@ -174,7 +176,7 @@ fn resize_bicubic(image: &ImageData, new_width: u32, new_height: u32) -> Transfo
 }
 
 /// Lanczos kernel
-fn lanczos_kernel(x: f32, a: f32) -> f32 {
+pub(super) fn lanczos_kernel(x: f32, a: f32) -> f32 {
     if x.abs() < f32::EPSILON {
         return 1.0;
     }
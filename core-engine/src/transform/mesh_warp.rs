@@ -0,0 +1,234 @@
+//! Mesh warp
+//!
+//! Bend an image with a grid of control points. The image is divided into
+//! equal cells and each cell is stretched onto the quad spanned by its four
+//! control points, positions inside the quad interpolated bilinearly. Only
+//! the area each quad covers is visited, so previews stay cheap.
+
+use serde::{Deserialize, Serialize};
+
+use super::warp::sample;
+use super::{ImageData, Interpolation, TransformError, TransformResult};
+use glam::Vec2;
+
+/// Grid of control points for a mesh warp
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WarpMesh {
+    /// Cells across
+    columns: u32,
+    /// Cells down
+    rows: u32,
+    /// Control points row by row, `(columns + 1) * (rows + 1)` of them
+    points: Vec<(f32, f32)>,
+}
+
+impl WarpMesh {
+    /// Create an undistorted mesh of `columns` x `rows` cells over a
+    /// `width` x `height` rectangle at the origin
+    pub fn new(columns: u32, rows: u32, width: f32, height: f32) -> Self {
+        let (columns, rows) = (columns.max(1), rows.max(1));
+        let points = (0..=rows)
+            .flat_map(|row| {
+                (0..=columns).map(move |column| {
+                    (column as f32 * width / columns as f32, row as f32 * height / rows as f32)
+                })
+            })
+            .collect();
+        Self { columns, rows, points }
+    }
+
+    /// Cells across
+    pub fn columns(&self) -> u32 {
+        self.columns
+    }
+
+    /// Cells down
+    pub fn rows(&self) -> u32 {
+        self.rows
+    }
+
+    /// Control points row by row
+    pub fn points(&self) -> &[(f32, f32)] {
+        &self.points
+    }
+
+    /// Control point at a grid position
+    pub fn point(&self, column: u32, row: u32) -> Option<(f32, f32)> {
+        if column > self.columns || row > self.rows {
+            return None;
+        }
+        Some(self.points[(row * (self.columns + 1) + column) as usize])
+    }
+
+    /// Move the control point at a grid position
+    pub fn set_point(&mut self, column: u32, row: u32, point: (f32, f32)) -> TransformResult<()> {
+        if column > self.columns || row > self.rows {
+            return Err(TransformError::InvalidParameters(format!(
+                "No mesh point at column {} row {}",
+                column, row
+            )));
+        }
+        self.points[(row * (self.columns + 1) + column) as usize] = point;
+        Ok(())
+    }
+
+    /// The mesh with every control point passed through `map`
+    pub fn mapped(&self, map: impl Fn(Vec2) -> Vec2) -> Self {
+        let points = self
+            .points
+            .iter()
+            .map(|&(x, y)| {
+                let p = map(Vec2::new(x, y));
+                (p.x, p.y)
+            })
+            .collect();
+        Self { points, ..*self }
+    }
+
+    /// Position of a point given as fractions (0-1) across the whole mesh
+    pub fn position(&self, u: f32, v: f32) -> Vec2 {
+        let gx = (u.clamp(0.0, 1.0) * self.columns as f32).min(self.columns as f32 - 1e-4);
+        let gy = (v.clamp(0.0, 1.0) * self.rows as f32).min(self.rows as f32 - 1e-4);
+        let (column, row) = (gx.floor() as u32, gy.floor() as u32);
+        let [a, b, c, d] = self.cell(column, row);
+        let (s, t) = (gx - column as f32, gy - row as f32);
+        a + (b - a) * s + (d - a) * t + (a - b + c - d) * s * t
+    }
+
+    /// Corners of a cell: top-left, top-right, bottom-right, bottom-left
+    fn cell(&self, column: u32, row: u32) -> [Vec2; 4] {
+        let at = |c: u32, r: u32| {
+            let (x, y) = self.points[(r * (self.columns + 1) + c) as usize];
+            Vec2::new(x, y)
+        };
+        [at(column, row), at(column + 1, row), at(column + 1, row + 1), at(column, row + 1)]
+    }
+}
+
+/// Warp an image into a `width` x `height` buffer
+///
+/// The mesh's control points are destination positions for an even grid
+/// over the source image. Destination pixels no cell covers stay
+/// transparent; where folded cells overlap, later cells win.
+pub fn warp_mesh(
+    image: &ImageData,
+    mesh: &WarpMesh,
+    width: u32,
+    height: u32,
+    interpolation: Interpolation,
+) -> TransformResult<ImageData> {
+    if mesh.points.len() != ((mesh.columns + 1) * (mesh.rows + 1)) as usize {
+        return Err(TransformError::InvalidParameters("Invalid mesh".to_string()));
+    }
+    let mut result = ImageData::new(width, height);
+    let cell_width = image.width as f32 / mesh.columns as f32;
+    let cell_height = image.height as f32 / mesh.rows as f32;
+
+    for row in 0..mesh.rows {
+        for column in 0..mesh.columns {
+            let quad = mesh.cell(column, row);
+            let x0 = quad.iter().map(|p| p.x).fold(f32::MAX, f32::min).floor().clamp(0.0, width as f32) as u32;
+            let y0 = quad.iter().map(|p| p.y).fold(f32::MAX, f32::min).floor().clamp(0.0, height as f32) as u32;
+            let x1 = quad.iter().map(|p| p.x).fold(f32::MIN, f32::max).ceil().clamp(0.0, width as f32) as u32;
+            let y1 = quad.iter().map(|p| p.y).fold(f32::MIN, f32::max).ceil().clamp(0.0, height as f32) as u32;
+
+            for dest_y in y0..y1 {
+                for dest_x in x0..x1 {
+                    let p = Vec2::new(dest_x as f32 + 0.5, dest_y as f32 + 0.5);
+                    let Some((u, v)) = inverse_bilinear(p, quad) else {
+                        continue;
+                    };
+                    let src_x = (column as f32 + u) * cell_width;
+                    let src_y = (row as f32 + v) * cell_height;
+                    let rgba = sample(image, src_x - 0.5, src_y - 0.5, interpolation);
+                    if rgba[3] > 0 {
+                        let idx = ((dest_y * width + dest_x) * 4) as usize;
+                        result.pixels[idx..idx + 4].copy_from_slice(&rgba);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+/// Cell fractions (u, v) at which the bilinear patch over `quad` reaches
+/// `p`, if it does
+fn inverse_bilinear(p: Vec2, [a, b, c, d]: [Vec2; 4]) -> Option<(f32, f32)> {
+    const EDGE: f32 = 1e-4;
+    let cross = |u: Vec2, v: Vec2| u.x * v.y - u.y * v.x;
+    let (e, f, g, h) = (b - a, d - a, a - b + c - d, p - a);
+    let k2 = cross(g, f);
+    let k1 = cross(e, f) + cross(h, g);
+    let k0 = cross(h, e);
+
+    let candidates = if k2.abs() < 1e-6 {
+        if k1.abs() < 1e-9 {
+            return None;
+        }
+        [Some(-k0 / k1), None]
+    } else {
+        let discriminant = k1 * k1 - 4.0 * k0 * k2;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let root = discriminant.sqrt();
+        [Some((-k1 - root) / (2.0 * k2)), Some((-k1 + root) / (2.0 * k2))]
+    };
+
+    candidates.into_iter().flatten().find_map(|v| {
+        if !(-EDGE..=1.0 + EDGE).contains(&v) {
+            return None;
+        }
+        let denominator = e + g * v;
+        let u = if denominator.x.abs() > denominator.y.abs() {
+            (h.x - f.x * v) / denominator.x
+        } else if denominator.y.abs() > 1e-9 {
+            (h.y - f.y * v) / denominator.y
+        } else {
+            return None;
+        };
+        (-EDGE..=1.0 + EDGE).contains(&u).then(|| (u.clamp(0.0, 1.0), v.clamp(0.0, 1.0)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+
+    /// Left half red, right half blue
+    fn halves() -> ImageData {
+        let mut img = ImageData::new(8, 8);
+        for y in 0..8 {
+            for x in 0..8 {
+                let color = if x < 4 { Color::from_rgba(1.0, 0.0, 0.0, 1.0) } else { Color::from_rgba(0.0, 0.0, 1.0, 1.0) };
+                img.set_pixel(x, y, color);
+            }
+        }
+        img
+    }
+
+    #[test]
+    fn test_undistorted_mesh_is_identity() {
+        let img = halves();
+        let mesh = WarpMesh::new(2, 2, 8.0, 8.0);
+        let warped = warp_mesh(&img, &mesh, 8, 8, Interpolation::Bicubic).unwrap();
+        assert_eq!(warped.pixels, img.pixels);
+    }
+
+    #[test]
+    fn test_moving_center_point_bends_content() {
+        let img = halves();
+        let mut mesh = WarpMesh::new(2, 2, 8.0, 8.0);
+        mesh.set_point(1, 1, (6.0, 4.0)).unwrap();
+        assert!(mesh.set_point(3, 0, (0.0, 0.0)).is_err());
+        let warped = warp_mesh(&img, &mesh, 8, 8, Interpolation::Bilinear).unwrap();
+
+        // The red half now reaches past the middle at the center row only
+        assert!(warped.get_pixel(4, 4).r > 0.9);
+        assert!(warped.get_pixel(5, 0).b > 0.9);
+        assert_eq!(mesh.position(0.5, 0.5), Vec2::new(6.0, 4.0));
+    }
+}
@@ -1,18 +1,25 @@
 //! Transform module
 //!
-//! Image transformation tools: rotate, flip, crop, resize.
+//! Image transformation tools: rotate, flip, crop, resize and affine,
+//! perspective and mesh warps.
 
 pub mod rotate;
 pub mod flip;
 pub mod crop;
 pub mod canvas_resize;
 pub mod image_resize;
+pub mod warp;
+pub mod perspective;
+pub mod mesh_warp;
 
 pub use rotate::{rotate_90_cw, rotate_90_ccw, rotate_180, rotate_arbitrary};
 pub use flip::{flip_horizontal, flip_vertical};
 pub use crop::{crop_image, CropRegion};
 pub use canvas_resize::{canvas_resize, Anchor};
 pub use image_resize::{resize_image, Interpolation};
+pub use warp::warp_affine;
+pub use perspective::warp_perspective;
+pub use mesh_warp::{warp_mesh, WarpMesh};
 
 use crate::color::Color;
 
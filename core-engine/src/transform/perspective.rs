@@ -0,0 +1,103 @@
+//! Perspective warp
+//!
+//! Resample an image through a projective transform, for perspective and
+//! four-corner distort. Only the destination area the image lands in is
+//! visited, so small previews stay cheap on large layers.
+
+use super::warp::{sample, warp_affine};
+use super::{ImageData, Interpolation, TransformError, TransformResult};
+use crate::geometry::Homography;
+use glam::Vec2;
+
+/// Warp an image into a `width` x `height` buffer
+///
+/// `homography` maps source pixel coordinates to destination coordinates and
+/// must keep the whole image on one side of the horizon. Destination pixels
+/// the source does not reach stay transparent.
+pub fn warp_perspective(
+    image: &ImageData,
+    homography: &Homography,
+    width: u32,
+    height: u32,
+    interpolation: Interpolation,
+) -> TransformResult<ImageData> {
+    if let Some(transform) = homography.to_transform() {
+        return warp_affine(image, &transform, width, height, interpolation);
+    }
+    let inverse = homography.inverse().ok_or_else(|| {
+        TransformError::InvalidParameters("Transform is not invertible".to_string())
+    })?;
+
+    let (w, h) = (image.width as f32, image.height as f32);
+    let m = &homography.matrix;
+    let corners = [(0.0, 0.0), (w, 0.0), (w, h), (0.0, h)].map(|(x, y)| Vec2::new(x, y));
+    if corners.iter().any(|p| m[6] * p.x + m[7] * p.y + m[8] <= 1e-6) {
+        return Err(TransformError::InvalidParameters(
+            "Perspective reaches past the horizon".to_string(),
+        ));
+    }
+    let corners = corners.map(|p| homography.project(p).expect("checked above"));
+
+    let mut result = ImageData::new(width, height);
+    let min_x = corners.iter().map(|p| p.x).fold(f32::MAX, f32::min) - 3.0;
+    let min_y = corners.iter().map(|p| p.y).fold(f32::MAX, f32::min) - 3.0;
+    let max_x = corners.iter().map(|p| p.x).fold(f32::MIN, f32::max) + 3.0;
+    let max_y = corners.iter().map(|p| p.y).fold(f32::MIN, f32::max) + 3.0;
+    let x0 = min_x.floor().clamp(0.0, width as f32) as u32;
+    let y0 = min_y.floor().clamp(0.0, height as f32) as u32;
+    let x1 = max_x.ceil().clamp(0.0, width as f32) as u32;
+    let y1 = max_y.ceil().clamp(0.0, height as f32) as u32;
+
+    for dest_y in y0..y1 {
+        for dest_x in x0..x1 {
+            let Some(src) = inverse.project(Vec2::new(dest_x as f32 + 0.5, dest_y as f32 + 0.5)) else {
+                continue;
+            };
+            let rgba = sample(image, src.x - 0.5, src.y - 0.5, interpolation);
+            if rgba[3] > 0 {
+                let idx = ((dest_y * width + dest_x) * 4) as usize;
+                result.pixels[idx..idx + 4].copy_from_slice(&rgba);
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+
+    fn square(size: u32) -> ImageData {
+        let mut img = ImageData::new(size, size);
+        for y in 0..size {
+            for x in 0..size {
+                img.set_pixel(x, y, Color::from_rgba(1.0, 0.0, 0.0, 1.0));
+            }
+        }
+        img
+    }
+
+    #[test]
+    fn test_keystone_narrows_top() {
+        let img = square(10);
+        let from = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)].map(|(x, y)| Vec2::new(x, y));
+        let to = [(6.0, 2.0), (14.0, 2.0), (20.0, 12.0), (0.0, 12.0)].map(|(x, y)| Vec2::new(x, y));
+        let homography = Homography::from_quads(from, to).unwrap();
+        let warped = warp_perspective(&img, &homography, 20, 14, Interpolation::Bilinear).unwrap();
+
+        assert_eq!(warped.get_pixel(10, 3).to_rgba8(), (255, 0, 0, 255));
+        assert_eq!(warped.get_pixel(3, 3).a, 0.0);
+        assert_eq!(warped.get_pixel(4, 9).to_rgba8(), (255, 0, 0, 255));
+    }
+
+    #[test]
+    fn test_beyond_horizon_fails() {
+        let img = square(4);
+        let homography = Homography {
+            matrix: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, -0.5, 0.0, 1.0],
+        };
+        assert!(warp_perspective(&img, &homography, 8, 8, Interpolation::Nearest).is_err());
+    }
+}
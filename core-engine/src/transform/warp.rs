@@ -0,0 +1,214 @@
+//! Affine warp
+//!
+//! Resample an image through an arbitrary affine transform: move, scale,
+//! rotate, skew and flip in one pass with any [`Interpolation`].
+
+use super::image_resize::lanczos_kernel;
+use super::{ImageData, Interpolation, TransformError, TransformResult};
+use crate::geometry::Transform;
+use glam::Vec2;
+
+/// Warp an image into a `width` x `height` buffer
+///
+/// `transform` maps source pixel coordinates to destination coordinates.
+/// Destination pixels the source does not reach stay transparent. Colors are
+/// interpolated with premultiplied alpha, so transparent surroundings do not
+/// darken edges.
+pub fn warp_affine(
+    image: &ImageData,
+    transform: &Transform,
+    width: u32,
+    height: u32,
+    interpolation: Interpolation,
+) -> TransformResult<ImageData> {
+    let inverse = transform.inverse().ok_or_else(|| {
+        TransformError::InvalidParameters("Transform is not invertible".to_string())
+    })?;
+
+    let mut result = ImageData::new(width, height);
+    let Some((x0, y0, x1, y1)) = warped_bounds(image, transform, width, height) else {
+        return Ok(result);
+    };
+
+    for dest_y in y0..y1 {
+        for dest_x in x0..x1 {
+            let src = inverse.transform_point(Vec2::new(dest_x as f32 + 0.5, dest_y as f32 + 0.5));
+            let rgba = sample(image, src.x - 0.5, src.y - 0.5, interpolation);
+            if rgba[3] > 0 {
+                let idx = ((dest_y * width + dest_x) * 4) as usize;
+                result.pixels[idx..idx + 4].copy_from_slice(&rgba);
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+/// Destination pixel range covered by the warped image, with a margin for
+/// interpolation kernels
+fn warped_bounds(
+    image: &ImageData,
+    transform: &Transform,
+    width: u32,
+    height: u32,
+) -> Option<(u32, u32, u32, u32)> {
+    let (w, h) = (image.width as f32, image.height as f32);
+    let corners = [(0.0, 0.0), (w, 0.0), (0.0, h), (w, h)]
+        .map(|(x, y)| transform.transform_point(Vec2::new(x, y)));
+
+    let min_x = corners.iter().map(|p| p.x).fold(f32::MAX, f32::min) - 3.0;
+    let min_y = corners.iter().map(|p| p.y).fold(f32::MAX, f32::min) - 3.0;
+    let max_x = corners.iter().map(|p| p.x).fold(f32::MIN, f32::max) + 3.0;
+    let max_y = corners.iter().map(|p| p.y).fold(f32::MIN, f32::max) + 3.0;
+
+    let x0 = min_x.floor().clamp(0.0, width as f32) as u32;
+    let y0 = min_y.floor().clamp(0.0, height as f32) as u32;
+    let x1 = max_x.ceil().clamp(0.0, width as f32) as u32;
+    let y1 = max_y.ceil().clamp(0.0, height as f32) as u32;
+    (x0 < x1 && y0 < y1).then_some((x0, y0, x1, y1))
+}
+
+/// Sample RGBA at a continuous position where integers are pixel centers
+pub(super) fn sample(image: &ImageData, x: f32, y: f32, interpolation: Interpolation) -> [u8; 4] {
+    match interpolation {
+        Interpolation::Nearest => {
+            let (px, py) = ((x + 0.5).floor(), (y + 0.5).floor());
+            if px < 0.0 || py < 0.0 || px >= image.width as f32 || py >= image.height as f32 {
+                return [0; 4];
+            }
+            let idx = ((py as u32 * image.width + px as u32) * 4) as usize;
+            [
+                image.pixels[idx],
+                image.pixels[idx + 1],
+                image.pixels[idx + 2],
+                image.pixels[idx + 3],
+            ]
+        }
+        Interpolation::Bilinear => sample_kernel(image, x, y, 1, |t| (1.0 - t.abs()).max(0.0)),
+        Interpolation::Bicubic => sample_kernel(image, x, y, 2, catmull_rom),
+        Interpolation::Lanczos => sample_kernel(image, x, y, 3, |t| lanczos_kernel(t, 3.0)),
+    }
+}
+
+/// Separable kernel sampling over premultiplied RGBA
+///
+/// Pixels outside the image count as transparent.
+fn sample_kernel(image: &ImageData, x: f32, y: f32, radius: i32, kernel: impl Fn(f32) -> f32) -> [u8; 4] {
+    let (cx, cy) = (x.floor() as i32, y.floor() as i32);
+    let mut sum = [0.0f32; 4];
+    let mut total_weight = 0.0f32;
+
+    for py in cy - radius + 1..=cy + radius {
+        let wy = kernel(y - py as f32);
+        if wy == 0.0 {
+            continue;
+        }
+        for px in cx - radius + 1..=cx + radius {
+            let weight = wy * kernel(x - px as f32);
+            total_weight += weight;
+            if px < 0 || py < 0 || px >= image.width as i32 || py >= image.height as i32 {
+                continue;
+            }
+
+            let idx = ((py as u32 * image.width + px as u32) * 4) as usize;
+            let alpha = image.pixels[idx + 3] as f32 / 255.0;
+            for (total, &value) in sum.iter_mut().zip(&image.pixels[idx..idx + 3]) {
+                *total += value as f32 * alpha * weight;
+            }
+            sum[3] += image.pixels[idx + 3] as f32 * weight;
+        }
+    }
+
+    if total_weight.abs() < f32::EPSILON {
+        return [0; 4];
+    }
+    let alpha = (sum[3] / total_weight).clamp(0.0, 255.0);
+    if alpha < 0.5 {
+        return [0; 4];
+    }
+    let unpremultiply = 255.0 / (alpha * total_weight);
+    [
+        (sum[0] * unpremultiply).round().clamp(0.0, 255.0) as u8,
+        (sum[1] * unpremultiply).round().clamp(0.0, 255.0) as u8,
+        (sum[2] * unpremultiply).round().clamp(0.0, 255.0) as u8,
+        alpha.round() as u8,
+    ]
+}
+
+/// Catmull-Rom cubic kernel, matching bicubic resizing
+fn catmull_rom(t: f32) -> f32 {
+    let t = t.abs();
+    if t < 1.0 {
+        1.5 * t * t * t - 2.5 * t * t + 1.0
+    } else if t < 2.0 {
+        -0.5 * t * t * t + 2.5 * t * t - 4.0 * t + 2.0
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+
+    fn square() -> ImageData {
+        let mut img = ImageData::new(4, 4);
+        for y in 0..4 {
+            for x in 0..4 {
+                img.set_pixel(x, y, Color::from_rgba(1.0, 0.0, 0.0, 1.0));
+            }
+        }
+        img
+    }
+
+    #[test]
+    fn test_integer_translation_is_exact() {
+        let img = square();
+        for interpolation in [
+            Interpolation::Nearest,
+            Interpolation::Bilinear,
+            Interpolation::Bicubic,
+            Interpolation::Lanczos,
+        ] {
+            let warped =
+                warp_affine(&img, &Transform::translation(3.0, 2.0), 10, 10, interpolation).unwrap();
+            assert_eq!(warped.get_pixel(3, 2).to_rgba8(), (255, 0, 0, 255));
+            assert_eq!(warped.get_pixel(6, 5).to_rgba8(), (255, 0, 0, 255));
+            assert_eq!(warped.get_pixel(2, 2).a, 0.0);
+            assert_eq!(warped.get_pixel(7, 5).a, 0.0);
+        }
+    }
+
+    #[test]
+    fn test_edges_keep_color() {
+        let img = square();
+        let warped =
+            warp_affine(&img, &Transform::translation(0.5, 0.0), 6, 4, Interpolation::Bilinear)
+                .unwrap();
+
+        // The half-covered edge is half transparent, not darkened
+        let (r, _, _, a) = warped.get_pixel(0, 1).to_rgba8();
+        assert_eq!(r, 255);
+        assert!((120..=135).contains(&a));
+    }
+
+    #[test]
+    fn test_scale_and_flip() {
+        let mut img = ImageData::new(2, 1);
+        img.set_pixel(0, 0, Color::from_rgba(1.0, 0.0, 0.0, 1.0));
+        img.set_pixel(1, 0, Color::from_rgba(0.0, 0.0, 1.0, 1.0));
+
+        // Mirror and double in size
+        let transform = Transform::scale(-2.0, 2.0).multiply(&Transform::translation(4.0, 0.0));
+        let warped = warp_affine(&img, &transform, 4, 2, Interpolation::Nearest).unwrap();
+        assert!(warped.get_pixel(0, 1).b > 0.9);
+        assert!(warped.get_pixel(3, 0).r > 0.9);
+    }
+
+    #[test]
+    fn test_singular_transform_fails() {
+        let img = square();
+        assert!(warp_affine(&img, &Transform::scale(0.0, 1.0), 4, 4, Interpolation::Nearest).is_err());
+    }
+}
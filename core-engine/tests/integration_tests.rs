@@ -2,6 +2,22 @@
 
 use drawconnect_core::*;
 
+/// Create an engine with a `width` x `height` document holding one empty layer
+///
+/// The canvas, the layer manager and the selection manager all use that size.
+fn engine_with_layer(width: u32, height: u32) -> (DrawEngine, uuid::Uuid) {
+    let engine = DrawEngine::new().expect("Failed to create engine");
+    engine.canvas().write().resize(width, height).unwrap();
+    engine.selection_manager().write().set_canvas_size(width, height);
+    let layer_id = {
+        let layer_manager_arc = engine.layer_manager();
+        let mut layer_manager = layer_manager_arc.write();
+        layer_manager.set_canvas_size(width, height);
+        layer_manager.add_layer("Layer 1")
+    };
+    (engine, layer_id)
+}
+
 /// Test complete drawing workflow
 #[test]
 fn test_complete_drawing_workflow() {
@@ -113,39 +129,83 @@ fn test_color_conversions() {
     assert!((color.b - back.b).abs() < 0.01);
 }
 
-/// Test canvas undo/redo
+/// Test canvas undo/redo through the engine history
 #[test]
 fn test_canvas_undo_redo() {
-    let mut canvas = Canvas::with_size(100, 100).unwrap();
+    let engine = DrawEngine::new().expect("Failed to create engine");
+    let canvas_arc = engine.canvas();
 
     // Draw something
-    canvas.set_pixel(50, 50, Color::red()).unwrap();
-    canvas.save_undo("Draw red pixel");
+    canvas_arc.write().set_pixel(50, 50, Color::red()).unwrap();
 
-    // Draw something else
-    canvas.set_pixel(50, 50, Color::blue()).unwrap();
+    // Record the canvas, then draw something else
+    engine.record_canvas_state("Draw blue pixel");
+    canvas_arc.write().set_pixel(50, 50, Color::blue()).unwrap();
 
     // Verify blue
-    let pixel = canvas.get_pixel(50, 50).unwrap();
+    let pixel = canvas_arc.read().get_pixel(50, 50).unwrap();
     assert!((pixel.b - 1.0).abs() < 0.01);
 
     // Undo
-    assert!(canvas.can_undo());
-    canvas.undo();
+    assert!(engine.can_undo());
+    assert!(engine.undo().unwrap());
 
     // Verify red restored
-    let pixel = canvas.get_pixel(50, 50).unwrap();
+    let pixel = canvas_arc.read().get_pixel(50, 50).unwrap();
     assert!((pixel.r - 1.0).abs() < 0.01);
 
     // Redo
-    assert!(canvas.can_redo());
-    canvas.redo();
+    assert!(engine.can_redo());
+    assert!(engine.redo().unwrap());
 
     // Verify blue again
-    let pixel = canvas.get_pixel(50, 50).unwrap();
+    let pixel = canvas_arc.read().get_pixel(50, 50).unwrap();
     assert!((pixel.b - 1.0).abs() < 0.01);
 }
 
+/// Test that canvas-level transforms share the layer undo history
+#[test]
+fn test_canvas_transform_undo() {
+    let (engine, layer_id) = engine_with_layer(64, 48);
+    let layer_size = || {
+        let layer_arc = engine.layer_manager().read().get_layer(layer_id).unwrap();
+        let layer = layer_arc.read();
+        (layer.width(), layer.height())
+    };
+    let canvas_size = || {
+        let canvas_arc = engine.canvas();
+        let canvas = canvas_arc.read();
+        (canvas.width(), canvas.height())
+    };
+
+    engine.flood_fill(0, 0, Color::red(), 0.1).unwrap();
+    engine.rotate_canvas(90.0).unwrap();
+    assert_eq!(canvas_size(), (48, 64));
+    assert_eq!(layer_size(), (48, 64));
+
+    engine.crop_canvas(transform::CropRegion::new(0, 0, 10, 20)).unwrap();
+    assert_eq!(canvas_size(), (10, 20));
+    assert_eq!(layer_size(), (10, 20));
+
+    // Crop, rotate and fill all undo through the same history
+    assert!(engine.undo().unwrap());
+    assert_eq!(layer_size(), (48, 64));
+    assert!(engine.undo().unwrap());
+    assert_eq!(canvas_size(), (64, 48));
+    assert_eq!(layer_size(), (64, 48));
+    assert!(engine.undo().unwrap());
+    {
+        let layer_arc = engine.layer_manager().read().get_layer(layer_id).unwrap();
+        assert_eq!(layer_arc.read().get_pixel(0, 0).unwrap().a, 0.0);
+    }
+
+    assert!(engine.redo().unwrap());
+    assert!(engine.redo().unwrap());
+    assert_eq!(canvas_size(), (48, 64));
+    let layer_arc = engine.layer_manager().read().get_layer(layer_id).unwrap();
+    assert!(layer_arc.read().get_pixel(0, 0).unwrap().r > 0.99);
+}
+
 /// Test blend modes
 #[test]
 fn test_all_blend_modes() {
@@ -418,3 +478,1269 @@ fn test_brush_color_applied_correctly() {
         }
     }
 }
+
+/// Test jumping between branches of the history tree
+#[test]
+fn test_history_tree_branches() {
+    let (engine, layer_id) = engine_with_layer(64, 64);
+    let pixel_at_origin = || {
+        let layer_arc = engine.layer_manager().read().get_layer(layer_id).unwrap();
+        let layer = layer_arc.read();
+        layer.get_pixel(0, 0).unwrap()
+    };
+
+    engine.flood_fill(0, 0, Color::red(), 0.1).unwrap();
+    let red_node = engine.history_manager().read().current_node();
+
+    // Undo and take a different branch
+    assert!(engine.undo().unwrap());
+    engine.flood_fill(0, 0, Color::blue(), 0.1).unwrap();
+    let blue_node = engine.history_manager().read().current_node();
+    assert_eq!(engine.history_nodes().len(), 3);
+
+    assert!(engine.jump_to_history_node(red_node).unwrap());
+    assert!(pixel_at_origin().r > 0.99);
+
+    assert!(engine.jump_to_history_node(blue_node).unwrap());
+    assert!(pixel_at_origin().b > 0.99);
+
+    // Named snapshots restore as a new undoable step
+    let snapshot = engine.create_history_snapshot("before coloring").unwrap();
+    engine.flood_fill(0, 0, Color::green(), 0.1).unwrap();
+    engine.restore_history_snapshot(snapshot).unwrap();
+    assert!(pixel_at_origin().b > 0.99);
+    assert!(engine.undo().unwrap());
+    assert!(pixel_at_origin().g > 0.99);
+}
+
+/// Test that strokes only land inside the selection
+#[test]
+fn test_painting_respects_selection_coverage() {
+    let (engine, layer_id) = engine_with_layer(64, 64);
+    {
+        let selection_manager_arc = engine.selection_manager();
+        let mut selection_manager = selection_manager_arc.write();
+        selection_manager.select_rectangle(0.0, 0.0, 32.0, 64.0);
+    }
+
+    let mut stroke = Stroke::new();
+    stroke.add_point(StrokePoint::full(8.0, 32.0, 1.0, 0.0, 0.0, 0.0, 0));
+    stroke.add_point(StrokePoint::full(56.0, 32.0, 1.0, 0.0, 0.0, 0.0, 16));
+    engine.process_stroke(&stroke).expect("Failed to process stroke");
+
+    let layer_arc = engine.layer_manager().read().get_layer(layer_id).unwrap();
+    let layer = layer_arc.read();
+    assert!(layer.get_pixel(16, 32).unwrap().a > 0.0);
+    assert_eq!(layer.get_pixel(48, 32).unwrap().a, 0.0);
+}
+
+/// Test that erasing, fills, pastes, filters and adjustments honor selection coverage
+#[test]
+fn test_pixel_edits_respect_selection_coverage() {
+    use drawconnect_core::adjustments::{Adjustment, Invert};
+    use drawconnect_core::filters::{Filter, Pixelate};
+
+    let (engine, layer_id) = engine_with_layer(64, 64);
+    let layer_arc = engine.layer_manager().read().get_layer(layer_id).unwrap();
+    {
+        let selection_manager_arc = engine.selection_manager();
+        let mut selection_manager = selection_manager_arc.write();
+        selection_manager.select_rectangle(0.0, 0.0, 32.0, 64.0);
+        selection_manager.feather(4.0);
+    }
+
+    // Fills stop at the selection and fade across its feathered edge
+    engine.flood_fill(8, 8, Color::from_rgb(1.0, 0.0, 0.0), 0.1).unwrap();
+    let edge = layer_arc.read().get_pixel(32, 8).unwrap().a;
+    assert_eq!(layer_arc.read().get_pixel(8, 8).unwrap().a, 1.0);
+    assert!(edge > 0.0 && edge < 1.0);
+    assert_eq!(layer_arc.read().get_pixel(48, 8).unwrap().a, 0.0);
+
+    // Pasted content is clipped the same way
+    let pasted = [0u8, 0, 255, 255].repeat(64 * 8);
+    engine.paste_pixels(&pasted, 64, 8, 0, 40).unwrap();
+    assert!(layer_arc.read().get_pixel(8, 44).unwrap().b > 0.99);
+    assert_eq!(layer_arc.read().get_pixel(48, 44).unwrap().a, 0.0);
+    engine.undo().unwrap();
+    assert!(layer_arc.read().get_pixel(8, 44).unwrap().r > 0.99);
+
+    // Locked layers refuse pastes
+    layer_arc.write().lock.pixels = true;
+    assert!(engine.paste_pixels(&pasted, 64, 8, 0, 40).is_err());
+    assert!(layer_arc.read().get_pixel(8, 44).unwrap().r > 0.99);
+    layer_arc.write().lock.pixels = false;
+
+    // Erasing across the boundary only clears selected pixels
+    layer_arc.write().fill(Color::white());
+    engine.brush_engine().write().set_mode(BrushMode::Eraser);
+    let mut stroke = Stroke::new();
+    stroke.add_point(StrokePoint::full(8.0, 32.0, 1.0, 0.0, 0.0, 0.0, 0));
+    stroke.add_point(StrokePoint::full(56.0, 32.0, 1.0, 0.0, 0.0, 0.0, 16));
+    engine.process_stroke(&stroke).expect("Failed to process stroke");
+    assert!(layer_arc.read().get_pixel(16, 32).unwrap().a < 1.0);
+    assert_eq!(layer_arc.read().get_pixel(48, 32).unwrap().a, 1.0);
+
+    // Filters and adjustments only change selected pixels
+    let selection_manager = engine.selection_manager();
+    Invert.apply_with_selection(&mut layer_arc.write(), selection_manager.read().selection());
+    assert!(layer_arc.read().get_pixel(4, 4).unwrap().r < 0.01);
+    assert!(layer_arc.read().get_pixel(48, 4).unwrap().r > 0.99);
+    Pixelate::new(8).apply_with_selection(&mut layer_arc.write(), selection_manager.read().selection());
+    assert!(layer_arc.read().get_pixel(48, 4).unwrap().r > 0.99);
+
+    // Without a selection they change the whole layer
+    selection_manager.write().clear();
+    Invert.apply_with_selection(&mut layer_arc.write(), selection_manager.read().selection());
+    assert!(layer_arc.read().get_pixel(48, 4).unwrap().r < 0.01);
+}
+
+/// Test a soft brush stroke across a feathered selection edge
+///
+/// The stroke must match the one painted without a selection, faded by
+/// coverage once per pixel rather than once per rendered point.
+#[test]
+fn test_soft_brush_stroke_under_feathered_selection() {
+    let (engine, layer_id) = engine_with_layer(64, 64);
+    let layer_arc = engine.layer_manager().read().get_layer(layer_id).unwrap();
+    layer_arc.write().fill(Color::white());
+    let before = layer_arc.read().pixels.clone();
+    {
+        let brush_engine_arc = engine.brush_engine();
+        let mut brush_engine = brush_engine_arc.write();
+        brush_engine.set_color(Color::black());
+        let settings = &mut brush_engine.current_brush_mut().settings;
+        settings.size = 16.0;
+        settings.hardness = 0.0;
+        settings.flow = 0.3;
+    }
+    let stroke = || {
+        engine.begin_stroke().unwrap();
+        for (i, x) in (8..=56).step_by(2).enumerate() {
+            let point = StrokePoint::full(x as f32, 32.0, 1.0, 0.0, 0.0, 0.0, i as u64 * 8);
+            engine.add_stroke_point(point).unwrap();
+        }
+        engine.end_stroke().unwrap();
+    };
+
+    stroke();
+    let unselected = layer_arc.read().pixels.clone();
+    assert_ne!(unselected, before);
+    engine.undo().unwrap();
+    assert_eq!(layer_arc.read().pixels, before);
+
+    {
+        let selection_manager_arc = engine.selection_manager();
+        let mut selection_manager = selection_manager_arc.write();
+        selection_manager.select_rectangle(0.0, 0.0, 32.0, 64.0);
+        selection_manager.feather(4.0);
+    }
+    stroke();
+    let selection_manager = engine.selection_manager();
+    let selection_manager = selection_manager.read();
+    let mask = &selection_manager.selection().mask;
+    let mut partial = 0;
+    for y in 20..44 {
+        for x in 0..64 {
+            let idx = ((y * 64 + x) * 4) as usize;
+            let c = mask.get(x, y) as f32 / 255.0;
+            partial += (c > 0.1 && c < 0.9 && unselected[idx] < 200) as usize;
+            let expected = before[idx] as f32 + (unselected[idx] as f32 - before[idx] as f32) * c;
+            let actual = layer_arc.read().pixels[idx] as f32;
+            assert!(
+                (actual - expected).abs() <= 2.0,
+                "({}, {}) at coverage {:.2}: {} against {:.1}",
+                x,
+                y,
+                c,
+                actual,
+                expected
+            );
+        }
+    }
+    assert!(partial > 0);
+}
+
+/// Test filling flats on one layer from line art on another
+#[test]
+fn test_fill_from_reference_line_art() {
+    let (engine, flats_id) = engine_with_layer(32, 32);
+    let line_id = engine.layer_manager().write().add_layer("Lines");
+    engine.layer_manager().write().set_active_layer(flats_id).unwrap();
+
+    // Square outline with a one pixel break in its left edge
+    {
+        let layer_arc = engine.layer_manager().read().get_layer(line_id).unwrap();
+        let mut layer = layer_arc.write();
+        for i in 8..24 {
+            for (x, y) in [(i, 8), (i, 23), (23, i), (8, i)] {
+                if (x, y) != (8, 16) {
+                    layer.set_pixel(x, y, Color::black());
+                }
+            }
+        }
+    }
+
+    {
+        let selection_manager_arc = engine.selection_manager();
+        let mut selection_manager = selection_manager_arc.write();
+        let options = *selection_manager.wand_options();
+        selection_manager.set_wand_options(WandOptions {
+            close_gaps: 3.0,
+            expand: 1.0,
+            ..options
+        });
+    }
+    engine
+        .fill_wand_region(16, 16, Color::red(), SampleSource::Layer(line_id))
+        .expect("Failed to fill");
+
+    let layer_arc = engine.layer_manager().read().get_layer(flats_id).unwrap();
+    let layer = layer_arc.read();
+    assert_eq!(layer.get_pixel(16, 16).unwrap().a, 1.0);
+    assert_eq!(layer.get_pixel(9, 9).unwrap().a, 1.0);
+    // Filled under the line, but not through the break
+    assert_eq!(layer.get_pixel(8, 12).unwrap().a, 1.0);
+    assert_eq!(layer.get_pixel(4, 16).unwrap().a, 0.0);
+
+    engine
+        .select_layer_transparency(line_id, SelectionMode::Replace)
+        .expect("Failed to select transparency");
+    let selection_manager = engine.selection_manager();
+    assert!(selection_manager.read().selection().contains(8.0, 12.0));
+    assert!(!selection_manager.read().selection().contains(16.0, 16.0));
+}
+
+/// Test bucket filling merged line art with a pattern, then undoing it
+#[test]
+fn test_bucket_fill_merged_pattern_with_undo() {
+    let (engine, flats_id) = engine_with_layer(32, 32);
+    let line_id = engine.layer_manager().write().add_layer("Lines");
+    engine.layer_manager().write().set_active_layer(flats_id).unwrap();
+
+    // Square outline with a one pixel break in its top edge
+    {
+        let layer_arc = engine.layer_manager().read().get_layer(line_id).unwrap();
+        let mut layer = layer_arc.write();
+        for i in 8..24 {
+            for (x, y) in [(i, 8), (i, 23), (23, i), (8, i)] {
+                if (x, y) != (16, 8) {
+                    layer.set_pixel(x, y, Color::black());
+                }
+            }
+        }
+    }
+
+    let stripes = [[255u8, 0, 0, 255], [0, 0, 255, 255]].concat();
+    let pattern = FillPattern::new("Stripes", 2, 1, stripes).unwrap();
+    let options = FillOptions {
+        source: SampleSource::Merged,
+        close_gaps: 3.0,
+        ..FillOptions::default()
+    };
+    engine
+        .bucket_fill(16, 16, &FillContent::Pattern(pattern), &options)
+        .expect("Failed to fill");
+
+    let layer_arc = engine.layer_manager().read().get_layer(flats_id).unwrap();
+    {
+        let layer = layer_arc.read();
+        assert_eq!(layer.get_pixel(16, 16).unwrap().to_rgba8(), (255, 0, 0, 255));
+        assert_eq!(layer.get_pixel(17, 16).unwrap().to_rgba8(), (0, 0, 255, 255));
+        // The break is sealed and the edge fades under the lines
+        assert_eq!(layer.get_pixel(16, 4).unwrap().a, 0.0);
+        let rim = layer.get_pixel(8, 12).unwrap().a;
+        assert!(rim > 0.0 && rim < 1.0);
+    }
+
+    assert!(engine.undo().unwrap());
+    assert!(layer_arc.read().pixels.iter().all(|&v| v == 0));
+}
+
+/// Test editing a selection as a quick mask
+#[test]
+fn test_quick_mask_edits_selection() {
+    let (engine, layer_id) = engine_with_layer(64, 64);
+    {
+        let selection_manager_arc = engine.selection_manager();
+        let mut selection_manager = selection_manager_arc.write();
+        selection_manager.select_rectangle(0.0, 0.0, 32.0, 64.0);
+        selection_manager.enter_quick_mask();
+        assert!(!selection_manager.has_selection());
+    }
+
+    // Unselected areas are tinted in the view but not in the document
+    let document = engine.render().unwrap();
+    let view = engine.render_view().unwrap();
+    let right = ((32 * 64 + 48) * 4) as usize;
+    let left = ((32 * 64 + 16) * 4) as usize;
+    assert!(view[right] > view[right + 1]);
+    assert_eq!(document[right], document[right + 1]);
+    assert_eq!(view[left..left + 4], document[left..left + 4]);
+
+    // Fills land on the mask, not the layer, and can be undone
+    engine.flood_fill(48, 32, Color::white(), 0.1).unwrap();
+    let layer_arc = engine.layer_manager().read().get_layer(layer_id).unwrap();
+    assert_eq!(layer_arc.read().get_pixel(48, 32).unwrap().a, 0.0);
+    engine.undo().unwrap();
+    let surface = engine.selection_manager().read().quick_mask().unwrap().surface();
+    assert_eq!(surface.read().get_pixel(48, 32).unwrap().r, 0.0);
+    engine.flood_fill(48, 32, Color::white(), 0.1).unwrap();
+    assert_eq!(surface.read().get_pixel(48, 32).unwrap().r, 1.0);
+
+    engine.selection_manager().write().exit_quick_mask();
+    let selection_manager = engine.selection_manager();
+    let selection_manager = selection_manager.read();
+    assert!(!selection_manager.is_quick_mask());
+    assert!(selection_manager.selection().contains(16.0, 32.0));
+    assert!(selection_manager.selection().contains(48.0, 32.0));
+}
+
+/// Test moving selected pixels as a floating selection
+#[test]
+fn test_floating_selection_transform() {
+    use drawconnect_core::transform::Interpolation;
+
+    let (engine, layer_id) = engine_with_layer(64, 64);
+    let layer_arc = engine.layer_manager().read().get_layer(layer_id).unwrap();
+    layer_arc.write().fill(Color::from_rgb(1.0, 0.0, 0.0));
+    {
+        let selection_manager_arc = engine.selection_manager();
+        let mut selection_manager = selection_manager_arc.write();
+        selection_manager.select_rectangle(0.0, 0.0, 16.0, 16.0);
+    }
+    let root = engine.history_manager().read().current_node();
+
+    engine.float_selection().unwrap();
+    assert_eq!(layer_arc.read().get_pixel(8, 8).unwrap().a, 0.0);
+    engine
+        .selection_manager()
+        .write()
+        .floating_mut()
+        .unwrap()
+        .translate(32.0, 32.0);
+
+    // The preview shows the moved pixels without touching the layer yet
+    let view = engine.render_view().unwrap();
+    let moved = ((40 * 64 + 40) * 4) as usize;
+    assert_eq!(view[moved + 3], 255);
+    layer_arc.write().set_pixel(40, 40, Color::transparent());
+
+    engine.commit_floating_selection(Interpolation::Bicubic).unwrap();
+    assert_eq!(layer_arc.read().get_pixel(40, 40).unwrap().to_rgba8(), (255, 0, 0, 255));
+    assert_eq!(layer_arc.read().get_pixel(8, 8).unwrap().a, 0.0);
+    let selection_manager = engine.selection_manager();
+    assert!(selection_manager.read().selection().contains(40.0, 40.0));
+    assert!(!selection_manager.read().selection().contains(8.0, 8.0));
+
+    // Lift, move and drop undo as one step
+    engine.undo().unwrap();
+    assert_eq!(layer_arc.read().get_pixel(8, 8).unwrap().a, 1.0);
+
+    // A pending float is settled before jumping, not counted as a step
+    engine.redo().unwrap();
+    selection_manager.write().clear();
+    engine.flood_fill(60, 60, Color::blue(), 0.1).unwrap();
+    let filled = engine.history_manager().read().current_node();
+    selection_manager.write().select_rectangle(48.0, 0.0, 16.0, 16.0);
+    engine.float_selection().unwrap();
+    assert!(engine.jump_to_history_node(root).unwrap());
+    assert_eq!(engine.history_manager().read().current_node(), root);
+    assert!(!selection_manager.read().is_floating());
+    assert_eq!(layer_arc.read().get_pixel(8, 8).unwrap().to_rgba8(), (255, 0, 0, 255));
+
+    // Redo settles it too, so cancelling later cannot overwrite the redone step
+    engine.float_selection().unwrap();
+    assert!(engine.jump_to_history_node(filled).unwrap());
+    assert_eq!(engine.history_manager().read().current_node(), filled);
+    assert!(!selection_manager.read().is_floating());
+    assert_eq!(layer_arc.read().get_pixel(56, 8).unwrap().to_rgba8(), (0, 0, 255, 255));
+}
+
+/// Test that pointer events drive the engine through the current tool
+#[test]
+fn test_tools_dispatch_through_tool_manager() {
+    let (engine, layer_id) = engine_with_layer(64, 64);
+    let layer_arc = engine.layer_manager().read().get_layer(layer_id).unwrap();
+    let at = |x: f32, y: f32| ToolEvent::new(x, y, 1.0);
+    let shift = Modifiers { shift: true, ..Modifiers::default() };
+
+    // Shapes preview while dragging and paint on release
+    engine.tool_manager().write().set_primary_color(Color::red());
+    engine.set_tool(ToolType::Rectangle).unwrap();
+    engine.pointer_down(at(4.0, 4.0)).unwrap();
+    engine.pointer_move(at(20.0, 12.0).with_modifiers(shift)).unwrap();
+    assert_eq!(
+        engine.tool_preview(),
+        ToolPreview::Rect { x: 4.0, y: 4.0, width: 16.0, height: 16.0 }
+    );
+    assert_eq!(layer_arc.read().get_pixel(10, 10).unwrap().a, 0.0);
+    engine.pointer_up(at(20.0, 12.0).with_modifiers(shift)).unwrap();
+    assert_eq!(layer_arc.read().get_pixel(10, 18).unwrap().to_rgba8(), (255, 0, 0, 255));
+    assert_eq!(layer_arc.read().get_pixel(22, 10).unwrap().a, 0.0);
+
+    // The marquee selects on release and the selection clips the eyedropper's fill
+    engine.set_tool(ToolType::SelectRect).unwrap();
+    engine.pointer_down(at(0.0, 32.0)).unwrap();
+    engine.pointer_up(at(32.0, 64.0)).unwrap();
+    assert!(engine.selection_manager().read().selection().contains(16.0, 48.0));
+
+    engine.set_tool(ToolType::ColorPicker).unwrap();
+    engine.pointer_down(at(10.0, 10.0)).unwrap();
+    engine.pointer_up(at(10.0, 10.0)).unwrap();
+    assert!(engine.tool_manager().read().primary_color().r > 0.99);
+
+    engine.set_tool(ToolType::Fill).unwrap();
+    engine.pointer_down(at(40.0, 40.0)).unwrap();
+    engine.pointer_up(at(40.0, 40.0)).unwrap();
+    assert!(layer_arc.read().get_pixel(16, 48).unwrap().r > 0.99);
+    assert_eq!(layer_arc.read().get_pixel(48, 48).unwrap().a, 0.0);
+    engine.undo().unwrap();
+    assert_eq!(layer_arc.read().get_pixel(16, 48).unwrap().a, 0.0);
+    engine.selection_manager().write().clear();
+
+    // The move tool lifts the whole layer without a selection and drops it
+    engine.set_tool(ToolType::Move).unwrap();
+    engine.pointer_down(at(10.0, 10.0)).unwrap();
+    engine.pointer_move(at(40.0, 10.0)).unwrap();
+    assert!(matches!(engine.tool_preview(), ToolPreview::Handles { .. }));
+    engine.pointer_up(at(40.0, 10.0)).unwrap();
+    assert_eq!(layer_arc.read().get_pixel(10, 10).unwrap().a, 0.0);
+    assert_eq!(layer_arc.read().get_pixel(40, 10).unwrap().to_rgba8(), (255, 0, 0, 255));
+    assert!(!engine.selection_manager().read().has_selection());
+
+    // Free transform stays pending until cancelled
+    engine.set_tool(ToolType::Transform).unwrap();
+    engine.pointer_down(at(40.0, 10.0)).unwrap();
+    engine.pointer_up(at(50.0, 30.0)).unwrap();
+    assert!(engine.selection_manager().read().is_floating());
+    engine.cancel_tool().unwrap();
+    assert!(!engine.selection_manager().read().is_floating());
+    assert_eq!(layer_arc.read().get_pixel(40, 10).unwrap().to_rgba8(), (255, 0, 0, 255));
+
+    // Cancelling a stroke puts the pixels back
+    engine.set_tool(ToolType::Brush).unwrap();
+    engine.pointer_down(at(50.0, 50.0)).unwrap();
+    engine.pointer_move(at(60.0, 50.0)).unwrap();
+    engine.pointer_move(at(60.0, 60.0)).unwrap();
+    assert!(layer_arc.read().pixels.chunks_exact(4).skip(48 * 64).any(|px| px[3] > 0));
+    engine.cancel_tool().unwrap();
+    assert!(layer_arc.read().pixels.chunks_exact(4).skip(48 * 64).all(|px| px[3] == 0));
+
+    // Zooming keeps the clicked point in place
+    engine.set_tool(ToolType::Zoom).unwrap();
+    engine.pointer_down(at(16.0, 16.0)).unwrap();
+    let render_pipeline = engine.render_pipeline();
+    let context = render_pipeline.read();
+    assert_eq!(context.context().zoom, 2.0);
+    assert_eq!(context.context().pan_x, -16.0);
+}
+
+/// Test the clone stamp's alignment modes and the healing brush's blend
+#[test]
+fn test_clone_stamp_and_healing_brush() {
+    let (engine, layer_id) = engine_with_layer(64, 64);
+    let layer_arc = engine.layer_manager().read().get_layer(layer_id).unwrap();
+
+    // Dark checkerboard texture in the top left, flat light gray on the right
+    {
+        let mut layer = layer_arc.write();
+        for y in 0..64u32 {
+            for x in 0..64u32 {
+                let v = if x >= 32 {
+                    200
+                } else if (x + y) % 2 == 0 {
+                    75
+                } else {
+                    45
+                };
+                if x >= 32 || y < 40 {
+                    layer.set_pixel(x, y, Color::from_rgba8(v, v, v, 255));
+                }
+            }
+        }
+    }
+    {
+        let tool_manager_arc = engine.tool_manager();
+        let mut tool_manager = tool_manager_arc.write();
+        let options = tool_manager.options_mut();
+        options.size = 8.0;
+        options.hardness = 1.0;
+    }
+    let at = |x: f32, y: f32| ToolEvent::new(x, y, 1.0);
+    let alt = Modifiers { alt: true, ..Modifiers::default() };
+    let click = |x: f32, y: f32| {
+        engine.pointer_down(at(x, y)).unwrap();
+        engine.pointer_up(at(x, y)).unwrap();
+    };
+
+    // Aligned strokes keep the first offset and clone from the empty bottom left
+    engine.set_tool(ToolType::Clone).unwrap();
+    engine.pointer_down(at(16.0, 32.0).with_modifiers(alt)).unwrap();
+    engine.pointer_up(at(16.0, 32.0).with_modifiers(alt)).unwrap();
+    click(48.0, 20.0);
+    assert_eq!(layer_arc.read().get_pixel(48, 20).unwrap().to_rgba8(), (75, 75, 75, 255));
+    click(48.0, 44.0);
+    assert_eq!(layer_arc.read().get_pixel(48, 44).unwrap().a, 0.0);
+    engine.undo().unwrap();
+
+    // Non-aligned strokes start from the source point every time
+    engine.tool_manager().write().options_mut().aligned = false;
+    click(48.0, 44.0);
+    assert_eq!(layer_arc.read().get_pixel(48, 44).unwrap().to_rgba8(), (75, 75, 75, 255));
+    engine.undo().unwrap();
+    engine.undo().unwrap();
+    assert_eq!(layer_arc.read().get_pixel(48, 20).unwrap().to_rgba8(), (200, 200, 200, 255));
+
+    // Healing keeps the clone's texture at the target's tone
+    engine.set_tool(ToolType::Heal).unwrap();
+    engine.pointer_down(at(16.0, 20.0).with_modifiers(alt)).unwrap();
+    engine.pointer_up(at(16.0, 20.0).with_modifiers(alt)).unwrap();
+    click(48.0, 20.0);
+    let layer = layer_arc.read();
+    let (a, b) = (layer.get_pixel(48, 20).unwrap(), layer.get_pixel(49, 20).unwrap());
+    assert!(((a.r + b.r) / 2.0 - 200.0 / 255.0).abs() < 0.03);
+    assert!((a.r - b.r).abs() > 0.08);
+    assert_eq!(layer.get_pixel(58, 20).unwrap().to_rgba8(), (200, 200, 200, 255));
+}
+
+/// Test a soft clone stroke across a feathered selection edge
+///
+/// Overlapping dabs must build up as they would without a selection, with
+/// the result faded by coverage once per pixel.
+#[test]
+fn test_soft_clone_stroke_under_feathered_selection() {
+    let (engine, layer_id) = engine_with_layer(64, 64);
+    let layer_arc = engine.layer_manager().read().get_layer(layer_id).unwrap();
+
+    // White on top to clone from, black below to clone onto
+    {
+        let mut layer = layer_arc.write();
+        for y in 0..64 {
+            let color = if y < 32 { Color::white() } else { Color::black() };
+            for x in 0..64 {
+                layer.set_pixel(x, y, color);
+            }
+        }
+    }
+    let before = layer_arc.read().pixels.clone();
+    {
+        let tool_manager_arc = engine.tool_manager();
+        let mut tool_manager = tool_manager_arc.write();
+        let options = tool_manager.options_mut();
+        options.size = 16.0;
+        options.hardness = 0.0;
+    }
+    let at = |x: f32, y: f32| ToolEvent::new(x, y, 1.0);
+    let alt = Modifiers { alt: true, ..Modifiers::default() };
+    engine.set_tool(ToolType::Clone).unwrap();
+    engine.pointer_down(at(8.0, 8.0).with_modifiers(alt)).unwrap();
+    engine.pointer_up(at(8.0, 8.0).with_modifiers(alt)).unwrap();
+    let stroke = || {
+        engine.pointer_down(at(8.0, 48.0)).unwrap();
+        for x in (10..=56).step_by(2) {
+            engine.pointer_move(at(x as f32, 48.0)).unwrap();
+        }
+        engine.pointer_up(at(56.0, 48.0)).unwrap();
+    };
+
+    stroke();
+    let unselected = layer_arc.read().pixels.clone();
+    engine.undo().unwrap();
+    assert_eq!(layer_arc.read().pixels, before);
+
+    {
+        let selection_manager_arc = engine.selection_manager();
+        let mut selection_manager = selection_manager_arc.write();
+        selection_manager.select_rectangle(0.0, 0.0, 32.0, 64.0);
+        selection_manager.feather(4.0);
+    }
+    stroke();
+    let selection_manager = engine.selection_manager();
+    let selection_manager = selection_manager.read();
+    let mask = &selection_manager.selection().mask;
+    let mut partial = 0;
+    for y in 40..56 {
+        for x in 0..64 {
+            let idx = ((y * 64 + x) * 4) as usize;
+            let c = mask.get(x, y) as f32 / 255.0;
+            partial += (c > 0.1 && c < 0.9) as usize;
+            let expected = before[idx] as f32 + (unselected[idx] as f32 - before[idx] as f32) * c;
+            let actual = layer_arc.read().pixels[idx] as f32;
+            assert!(
+                (actual - expected).abs() <= 2.0,
+                "({}, {}) at coverage {:.2}: {} against {:.1}",
+                x,
+                y,
+                c,
+                actual,
+                expected
+            );
+        }
+    }
+    assert!(partial > 0);
+}
+
+/// Test content-aware fill into the active layer and onto a new layer
+#[test]
+fn test_content_aware_fill_selection() {
+    let (engine, layer_id) = engine_with_layer(32, 32);
+    let layer_arc = engine.layer_manager().read().get_layer(layer_id).unwrap();
+
+    // Horizontal stripes with a white blob to remove
+    {
+        let mut layer = layer_arc.write();
+        for y in 0..32 {
+            for x in 0..32 {
+                let color = if (12..20).contains(&x) && (12..20).contains(&y) {
+                    Color::white()
+                } else if y / 4 % 2 == 0 {
+                    Color::red()
+                } else {
+                    Color::green()
+                };
+                layer.set_pixel(x, y, color);
+            }
+        }
+    }
+    assert!(engine.content_aware_fill(&ContentAwareOptions::default(), |_| {}).is_err());
+    engine.selection_manager().write().select_rectangle(12.0, 12.0, 8.0, 8.0);
+    let layer_count = engine.layer_manager().read().layer_count();
+
+    let mut last_progress = 0.0;
+    let new_id = engine
+        .content_aware_fill(
+            &ContentAwareOptions {
+                output: ContentAwareOutput::NewLayer,
+                ..ContentAwareOptions::default()
+            },
+            |p| last_progress = p,
+        )
+        .unwrap()
+        .expect("New layer");
+    assert_eq!(last_progress, 1.0);
+    let new_arc = engine.layer_manager().read().get_layer(new_id).unwrap();
+    assert!(new_arc.read().get_pixel(16, 16).unwrap().g + new_arc.read().get_pixel(16, 16).unwrap().r > 0.99);
+    assert_eq!(new_arc.read().get_pixel(4, 4).unwrap().a, 0.0);
+    assert_eq!(layer_arc.read().get_pixel(16, 16).unwrap(), Color::white());
+    assert_eq!(engine.layer_manager().read().layer_count(), layer_count + 1);
+
+    // Undo removes the new layer, redo brings it back filled
+    engine.undo().unwrap();
+    assert_eq!(engine.layer_manager().read().layer_count(), layer_count);
+    assert!(engine.layer_manager().read().get_layer(new_id).is_none());
+    engine.redo().unwrap();
+    let redone = engine.layer_manager().read().get_layer(new_id).expect("Layer restored by redo");
+    assert!(redone.read().get_pixel(16, 16).unwrap().a > 0.99);
+    engine.undo().unwrap();
+    assert_eq!(engine.layer_manager().read().layer_count(), layer_count);
+
+    engine.layer_manager().write().set_active_layer(layer_id).unwrap();
+    engine.content_aware_fill(&ContentAwareOptions::default(), |_| {}).unwrap();
+    let white = (12..20)
+        .flat_map(|y| (12..20).map(move |x| (x, y)))
+        .filter(|&(x, y)| layer_arc.read().get_pixel(x, y).unwrap().b > 0.5)
+        .count();
+    assert_eq!(white, 0);
+    engine.undo().unwrap();
+    assert_eq!(layer_arc.read().get_pixel(16, 16).unwrap(), Color::white());
+}
+
+/// Test content-aware fill sampling every layer keeps transparent areas clear
+#[test]
+fn test_content_aware_fill_merged_keeps_transparency() {
+    let (engine, back_id) = engine_with_layer(32, 32);
+    let top_id = engine.layer_manager().write().add_layer("Top");
+
+    // Only the left quarter of the document is painted
+    let back_arc = engine.layer_manager().read().get_layer(back_id).unwrap();
+    for y in 0..32 {
+        for x in 0..8 {
+            back_arc.write().set_pixel(x, y, Color::green());
+        }
+    }
+    engine.selection_manager().write().select_rectangle(20.0, 12.0, 6.0, 6.0);
+    let options = ContentAwareOptions { sample_all_layers: true, ..ContentAwareOptions::default() };
+    engine.content_aware_fill(&options, |_| {}).unwrap();
+
+    let top_arc = engine.layer_manager().read().get_layer(top_id).unwrap();
+    for y in 12..18 {
+        for x in 20..26 {
+            assert_eq!(top_arc.read().get_pixel(x, y).unwrap().a, 0.0, "({}, {})", x, y);
+        }
+    }
+}
+
+/// Test multi-stop gradients drawn by the gradient tool inside the selection
+#[test]
+fn test_gradient_tool_draws_inside_selection() {
+    let (engine, layer_id) = engine_with_layer(64, 64);
+    engine.selection_manager().write().select_rectangle(0.0, 0.0, 32.0, 64.0);
+    let layer_arc = engine.layer_manager().read().get_layer(layer_id).unwrap();
+
+    {
+        let tool_manager_arc = engine.tool_manager();
+        let mut tool_manager = tool_manager_arc.write();
+        tool_manager.set_primary_color(Color::red());
+        let options = &mut tool_manager.options_mut().gradient;
+        options.gradient = Gradient::new(
+            "Red to Blue",
+            vec![
+                ColorStop::new(0.0, StopColor::Foreground),
+                ColorStop::new(1.0, StopColor::Fixed(Color::from_rgb(0.0, 0.0, 1.0))),
+            ],
+            vec![OpacityStop::new(0.0, 1.0), OpacityStop::new(1.0, 1.0)],
+        );
+        options.shape = GradientShape::Radial;
+        options.dither = false;
+    }
+
+    engine.set_tool(ToolType::Gradient).unwrap();
+    engine.pointer_down(ToolEvent::new(0.0, 0.0, 1.0)).unwrap();
+    engine.pointer_move(ToolEvent::new(16.0, 0.0, 1.0)).unwrap();
+    assert_eq!(
+        engine.tool_preview(),
+        ToolPreview::Line { x0: 0.0, y0: 0.0, x1: 16.0, y1: 0.0 }
+    );
+    engine.pointer_up(ToolEvent::new(16.0, 0.0, 1.0)).unwrap();
+
+    let layer = layer_arc.read();
+    assert!(layer.get_pixel(0, 0).unwrap().r > 0.9);
+    // Past the radius the end color extends
+    assert_eq!(layer.get_pixel(20, 20).unwrap().to_rgba8(), (0, 0, 255, 255));
+    assert_eq!(layer.get_pixel(40, 20).unwrap().a, 0.0);
+    drop(layer);
+
+    engine.undo().unwrap();
+    assert_eq!(layer_arc.read().get_pixel(20, 20).unwrap().a, 0.0);
+}
+
+/// Test shape tools drawing into raster pixels and vector layers
+#[test]
+fn test_shape_tools_raster_and_vector_output() {
+    let (engine, background_id) = engine_with_layer(64, 64);
+    let top_id = engine.layer_manager().write().add_layer("Top");
+    engine.layer_manager().write().set_active_layer(background_id).unwrap();
+    let background = engine.layer_manager().read().get_layer(background_id).unwrap();
+    let at = |x: f32, y: f32| ToolEvent::new(x, y, 1.0);
+    let from_center = Modifiers { alt: true, ..Modifiers::default() };
+
+    {
+        let tool_manager_arc = engine.tool_manager();
+        let mut tool_manager = tool_manager_arc.write();
+        tool_manager.set_primary_color(Color::red());
+        tool_manager.set_secondary_color(Color::from_rgb(0.0, 0.0, 1.0));
+        let shape = &mut tool_manager.options_mut().shape;
+        shape.star = true;
+        shape.stroke = true;
+        shape.stroke_width = 2.0;
+    }
+
+    // A star drawn from its center, filled and stroked
+    engine.set_tool(ToolType::Polygon).unwrap();
+    engine.pointer_down(at(32.0, 32.0).with_modifiers(from_center)).unwrap();
+    engine.pointer_move(at(52.0, 52.0).with_modifiers(from_center)).unwrap();
+    match engine.tool_preview() {
+        ToolPreview::Path { points, closed, .. } => {
+            assert!(closed);
+            assert_eq!(points.len(), 10);
+            assert_eq!(points[0], (32.0, 12.0));
+        }
+        preview => panic!("Unexpected preview {:?}", preview),
+    }
+    engine.pointer_up(at(52.0, 52.0).with_modifiers(from_center)).unwrap();
+    assert_eq!(background.read().get_pixel(32, 32).unwrap().to_rgba8(), (255, 0, 0, 255));
+    assert_eq!(background.read().get_pixel(32, 12).unwrap().to_rgba8().2, 255);
+    // Between the two upper points of the star stays empty
+    assert_eq!(background.read().get_pixel(44, 14).unwrap().a, 0.0);
+    engine.undo().unwrap();
+    assert_eq!(background.read().get_pixel(32, 32).unwrap().a, 0.0);
+
+    // Vector output adds a layer above the active one holding the shape
+    engine.tool_manager().write().options_mut().shape.output = ShapeOutput::Vector;
+    engine.set_tool(ToolType::Rectangle).unwrap();
+    engine.pointer_down(at(8.0, 8.0)).unwrap();
+    engine.pointer_up(at(24.0, 24.0)).unwrap();
+    assert_eq!(background.read().get_pixel(16, 16).unwrap().a, 0.0);
+
+    let layer_manager_arc = engine.layer_manager();
+    let vector_arc = {
+        let layer_manager = layer_manager_arc.read();
+        let ids: Vec<_> = layer_manager.layers().iter().map(|layer| layer.read().id).collect();
+        assert_eq!(ids.len(), 3);
+        assert_eq!((ids[0], ids[2]), (background_id, top_id));
+        layer_manager.get_layer(ids[1]).unwrap()
+    };
+    let vector_id = {
+        let vector = vector_arc.read();
+        assert_eq!(vector.layer_type, LayerType::Vector);
+        assert_eq!(vector.shapes.len(), 1);
+        assert_eq!(vector.shapes[0].kind, ShapeKind::Rectangle);
+        assert_eq!(vector.get_pixel(16, 16).unwrap().to_rgba8(), (255, 0, 0, 255));
+        vector.id
+    };
+
+    // Editing the shape redraws the layer
+    let mut shapes = vector_arc.read().shapes.clone();
+    shapes[0].end = (40.0, 40.0);
+    engine.set_layer_shapes(vector_id, shapes).unwrap();
+    assert_eq!(vector_arc.read().get_pixel(36, 36).unwrap().to_rgba8(), (255, 0, 0, 255));
+    assert!(engine.set_layer_shapes(background_id, Vec::new()).is_err());
+
+    // Locked layers keep their shapes and pixels, without a history step
+    let undo_count = engine.history_manager().read().undo_count();
+    vector_arc.write().lock.pixels = true;
+    assert!(engine.set_layer_shapes(vector_id, Vec::new()).is_err());
+    assert_eq!(vector_arc.read().shapes.len(), 1);
+    assert_eq!(engine.history_manager().read().undo_count(), undo_count);
+    vector_arc.write().lock.pixels = false;
+
+    // Undoing the edit restores the shape and its pixels, redo reapplies it
+    engine.undo().unwrap();
+    assert_eq!(vector_arc.read().shapes[0].end, (24.0, 24.0));
+    assert_eq!(vector_arc.read().get_pixel(36, 36).unwrap().a, 0.0);
+    engine.redo().unwrap();
+    assert_eq!(vector_arc.read().shapes[0].end, (40.0, 40.0));
+    assert_eq!(vector_arc.read().get_pixel(36, 36).unwrap().to_rgba8(), (255, 0, 0, 255));
+
+    // Undoing the shape itself removes its layer
+    engine.undo().unwrap();
+    engine.undo().unwrap();
+    assert_eq!(layer_manager_arc.read().layer_count(), 2);
+    assert!(layer_manager_arc.read().get_layer(vector_id).is_none());
+    engine.redo().unwrap();
+    let restored = layer_manager_arc.read().get_layer(vector_id).expect("Layer restored by redo");
+    assert_eq!(restored.read().shapes.len(), 1);
+    assert_eq!(restored.read().get_pixel(16, 16).unwrap().to_rgba8(), (255, 0, 0, 255));
+}
+
+/// Test reverting one past step while keeping later ones
+#[test]
+fn test_selective_undo() {
+    let (engine, first) = engine_with_layer(32, 32);
+    let second = engine.layer_manager().write().add_layer("Layer 2");
+    let pixel = |layer_id| {
+        let layer_arc = engine.layer_manager().read().get_layer(layer_id).unwrap();
+        let color = layer_arc.read().get_pixel(0, 0).unwrap();
+        color
+    };
+
+    engine.layer_manager().write().set_active_layer(first).unwrap();
+    engine.flood_fill(0, 0, Color::red(), 0.1).unwrap();
+    let red_fill = engine.history_manager().read().current_node();
+    engine.layer_manager().write().set_active_layer(second).unwrap();
+    engine.flood_fill(0, 0, Color::blue(), 0.1).unwrap();
+
+    // The later fill is on another layer, so the red fill reverts alone
+    assert!(matches!(engine.revert_history_node(red_fill).unwrap(), SelectiveUndo::Reverted(_)));
+    assert_eq!(pixel(first).a, 0.0);
+    assert!(pixel(second).b > 0.99);
+
+    // The revert itself is undoable
+    assert!(engine.undo().unwrap());
+    assert!(pixel(first).r > 0.99);
+
+    // A later edit to the same pixels blocks the revert
+    engine.layer_manager().write().set_active_layer(first).unwrap();
+    engine.flood_fill(0, 0, Color::green(), 0.1).unwrap();
+    let green_fill = engine.history_manager().read().current_node();
+    match engine.revert_history_node(red_fill).unwrap() {
+        SelectiveUndo::Conflicts(conflicts) => {
+            assert_eq!(conflicts.len(), 1);
+            assert_eq!(conflicts[0].node, green_fill);
+            assert_eq!(conflicts[0].layer_id, Some(first));
+        }
+        SelectiveUndo::Reverted(_) => panic!("expected a conflict"),
+    }
+    assert!(pixel(first).g > 0.99);
+}
+
+/// Test that undo history survives saving and reopening a document
+#[test]
+fn test_history_persists_in_document() {
+    let path = std::env::temp_dir().join(format!("dc-history-{}.dcpaint", uuid::Uuid::new_v4()));
+    let (engine, layer_id) = engine_with_layer(64, 64);
+    engine.flood_fill(0, 0, Color::red(), 0.1).unwrap();
+    engine.save_document(&path, format::DEFAULT_SAVED_HISTORY_BYTES).unwrap();
+
+    let reopened = DrawEngine::new().expect("Failed to create engine");
+    reopened.open_document(&path).unwrap();
+    std::fs::remove_file(&path).ok();
+
+    let pixel_at_origin = || {
+        let layer_arc = reopened.layer_manager().read().get_layer(layer_id).unwrap();
+        let layer = layer_arc.read();
+        layer.get_pixel(0, 0).unwrap()
+    };
+    assert!(pixel_at_origin().r > 0.99);
+
+    // Undo past the save point
+    assert!(reopened.can_undo());
+    assert!(reopened.undo().unwrap());
+    assert_eq!(pixel_at_origin().a, 0.0);
+    assert!(reopened.redo().unwrap());
+    assert!(pixel_at_origin().r > 0.99);
+}
+
+/// Test that saved selection channels persist and combine with the selection
+#[test]
+fn test_selection_channels_persist_in_document() {
+    let path = std::env::temp_dir().join(format!("dc-channels-{}.dcpaint", uuid::Uuid::new_v4()));
+    let engine = DrawEngine::new().expect("Failed to create engine");
+    let layer_id = engine.layer_manager().write().add_layer("Layer 1");
+    engine.resize_canvas(64, 64, transform::Anchor::TopLeft, Color::transparent()).unwrap();
+    let channel_id = {
+        let selection_manager_arc = engine.selection_manager();
+        let mut selection_manager = selection_manager_arc.write();
+        selection_manager.select_rectangle(0.0, 0.0, 32.0, 64.0);
+        selection_manager.save_channel("Left half").unwrap()
+    };
+    engine.save_document(&path, 0).unwrap();
+
+    let reopened = DrawEngine::new().expect("Failed to create engine");
+    reopened.open_document(&path).unwrap();
+    std::fs::remove_file(&path).ok();
+
+    {
+        let selection_manager_arc = reopened.selection_manager();
+        let mut selection_manager = selection_manager_arc.write();
+        assert_eq!(selection_manager.channels().len(), 1);
+        assert_eq!(selection_manager.channels()[0].name, "Left half");
+
+        selection_manager.select_rectangle(0.0, 0.0, 64.0, 32.0);
+        selection_manager.load_channel(channel_id, SelectionMode::Intersect).unwrap();
+        let selection = selection_manager.selection();
+        assert!(selection.contains(10.0, 10.0));
+        assert!(!selection.contains(40.0, 10.0));
+        assert!(!selection.contains(10.0, 40.0));
+
+        selection_manager.load_channel(channel_id, SelectionMode::Subtract).unwrap();
+        assert!(!selection_manager.selection().contains(10.0, 10.0));
+    }
+
+    // Channels round-trip through layer masks
+    reopened.channel_to_layer_mask(channel_id, layer_id).unwrap();
+    let copy_id = reopened.layer_mask_to_channel(layer_id, "Copy").unwrap();
+    let selection_manager_arc = reopened.selection_manager();
+    let selection_manager = selection_manager_arc.read();
+    assert_eq!(
+        selection_manager.channel(copy_id).unwrap().mask,
+        selection_manager.channel(channel_id).unwrap().mask
+    );
+}
+
+/// Test liquify pushing, freezing and reconstructing as one undo step
+#[test]
+fn test_liquify_tool_session() {
+    let (engine, layer_id) = engine_with_layer(64, 64);
+    let layer_arc = engine.layer_manager().read().get_layer(layer_id).unwrap();
+
+    // A black vertical bar on white
+    {
+        let mut layer = layer_arc.write();
+        for y in 0..64 {
+            for x in 0..64 {
+                let color = if (28..36).contains(&x) { Color::black() } else { Color::white() };
+                layer.set_pixel(x, y, color);
+            }
+        }
+    }
+    let original = layer_arc.read().pixels.clone();
+    {
+        let tool_manager_arc = engine.tool_manager();
+        let mut tool_manager = tool_manager_arc.write();
+        let options = &mut tool_manager.options_mut().liquify;
+        options.size = 24.0;
+        options.strength = 1.0;
+    }
+    let at = |x: f32, y: f32| ToolEvent::new(x, y, 1.0);
+    engine.set_tool(ToolType::Liquify).unwrap();
+
+    // Freeze the bottom half, then push the bar right along its whole length
+    engine.tool_manager().write().options_mut().liquify.mode = LiquifyMode::Freeze;
+    for x in [0.0, 16.0, 32.0, 48.0, 64.0] {
+        engine.pointer_down(at(x, 56.0)).unwrap();
+        engine.pointer_up(at(x, 56.0)).unwrap();
+    }
+    assert!(engine.is_liquifying());
+    engine.tool_manager().write().options_mut().liquify.mode = LiquifyMode::Push;
+    for y in [16.0, 56.0] {
+        engine.pointer_down(at(30.0, y)).unwrap();
+        engine.pointer_move(at(34.0, y)).unwrap();
+        engine.pointer_up(at(38.0, y)).unwrap();
+    }
+    assert!(layer_arc.read().get_pixel(38, 16).unwrap().r < 0.25);
+    assert_eq!(layer_arc.read().get_pixel(38, 56).unwrap().to_rgba8(), (255, 255, 255, 255));
+
+    // Nothing reaches history until the session is committed
+    assert!(!engine.can_undo());
+    let pushed = layer_arc.read().pixels.clone();
+    engine.reconstruct_liquify(0.5).unwrap();
+    assert_ne!(layer_arc.read().pixels, pushed);
+    assert_eq!(layer_arc.read().get_pixel(38, 56).unwrap().to_rgba8(), (255, 255, 255, 255));
+    engine.set_tool(ToolType::Brush).unwrap();
+    assert!(!engine.is_liquifying());
+    assert!(engine.undo().unwrap());
+    assert_eq!(layer_arc.read().pixels, original);
+
+    // Cancelling restores the layer without an undo step
+    engine.begin_liquify().unwrap();
+    engine.liquify_dab(LiquifyMode::TwirlClockwise, (32.0, 32.0), 16.0, 1.0, (0.0, 0.0)).unwrap();
+    assert_ne!(layer_arc.read().pixels, original);
+    engine.cancel_liquify().unwrap();
+    assert_eq!(layer_arc.read().pixels, original);
+    assert!(engine.commit_liquify().is_err());
+
+    // Undo cancels a pending session first, then still steps back
+    engine.begin_liquify().unwrap();
+    engine.liquify_dab(LiquifyMode::TwirlClockwise, (32.0, 32.0), 16.0, 1.0, (0.0, 0.0)).unwrap();
+    engine.commit_liquify().unwrap();
+    let twirled = layer_arc.read().pixels.clone();
+    engine.begin_liquify().unwrap();
+    engine.liquify_dab(LiquifyMode::Bloat, (16.0, 16.0), 12.0, 1.0, (0.0, 0.0)).unwrap();
+    assert!(engine.undo().unwrap());
+    assert!(!engine.is_liquifying());
+    assert_eq!(layer_arc.read().pixels, original);
+    assert!(engine.liquify_dab(LiquifyMode::Bloat, (16.0, 16.0), 12.0, 1.0, (0.0, 0.0)).is_err());
+    assert_eq!(layer_arc.read().pixels, original);
+
+    // Redo cancels it as well before reapplying the committed session
+    engine.begin_liquify().unwrap();
+    engine.liquify_dab(LiquifyMode::Bloat, (16.0, 16.0), 12.0, 1.0, (0.0, 0.0)).unwrap();
+    assert!(engine.redo().unwrap());
+    assert!(!engine.is_liquifying());
+    assert_eq!(layer_arc.read().pixels, twirled);
+
+    // Jumping back two nodes with a stroke pending lands two nodes back
+    let root = engine.history_manager().read().root_node();
+    engine.begin_liquify().unwrap();
+    engine.liquify_dab(LiquifyMode::Bloat, (16.0, 16.0), 12.0, 1.0, (0.0, 0.0)).unwrap();
+    engine.commit_liquify().unwrap();
+    engine.set_tool(ToolType::Liquify).unwrap();
+    engine.pointer_down(at(48.0, 48.0)).unwrap();
+    engine.pointer_move(at(52.0, 48.0)).unwrap();
+    assert!(engine.is_liquifying());
+    assert!(engine.jump_to_history_node(root).unwrap());
+    assert_eq!(engine.history_manager().read().current_node(), root);
+    assert!(!engine.is_liquifying());
+    assert_eq!(layer_arc.read().pixels, original);
+}
+
+/// Test free transform distort, perspective and mesh warp through the tool
+#[test]
+fn test_free_transform_distort_and_warp() {
+    let (engine, layer_id) = engine_with_layer(64, 64);
+    let layer_arc = engine.layer_manager().read().get_layer(layer_id).unwrap();
+    {
+        let mut layer = layer_arc.write();
+        for y in 16..48 {
+            for x in 16..48 {
+                layer.set_pixel(x, y, Color::red());
+            }
+        }
+    }
+    engine.selection_manager().write().select_rectangle(16.0, 16.0, 32.0, 32.0);
+    let at = |x: f32, y: f32| ToolEvent::new(x, y, 1.0);
+    let drag = |from: (f32, f32), to: (f32, f32)| {
+        engine.pointer_down(at(from.0, from.1)).unwrap();
+        engine.pointer_move(at(to.0, to.1)).unwrap();
+        engine.pointer_up(at(to.0, to.1)).unwrap();
+    };
+    engine.set_tool(ToolType::Transform).unwrap();
+
+    // Distort drags one corner alone, perspective mirrors it onto its neighbor
+    engine.tool_manager().write().options_mut().transform.mode = FreeTransformMode::Distort;
+    drag((48.0, 48.0), (56.0, 56.0));
+    engine.tool_manager().write().options_mut().transform.mode = FreeTransformMode::Perspective;
+    drag((16.0, 16.0), (24.0, 16.0));
+    let ToolPreview::Handles { corners } = engine.tool_preview() else {
+        panic!("expected corner handles");
+    };
+    let near = |a: (f32, f32), b: (f32, f32)| (a.0 - b.0).abs() < 0.01 && (a.1 - b.1).abs() < 0.01;
+    assert!(near(corners[0], (24.0, 16.0)));
+    assert!(near(corners[1], (40.0, 16.0)));
+    assert!(near(corners[2], (56.0, 56.0)));
+
+    // Warp mode switches to a mesh that keeps the shape so far
+    {
+        let tool_manager_arc = engine.tool_manager();
+        let mut tool_manager = tool_manager_arc.write();
+        let options = &mut tool_manager.options_mut().transform;
+        options.mode = FreeTransformMode::Warp;
+        options.warp_columns = 2;
+        options.warp_rows = 2;
+        options.interpolation = transform::Interpolation::Lanczos;
+    }
+    drag((5.0, 5.0), (5.0, 6.0));
+    let ToolPreview::Mesh { columns: 2, rows: 2, points } = engine.tool_preview() else {
+        panic!("expected a 2x2 mesh");
+    };
+    assert!(near(points[0], (24.0, 17.0)));
+    let pulled = (4.0, points[3].1);
+    drag(points[3], pulled);
+    let (px, py) = (6, pulled.1 as u32);
+
+    // The preview shows the warp before anything is committed
+    let view = engine.render_view().unwrap();
+    let idx = ((py * 64 + px) * 4) as usize;
+    assert!(view[idx] > 200 && view[idx + 1] < 50);
+    engine.commit_tool().unwrap();
+    assert!(!engine.selection_manager().read().is_floating());
+    assert_eq!(layer_arc.read().get_pixel(px, py).unwrap().to_rgba8(), (255, 0, 0, 255));
+    assert_eq!(layer_arc.read().get_pixel(20, 17).unwrap().a, 0.0);
+    assert!(engine.selection_manager().read().selection().contains(px as f32 + 0.5, py as f32 + 0.5));
+
+    engine.undo().unwrap();
+    assert_eq!(layer_arc.read().get_pixel(20, 20).unwrap().to_rgba8(), (255, 0, 0, 255));
+    assert_eq!(layer_arc.read().get_pixel(px, py).unwrap().a, 0.0);
+}
+
+/// Test eyedropper sample sizes, layer scope and color history
+#[test]
+fn test_eyedropper_sampling_and_history() {
+    let (engine, base_id) = engine_with_layer(32, 32);
+    let top_id = engine.layer_manager().write().add_layer("Top");
+    {
+        let layer_manager = engine.layer_manager();
+        let layer_manager = layer_manager.read();
+        let base = layer_manager.get_layer(base_id).unwrap();
+        let mut base = base.write();
+        base.fill(Color::white());
+        for x in 0..16 {
+            for y in 0..32 {
+                base.set_pixel(x, y, Color::black());
+            }
+        }
+        drop(base);
+        let top = layer_manager.get_layer(top_id).unwrap();
+        let mut top = top.write();
+        top.fill(Color::from_rgba(1.0, 0.0, 0.0, 1.0));
+        top.blend_mode = BlendMode::Multiply;
+    }
+
+    // Multiply darkens the composite and hides red over black
+    let mut options = PickerOptions { layers: SampleLayers::All, ..PickerOptions::default() };
+    let color = engine.pick_color(24, 8, &options).unwrap();
+    assert_eq!(color.to_rgba8(), (255, 0, 0, 255));
+    assert_eq!(engine.pick_color(8, 8, &options).unwrap().to_rgba8(), (0, 0, 0, 255));
+
+    // Averaging across the black/white edge of the layer below
+    options.layers = SampleLayers::Below;
+    options.sample_size = SampleSize::Average31;
+    let gray = engine.pick_color(15, 16, &options).unwrap();
+    assert!((gray.r - 0.5).abs() < 0.05 && (gray.r - gray.b).abs() < 0.01);
+    options.sample_size = SampleSize::Point;
+    options.layers = SampleLayers::Current;
+    assert_eq!(engine.pick_color(8, 8, &options).unwrap().to_rgba8(), (255, 0, 0, 255));
+    assert!(engine.pick_color(40, 8, &options).is_err());
+    assert_eq!(engine.color_manager().read().history().len(), 3);
+
+    // The tool samples while dragging and records the release color only
+    engine.color_manager().write().clear_history();
+    engine.tool_manager().write().options_mut().picker =
+        PickerOptions { sample_size: SampleSize::Average5, layers: SampleLayers::All };
+    engine.set_tool(ToolType::ColorPicker).unwrap();
+    engine.pointer_down(ToolEvent::new(8.0, 8.0, 1.0)).unwrap();
+    assert_eq!(
+        engine.tool_preview(),
+        ToolPreview::Rect { x: 6.0, y: 6.0, width: 5.0, height: 5.0 }
+    );
+    engine.pointer_move(ToolEvent::new(24.0, 8.0, 1.0)).unwrap();
+    engine.pointer_up(ToolEvent::new(24.0, 8.0, 1.0)).unwrap();
+    assert_eq!(engine.tool_manager().read().primary_color().to_rgba8(), (255, 0, 0, 255));
+    let history = engine.color_manager().read().history().to_vec();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].to_rgba8(), (255, 0, 0, 255));
+}
+
+/// Test the ruler tool, unit readouts and straightening a layer
+#[test]
+fn test_measure_tool_and_straighten_layer() {
+    let (engine, layer_id) = engine_with_layer(64, 64);
+    engine.canvas().write().settings_mut().dpi = 254;
+    let layer_arc = engine.layer_manager().read().get_layer(layer_id).unwrap();
+    {
+        // A horizon tilted up to the right, about 1 pixel in 10
+        let mut layer = layer_arc.write();
+        for x in 2..62 {
+            let y = 37.0 - x as f32 * 0.1;
+            layer.set_pixel(x, y.round() as u32, Color::black());
+        }
+    }
+    let at = |x: f32, y: f32| ToolEvent::new(x, y, 1.0);
+
+    engine.set_tool(ToolType::Measure).unwrap();
+    engine.pointer_down(at(2.0, 36.8)).unwrap();
+    engine.pointer_move(at(30.0, 34.0)).unwrap();
+    assert_eq!(
+        engine.tool_preview(),
+        ToolPreview::Line { x0: 2.0, y0: 36.8, x1: 30.0, y1: 34.0 }
+    );
+    engine.pointer_up(at(30.0, 34.0)).unwrap();
+
+    // Dragging the end of the line adjusts it instead of starting a new one
+    engine.pointer_down(at(31.0, 33.0)).unwrap();
+    engine.pointer_up(at(62.0, 31.0)).unwrap();
+    let measurements = engine.measurements();
+    assert_eq!(measurements.len(), 1);
+    assert_eq!(measurements[0].end, (62.0, 31.0));
+
+    // A click without a drag measures nothing
+    engine.pointer_down(at(10.0, 10.0)).unwrap();
+    engine.pointer_up(at(10.0, 10.0)).unwrap();
+    assert_eq!(engine.measurements().len(), 1);
+
+    let readout = engine.measurement_readouts(MeasureUnit::Millimeters)[0];
+    assert!((readout.angle - 5.52).abs() < 0.01);
+    assert!((readout.distance - 6.03).abs() < 0.01);
+    assert!((readout.width - 6.0).abs() < 1e-4);
+    let pixels = engine.measurement_readouts(MeasureUnit::Pixels)[0];
+    assert!((pixels.height - 5.8).abs() < 1e-4);
+
+    // Straightening levels the horizon in one undo step
+    let angle = engine.straighten_layer(readout.id).unwrap();
+    assert!((angle - readout.angle).abs() < 1e-4);
+    let dark_rows = |x: u32| -> Vec<u32> {
+        let layer = layer_arc.read();
+        (0..64).filter(|&y| layer.get_pixel(x, y).unwrap().a > 0.3).collect()
+    };
+    let (left, right) = (dark_rows(12), dark_rows(50));
+    assert!(!left.is_empty() && !right.is_empty());
+    assert!((left[0] as i32 - right[0] as i32).abs() <= 1);
+    engine.undo().unwrap();
+    assert_eq!(layer_arc.read().get_pixel(50, 32).unwrap().a, 1.0);
+    assert!(engine.straighten_layer(uuid::Uuid::new_v4()).is_err());
+}
+
+/// Test contracting magic wand selections with a negative expand
+#[test]
+fn test_magic_wand_contracts_through_manager() {
+    let (engine, layer_id) = engine_with_layer(32, 32);
+    {
+        let layer_arc = engine.layer_manager().read().get_layer(layer_id).unwrap();
+        let mut layer = layer_arc.write();
+        for y in 8..24 {
+            for x in 8..24 {
+                layer.set_pixel(x, y, Color::red());
+            }
+        }
+    }
+
+    {
+        let selection_manager_arc = engine.selection_manager();
+        let mut selection_manager = selection_manager_arc.write();
+        let options = *selection_manager.wand_options();
+        selection_manager.set_wand_options(WandOptions { expand: -2.0, ..options });
+        assert_eq!(selection_manager.wand_options().expand, -2.0);
+    }
+    engine
+        .select_magic_wand(16, 16, SampleSource::ActiveLayer)
+        .expect("Failed to select");
+
+    // The region pulls back from its edges
+    let selection_manager = engine.selection_manager();
+    let selection = selection_manager.read().selection().clone();
+    assert!(selection.contains(16.5, 16.5));
+    assert!(selection.contains(11.5, 16.5));
+    assert!(!selection.contains(8.5, 16.5));
+    assert!(!selection.contains(23.5, 16.5));
+}
@@ -20,8 +20,15 @@ use image::GenericImageView;
 
 use drawconnect_core::{
     DrawEngine, Color, Stroke, StrokePoint, BrushMode,
-    selection::{SelectionMode, SelectionInfo},
-    import::{AbrParser, PatParser, SwatchParser},
+    geometry::Transform,
+    transform::Interpolation,
+    selection::{
+        ColorRange, ColorRangeSpace, ColorRangeTarget, SampleSource, SelectionMode, SelectionInfo,
+        SelectionOutline, WandOptions,
+    },
+    import::{AbrParser, GrdParser, PatParser, SwatchParser},
+    ContentAwareOptions, FillContent, FillOptions, FillPattern, Gradient, MeasureReadout, MeasureUnit, PickerOptions, Shape, ToolEvent, ToolOptions, ToolPreview,
+    ToolType,
 };
 
 use plugin_commands::PluginManagerState;
@@ -115,6 +122,13 @@ pub struct ImportedPatternInfo {
     pub height: u32,
 }
 
+/// Saved selection channel info for frontend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelInfo {
+    pub id: String,
+    pub name: String,
+}
+
 /// Imported color swatch info from PS .aco/.ase file
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ImportedSwatchInfo {
@@ -305,8 +319,8 @@ fn render_canvas(state: State<AppState>) -> Result<String, String> {
     let engine_lock = state.engine.read();
     let engine = engine_lock.as_ref().ok_or("No canvas open")?;
 
-    // Get raw RGBA pixels from render pipeline
-    let pixels = engine.render().map_err(|e| e.to_string())?;
+    // Get raw RGBA pixels from render pipeline, with editing overlays
+    let pixels = engine.render_view().map_err(|e| e.to_string())?;
 
     // Get canvas dimensions
     let canvas = engine.canvas();
@@ -910,30 +924,15 @@ async fn import_image(state: State<'_, AppState>, path: String, x: Option<i32>,
     }
 
     let rgba_img = img.to_rgba8();
+    let (layer_width, layer_height) = {
+        let layer_manager_arc = engine.layer_manager();
+        let layer_manager = layer_manager_arc.read();
+        let active_layer = layer_manager.active_layer().ok_or("No active layer")?;
+        let layer = active_layer.read();
+        (layer.width(), layer.height())
+    };
 
-    // Get active layer and draw the image onto it
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    let active_layer = layer_manager.active_layer()
-        .ok_or("No active layer")?;
-
-    let mut layer = active_layer.write();
-    let layer_width = layer.width();
-    let layer_height = layer.height();
-
-    if layer_width == 0 || layer_height == 0 {
-        return Err(format!("Layer has invalid dimensions: {}x{}", layer_width, layer_height));
-    }
-
-    // Verify and fix pixel array size if needed
-    let expected_size = (layer_width * layer_height * 4) as usize;
-    if layer.pixels.len() != expected_size {
-        // Resize pixel array to correct size
-        layer.pixels.resize(expected_size, 0);
-    }
-
-    // Calculate offset - center the image if no position specified
+    // Center the image if no position specified
     let offset_x = x.unwrap_or_else(|| {
         ((layer_width as i32) - (img_width as i32)) / 2
     });
@@ -941,48 +940,9 @@ async fn import_image(state: State<'_, AppState>, path: String, x: Option<i32>,
         ((layer_height as i32) - (img_height as i32)) / 2
     });
 
-    let mut pixels_written = 0u32;
-
-    // Draw each pixel from the image onto the layer - write directly to pixels array
-    for py in 0..img_height {
-        for px in 0..img_width {
-            let layer_x = offset_x + px as i32;
-            let layer_y = offset_y + py as i32;
-
-            // Skip pixels outside layer bounds
-            if layer_x < 0 || layer_y < 0 {
-                continue;
-            }
-
-            let layer_x = layer_x as u32;
-            let layer_y = layer_y as u32;
-
-            if layer_x >= layer_width || layer_y >= layer_height {
-                continue;
-            }
-
-            let pixel = rgba_img.get_pixel(px, py);
-
-            // Write all non-transparent pixels
-            if pixel[3] > 0 {
-                let idx = ((layer_y * layer_width + layer_x) * 4) as usize;
-                // Double check bounds (should always pass after resize)
-                if idx + 4 <= layer.pixels.len() {
-                    layer.pixels[idx] = pixel[0];
-                    layer.pixels[idx + 1] = pixel[1];
-                    layer.pixels[idx + 2] = pixel[2];
-                    layer.pixels[idx + 3] = pixel[3];
-                    pixels_written += 1;
-                }
-            }
-        }
-    }
-
-    if pixels_written == 0 {
-        return Err("No pixels were written - image may be fully transparent or outside canvas bounds".to_string());
-    }
-
-    Ok(())
+    // Composite onto the active layer, clipped by the selection
+    engine.paste_pixels(rgba_img.as_raw(), img_width, img_height, offset_x, offset_y)
+        .map_err(|e| e.to_string())
 }
 
 /// Import image as new layer (centered)
@@ -1186,6 +1146,20 @@ fn select_rect(state: State<AppState>, x: f32, y: f32, width: f32, height: f32)
     Ok(selection_manager.get_info())
 }
 
+/// Create an ellipse selection inscribed in a rectangle
+#[tauri::command]
+fn select_ellipse(state: State<AppState>, x: f32, y: f32, width: f32, height: f32) -> Result<SelectionInfo, String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    let selection_manager_arc = engine.selection_manager();
+    let mut selection_manager = selection_manager_arc.write();
+
+    selection_manager.select_ellipse(x, y, width, height);
+
+    Ok(selection_manager.get_info())
+}
+
 /// Create a lasso selection from points
 #[tauri::command]
 fn select_lasso(state: State<AppState>, points: Vec<(f32, f32)>) -> Result<SelectionInfo, String> {
@@ -1200,259 +1174,1062 @@ fn select_lasso(state: State<AppState>, points: Vec<(f32, f32)>) -> Result<Selec
     Ok(selection_manager.get_info())
 }
 
-/// Magic wand selection at a point
+/// Start a magnetic lasso outline snapping to edges in `source`
 #[tauri::command]
-fn select_magic_wand(state: State<AppState>, x: u32, y: u32, tolerance: Option<f32>) -> Result<SelectionInfo, String> {
+fn begin_magnetic_lasso(state: State<AppState>, source: Option<String>, snap_radius: Option<u32>) -> Result<(), String> {
     let engine_lock = state.engine.read();
     let engine = engine_lock.as_ref().ok_or("No canvas open")?;
 
-    // Get the current layer pixels
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
+    engine.begin_magnetic_lasso(parse_sample_source(source)?)
+        .map_err(|e| e.to_string())?;
 
-    let active_layer = layer_manager.active_layer()
-        .ok_or("No active layer")?;
+    if let Some(radius) = snap_radius {
+        if let Some(lasso) = engine.selection_manager().write().magnetic_lasso_mut() {
+            lasso.snap_radius = radius;
+        }
+    }
 
-    let layer = active_layer.read();
-    let width = layer.width();
-    let height = layer.height();
-    let pixels = layer.pixels.clone();
-    drop(layer);
-    drop(layer_manager);
+    Ok(())
+}
+
+/// Place a magnetic lasso anchor, returning the traced path
+#[tauri::command]
+fn add_magnetic_anchor(state: State<AppState>, x: f32, y: f32) -> Result<Vec<(f32, f32)>, String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
 
     let selection_manager_arc = engine.selection_manager();
     let mut selection_manager = selection_manager_arc.write();
+    let lasso = selection_manager.magnetic_lasso_mut().ok_or("No magnetic lasso in progress")?;
 
-    if let Some(tol) = tolerance {
-        selection_manager.set_tolerance(tol);
-    }
+    lasso.add_anchor(x, y);
+    Ok(lasso.path())
+}
 
-    selection_manager.select_magic_wand(x, y, &pixels, width, height)
-        .map_err(|e| e.to_string())?;
+/// Remove the last magnetic lasso anchor, returning the traced path
+#[tauri::command]
+fn remove_magnetic_anchor(state: State<AppState>) -> Result<Vec<(f32, f32)>, String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
 
-    Ok(selection_manager.get_info())
+    let selection_manager_arc = engine.selection_manager();
+    let mut selection_manager = selection_manager_arc.write();
+    let lasso = selection_manager.magnetic_lasso_mut().ok_or("No magnetic lasso in progress")?;
+
+    lasso.remove_last_anchor();
+    Ok(lasso.path())
 }
 
-/// Get current selection info
+/// Preview the magnetic lasso path to the cursor
 #[tauri::command]
-fn get_selection(state: State<AppState>) -> Result<SelectionInfo, String> {
+fn preview_magnetic_lasso(state: State<AppState>, x: f32, y: f32) -> Result<Vec<(f32, f32)>, String> {
     let engine_lock = state.engine.read();
     let engine = engine_lock.as_ref().ok_or("No canvas open")?;
 
     let selection_manager_arc = engine.selection_manager();
     let selection_manager = selection_manager_arc.read();
+    let lasso = selection_manager.magnetic_lasso().ok_or("No magnetic lasso in progress")?;
 
-    Ok(selection_manager.get_info())
+    Ok(lasso.preview(x, y))
 }
 
-/// Clear current selection
+/// Close the magnetic lasso outline into a selection
 #[tauri::command]
-fn clear_selection(state: State<AppState>) -> Result<(), String> {
+fn close_magnetic_lasso(state: State<AppState>) -> Result<SelectionInfo, String> {
     let engine_lock = state.engine.read();
     let engine = engine_lock.as_ref().ok_or("No canvas open")?;
 
     let selection_manager_arc = engine.selection_manager();
     let mut selection_manager = selection_manager_arc.write();
+    selection_manager.finish_magnetic_lasso()
+        .map_err(|e| e.to_string())?;
 
-    selection_manager.clear();
+    Ok(selection_manager.get_info())
+}
+
+/// Abandon the magnetic lasso outline
+#[tauri::command]
+fn cancel_magnetic_lasso(state: State<AppState>) -> Result<(), String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
 
+    engine.selection_manager().write().cancel_magnetic_lasso();
     Ok(())
 }
 
-/// Select all
+/// Parse a sampling source: "active", "merged" or a layer id
+fn parse_sample_source(source: Option<String>) -> Result<SampleSource, String> {
+    match source.as_deref() {
+        None | Some("active") => Ok(SampleSource::ActiveLayer),
+        Some("merged") => Ok(SampleSource::Merged),
+        Some(id) => Ok(SampleSource::Layer(Uuid::parse_str(id).map_err(|e| e.to_string())?)),
+    }
+}
+
+/// Parse a selection mode name, defaulting to replace
+fn parse_selection_mode(mode: &str) -> SelectionMode {
+    match mode.to_lowercase().as_str() {
+        "add" => SelectionMode::Add,
+        "subtract" => SelectionMode::Subtract,
+        "intersect" => SelectionMode::Intersect,
+        _ => SelectionMode::Replace,
+    }
+}
+
+/// Magic wand selection at a point
+///
+/// `source` picks the sampled pixels ("active", "merged" or a reference
+/// layer id); `close_gaps` and `expand` help with line art.
 #[tauri::command]
-fn select_all(state: State<AppState>) -> Result<SelectionInfo, String> {
+fn select_magic_wand(
+    state: State<AppState>,
+    x: u32,
+    y: u32,
+    tolerance: Option<f32>,
+    source: Option<String>,
+    close_gaps: Option<f32>,
+    expand: Option<f32>,
+) -> Result<SelectionInfo, String> {
     let engine_lock = state.engine.read();
     let engine = engine_lock.as_ref().ok_or("No canvas open")?;
 
-    let canvas_arc = engine.canvas();
-    let canvas = canvas_arc.read();
-    let width = canvas.width();
-    let height = canvas.height();
-    drop(canvas);
+    let source = parse_sample_source(source)?;
+    {
+        let selection_manager_arc = engine.selection_manager();
+        let mut selection_manager = selection_manager_arc.write();
+        let current = *selection_manager.wand_options();
+        selection_manager.set_wand_options(WandOptions {
+            tolerance: tolerance.unwrap_or(current.tolerance),
+            close_gaps: close_gaps.unwrap_or(current.close_gaps),
+            expand: expand.unwrap_or(current.expand),
+            ..current
+        });
+    }
 
-    let selection_manager_arc = engine.selection_manager();
-    let mut selection_manager = selection_manager_arc.write();
+    engine.select_magic_wand(x, y, source)
+        .map_err(|e| e.to_string())?;
 
-    selection_manager.select_all(width, height);
+    Ok(engine.selection_manager().read().get_info())
+}
 
-    Ok(selection_manager.get_info())
+/// Select from a layer's transparency
+#[tauri::command]
+fn select_layer_transparency(state: State<AppState>, layer_id: String, mode: String) -> Result<SelectionInfo, String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    let uuid = Uuid::parse_str(&layer_id).map_err(|e| e.to_string())?;
+    engine.select_layer_transparency(uuid, parse_selection_mode(&mode))
+        .map_err(|e| e.to_string())?;
+
+    Ok(engine.selection_manager().read().get_info())
 }
 
-/// Invert selection
+/// Fill the magic wand region at a point on the active layer
+///
+/// Uses the current wand settings; `source` works as in `select_magic_wand`.
 #[tauri::command]
-fn invert_selection(state: State<AppState>) -> Result<SelectionInfo, String> {
+fn fill_wand_region(state: State<AppState>, x: u32, y: u32, hex: String, source: Option<String>) -> Result<(), String> {
     let engine_lock = state.engine.read();
     let engine = engine_lock.as_ref().ok_or("No canvas open")?;
 
-    let canvas_arc = engine.canvas();
-    let canvas = canvas_arc.read();
-    let width = canvas.width();
-    let height = canvas.height();
-    drop(canvas);
+    let fill_color = Color::from_hex(&hex).ok_or("Invalid color hex")?;
+    let source = parse_sample_source(source)?;
+
+    engine.fill_wand_region(x, y, fill_color, source)
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Pixels of the active layer with their dimensions
+fn active_layer_pixels(engine: &DrawEngine) -> Result<(Vec<u8>, u32, u32), String> {
+    let layer_manager_arc = engine.layer_manager();
+    let layer_manager = layer_manager_arc.read();
+
+    let active_layer = layer_manager.active_layer()
+        .ok_or("No active layer")?;
+
+    let layer = active_layer.read();
+    Ok((layer.pixels.clone(), layer.width(), layer.height()))
+}
+
+/// Color Range selection on the active layer
+///
+/// `target` is "sampled", "highlights", "midtones", "shadows" or "skin";
+/// sampled colors are hex strings.
+#[tauri::command]
+fn select_color_range(
+    state: State<AppState>,
+    target: String,
+    colors: Option<Vec<String>>,
+    fuzziness: f32,
+    lab: Option<bool>,
+) -> Result<SelectionInfo, String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    let target = match target.to_lowercase().as_str() {
+        "highlights" => ColorRangeTarget::Highlights,
+        "midtones" => ColorRangeTarget::Midtones,
+        "shadows" => ColorRangeTarget::Shadows,
+        "skin" => ColorRangeTarget::SkinTones,
+        _ => {
+            let samples = colors
+                .unwrap_or_default()
+                .iter()
+                .map(|hex| Color::from_hex(hex).ok_or("Invalid color hex"))
+                .collect::<Result<Vec<_>, _>>()?;
+            ColorRangeTarget::Sampled(samples)
+        }
+    };
+    let space = if lab.unwrap_or(false) { ColorRangeSpace::Lab } else { ColorRangeSpace::Rgb };
+    let range = ColorRange::new(target, fuzziness).with_space(space);
+
+    let (pixels, width, height) = active_layer_pixels(engine)?;
 
     let selection_manager_arc = engine.selection_manager();
     let mut selection_manager = selection_manager_arc.write();
 
-    selection_manager.invert(width, height);
+    selection_manager.select_color_range(&range, &pixels, width, height)
+        .map_err(|e| e.to_string())?;
 
     Ok(selection_manager.get_info())
 }
 
-/// Expand selection by pixels
+/// Add pixels similar in color to the selection anywhere on the active layer
 #[tauri::command]
-fn expand_selection(state: State<AppState>, pixels: f32) -> Result<SelectionInfo, String> {
+fn select_similar(state: State<AppState>) -> Result<SelectionInfo, String> {
     let engine_lock = state.engine.read();
     let engine = engine_lock.as_ref().ok_or("No canvas open")?;
 
+    let (pixels, width, height) = active_layer_pixels(engine)?;
+
     let selection_manager_arc = engine.selection_manager();
     let mut selection_manager = selection_manager_arc.write();
 
-    selection_manager.expand(pixels);
+    selection_manager.select_similar(&pixels, width, height)
+        .map_err(|e| e.to_string())?;
 
     Ok(selection_manager.get_info())
 }
 
-/// Shrink selection by pixels
+/// Add similarly colored pixels adjacent to the selection
 #[tauri::command]
-fn shrink_selection(state: State<AppState>, pixels: f32) -> Result<SelectionInfo, String> {
+fn grow_selection(state: State<AppState>) -> Result<SelectionInfo, String> {
     let engine_lock = state.engine.read();
     let engine = engine_lock.as_ref().ok_or("No canvas open")?;
 
+    let (pixels, width, height) = active_layer_pixels(engine)?;
+
     let selection_manager_arc = engine.selection_manager();
     let mut selection_manager = selection_manager_arc.write();
 
-    selection_manager.contract(pixels);
+    selection_manager.grow(&pixels, width, height)
+        .map_err(|e| e.to_string())?;
 
     Ok(selection_manager.get_info())
 }
 
-/// Set selection mode
+/// Get current selection info
 #[tauri::command]
-fn set_selection_mode(state: State<AppState>, mode: String) -> Result<(), String> {
+fn get_selection(state: State<AppState>) -> Result<SelectionInfo, String> {
     let engine_lock = state.engine.read();
     let engine = engine_lock.as_ref().ok_or("No canvas open")?;
 
     let selection_manager_arc = engine.selection_manager();
-    let mut selection_manager = selection_manager_arc.write();
+    let selection_manager = selection_manager_arc.read();
 
-    let selection_mode = match mode.to_lowercase().as_str() {
-        "add" => SelectionMode::Add,
-        "subtract" => SelectionMode::Subtract,
-        "intersect" => SelectionMode::Intersect,
-        _ => SelectionMode::Replace,
-    };
+    Ok(selection_manager.get_info())
+}
 
-    selection_manager.set_mode(selection_mode);
+/// Trace the selection outline for marching ants at a display zoom
+#[tauri::command]
+fn get_selection_outline(state: State<AppState>, zoom: f32) -> Result<SelectionOutline, String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
 
-    Ok(())
+    Ok(engine.selection_manager().read().outline(zoom))
 }
 
-// ============================================================================
-// Eyedropper and Fill Commands
-// ============================================================================
-
-/// Pick color at position (eyedropper tool)
+/// Move, scale, rotate, skew or flip the selection shape by a matrix [a, b, c, d, tx, ty]
 #[tauri::command]
-fn pick_color(state: State<AppState>, x: u32, y: u32) -> Result<ColorData, String> {
+fn transform_selection(
+    state: State<AppState>,
+    matrix: [f32; 6],
+    interpolation: Option<String>,
+) -> Result<SelectionInfo, String> {
     let engine_lock = state.engine.read();
     let engine = engine_lock.as_ref().ok_or("No canvas open")?;
 
-    let color = engine.pick_color(x, y).map_err(|e| e.to_string())?;
+    let selection_manager_arc = engine.selection_manager();
+    let mut selection_manager = selection_manager_arc.write();
+    selection_manager.transform_selection(&Transform { matrix }, parse_interpolation(interpolation))
+        .map_err(|e| e.to_string())?;
 
-    Ok(ColorData {
-        r: color.r,
-        g: color.g,
-        b: color.b,
-        a: color.a,
-        hex: color.to_hex(),
-    })
+    Ok(selection_manager.get_info())
 }
 
-/// Flood fill at position (paint bucket tool)
+/// Lift the selected pixels into a floating selection, returning its corners
 #[tauri::command]
-fn flood_fill(state: State<AppState>, x: u32, y: u32, hex: String, tolerance: Option<f32>) -> Result<(), String> {
+fn float_selection(state: State<AppState>) -> Result<[(f32, f32); 4], String> {
     let engine_lock = state.engine.read();
     let engine = engine_lock.as_ref().ok_or("No canvas open")?;
 
-    let fill_color = Color::from_hex(&hex).ok_or("Invalid color hex")?;
-    let tolerance = tolerance.unwrap_or(0.1);
-
-    engine.flood_fill(x, y, fill_color, tolerance).map_err(|e| e.to_string())?;
+    engine.float_selection().map_err(|e| e.to_string())?;
 
-    Ok(())
+    let selection_manager = engine.selection_manager();
+    let selection_manager = selection_manager.read();
+    let floating = selection_manager.floating().ok_or("No floating selection")?;
+    Ok(floating.corners())
 }
 
-// ============================================================================
-// Image Adjustments Commands
-// ============================================================================
-
-/// Adjust brightness and contrast
+/// Apply a further matrix [a, b, c, d, tx, ty] to the floating selection,
+/// returning its corners
 #[tauri::command]
-fn adjust_brightness_contrast(state: State<AppState>, brightness: f32, contrast: f32) -> Result<(), String> {
-    use drawconnect_core::adjustments::{BrightnessContrast, Adjustment};
-    use drawconnect_core::{LayerSnapshot, HistoryState};
-
+fn transform_floating_selection(state: State<AppState>, matrix: [f32; 6]) -> Result<[(f32, f32); 4], String> {
     let engine_lock = state.engine.read();
     let engine = engine_lock.as_ref().ok_or("No canvas open")?;
 
-    // Convert from -100..100 range to -1.0..1.0 range
-    let adjustment = BrightnessContrast::new(brightness / 100.0, contrast / 100.0);
+    let selection_manager_arc = engine.selection_manager();
+    let mut selection_manager = selection_manager_arc.write();
+    let floating = selection_manager.floating_mut().ok_or("No floating selection")?;
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
+    floating.apply(&Transform { matrix });
+    Ok(floating.corners())
+}
 
-    if let Some(active_layer) = layer_manager.active_layer() {
-        let mut layer = active_layer.write();
+/// Flip the floating selection around its center, returning its corners
+#[tauri::command]
+fn flip_floating_selection(state: State<AppState>, horizontal: bool) -> Result<[(f32, f32); 4], String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
 
-        // Save current state for undo before modifying
-        let snapshot = LayerSnapshot::new(
-            layer.id,
-            layer.pixels.clone(),
-            layer.width(),
-            layer.height(),
-        );
-        let mut history_state = HistoryState::new("Brightness/Contrast");
-        history_state.add_snapshot(snapshot);
-        engine.history_manager().write().push_state(history_state);
+    let selection_manager_arc = engine.selection_manager();
+    let mut selection_manager = selection_manager_arc.write();
+    let floating = selection_manager.floating_mut().ok_or("No floating selection")?;
 
-        // Apply the adjustment
-        adjustment.apply_to_layer(&mut layer);
+    if horizontal {
+        floating.flip_horizontal();
+    } else {
+        floating.flip_vertical();
     }
-
-    Ok(())
+    Ok(floating.corners())
 }
 
-/// Adjust levels
+/// Pin the floating selection's corners (top-left, top-right, bottom-right,
+/// bottom-left) to free positions, returning its corners
 #[tauri::command]
-fn adjust_levels(
-    state: State<AppState>,
-    input_black: f32,
-    input_white: f32,
-    gamma: f32,
-    output_black: f32,
-    output_white: f32,
-    channel: Option<String>,
-) -> Result<(), String> {
-    use drawconnect_core::adjustments::{Levels, CurveChannel, Adjustment};
-    use drawconnect_core::{LayerSnapshot, HistoryState};
-
+fn distort_floating_selection(state: State<AppState>, corners: [(f32, f32); 4]) -> Result<[(f32, f32); 4], String> {
     let engine_lock = state.engine.read();
     let engine = engine_lock.as_ref().ok_or("No canvas open")?;
 
-    let curve_channel = match channel.as_deref() {
-        Some("red") | Some("r") => CurveChannel::Red,
-        Some("green") | Some("g") => CurveChannel::Green,
-        Some("blue") | Some("b") => CurveChannel::Blue,
+    let selection_manager_arc = engine.selection_manager();
+    let mut selection_manager = selection_manager_arc.write();
+    let floating = selection_manager.floating_mut().ok_or("No floating selection")?;
+
+    floating.distort(corners).map_err(|e| e.to_string())?;
+    Ok(floating.corners())
+}
+
+/// Drag one corner of the floating selection in perspective, mirroring the
+/// move onto the neighboring corner, returning its corners
+#[tauri::command]
+fn perspective_floating_selection(
+    state: State<AppState>,
+    corner: usize,
+    x: f32,
+    y: f32,
+) -> Result<[(f32, f32); 4], String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    let selection_manager_arc = engine.selection_manager();
+    let mut selection_manager = selection_manager_arc.write();
+    let floating = selection_manager.floating_mut().ok_or("No floating selection")?;
+
+    floating.perspective(corner, (x, y)).map_err(|e| e.to_string())?;
+    Ok(floating.corners())
+}
+
+/// Put a warp mesh of `columns` x `rows` cells over the floating selection,
+/// returning its control points row by row
+#[tauri::command]
+fn warp_floating_selection(state: State<AppState>, columns: u32, rows: u32) -> Result<Vec<(f32, f32)>, String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    let selection_manager_arc = engine.selection_manager();
+    let mut selection_manager = selection_manager_arc.write();
+    let floating = selection_manager.floating_mut().ok_or("No floating selection")?;
+
+    floating.begin_mesh(columns, rows);
+    floating.mesh_points().ok_or_else(|| "No warp mesh".to_string())
+}
+
+/// Move one control point of the floating selection's warp mesh, returning
+/// the control points row by row
+#[tauri::command]
+fn move_floating_mesh_point(
+    state: State<AppState>,
+    column: u32,
+    row: u32,
+    x: f32,
+    y: f32,
+) -> Result<Vec<(f32, f32)>, String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    let selection_manager_arc = engine.selection_manager();
+    let mut selection_manager = selection_manager_arc.write();
+    let floating = selection_manager.floating_mut().ok_or("No floating selection")?;
+
+    floating.move_mesh_point(column, row, (x, y)).map_err(|e| e.to_string())?;
+    floating.mesh_points().ok_or_else(|| "No warp mesh".to_string())
+}
+
+/// Drop the floating selection into its layer
+#[tauri::command]
+fn commit_floating_selection(state: State<AppState>, interpolation: Option<String>) -> Result<SelectionInfo, String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    engine.commit_floating_selection(parse_interpolation(interpolation))
+        .map_err(|e| e.to_string())?;
+
+    Ok(engine.selection_manager().read().get_info())
+}
+
+/// Put the floating selection back where it was lifted from
+#[tauri::command]
+fn cancel_floating_selection(state: State<AppState>) -> Result<SelectionInfo, String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    engine.cancel_floating_selection().map_err(|e| e.to_string())?;
+
+    Ok(engine.selection_manager().read().get_info())
+}
+
+/// Switch the active tool, committing any pending work of the previous one
+#[tauri::command]
+fn set_tool(state: State<AppState>, tool: ToolType) -> Result<(), String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    engine.set_tool(tool).map_err(|e| e.to_string())
+}
+
+/// Forward a pointer press in canvas coordinates to the active tool
+#[tauri::command]
+fn tool_pointer_down(state: State<AppState>, event: ToolEvent) -> Result<ToolPreview, String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    engine.pointer_down(event).map_err(|e| e.to_string())?;
+    Ok(engine.tool_preview())
+}
+
+/// Forward a pointer move in canvas coordinates to the active tool
+#[tauri::command]
+fn tool_pointer_move(state: State<AppState>, event: ToolEvent) -> Result<ToolPreview, String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    engine.pointer_move(event).map_err(|e| e.to_string())?;
+    Ok(engine.tool_preview())
+}
+
+/// Forward a pointer release in canvas coordinates to the active tool
+#[tauri::command]
+fn tool_pointer_up(state: State<AppState>, event: ToolEvent) -> Result<ToolPreview, String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    engine.pointer_up(event).map_err(|e| e.to_string())?;
+    Ok(engine.tool_preview())
+}
+
+/// Finish the active tool's pending work
+#[tauri::command]
+fn commit_tool(state: State<AppState>) -> Result<(), String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    engine.commit_tool().map_err(|e| e.to_string())
+}
+
+/// Abandon the active tool's pending work
+#[tauri::command]
+fn cancel_tool(state: State<AppState>) -> Result<(), String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    engine.cancel_tool().map_err(|e| e.to_string())
+}
+
+/// Relax the pending liquify mesh `amount` (0-1) of the way back to the
+/// original layer
+#[tauri::command]
+fn reconstruct_liquify(state: State<AppState>, amount: f32) -> Result<(), String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    engine.reconstruct_liquify(amount).map_err(|e| e.to_string())
+}
+
+/// Ruler lines kept by the measure tool, read out in `unit` (pixels by default)
+#[tauri::command]
+fn get_measurements(state: State<AppState>, unit: Option<MeasureUnit>) -> Result<Vec<MeasureReadout>, String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    Ok(engine.measurement_readouts(unit.unwrap_or_default()))
+}
+
+/// Keep a ruler line between two canvas points, returning its id
+#[tauri::command]
+fn add_measurement(state: State<AppState>, x0: f32, y0: f32, x1: f32, y1: f32) -> Result<String, String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    Ok(engine.add_measurement((x0, y0), (x1, y1)).to_string())
+}
+
+/// Remove one ruler line
+#[tauri::command]
+fn remove_measurement(state: State<AppState>, id: String) -> Result<bool, String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    let uuid = Uuid::parse_str(&id).map_err(|e| e.to_string())?;
+    Ok(engine.remove_measurement(uuid))
+}
+
+/// Remove every ruler line
+#[tauri::command]
+fn clear_measurements(state: State<AppState>) -> Result<(), String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    engine.clear_measurements();
+    Ok(())
+}
+
+/// Rotate the active layer to level a ruler line, returning the clockwise
+/// rotation in degrees
+#[tauri::command]
+fn straighten_layer(state: State<AppState>, id: String) -> Result<f32, String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    let uuid = Uuid::parse_str(&id).map_err(|e| e.to_string())?;
+    engine.straighten_layer(uuid).map_err(|e| e.to_string())
+}
+
+/// Overlay the frontend should draw for the active tool
+#[tauri::command]
+fn get_tool_preview(state: State<AppState>) -> Result<ToolPreview, String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    Ok(engine.tool_preview())
+}
+
+/// Get the shared tool options
+#[tauri::command]
+fn get_tool_options(state: State<AppState>) -> Result<ToolOptions, String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    let options = engine.tool_manager().read().options().clone();
+    Ok(options)
+}
+
+/// Replace the shared tool options
+#[tauri::command]
+fn set_tool_options(state: State<AppState>, options: ToolOptions) -> Result<(), String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    *engine.tool_manager().write().options_mut() = options;
+    Ok(())
+}
+
+/// Set the primary and secondary colors used by the tools
+#[tauri::command]
+fn set_tool_colors(state: State<AppState>, primary: String, secondary: String) -> Result<(), String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    let primary = Color::from_hex(&primary).ok_or_else(|| format!("Invalid color: {}", primary))?;
+    let secondary = Color::from_hex(&secondary).ok_or_else(|| format!("Invalid color: {}", secondary))?;
+
+    let tool_manager = engine.tool_manager();
+    let mut tool_manager = tool_manager.write();
+    tool_manager.set_primary_color(primary);
+    tool_manager.set_secondary_color(secondary);
+    Ok(())
+}
+
+/// Parse an interpolation name, defaulting to bilinear
+fn parse_interpolation(interpolation: Option<String>) -> Interpolation {
+    interpolation
+        .and_then(|s| s.parse::<Interpolation>().ok())
+        .unwrap_or(Interpolation::Bilinear)
+}
+
+/// Clear current selection
+#[tauri::command]
+fn clear_selection(state: State<AppState>) -> Result<(), String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    let selection_manager_arc = engine.selection_manager();
+    let mut selection_manager = selection_manager_arc.write();
+
+    selection_manager.clear();
+
+    Ok(())
+}
+
+/// Select all
+#[tauri::command]
+fn select_all(state: State<AppState>) -> Result<SelectionInfo, String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    let canvas_arc = engine.canvas();
+    let canvas = canvas_arc.read();
+    let width = canvas.width();
+    let height = canvas.height();
+    drop(canvas);
+
+    let selection_manager_arc = engine.selection_manager();
+    let mut selection_manager = selection_manager_arc.write();
+
+    selection_manager.select_all(width, height);
+
+    Ok(selection_manager.get_info())
+}
+
+/// Invert selection
+#[tauri::command]
+fn invert_selection(state: State<AppState>) -> Result<SelectionInfo, String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    let canvas_arc = engine.canvas();
+    let canvas = canvas_arc.read();
+    let width = canvas.width();
+    let height = canvas.height();
+    drop(canvas);
+
+    let selection_manager_arc = engine.selection_manager();
+    let mut selection_manager = selection_manager_arc.write();
+
+    selection_manager.invert(width, height);
+
+    Ok(selection_manager.get_info())
+}
+
+/// Expand selection by pixels
+#[tauri::command]
+fn expand_selection(state: State<AppState>, pixels: f32) -> Result<SelectionInfo, String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    let selection_manager_arc = engine.selection_manager();
+    let mut selection_manager = selection_manager_arc.write();
+
+    selection_manager.expand(pixels);
+
+    Ok(selection_manager.get_info())
+}
+
+/// Shrink selection by pixels
+#[tauri::command]
+fn shrink_selection(state: State<AppState>, pixels: f32) -> Result<SelectionInfo, String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    let selection_manager_arc = engine.selection_manager();
+    let mut selection_manager = selection_manager_arc.write();
+
+    selection_manager.contract(pixels);
+
+    Ok(selection_manager.get_info())
+}
+
+/// Replace selection with a border of given width
+#[tauri::command]
+fn border_selection(state: State<AppState>, width: f32) -> Result<SelectionInfo, String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    let selection_manager_arc = engine.selection_manager();
+    let mut selection_manager = selection_manager_arc.write();
+
+    selection_manager.border(width);
+
+    Ok(selection_manager.get_info())
+}
+
+/// Smooth selection edges
+#[tauri::command]
+fn smooth_selection(state: State<AppState>, radius: f32) -> Result<SelectionInfo, String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    let selection_manager_arc = engine.selection_manager();
+    let mut selection_manager = selection_manager_arc.write();
+
+    selection_manager.smooth(radius);
+
+    Ok(selection_manager.get_info())
+}
+
+/// Feather selection edges
+#[tauri::command]
+fn feather_selection(state: State<AppState>, radius: f32) -> Result<SelectionInfo, String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    let selection_manager_arc = engine.selection_manager();
+    let mut selection_manager = selection_manager_arc.write();
+
+    selection_manager.feather(radius);
+
+    Ok(selection_manager.get_info())
+}
+
+/// Turn quick mask mode on or off
+///
+/// While on, painting, fills, filters and adjustments edit the selection.
+#[tauri::command]
+fn set_quick_mask(state: State<AppState>, enabled: bool) -> Result<SelectionInfo, String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    let selection_manager_arc = engine.selection_manager();
+    let mut selection_manager = selection_manager_arc.write();
+
+    if enabled {
+        selection_manager.enter_quick_mask();
+    } else {
+        selection_manager.exit_quick_mask();
+    }
+
+    Ok(selection_manager.get_info())
+}
+
+/// List saved selection channels
+#[tauri::command]
+fn list_selection_channels(state: State<AppState>) -> Result<Vec<ChannelInfo>, String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    let channels = engine.selection_manager().read().channels()
+        .iter()
+        .map(|channel| ChannelInfo {
+            id: channel.id.to_string(),
+            name: channel.name.clone(),
+        })
+        .collect();
+
+    Ok(channels)
+}
+
+/// Save the current selection as a named channel
+#[tauri::command]
+fn save_selection_channel(state: State<AppState>, name: String) -> Result<String, String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    let id = engine.selection_manager().write().save_channel(name)
+        .map_err(|e| e.to_string())?;
+
+    Ok(id.to_string())
+}
+
+/// Combine a saved channel into the selection
+#[tauri::command]
+fn load_selection_channel(state: State<AppState>, channel_id: String, mode: String) -> Result<SelectionInfo, String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    let uuid = Uuid::parse_str(&channel_id).map_err(|e| e.to_string())?;
+    let selection_manager_arc = engine.selection_manager();
+    let mut selection_manager = selection_manager_arc.write();
+    selection_manager.load_channel(uuid, parse_selection_mode(&mode))
+        .map_err(|e| e.to_string())?;
+
+    Ok(selection_manager.get_info())
+}
+
+/// Rename a saved selection channel
+#[tauri::command]
+fn rename_selection_channel(state: State<AppState>, channel_id: String, name: String) -> Result<(), String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    let uuid = Uuid::parse_str(&channel_id).map_err(|e| e.to_string())?;
+    engine.selection_manager().write().rename_channel(uuid, name)
+        .map_err(|e| e.to_string())
+}
+
+/// Delete a saved selection channel
+#[tauri::command]
+fn delete_selection_channel(state: State<AppState>, channel_id: String) -> Result<(), String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    let uuid = Uuid::parse_str(&channel_id).map_err(|e| e.to_string())?;
+    engine.selection_manager().write().remove_channel(uuid)
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Apply a saved selection channel as a layer mask
+#[tauri::command]
+fn channel_to_layer_mask(state: State<AppState>, channel_id: String, layer_id: String) -> Result<(), String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    let channel_uuid = Uuid::parse_str(&channel_id).map_err(|e| e.to_string())?;
+    let layer_uuid = Uuid::parse_str(&layer_id).map_err(|e| e.to_string())?;
+    engine.channel_to_layer_mask(channel_uuid, layer_uuid)
+        .map_err(|e| e.to_string())
+}
+
+/// Save a layer's mask as a new selection channel
+#[tauri::command]
+fn layer_mask_to_channel(state: State<AppState>, layer_id: String, name: String) -> Result<String, String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    let uuid = Uuid::parse_str(&layer_id).map_err(|e| e.to_string())?;
+    let id = engine.layer_mask_to_channel(uuid, name)
+        .map_err(|e| e.to_string())?;
+
+    Ok(id.to_string())
+}
+
+/// Get the shapes of a vector layer
+#[tauri::command]
+fn get_layer_shapes(state: State<AppState>, layer_id: String) -> Result<Vec<Shape>, String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    let uuid = Uuid::parse_str(&layer_id).map_err(|e| e.to_string())?;
+    let layer = engine.layer_manager().read().get_layer(uuid).ok_or("Layer not found")?;
+    let shapes = layer.read().shapes.clone();
+    Ok(shapes)
+}
+
+/// Replace the shapes of a vector layer and redraw it
+#[tauri::command]
+fn set_layer_shapes(state: State<AppState>, layer_id: String, shapes: Vec<Shape>) -> Result<(), String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    let uuid = Uuid::parse_str(&layer_id).map_err(|e| e.to_string())?;
+    engine.set_layer_shapes(uuid, shapes)
+        .map_err(|e| e.to_string())
+}
+
+/// Set selection mode
+#[tauri::command]
+fn set_selection_mode(state: State<AppState>, mode: String) -> Result<(), String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    let selection_manager_arc = engine.selection_manager();
+    let mut selection_manager = selection_manager_arc.write();
+
+    selection_manager.set_mode(parse_selection_mode(&mode));
+
+    Ok(())
+}
+
+// ============================================================================
+// Eyedropper and Fill Commands
+// ============================================================================
+
+/// Pick color at position (eyedropper tool) and add it to the color history
+#[tauri::command]
+fn pick_color(
+    state: State<AppState>,
+    x: u32,
+    y: u32,
+    options: Option<PickerOptions>,
+) -> Result<ColorData, String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    let color = engine
+        .pick_color(x, y, &options.unwrap_or_default())
+        .map_err(|e| e.to_string())?;
+
+    Ok(ColorData {
+        r: color.r,
+        g: color.g,
+        b: color.b,
+        a: color.a,
+        hex: color.to_hex(),
+    })
+}
+
+/// Get recently picked colors, newest first
+#[tauri::command]
+fn get_color_history(state: State<AppState>) -> Result<Vec<ColorData>, String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    let color_manager_arc = engine.color_manager();
+    let color_manager = color_manager_arc.read();
+    Ok(color_manager
+        .history()
+        .iter()
+        .map(|color| ColorData {
+            r: color.r,
+            g: color.g,
+            b: color.b,
+            a: color.a,
+            hex: color.to_hex(),
+        })
+        .collect())
+}
+
+/// Forget recently picked colors
+#[tauri::command]
+fn clear_color_history(state: State<AppState>) -> Result<(), String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    engine.color_manager().write().clear_history();
+    Ok(())
+}
+
+/// Flood fill at position (paint bucket tool)
+#[tauri::command]
+fn flood_fill(state: State<AppState>, x: u32, y: u32, hex: String, tolerance: Option<f32>) -> Result<(), String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    let fill_color = Color::from_hex(&hex).ok_or("Invalid color hex")?;
+    let tolerance = tolerance.unwrap_or(0.1);
+
+    engine.flood_fill(x, y, fill_color, tolerance).map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Fill the region around a point with a color, pattern or gradient
+///
+/// `options` chooses where the region is found, gap closing, expansion
+/// and anti-aliasing.
+#[tauri::command]
+fn bucket_fill(
+    state: State<AppState>,
+    x: u32,
+    y: u32,
+    content: FillContent,
+    options: Option<FillOptions>,
+) -> Result<(), String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    engine.bucket_fill(x, y, &content, &options.unwrap_or_default())
+        .map_err(|e| e.to_string())
+}
+
+/// Fill the selection with content synthesized from the rest of the image
+///
+/// Emits `content-aware-progress` events with the finished fraction while it
+/// runs; returns the id of the new layer when the output is a new layer.
+#[tauri::command]
+fn content_aware_fill(
+    window: tauri::Window,
+    state: State<AppState>,
+    options: Option<ContentAwareOptions>,
+) -> Result<Option<String>, String> {
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    let id = engine
+        .content_aware_fill(&options.unwrap_or_default(), |progress| {
+            let _ = window.emit("content-aware-progress", progress);
+        })
+        .map_err(|e| e.to_string())?;
+    Ok(id.map(|id| id.to_string()))
+}
+
+// ============================================================================
+// Image Adjustments Commands
+// ============================================================================
+
+/// Adjust brightness and contrast
+#[tauri::command]
+fn adjust_brightness_contrast(state: State<AppState>, brightness: f32, contrast: f32) -> Result<(), String> {
+    use drawconnect_core::adjustments::{BrightnessContrast, Adjustment};
+    use drawconnect_core::{LayerSnapshot, HistoryState};
+
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    // Convert from -100..100 range to -1.0..1.0 range
+    let adjustment = BrightnessContrast::new(brightness / 100.0, contrast / 100.0);
+
+    if let Some(active_layer) = engine.edit_target() {
+        let mut layer = active_layer.write();
+
+        // Save current state for undo before modifying
+        let snapshot = LayerSnapshot::new(
+            layer.id,
+            layer.pixels.clone(),
+            layer.width(),
+            layer.height(),
+        );
+        let mut history_state = HistoryState::new("Brightness/Contrast");
+        history_state.add_snapshot(snapshot);
+        engine.history_manager().write().push_state(history_state);
+
+        // Apply the adjustment
+        adjustment.apply_with_selection(&mut layer, engine.selection_manager().read().selection());
+    }
+
+    Ok(())
+}
+
+/// Adjust levels
+#[tauri::command]
+fn adjust_levels(
+    state: State<AppState>,
+    input_black: f32,
+    input_white: f32,
+    gamma: f32,
+    output_black: f32,
+    output_white: f32,
+    channel: Option<String>,
+) -> Result<(), String> {
+    use drawconnect_core::adjustments::{Levels, CurveChannel, Adjustment};
+    use drawconnect_core::{LayerSnapshot, HistoryState};
+
+    let engine_lock = state.engine.read();
+    let engine = engine_lock.as_ref().ok_or("No canvas open")?;
+
+    let curve_channel = match channel.as_deref() {
+        Some("red") | Some("r") => CurveChannel::Red,
+        Some("green") | Some("g") => CurveChannel::Green,
+        Some("blue") | Some("b") => CurveChannel::Blue,
         _ => CurveChannel::RGB,
     };
 
     let adjustment = Levels::new(input_black, input_white, gamma, output_black, output_white, curve_channel);
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    if let Some(active_layer) = layer_manager.active_layer() {
+    if let Some(active_layer) = engine.edit_target() {
         let mut layer = active_layer.write();
 
         // Save current state for undo before modifying
@@ -1466,7 +2243,7 @@ fn adjust_levels(
         history_state.add_snapshot(snapshot);
         engine.history_manager().write().push_state(history_state);
 
-        adjustment.apply_to_layer(&mut layer);
+        adjustment.apply_with_selection(&mut layer, engine.selection_manager().read().selection());
     }
 
     Ok(())
@@ -1498,12 +2275,9 @@ fn adjust_curves(
 
     let adjustment = Curves::new(curve_points, curve_channel);
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    if let Some(active_layer) = layer_manager.active_layer() {
+    if let Some(active_layer) = engine.edit_target() {
         let mut layer = active_layer.write();
-        adjustment.apply_to_layer(&mut layer);
+        adjustment.apply_with_selection(&mut layer, engine.selection_manager().read().selection());
     }
 
     Ok(())
@@ -1527,10 +2301,7 @@ fn adjust_hue_saturation(
     // saturation and lightness need conversion from -100..100 to -1.0..1.0
     let adjustment = HueSaturation::new(hue, saturation / 100.0, lightness / 100.0);
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    if let Some(active_layer) = layer_manager.active_layer() {
+    if let Some(active_layer) = engine.edit_target() {
         let mut layer = active_layer.write();
 
         // Save current state for undo before modifying
@@ -1544,7 +2315,7 @@ fn adjust_hue_saturation(
         history_state.add_snapshot(snapshot);
         engine.history_manager().write().push_state(history_state);
 
-        adjustment.apply_to_layer(&mut layer);
+        adjustment.apply_with_selection(&mut layer, engine.selection_manager().read().selection());
     }
 
     Ok(())
@@ -1569,12 +2340,9 @@ fn adjust_color_balance(
         [highlights.0, highlights.1, highlights.2],
     );
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    if let Some(active_layer) = layer_manager.active_layer() {
+    if let Some(active_layer) = engine.edit_target() {
         let mut layer = active_layer.write();
-        adjustment.apply_to_layer(&mut layer);
+        adjustment.apply_with_selection(&mut layer, engine.selection_manager().read().selection());
     }
 
     Ok(())
@@ -1594,12 +2362,9 @@ fn adjust_vibrance(
 
     let adjustment = Vibrance::new(vibrance, saturation);
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    if let Some(active_layer) = layer_manager.active_layer() {
+    if let Some(active_layer) = engine.edit_target() {
         let mut layer = active_layer.write();
-        adjustment.apply_to_layer(&mut layer);
+        adjustment.apply_with_selection(&mut layer, engine.selection_manager().read().selection());
     }
 
     Ok(())
@@ -1620,12 +2385,9 @@ fn adjust_exposure(
 
     let adjustment = Exposure::new(exposure, offset, gamma);
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    if let Some(active_layer) = layer_manager.active_layer() {
+    if let Some(active_layer) = engine.edit_target() {
         let mut layer = active_layer.write();
-        adjustment.apply_to_layer(&mut layer);
+        adjustment.apply_with_selection(&mut layer, engine.selection_manager().read().selection());
     }
 
     Ok(())
@@ -1649,12 +2411,9 @@ fn adjust_black_white(
 
     let adjustment = BlackWhite::new(red, yellow, green, cyan, blue, magenta);
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    if let Some(active_layer) = layer_manager.active_layer() {
+    if let Some(active_layer) = engine.edit_target() {
         let mut layer = active_layer.write();
-        adjustment.apply_to_layer(&mut layer);
+        adjustment.apply_with_selection(&mut layer, engine.selection_manager().read().selection());
     }
 
     Ok(())
@@ -1676,12 +2435,9 @@ fn adjust_photo_filter(
     let filter_color = Color::from_hex(&color).ok_or("Invalid color")?;
     let adjustment = PhotoFilter::new(filter_color, density, preserve_luminosity);
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    if let Some(active_layer) = layer_manager.active_layer() {
+    if let Some(active_layer) = engine.edit_target() {
         let mut layer = active_layer.write();
-        adjustment.apply_to_layer(&mut layer);
+        adjustment.apply_with_selection(&mut layer, engine.selection_manager().read().selection());
     }
 
     Ok(())
@@ -1697,12 +2453,9 @@ fn adjust_invert(state: State<AppState>) -> Result<(), String> {
 
     let adjustment = Invert::new();
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    if let Some(active_layer) = layer_manager.active_layer() {
+    if let Some(active_layer) = engine.edit_target() {
         let mut layer = active_layer.write();
-        adjustment.apply_to_layer(&mut layer);
+        adjustment.apply_with_selection(&mut layer, engine.selection_manager().read().selection());
     }
 
     Ok(())
@@ -1719,10 +2472,7 @@ fn adjust_posterize(state: State<AppState>, levels: u8) -> Result<(), String> {
 
     let adjustment = Posterize::new(levels);
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    if let Some(active_layer) = layer_manager.active_layer() {
+    if let Some(active_layer) = engine.edit_target() {
         let mut layer = active_layer.write();
 
         // Save current state for undo before modifying
@@ -1736,7 +2486,7 @@ fn adjust_posterize(state: State<AppState>, levels: u8) -> Result<(), String> {
         history_state.add_snapshot(snapshot);
         engine.history_manager().write().push_state(history_state);
 
-        adjustment.apply_to_layer(&mut layer);
+        adjustment.apply_with_selection(&mut layer, engine.selection_manager().read().selection());
     }
 
     Ok(())
@@ -1753,10 +2503,7 @@ fn adjust_threshold(state: State<AppState>, level: u8) -> Result<(), String> {
 
     let adjustment = Threshold::new(level);
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    if let Some(active_layer) = layer_manager.active_layer() {
+    if let Some(active_layer) = engine.edit_target() {
         let mut layer = active_layer.write();
 
         // Save current state for undo before modifying
@@ -1770,7 +2517,7 @@ fn adjust_threshold(state: State<AppState>, level: u8) -> Result<(), String> {
         history_state.add_snapshot(snapshot);
         engine.history_manager().write().push_state(history_state);
 
-        adjustment.apply_to_layer(&mut layer);
+        adjustment.apply_with_selection(&mut layer, engine.selection_manager().read().selection());
     }
 
     Ok(())
@@ -1791,10 +2538,7 @@ fn filter_gaussian_blur(state: State<AppState>, radius: f32) -> Result<(), Strin
 
     let filter = GaussianBlur::new(radius);
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    if let Some(active_layer) = layer_manager.active_layer() {
+    if let Some(active_layer) = engine.edit_target() {
         let mut layer = active_layer.write();
 
         // Save current state for undo before modifying
@@ -1808,7 +2552,7 @@ fn filter_gaussian_blur(state: State<AppState>, radius: f32) -> Result<(), Strin
         history_state.add_snapshot(snapshot);
         engine.history_manager().write().push_state(history_state);
 
-        filter.apply_to_layer(&mut layer);
+        filter.apply_with_selection(&mut layer, engine.selection_manager().read().selection());
     }
 
     Ok(())
@@ -1824,12 +2568,9 @@ fn filter_box_blur(state: State<AppState>, radius: u32) -> Result<(), String> {
 
     let filter = BoxBlur::new(radius);
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    if let Some(active_layer) = layer_manager.active_layer() {
+    if let Some(active_layer) = engine.edit_target() {
         let mut layer = active_layer.write();
-        filter.apply_to_layer(&mut layer);
+        filter.apply_with_selection(&mut layer, engine.selection_manager().read().selection());
     }
 
     Ok(())
@@ -1845,12 +2586,9 @@ fn filter_motion_blur(state: State<AppState>, angle: f32, distance: u32) -> Resu
 
     let filter = MotionBlur::new(angle, distance);
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    if let Some(active_layer) = layer_manager.active_layer() {
+    if let Some(active_layer) = engine.edit_target() {
         let mut layer = active_layer.write();
-        filter.apply_to_layer(&mut layer);
+        filter.apply_with_selection(&mut layer, engine.selection_manager().read().selection());
     }
 
     Ok(())
@@ -1877,12 +2615,9 @@ fn filter_radial_blur(
 
     let filter = RadialBlur::new(amount, center_x, center_y, blur_type);
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    if let Some(active_layer) = layer_manager.active_layer() {
+    if let Some(active_layer) = engine.edit_target() {
         let mut layer = active_layer.write();
-        filter.apply_to_layer(&mut layer);
+        filter.apply_with_selection(&mut layer, engine.selection_manager().read().selection());
     }
 
     Ok(())
@@ -1903,12 +2638,9 @@ fn filter_unsharp_mask(
 
     let filter = UnsharpMask::new(amount, radius, threshold);
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    if let Some(active_layer) = layer_manager.active_layer() {
+    if let Some(active_layer) = engine.edit_target() {
         let mut layer = active_layer.write();
-        filter.apply_to_layer(&mut layer);
+        filter.apply_with_selection(&mut layer, engine.selection_manager().read().selection());
     }
 
     Ok(())
@@ -1924,12 +2656,9 @@ fn filter_high_pass(state: State<AppState>, radius: f32) -> Result<(), String> {
 
     let filter = HighPass::new(radius);
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    if let Some(active_layer) = layer_manager.active_layer() {
+    if let Some(active_layer) = engine.edit_target() {
         let mut layer = active_layer.write();
-        filter.apply_to_layer(&mut layer);
+        filter.apply_with_selection(&mut layer, engine.selection_manager().read().selection());
     }
 
     Ok(())
@@ -1955,12 +2684,9 @@ fn filter_add_noise(
 
     let filter = AddNoise::new(amount, noise_type, monochrome);
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    if let Some(active_layer) = layer_manager.active_layer() {
+    if let Some(active_layer) = engine.edit_target() {
         let mut layer = active_layer.write();
-        filter.apply_to_layer(&mut layer);
+        filter.apply_with_selection(&mut layer, engine.selection_manager().read().selection());
     }
 
     Ok(())
@@ -1980,12 +2706,9 @@ fn filter_reduce_noise(
 
     let filter = ReduceNoise::new(strength, preserve_details);
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    if let Some(active_layer) = layer_manager.active_layer() {
+    if let Some(active_layer) = engine.edit_target() {
         let mut layer = active_layer.write();
-        filter.apply_to_layer(&mut layer);
+        filter.apply_with_selection(&mut layer, engine.selection_manager().read().selection());
     }
 
     Ok(())
@@ -2001,12 +2724,9 @@ fn filter_find_edges(state: State<AppState>) -> Result<(), String> {
 
     let filter = FindEdges::new();
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    if let Some(active_layer) = layer_manager.active_layer() {
+    if let Some(active_layer) = engine.edit_target() {
         let mut layer = active_layer.write();
-        filter.apply_to_layer(&mut layer);
+        filter.apply_with_selection(&mut layer, engine.selection_manager().read().selection());
     }
 
     Ok(())
@@ -2028,10 +2748,7 @@ fn filter_emboss(
 
     let filter = Emboss::new(angle, height, amount);
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    if let Some(active_layer) = layer_manager.active_layer() {
+    if let Some(active_layer) = engine.edit_target() {
         let mut layer = active_layer.write();
 
         // Save current state for undo before modifying
@@ -2045,7 +2762,7 @@ fn filter_emboss(
         history_state.add_snapshot(snapshot);
         engine.history_manager().write().push_state(history_state);
 
-        filter.apply_to_layer(&mut layer);
+        filter.apply_with_selection(&mut layer, engine.selection_manager().read().selection());
     }
 
     Ok(())
@@ -2062,10 +2779,7 @@ fn filter_pixelate(state: State<AppState>, cell_size: u32) -> Result<(), String>
 
     let filter = Pixelate::new(cell_size);
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    if let Some(active_layer) = layer_manager.active_layer() {
+    if let Some(active_layer) = engine.edit_target() {
         let mut layer = active_layer.write();
 
         // Save current state for undo before modifying
@@ -2079,7 +2793,7 @@ fn filter_pixelate(state: State<AppState>, cell_size: u32) -> Result<(), String>
         history_state.add_snapshot(snapshot);
         engine.history_manager().write().push_state(history_state);
 
-        filter.apply_to_layer(&mut layer);
+        filter.apply_with_selection(&mut layer, engine.selection_manager().read().selection());
     }
 
     Ok(())
@@ -2096,10 +2810,7 @@ fn filter_oil_paint(state: State<AppState>, radius: u32, levels: u32) -> Result<
 
     let filter = OilPaint::new(radius, levels);
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    if let Some(active_layer) = layer_manager.active_layer() {
+    if let Some(active_layer) = engine.edit_target() {
         let mut layer = active_layer.write();
 
         // Save current state for undo before modifying
@@ -2113,7 +2824,7 @@ fn filter_oil_paint(state: State<AppState>, radius: u32, levels: u32) -> Result<
         history_state.add_snapshot(snapshot);
         engine.history_manager().write().push_state(history_state);
 
-        filter.apply_to_layer(&mut layer);
+        filter.apply_with_selection(&mut layer, engine.selection_manager().read().selection());
     }
 
     Ok(())
@@ -2141,10 +2852,7 @@ fn filter_spherize(state: State<AppState>, amount: i32, mode: Option<String>) ->
 
     let filter = Spherize::new(amount, spherize_mode);
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    if let Some(active_layer) = layer_manager.active_layer() {
+    if let Some(active_layer) = engine.edit_target() {
         let mut layer = active_layer.write();
 
         let snapshot = LayerSnapshot::new(
@@ -2157,7 +2865,7 @@ fn filter_spherize(state: State<AppState>, amount: i32, mode: Option<String>) ->
         history_state.add_snapshot(snapshot);
         engine.history_manager().write().push_state(history_state);
 
-        filter.apply_to_layer(&mut layer);
+        filter.apply_with_selection(&mut layer, engine.selection_manager().read().selection());
     }
 
     Ok(())
@@ -2175,10 +2883,7 @@ fn filter_twirl(state: State<AppState>, angle: f32, radius: Option<f32>) -> Resu
 
     let filter = Twirl::new(angle, radius.unwrap_or(100.0));
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    if let Some(active_layer) = layer_manager.active_layer() {
+    if let Some(active_layer) = engine.edit_target() {
         let mut layer = active_layer.write();
 
         let snapshot = LayerSnapshot::new(
@@ -2191,7 +2896,7 @@ fn filter_twirl(state: State<AppState>, angle: f32, radius: Option<f32>) -> Resu
         history_state.add_snapshot(snapshot);
         engine.history_manager().write().push_state(history_state);
 
-        filter.apply_to_layer(&mut layer);
+        filter.apply_with_selection(&mut layer, engine.selection_manager().read().selection());
     }
 
     Ok(())
@@ -2220,10 +2925,7 @@ fn filter_wave(
 
     let filter = Wave::new(wt, wavelength, amplitude);
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    if let Some(active_layer) = layer_manager.active_layer() {
+    if let Some(active_layer) = engine.edit_target() {
         let mut layer = active_layer.write();
 
         let snapshot = LayerSnapshot::new(
@@ -2236,7 +2938,7 @@ fn filter_wave(
         history_state.add_snapshot(snapshot);
         engine.history_manager().write().push_state(history_state);
 
-        filter.apply_to_layer(&mut layer);
+        filter.apply_with_selection(&mut layer, engine.selection_manager().read().selection());
     }
 
     Ok(())
@@ -2260,10 +2962,7 @@ fn filter_ripple(state: State<AppState>, amount: f32, size: Option<String>) -> R
 
     let filter = Ripple::new(amount, ripple_size);
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    if let Some(active_layer) = layer_manager.active_layer() {
+    if let Some(active_layer) = engine.edit_target() {
         let mut layer = active_layer.write();
 
         let snapshot = LayerSnapshot::new(
@@ -2276,7 +2975,7 @@ fn filter_ripple(state: State<AppState>, amount: f32, size: Option<String>) -> R
         history_state.add_snapshot(snapshot);
         engine.history_manager().write().push_state(history_state);
 
-        filter.apply_to_layer(&mut layer);
+        filter.apply_with_selection(&mut layer, engine.selection_manager().read().selection());
     }
 
     Ok(())
@@ -2309,10 +3008,7 @@ fn filter_vignette(
         filter.feather = f;
     }
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    if let Some(active_layer) = layer_manager.active_layer() {
+    if let Some(active_layer) = engine.edit_target() {
         let mut layer = active_layer.write();
 
         let snapshot = LayerSnapshot::new(
@@ -2325,7 +3021,7 @@ fn filter_vignette(
         history_state.add_snapshot(snapshot);
         engine.history_manager().write().push_state(history_state);
 
-        filter.apply_to_layer(&mut layer);
+        filter.apply_with_selection(&mut layer, engine.selection_manager().read().selection());
     }
 
     Ok(())
@@ -2355,10 +3051,7 @@ fn filter_lens_flare(
         _ => FlareStyle::Zoom50_300,
     };
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    if let Some(active_layer) = layer_manager.active_layer() {
+    if let Some(active_layer) = engine.edit_target() {
         let mut layer = active_layer.write();
 
         let snapshot = LayerSnapshot::new(
@@ -2371,7 +3064,7 @@ fn filter_lens_flare(
         history_state.add_snapshot(snapshot);
         engine.history_manager().write().push_state(history_state);
 
-        filter.apply_to_layer(&mut layer);
+        filter.apply_with_selection(&mut layer, engine.selection_manager().read().selection());
     }
 
     Ok(())
@@ -2407,10 +3100,7 @@ fn filter_clouds(
         }
     }
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    if let Some(active_layer) = layer_manager.active_layer() {
+    if let Some(active_layer) = engine.edit_target() {
         let mut layer = active_layer.write();
 
         let snapshot = LayerSnapshot::new(
@@ -2423,7 +3113,7 @@ fn filter_clouds(
         history_state.add_snapshot(snapshot);
         engine.history_manager().write().push_state(history_state);
 
-        filter.apply_to_layer(&mut layer);
+        filter.apply_with_selection(&mut layer, engine.selection_manager().read().selection());
     }
 
     Ok(())
@@ -2433,117 +3123,47 @@ fn filter_clouds(
 // Transform Commands
 // ============================================================================
 
-/// Rotate 90 degrees clockwise
+/// Rotate the canvas 90 degrees clockwise
 #[tauri::command]
 fn transform_rotate_90_cw(state: State<AppState>) -> Result<(), String> {
-    use drawconnect_core::transform::{ImageData, rotate_90_cw};
-
     let engine_lock = state.engine.read();
     let engine = engine_lock.as_ref().ok_or("No canvas open")?;
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    if let Some(active_layer) = layer_manager.active_layer() {
-        let mut layer = active_layer.write();
-        let width = layer.width();
-        let height = layer.height();
-
-        let img = ImageData::from_pixels(layer.pixels.clone(), width, height)
-            .map_err(|e| e.to_string())?;
-
-        let rotated = rotate_90_cw(&img);
-        layer.pixels = rotated.pixels;
-        layer.bounds = (0, 0, rotated.width, rotated.height);
-    }
-
-    Ok(())
+    engine.rotate_canvas(90.0).map_err(|e| e.to_string())
 }
 
-/// Rotate 90 degrees counter-clockwise
+/// Rotate the canvas 90 degrees counter-clockwise
 #[tauri::command]
 fn transform_rotate_90_ccw(state: State<AppState>) -> Result<(), String> {
-    use drawconnect_core::transform::{ImageData, rotate_90_ccw};
-
     let engine_lock = state.engine.read();
     let engine = engine_lock.as_ref().ok_or("No canvas open")?;
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    if let Some(active_layer) = layer_manager.active_layer() {
-        let mut layer = active_layer.write();
-        let width = layer.width();
-        let height = layer.height();
-
-        let img = ImageData::from_pixels(layer.pixels.clone(), width, height)
-            .map_err(|e| e.to_string())?;
-
-        let rotated = rotate_90_ccw(&img);
-        layer.pixels = rotated.pixels;
-        layer.bounds = (0, 0, rotated.width, rotated.height);
-    }
-
-    Ok(())
+    engine.rotate_canvas(270.0).map_err(|e| e.to_string())
 }
 
-/// Rotate 180 degrees
+/// Rotate the canvas 180 degrees
 #[tauri::command]
 fn transform_rotate_180(state: State<AppState>) -> Result<(), String> {
-    use drawconnect_core::transform::{ImageData, rotate_180};
-
     let engine_lock = state.engine.read();
     let engine = engine_lock.as_ref().ok_or("No canvas open")?;
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    if let Some(active_layer) = layer_manager.active_layer() {
-        let mut layer = active_layer.write();
-        let width = layer.width();
-        let height = layer.height();
-
-        let img = ImageData::from_pixels(layer.pixels.clone(), width, height)
-            .map_err(|e| e.to_string())?;
-
-        let rotated = rotate_180(&img);
-        layer.pixels = rotated.pixels;
-    }
-
-    Ok(())
+    engine.rotate_canvas(180.0).map_err(|e| e.to_string())
 }
 
-/// Rotate by arbitrary angle
+/// Rotate the canvas by an arbitrary angle
 #[tauri::command]
 fn transform_rotate(state: State<AppState>, angle: f32) -> Result<(), String> {
-    use drawconnect_core::transform::{ImageData, rotate_arbitrary};
-
     let engine_lock = state.engine.read();
     let engine = engine_lock.as_ref().ok_or("No canvas open")?;
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    if let Some(active_layer) = layer_manager.active_layer() {
-        let mut layer = active_layer.write();
-        let width = layer.width();
-        let height = layer.height();
-
-        let img = ImageData::from_pixels(layer.pixels.clone(), width, height)
-            .map_err(|e| e.to_string())?;
-
-        let rotated = rotate_arbitrary(&img, angle).map_err(|e| e.to_string())?;
-        layer.pixels = rotated.pixels;
-        layer.bounds = (0, 0, rotated.width, rotated.height);
-    }
-
-    Ok(())
+    engine.rotate_canvas(angle).map_err(|e| e.to_string())
 }
 
 /// Flip horizontally
 #[tauri::command]
 fn transform_flip_horizontal(state: State<AppState>) -> Result<(), String> {
     use drawconnect_core::transform::{ImageData, flip_horizontal};
+    use drawconnect_core::{LayerSnapshot, HistoryState};
 
     let engine_lock = state.engine.read();
     let engine = engine_lock.as_ref().ok_or("No canvas open")?;
@@ -2559,6 +3179,12 @@ fn transform_flip_horizontal(state: State<AppState>) -> Result<(), String> {
         let img = ImageData::from_pixels(layer.pixels.clone(), width, height)
             .map_err(|e| e.to_string())?;
 
+        // Save current state for undo before modifying
+        let snapshot = LayerSnapshot::new(layer.id, layer.pixels.clone(), width, height);
+        let mut history_state = HistoryState::new("Flip Horizontal");
+        history_state.add_snapshot(snapshot);
+        engine.history_manager().write().push_state(history_state);
+
         let flipped = flip_horizontal(&img);
         layer.pixels = flipped.pixels;
     }
@@ -2570,6 +3196,7 @@ fn transform_flip_horizontal(state: State<AppState>) -> Result<(), String> {
 #[tauri::command]
 fn transform_flip_vertical(state: State<AppState>) -> Result<(), String> {
     use drawconnect_core::transform::{ImageData, flip_vertical};
+    use drawconnect_core::{LayerSnapshot, HistoryState};
 
     let engine_lock = state.engine.read();
     let engine = engine_lock.as_ref().ok_or("No canvas open")?;
@@ -2585,6 +3212,12 @@ fn transform_flip_vertical(state: State<AppState>) -> Result<(), String> {
         let img = ImageData::from_pixels(layer.pixels.clone(), width, height)
             .map_err(|e| e.to_string())?;
 
+        // Save current state for undo before modifying
+        let snapshot = LayerSnapshot::new(layer.id, layer.pixels.clone(), width, height);
+        let mut history_state = HistoryState::new("Flip Vertical");
+        history_state.add_snapshot(snapshot);
+        engine.history_manager().write().push_state(history_state);
+
         let flipped = flip_vertical(&img);
         layer.pixels = flipped.pixels;
     }
@@ -2592,7 +3225,7 @@ fn transform_flip_vertical(state: State<AppState>) -> Result<(), String> {
     Ok(())
 }
 
-/// Crop image
+/// Crop the canvas
 #[tauri::command]
 fn transform_crop(
     state: State<AppState>,
@@ -2601,30 +3234,14 @@ fn transform_crop(
     width: u32,
     height: u32,
 ) -> Result<(), String> {
-    use drawconnect_core::transform::{ImageData, crop_image, CropRegion};
+    use drawconnect_core::transform::CropRegion;
 
     let engine_lock = state.engine.read();
     let engine = engine_lock.as_ref().ok_or("No canvas open")?;
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    if let Some(active_layer) = layer_manager.active_layer() {
-        let mut layer = active_layer.write();
-        let layer_width = layer.width();
-        let layer_height = layer.height();
-
-        let img = ImageData::from_pixels(layer.pixels.clone(), layer_width, layer_height)
-            .map_err(|e| e.to_string())?;
-
-        let region = CropRegion::new(x, y, width, height);
-        let cropped = crop_image(&img, region).map_err(|e| e.to_string())?;
-
-        layer.pixels = cropped.pixels;
-        layer.bounds = (0, 0, cropped.width, cropped.height);
-    }
-
-    Ok(())
+    engine
+        .crop_canvas(CropRegion::new(x, y, width, height))
+        .map_err(|e| e.to_string())
 }
 
 /// Resize canvas
@@ -2636,7 +3253,7 @@ fn transform_canvas_resize(
     anchor: Option<String>,
     fill_color: Option<String>,
 ) -> Result<(), String> {
-    use drawconnect_core::transform::{ImageData, canvas_resize, Anchor};
+    use drawconnect_core::transform::Anchor;
 
     let engine_lock = state.engine.read();
     let engine = engine_lock.as_ref().ok_or("No canvas open")?;
@@ -2649,25 +3266,9 @@ fn transform_canvas_resize(
         .and_then(|s| Color::from_hex(&s))
         .unwrap_or(Color::transparent());
 
-    let layer_manager_arc = engine.layer_manager();
-    let layer_manager = layer_manager_arc.read();
-
-    if let Some(active_layer) = layer_manager.active_layer() {
-        let mut layer = active_layer.write();
-        let layer_width = layer.width();
-        let layer_height = layer.height();
-
-        let img = ImageData::from_pixels(layer.pixels.clone(), layer_width, layer_height)
-            .map_err(|e| e.to_string())?;
-
-        let resized = canvas_resize(&img, width, height, anchor, fill)
-            .map_err(|e| e.to_string())?;
-
-        layer.pixels = resized.pixels;
-        layer.bounds = (0, 0, resized.width, resized.height);
-    }
-
-    Ok(())
+    engine
+        .resize_canvas(width, height, anchor, fill)
+        .map_err(|e| e.to_string())
 }
 
 /// Resize image
@@ -2679,6 +3280,7 @@ fn transform_image_resize(
     interpolation: Option<String>,
 ) -> Result<(), String> {
     use drawconnect_core::transform::{ImageData, resize_image, Interpolation};
+    use drawconnect_core::{LayerSnapshot, HistoryState};
 
     let engine_lock = state.engine.read();
     let engine = engine_lock.as_ref().ok_or("No canvas open")?;
@@ -2698,6 +3300,12 @@ fn transform_image_resize(
         let img = ImageData::from_pixels(layer.pixels.clone(), layer_width, layer_height)
             .map_err(|e| e.to_string())?;
 
+        // Save current state for undo before modifying
+        let snapshot = LayerSnapshot::new(layer.id, layer.pixels.clone(), layer_width, layer_height);
+        let mut history_state = HistoryState::new("Resize Image");
+        history_state.add_snapshot(snapshot);
+        engine.history_manager().write().push_state(history_state);
+
         let resized = resize_image(&img, width, height, interpolation)
             .map_err(|e| e.to_string())?;
 
@@ -2762,6 +3370,35 @@ fn import_pat_patterns(path: String) -> Result<Vec<ImportedPatternInfo>, String>
     Ok(result)
 }
 
+/// Import PS patterns from .pat file as bucket fill patterns
+#[tauri::command]
+fn import_pat_fill_patterns(path: String) -> Result<Vec<FillPattern>, String> {
+    use std::fs;
+
+    let data = fs::read(&path)
+        .map_err(|e| format!("Failed to read PAT file '{}': {}", path, e))?;
+
+    let patterns = PatParser::parse(&data)
+        .map_err(|e| format!("Failed to parse PAT file: {}", e))?;
+
+    patterns
+        .into_iter()
+        .map(|p| FillPattern::try_from(p).map_err(|e| e.to_string()))
+        .collect()
+}
+
+/// Import PS gradients from .grd file
+#[tauri::command]
+fn import_grd_gradients(path: String) -> Result<Vec<Gradient>, String> {
+    use std::fs;
+
+    let data = fs::read(&path)
+        .map_err(|e| format!("Failed to read GRD file '{}': {}", path, e))?;
+
+    GrdParser::parse(&data)
+        .map_err(|e| format!("Failed to parse GRD file: {}", e))
+}
+
 /// Import color swatches from .aco or .ase file
 #[tauri::command]
 fn import_color_swatches(path: String) -> Result<Vec<ImportedSwatchInfo>, String> {
@@ -2851,18 +3488,74 @@ fn main() {
             color_from_hsb,
             // Selection
             select_rect,
+            select_ellipse,
             select_lasso,
+            begin_magnetic_lasso,
+            add_magnetic_anchor,
+            remove_magnetic_anchor,
+            preview_magnetic_lasso,
+            close_magnetic_lasso,
+            cancel_magnetic_lasso,
             select_magic_wand,
+            select_layer_transparency,
+            fill_wand_region,
+            select_color_range,
+            select_similar,
+            grow_selection,
             get_selection,
+            get_selection_outline,
+            transform_selection,
+            float_selection,
+            transform_floating_selection,
+            flip_floating_selection,
+            distort_floating_selection,
+            perspective_floating_selection,
+            warp_floating_selection,
+            move_floating_mesh_point,
+            commit_floating_selection,
+            cancel_floating_selection,
+            set_tool,
+            tool_pointer_down,
+            tool_pointer_move,
+            tool_pointer_up,
+            commit_tool,
+            cancel_tool,
+            reconstruct_liquify,
+            get_measurements,
+            add_measurement,
+            remove_measurement,
+            clear_measurements,
+            straighten_layer,
+            get_tool_preview,
+            get_tool_options,
+            set_tool_options,
+            set_tool_colors,
             clear_selection,
             select_all,
             invert_selection,
             expand_selection,
             shrink_selection,
+            border_selection,
+            smooth_selection,
+            feather_selection,
+            set_quick_mask,
+            list_selection_channels,
+            save_selection_channel,
+            load_selection_channel,
+            rename_selection_channel,
+            delete_selection_channel,
+            channel_to_layer_mask,
+            layer_mask_to_channel,
+            get_layer_shapes,
+            set_layer_shapes,
             set_selection_mode,
             // Eyedropper and Fill
             pick_color,
+            get_color_history,
+            clear_color_history,
             flood_fill,
+            bucket_fill,
+            content_aware_fill,
             // Image Adjustments
             adjust_brightness_contrast,
             adjust_levels,
@@ -2911,6 +3604,8 @@ fn main() {
             // PS Resource Import
             import_abr_brushes,
             import_pat_patterns,
+            import_pat_fill_patterns,
+            import_grd_gradients,
             import_color_swatches,
             // Plugins
             plugin_commands::init_plugin_system,
@@ -41,8 +41,9 @@ export interface SelectionInfo {
   bounds: [number, number, number, number] | null
   mode: SelectionMode
   feather: number
-  shape_type: 'none' | 'rectangle' | 'lasso' | 'mask'
+  shape_type: 'none' | 'rectangle' | 'ellipse' | 'lasso' | 'mask'
   points?: Array<[number, number]>
+  quick_mask: boolean
 }
 
 interface AppState {